@@ -0,0 +1,65 @@
+//! Rendering of large zones aligned to their longest name, to a fixed
+//! name width and without alignment. Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use zonefile::render::{build_zonefile, Comments, FormatArgs};
+
+/// Numbers of A records in the rendered zones.
+const SIZES: [usize; 2] = [10_000, 200_000];
+
+fn entries(count: usize) -> Vec<ZoneEntry> {
+    (0..count)
+        .map(|i| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(format!("host-{i}.example.org.").as_str())
+                .unwrap(),
+            type_: Type::A,
+            class: Class::IN,
+            ttl: 300,
+            rdata: format!("10.{}.{}.{}", (i >> 16) & 0xff, (i >> 8) & 0xff, i & 0xff),
+        })
+        .collect()
+}
+
+fn render(c: &mut Criterion) {
+    let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+    let comments = Comments::default();
+
+    let formats = [
+        ("aligned", FormatArgs::default()),
+        (
+            "name-width",
+            FormatArgs {
+                name_width: Some(24),
+                ..FormatArgs::default()
+            },
+        ),
+        (
+            "no-align",
+            FormatArgs {
+                no_align: true,
+                ..FormatArgs::default()
+            },
+        ),
+    ];
+
+    let mut group = c.benchmark_group("build_zonefile");
+    group.sample_size(10);
+
+    for size in SIZES {
+        let entries = entries(size);
+        group.throughput(Throughput::Elements(size as u64));
+
+        for (name, format) in &formats {
+            group.bench_with_input(BenchmarkId::new(*name, size), &entries, |b, entries| {
+                b.iter(|| build_zonefile(&origin, entries, format, &comments))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
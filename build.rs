@@ -0,0 +1,72 @@
+use std::{path::Path, process::Command};
+
+/// Date and time in UTC of the Unix timestamp, formatted as in RFC 3339.
+fn rfc3339(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86400, timestamp % 86400);
+
+    // Civil date from the number of days since the epoch, after Howard Hinnant's algorithm.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn main() {
+    // Images are built from a copy of the sources, which may not include the
+    // repository, so the commit can also be passed in through the environment.
+    let git_sha = std::env::var("GIT_SHA").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|sha| sha.trim().to_string())
+    });
+
+    // The timestamp is only embedded when pinned, since the current time
+    // would make every build differ, and the crate recompile each time.
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .map(rfc3339);
+
+    println!(
+        "cargo:rustc-env=ZONEFILE_GIT_SHA={}",
+        git_sha.as_deref().unwrap_or("unknown")
+    );
+    println!(
+        "cargo:rustc-env=ZONEFILE_BUILD_TIMESTAMP={}",
+        timestamp.as_deref().unwrap_or("unknown")
+    );
+    println!(
+        "cargo:rustc-env=ZONEFILE_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+
+    // Paths which do not exist, as without the repository, would rerun this on every build.
+    for path in [".git/HEAD", ".git/refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
@@ -0,0 +1,406 @@
+use clap::Args;
+use futures::{channel::mpsc, StreamExt as _};
+use k8s_openapi::serde_json::Value;
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind},
+    runtime::{
+        reflector::{self, store::Writer, ObjectRef, Store},
+        watcher,
+    },
+    Api, Client,
+};
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use tokio::task::JoinSet;
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::service::ServiceRecord;
+
+pub const DEFAULT_SOLVER_GROUP: &str = "acme.kubi.zone";
+
+/// TTL of challenge records, which are short-lived, and must not be
+/// cached by resolvers beyond the lifetime of the challenge.
+const CHALLENGE_TTL: u32 = 60;
+
+#[derive(Debug, Clone, Args)]
+pub struct AcmeArgs {
+    /// Publish the TXT records of pending cert-manager DNS-01 challenges,
+    /// and withdraw them once the challenges are complete.
+    #[arg(long, env = "ZONEFILE_ACME_CHALLENGES")]
+    pub acme_challenges: bool,
+
+    /// Group name of the webhook solver which issuers use for the challenges
+    /// published by the controller. Challenges for other solvers are ignored.
+    #[arg(long, env = "ZONEFILE_ACME_SOLVER_GROUP", default_value = DEFAULT_SOLVER_GROUP)]
+    pub acme_solver_group: String,
+}
+
+pub fn challenge_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "acme.cert-manager.io",
+        "v1",
+        "Challenge",
+    ))
+}
+
+/// TXT record which must be published for a challenge to succeed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingChallenge {
+    /// Name being validated, below which the record is published.
+    pub name: FullyQualifiedDomainName,
+    pub key: String,
+}
+
+/// Name validated by a cert-manager Challenge, if it is a DNS-01 challenge
+/// for the solver, whichever state it is in.
+fn challenged_name(challenge: &Value, solver_group: &str) -> Option<FullyQualifiedDomainName> {
+    let spec = challenge.get("spec")?;
+
+    if spec.get("type")?.as_str()? != "DNS-01"
+        || spec.pointer("/solver/dns01/webhook/groupName")?.as_str()? != solver_group
+    {
+        return None;
+    }
+
+    let dns_name = spec.get("dnsName")?.as_str()?.trim_start_matches("*.");
+
+    FullyQualifiedDomainName::try_from(format!("{dns_name}.")).ok()
+}
+
+impl PendingChallenge {
+    /// Read a cert-manager Challenge, if it is a pending DNS-01 challenge for the solver.
+    fn from_challenge(challenge: &Value, solver_group: &str) -> Option<Self> {
+        let name = challenged_name(challenge, solver_group)?;
+
+        // Challenges in a final state are no longer being validated.
+        if matches!(
+            challenge.pointer("/status/state").and_then(Value::as_str),
+            Some("valid" | "invalid" | "errored" | "expired")
+        ) {
+            return None;
+        }
+
+        Some(PendingChallenge {
+            name,
+            key: challenge.pointer("/spec/key")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// Challenges of the cluster, kept up to date by [`AcmeArgs::watch`].
+#[derive(Clone)]
+pub struct Challenges {
+    store: Store<DynamicObject>,
+    solver_group: String,
+}
+
+impl Challenges {
+    pub fn pending(&self) -> Vec<PendingChallenge> {
+        self.store
+            .state()
+            .iter()
+            .filter_map(|challenge| {
+                PendingChallenge::from_challenge(&challenge.data, &self.solver_group)
+            })
+            .collect()
+    }
+
+    /// Zonefiles which published a zone containing the name validated by the
+    /// challenge, and which should therefore be reconciled when it changes, so
+    /// that its record is published or withdrawn. Zonefiles which have not
+    /// published anything yet are reconciled regardless.
+    pub fn zonefiles_publishing(
+        &self,
+        challenge: &DynamicObject,
+        zonefiles: &Store<ZoneFile>,
+    ) -> Vec<ObjectRef<ZoneFile>> {
+        let Some(name) = challenged_name(&challenge.data, &self.solver_group) else {
+            return Vec::new();
+        };
+
+        zonefiles
+            .state()
+            .iter()
+            .filter(|zonefile| {
+                zonefile.status.as_ref().is_none_or(|status| {
+                    publishes_within(status.serial.keys().map(String::as_str), &name)
+                })
+            })
+            .map(|zonefile| ObjectRef::from_obj(zonefile.as_ref()))
+            .collect()
+    }
+}
+
+/// Whether any of the published origins contains the name.
+pub fn publishes_within<'a>(
+    mut origins: impl Iterator<Item = &'a str>,
+    name: &FullyQualifiedDomainName,
+) -> bool {
+    origins.any(|origin| {
+        FullyQualifiedDomainName::try_from(origin).is_ok_and(|origin| within(name, &origin))
+    })
+}
+
+impl AcmeArgs {
+    /// Start watching the challenges of the cluster, if enabled. Returns the
+    /// challenges, along with a stream of the challenges which changed.
+    pub fn watch(
+        &self,
+        client: Client,
+        config: watcher::Config,
+        tasks: &mut JoinSet<()>,
+    ) -> Option<(Challenges, mpsc::UnboundedReceiver<DynamicObject>)> {
+        if !self.acme_challenges {
+            return None;
+        }
+
+        let resource = challenge_resource();
+        let writer = Writer::new(resource.clone());
+        let store = writer.as_reader();
+
+        let (changed, receiver) = mpsc::unbounded();
+        tasks.spawn(
+            reflector::reflector(
+                writer,
+                watcher(Api::<DynamicObject>::all_with(client, &resource), config),
+            )
+            .for_each(move |event| {
+                match event {
+                    Ok(event) => {
+                        for challenge in event.into_iter_touched() {
+                            let _ = changed.unbounded_send(challenge);
+                        }
+                    }
+                    Err(e) => warn!("failed to watch challenges: {e}"),
+                }
+
+                futures::future::ready(())
+            }),
+        );
+
+        Some((
+            Challenges {
+                store,
+                solver_group: self.acme_solver_group.clone(),
+            },
+            receiver,
+        ))
+    }
+}
+
+fn within(name: &FullyQualifiedDomainName, zone: &FullyQualifiedDomainName) -> bool {
+    name == zone || name.is_subdomain_of(zone)
+}
+
+/// TXT records for the challenges within the zone, except for challenges
+/// within more specific zones of the zonefile, which publish them instead.
+pub fn challenge_records(
+    origin: &FullyQualifiedDomainName,
+    challenges: &[PendingChallenge],
+    origins: &[FullyQualifiedDomainName],
+) -> Vec<ServiceRecord> {
+    challenges
+        .iter()
+        .filter(|challenge| {
+            within(&challenge.name, origin)
+                && !origins
+                    .iter()
+                    .any(|other| other.is_subdomain_of(origin) && within(&challenge.name, other))
+        })
+        .map(|challenge| ServiceRecord {
+            owner: format!("_acme-challenge.{}", challenge.name).to_ascii_lowercase(),
+            type_: Type::TXT,
+            class: Class::IN,
+            ttl: CHALLENGE_TTL,
+            rdata: format!("\"{}\"", challenge.key),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::serde_json::{json, Value};
+    use kube::{
+        api::DynamicObject,
+        runtime::{
+            reflector::{self, store::Writer, ObjectRef},
+            watcher,
+        },
+    };
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use zonefile_crds::{ZoneFile, ZoneFileSpec, ZoneFileStatus};
+
+    use crate::service::ServiceRecord;
+
+    use super::{
+        challenge_records, challenge_resource, challenged_name, publishes_within, Challenges,
+        PendingChallenge, DEFAULT_SOLVER_GROUP,
+    };
+
+    fn fqdn(name: &str) -> FullyQualifiedDomainName {
+        FullyQualifiedDomainName::try_from(name).unwrap()
+    }
+
+    #[test]
+    fn reads_pending_challenges() {
+        let mut challenge = json!({
+            "spec": {
+                "type": "DNS-01",
+                "dnsName": "www.example.org",
+                "key": "LoqXcYV8q5ONbJQxbmR7SCTNo3tiAXDfowyjxAjEuX0",
+                "solver": {
+                    "dns01": {
+                        "webhook": {
+                            "groupName": DEFAULT_SOLVER_GROUP,
+                            "solverName": "kubizone",
+                        },
+                    },
+                },
+            },
+            "status": {
+                "state": "pending",
+            },
+        });
+
+        assert_eq!(
+            PendingChallenge::from_challenge(&challenge, DEFAULT_SOLVER_GROUP),
+            Some(PendingChallenge {
+                name: fqdn("www.example.org."),
+                key: "LoqXcYV8q5ONbJQxbmR7SCTNo3tiAXDfowyjxAjEuX0".to_string(),
+            })
+        );
+
+        assert!(PendingChallenge::from_challenge(&challenge, "acme.example.org").is_none());
+
+        challenge["status"]["state"] = json!("valid");
+        assert!(PendingChallenge::from_challenge(&challenge, DEFAULT_SOLVER_GROUP).is_none());
+
+        // Completed challenges still concern the zones containing them, which withdraw their records.
+        let name = challenged_name(&challenge, DEFAULT_SOLVER_GROUP).unwrap();
+        assert!(publishes_within(
+            ["example.net.", "example.org."].into_iter(),
+            &name
+        ));
+        assert!(publishes_within(["www.example.org."].into_iter(), &name));
+        assert!(!publishes_within(
+            ["example.net.", "dev.example.org."].into_iter(),
+            &name
+        ));
+    }
+
+    #[test]
+    fn challenges_are_published_by_the_most_specific_zone() {
+        let challenges = [
+            PendingChallenge {
+                name: fqdn("example.org."),
+                key: "apex".to_string(),
+            },
+            PendingChallenge {
+                name: fqdn("www.dev.example.org."),
+                key: "dev".to_string(),
+            },
+            PendingChallenge {
+                name: fqdn("example.net."),
+                key: "other".to_string(),
+            },
+        ];
+
+        let origins = [fqdn("example.org."), fqdn("dev.example.org.")];
+
+        assert_eq!(
+            challenge_records(&origins[0], &challenges, &origins),
+            vec![ServiceRecord {
+                owner: String::from("_acme-challenge.example.org."),
+                type_: Type::TXT,
+                class: Class::IN,
+                ttl: 60,
+                rdata: String::from("\"apex\""),
+            }]
+        );
+
+        assert_eq!(
+            challenge_records(&origins[1], &challenges, &origins),
+            vec![ServiceRecord {
+                owner: String::from("_acme-challenge.www.dev.example.org."),
+                type_: Type::TXT,
+                class: Class::IN,
+                ttl: 60,
+                rdata: String::from("\"dev\""),
+            }]
+        );
+    }
+
+    fn dns01_challenge(dns_name: &str) -> Value {
+        json!({
+            "spec": {
+                "type": "DNS-01",
+                "dnsName": dns_name,
+                "key": "token",
+                "solver": {
+                    "dns01": {
+                        "webhook": {
+                            "groupName": DEFAULT_SOLVER_GROUP,
+                        },
+                    },
+                },
+            },
+        })
+    }
+
+    fn zonefile(name: &str, origins: Option<&[&str]>) -> ZoneFile {
+        let mut zonefile = ZoneFile::new(
+            name,
+            ZoneFileSpec {
+                zone_refs: Vec::new(),
+                config_map_name: None,
+            },
+        );
+        zonefile.metadata.namespace = Some(String::from("default"));
+        zonefile.status = origins.map(|origins| ZoneFileStatus {
+            hash: BTreeMap::new(),
+            serial: origins
+                .iter()
+                .map(|origin| (origin.to_string(), 1))
+                .collect(),
+        });
+        zonefile
+    }
+
+    #[test]
+    fn reconciles_zonefiles_publishing_the_challenged_name() {
+        let challenges = Challenges {
+            store: Writer::new(challenge_resource()).as_reader(),
+            solver_group: DEFAULT_SOLVER_GROUP.to_string(),
+        };
+
+        let (zonefiles, mut writer) = reflector::store();
+        for zonefile in [
+            zonefile("org", Some(&["example.org."])),
+            zonefile("net", Some(&["example.net."])),
+            zonefile("unpublished", None),
+        ] {
+            writer.apply_watcher_event(&watcher::Event::Applied(zonefile));
+        }
+
+        let challenge = DynamicObject::new("challenge", &challenge_resource())
+            .data(dns01_challenge("www.example.org"));
+
+        let mut reconciled = challenges
+            .zonefiles_publishing(&challenge, &zonefiles)
+            .into_iter()
+            .map(|zonefile| zonefile.name)
+            .collect::<Vec<_>>();
+        reconciled.sort();
+
+        // Zonefiles without a status have not published anything, and may contain the name.
+        assert_eq!(reconciled, vec!["org", "unpublished"]);
+
+        let other = DynamicObject::new("other", &challenge_resource()).data(json!({}));
+        assert_eq!(
+            challenges.zonefiles_publishing(&other, &zonefiles),
+            Vec::<ObjectRef<ZoneFile>>::new()
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::io::Write as _;
+
+use age::{
+    armor::{ArmoredWriter, Format},
+    EncryptError, Encryptor,
+};
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding a comma-separated
+/// list of age recipients (`age1…`). When present, the keys delivered to the
+/// zonefile's [outputs](crate::output::Output) are encrypted to the recipients.
+pub const AGE_RECIPIENTS_ANNOTATION: &str = "zonefile.kubi.zone/age-recipients";
+
+/// X25519 recipient of an age encrypted file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient(age::x25519::Recipient);
+
+impl Recipient {
+    /// Parse a Bech32-encoded `age1…` recipient.
+    pub fn parse(recipient: &str) -> Option<Self> {
+        recipient.parse().ok().map(Recipient)
+    }
+
+    /// Parse a comma-separated list of recipients.
+    pub fn parse_list(recipients: &str) -> Result<Vec<Self>, String> {
+        recipients
+            .split(',')
+            .map(str::trim)
+            .filter(|recipient| !recipient.is_empty())
+            .map(|recipient| Recipient::parse(recipient).ok_or_else(|| recipient.to_string()))
+            .collect()
+    }
+}
+
+/// Encrypt the plaintext to the recipients, producing an ASCII-armored age
+/// file, as described by the [age specification](https://age-encryption.org/v1),
+/// which can be decrypted using `age --decrypt` or `sops`.
+pub fn encrypt(recipients: &[Recipient], plaintext: &str) -> Result<String, EncryptError> {
+    let encryptor = Encryptor::with_recipients(
+        recipients
+            .iter()
+            .map(|recipient| &recipient.0 as &dyn age::Recipient),
+    )?;
+
+    let mut armored = Vec::new();
+    let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(
+        &mut armored,
+        Format::AsciiArmor,
+    )?)?;
+    writer.write_all(plaintext.as_bytes())?;
+    writer.finish()?.finish()?;
+
+    // The armor is made up of base64 and its ASCII delimiters.
+    Ok(String::from_utf8(armored).expect("ASCII armor is valid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use age::{armor::ArmoredReader, x25519::Identity, Decryptor};
+    use ring::digest::{digest, SHA256};
+
+    use super::{encrypt, Recipient};
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn decrypt(identity: &Identity, armored: &str) -> String {
+        let decryptor = Decryptor::new(ArmoredReader::new(armored.as_bytes())).unwrap();
+
+        let mut plaintext = String::new();
+        decryptor
+            .decrypt(std::iter::once(identity as &dyn age::Identity))
+            .unwrap()
+            .read_to_string(&mut plaintext)
+            .unwrap();
+        plaintext
+    }
+
+    #[test]
+    fn parses_recipients() {
+        assert!(
+            Recipient::parse("age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p")
+                .is_some()
+        );
+
+        // Corrupted checksum.
+        assert!(
+            Recipient::parse("age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8q")
+                .is_none()
+        );
+
+        assert!(Recipient::parse_list(
+            "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p, ssh-ed25519 AAAA"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn encrypts_to_each_recipient() {
+        let identities = [Identity::generate(), Identity::generate()];
+        let recipients = Recipient::parse_list(&format!(
+            "{},{}",
+            identities[0].to_public(),
+            identities[1].to_public()
+        ))
+        .unwrap();
+
+        let armored = encrypt(&recipients, "$ORIGIN example.org.\n").unwrap();
+        let lines: Vec<&str> = armored.lines().collect();
+
+        assert_eq!(lines[0], "-----BEGIN AGE ENCRYPTED FILE-----");
+        assert_eq!(lines[lines.len() - 1], "-----END AGE ENCRYPTED FILE-----");
+
+        for identity in &identities {
+            assert_eq!(decrypt(identity, &armored), "$ORIGIN example.org.\n");
+        }
+
+        // Encryption is randomized, so equal plaintexts never produce equal files.
+        assert_ne!(
+            armored,
+            encrypt(&recipients, "$ORIGIN example.org.\n").unwrap()
+        );
+    }
+
+    /// The `armor` test vector of the age test kit
+    /// (<https://github.com/C2SP/CCTV/tree/main/age>), shared by its implementations.
+    #[test]
+    fn decrypts_reference_test_vector() {
+        let identity: Identity =
+            "AGE-SECRET-KEY-1EGTZVFFV20835NWYV6270LXYVK2VKNX2MMDKWYKLMGR48UAWX40Q2P2LM0"
+                .parse()
+                .unwrap();
+
+        let armored = indoc::indoc! {"
+            -----BEGIN AGE ENCRYPTED FILE-----
+            YWdlLWVuY3J5cHRpb24ub3JnL3YxCi0+IFgyNTUxOSBURWlGMHlwcXIrYnB2Y3FY
+            TnlDVkpwTDdPdXdQZFZ3UEw3S1FFYkZET0NjCmhqYWJHWHdTTFE5YzNTNkx3Mmkr
+            UzJUdTJmaXdRSEhzbGJCTjZCNDFGTEUKLS0tIFd5SnA5Ri85Rk9aaDdnSmRoZXEy
+            V0lKY3dIZ1ljOE5JVmgzZGR3aHJjTmcK7s9ix86RtDMnTmjU8vkTTLdMW/73vqpS
+            yPC8DpksHoMx+2Y=
+            -----END AGE ENCRYPTED FILE-----
+        "};
+
+        let mut payload = Vec::new();
+        Decryptor::new(ArmoredReader::new(armored.as_bytes()))
+            .unwrap()
+            .decrypt(std::iter::once(&identity as &dyn age::Identity))
+            .unwrap()
+            .read_to_end(&mut payload)
+            .unwrap();
+
+        assert_eq!(
+            digest(&SHA256, &payload).as_ref(),
+            bytes("013f54400c82da08037759ada907a8b864e97de81c088a182062c4b5622fd2ab")
+        );
+
+        // Files encrypted to the identity's recipient are read back by it.
+        let recipient = Recipient::parse(&identity.to_public().to_string()).unwrap();
+        let encrypted = encrypt(&[recipient], "$ORIGIN example.org.\n").unwrap();
+        assert_eq!(decrypt(&identity, &encrypted), "$ORIGIN example.org.\n");
+    }
+}
@@ -0,0 +1,145 @@
+use clap::Args;
+use kubizone_crds::v1alpha1::ZoneEntry;
+use thiserror::Error;
+
+use crate::diff::ZoneDiff;
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding a comma-separated
+/// list of origins whose anomalous contents may be published even though
+/// `--hold-anomalies` is set. It should be removed once the zones have been published.
+pub const ACKNOWLEDGE_ANOMALIES_ANNOTATION: &str = "zonefile.kubi.zone/acknowledge-anomalies";
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct AnomalyArgs {
+    /// Report zones which lost more than this many records
+    /// since their contents were last published.
+    #[arg(long, env = "ZONEFILE_ANOMALY_MAX_SHRINK")]
+    pub anomaly_max_shrink: Option<usize>,
+
+    /// Report zones in which every record has a TTL of zero.
+    #[arg(long, env = "ZONEFILE_ANOMALY_ZERO_TTLS")]
+    pub anomaly_zero_ttls: bool,
+
+    /// Keep the previously published contents of zones in which an
+    /// anomaly was found, rather than publishing them and reporting it,
+    /// until the anomalies are acknowledged through an annotation.
+    #[arg(long, env = "ZONEFILE_HOLD_ANOMALIES")]
+    pub hold_anomalies: bool,
+}
+
+/// Suspicious change to the contents of a zone.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Anomaly {
+    #[error("{removed} records were removed, more than the limit of {limit}")]
+    Shrunk { removed: usize, limit: usize },
+    #[error("all {records} records have a TTL of zero")]
+    ZeroTtls { records: usize },
+    #[error("serial {serial} is lower than the published serial {published}")]
+    SerialRollback { serial: u32, published: u32 },
+}
+
+impl Anomaly {
+    /// Reason reported in the `Anomaly` condition, and
+    /// the `check` label of the anomalies metric.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Anomaly::Shrunk { .. } => "ZoneShrank",
+            Anomaly::ZeroTtls { .. } => "ZeroTtls",
+            Anomaly::SerialRollback { .. } => "SerialRollback",
+        }
+    }
+}
+
+impl AnomalyArgs {
+    /// Check a rendered zone against its previously published contents, if any.
+    pub fn check(
+        &self,
+        previous: Option<&str>,
+        rendered: &str,
+        entries: &[ZoneEntry],
+    ) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+
+        if let (Some(limit), Some(previous)) = (self.anomaly_max_shrink, previous) {
+            let diff = ZoneDiff::between(previous, rendered);
+            let removed = diff.removed.len().saturating_sub(diff.added.len());
+
+            if removed > limit {
+                anomalies.push(Anomaly::Shrunk { removed, limit });
+            }
+        }
+
+        if self.anomaly_zero_ttls
+            && !entries.is_empty()
+            && entries.iter().all(|entry| entry.ttl == 0)
+        {
+            anomalies.push(Anomaly::ZeroTtls {
+                records: entries.len(),
+            });
+        }
+
+        anomalies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::{Anomaly, AnomalyArgs};
+
+    #[test]
+    fn detects_anomalies() {
+        let entry = |name: &str, ttl: u32| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_: Type::A,
+            class: Class::IN,
+            ttl,
+            rdata: "192.0.2.1".to_string(),
+        };
+
+        let checks = AnomalyArgs {
+            anomaly_max_shrink: Some(1),
+            anomaly_zero_ttls: true,
+            hold_anomalies: false,
+        };
+
+        let previous = "www IN A 192.0.2.1\nmail IN A 192.0.2.2\nftp IN A 192.0.2.3\n";
+
+        assert_eq!(
+            checks.check(
+                Some(previous),
+                "www IN A 192.0.2.1\nftp IN A 192.0.2.3\n",
+                &[entry("www.example.org.", 360)]
+            ),
+            vec![]
+        );
+
+        assert_eq!(
+            checks.check(
+                Some(previous),
+                "www IN A 192.0.2.1\n",
+                &[entry("www.example.org.", 0), entry("ftp.example.org.", 0)]
+            ),
+            vec![
+                Anomaly::Shrunk {
+                    removed: 2,
+                    limit: 1
+                },
+                Anomaly::ZeroTtls { records: 2 }
+            ]
+        );
+
+        // Zones published for the first time have nothing to shrink from.
+        assert_eq!(
+            checks.check(None, "", &[entry("www.example.org.", 360)]),
+            vec![]
+        );
+
+        assert_eq!(
+            AnomalyArgs::default().check(Some(previous), "", &[entry("www.example.org.", 0)]),
+            vec![]
+        );
+    }
+}
@@ -0,0 +1,513 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse as _, Response},
+    routing::get,
+    Json, Router,
+};
+use clap::Args;
+use k8s_openapi::serde_json::json;
+use ring::digest::{digest, SHA256};
+
+use crate::{diff::ZoneDiff, error::Error, server};
+
+#[derive(Debug, Clone, Args)]
+pub struct ApiArgs {
+    /// Address on which to expose the zones published by the controller at
+    /// `/zones`, `/zones/<origin>` and `/zones/<origin>/diff?from=<serial>`.
+    /// Only loopback addresses may be listened on without `--api-token`.
+    #[arg(long, env = "ZONEFILE_API_ADDRESS")]
+    pub api_address: Option<SocketAddr>,
+
+    /// Bearer token which requests to the API must present in their `Authorization`
+    /// header. Best passed through an environment variable populated from a Secret.
+    #[arg(long, env = "ZONEFILE_API_TOKEN", hide_env_values = true)]
+    pub api_token: Option<String>,
+
+    /// Number of previously published versions of each zone
+    /// kept in memory, which diffs can be requested against.
+    #[arg(long, env = "ZONEFILE_API_HISTORY", default_value_t = 16)]
+    pub api_history: usize,
+}
+
+impl ApiArgs {
+    /// Address to serve the API on, if enabled. Since the API exposes every published
+    /// zone, it is only served beyond the loopback interface when protected by a token.
+    pub fn address(&self) -> Result<Option<SocketAddr>, Error> {
+        match self.api_address {
+            Some(address) if !address.ip().is_loopback() && self.api_token.is_none() => {
+                Err(Error::Config(format!(
+                    "the API is only served on {address} with --api-token"
+                )))
+            }
+            address => Ok(address),
+        }
+    }
+}
+
+struct PublishedZone {
+    zonefile: String,
+    hash: String,
+    /// Published versions of the zone by serial, oldest first.
+    versions: VecDeque<(u32, String)>,
+}
+
+/// Zones most recently published by the controller, along with a
+/// short history of their previous versions.
+///
+/// This is kept in memory only, so a restarted controller serves
+/// each zone once its zonefile has been reconciled again, without
+/// any of the versions published before the restart, other than the
+/// last contents of zones which are not rendered anew.
+pub struct PublishedZones {
+    history: usize,
+    zones: Mutex<BTreeMap<String, PublishedZone>>,
+}
+
+/// Origins in requests may leave out the trailing dot.
+fn qualify(origin: &str) -> String {
+    format!("{}.", origin.trim_end_matches('.'))
+}
+
+impl PublishedZones {
+    pub fn new(history: usize) -> Self {
+        PublishedZones {
+            history: history.max(1),
+            zones: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record the zones rendered for the zonefile, and forget those of its zones
+    /// which are not among the origins it still publishes. Zones published at their
+    /// last contents rather than rendered anew are kept as they are.
+    pub fn publish<'a>(
+        &self,
+        zonefile: &str,
+        rendered: impl IntoIterator<Item = (&'a str, u32, &'a str, &'a str)>,
+        origins: &[&str],
+    ) {
+        let mut zones = self.zones.lock().unwrap();
+
+        for (origin, serial, hash, contents) in rendered {
+            let zone = zones
+                .entry(origin.to_string())
+                .or_insert_with(|| PublishedZone {
+                    zonefile: zonefile.to_string(),
+                    hash: String::new(),
+                    versions: VecDeque::new(),
+                });
+
+            // Versions published by another zonefile are no history of this one.
+            if zone.zonefile != zonefile {
+                zone.zonefile = zonefile.to_string();
+                zone.versions.clear();
+            }
+
+            zone.hash = hash.to_string();
+
+            if zone
+                .versions
+                .back()
+                .is_some_and(|(_, previous)| previous == contents)
+            {
+                continue;
+            }
+
+            zone.versions.retain(|(previous, _)| *previous != serial);
+            zone.versions.push_back((serial, contents.to_string()));
+            while zone.versions.len() > self.history {
+                zone.versions.pop_front();
+            }
+        }
+
+        zones
+            .retain(|origin, zone| zone.zonefile != zonefile || origins.contains(&origin.as_str()));
+    }
+
+    /// Record the zones which the zonefile keeps publishing at their last contents,
+    /// unless they are known already. Such zones are not rendered anew, so after a
+    /// restart, they would otherwise not be found until they are.
+    pub fn seed<'a>(
+        &self,
+        zonefile: &str,
+        retained: impl IntoIterator<Item = (&'a str, u32, &'a str, &'a str)>,
+    ) {
+        let mut zones = self.zones.lock().unwrap();
+
+        for (origin, serial, hash, contents) in retained {
+            zones
+                .entry(origin.to_string())
+                .or_insert_with(|| PublishedZone {
+                    zonefile: zonefile.to_string(),
+                    hash: hash.to_string(),
+                    versions: VecDeque::from([(serial, contents.to_string())]),
+                });
+        }
+    }
+
+    /// Forget all zones of a deleted zonefile.
+    pub fn forget(&self, zonefile: &str) {
+        self.zones
+            .lock()
+            .unwrap()
+            .retain(|_, zone| zone.zonefile != zonefile);
+    }
+
+    fn list(&self) -> Response {
+        let zones = self.zones.lock().unwrap();
+
+        Json(json!(zones
+            .iter()
+            .map(|(origin, zone)| json!({
+                "origin": origin,
+                "zonefile": zone.zonefile,
+                "serial": zone.versions.back().map(|(serial, _)| serial),
+                "hash": zone.hash,
+            }))
+            .collect::<Vec<_>>()))
+        .into_response()
+    }
+
+    fn zone(&self, origin: &str, as_json: bool) -> Response {
+        let zones = self.zones.lock().unwrap();
+        let Some(zone) = zones.get(&qualify(origin)) else {
+            return not_found();
+        };
+        let Some((serial, contents)) = zone.versions.back() else {
+            return not_found();
+        };
+
+        if as_json {
+            Json(json!({
+                "origin": qualify(origin),
+                "zonefile": zone.zonefile,
+                "serial": serial,
+                "hash": zone.hash,
+                "contents": contents,
+            }))
+            .into_response()
+        } else {
+            ([(CONTENT_TYPE, "text/dns")], contents.clone()).into_response()
+        }
+    }
+
+    fn diff(&self, origin: &str, from: u32) -> Response {
+        let zones = self.zones.lock().unwrap();
+        let Some(zone) = zones.get(&qualify(origin)) else {
+            return not_found();
+        };
+        let (Some((_, old)), Some((serial, new))) = (
+            zone.versions.iter().find(|(serial, _)| *serial == from),
+            zone.versions.back(),
+        ) else {
+            return not_found();
+        };
+
+        let diff = ZoneDiff::between(old, new);
+
+        Json(json!({
+            "origin": qualify(origin),
+            "from": from,
+            "to": serial,
+            "added": diff.added,
+            "removed": diff.removed,
+        }))
+        .into_response()
+    }
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "not found\n").into_response()
+}
+
+fn bad_request(message: &str) -> Response {
+    (StatusCode::BAD_REQUEST, format!("{message}\n")).into_response()
+}
+
+type Parameters = Query<BTreeMap<String, String>>;
+
+/// Refuse requests which do not present the token.
+async fn authorize(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let presented = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "));
+
+    match presented {
+        // Digests are compared rather than the tokens themselves, so that the time taken
+        // by the comparison tells nothing about how much of the token was presented.
+        Some(presented)
+            if digest(&SHA256, presented.as_bytes()).as_ref()
+                == digest(&SHA256, token.as_bytes()).as_ref() =>
+        {
+            next.run(request).await
+        }
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, "Bearer")],
+            "unauthorized\n",
+        )
+            .into_response(),
+    }
+}
+
+/// Routes of the zones API, requiring the token if given.
+fn router(zones: Arc<PublishedZones>, token: Option<&str>) -> Router {
+    let router = Router::new()
+        .route(
+            "/zones",
+            get(|State(zones): State<Arc<PublishedZones>>| async move { zones.list() }),
+        )
+        .route(
+            "/zones/:origin",
+            get(
+                |State(zones): State<Arc<PublishedZones>>,
+                 Path(origin): Path<String>,
+                 Query(query): Parameters| async move {
+                    match query.get("format").map(String::as_str) {
+                        None | Some("text") => zones.zone(&origin, false),
+                        Some("json") => zones.zone(&origin, true),
+                        Some(_) => bad_request("format must be either text or json"),
+                    }
+                },
+            ),
+        )
+        .route(
+            "/zones/:origin/diff",
+            get(
+                |State(zones): State<Arc<PublishedZones>>,
+                 Path(origin): Path<String>,
+                 Query(query): Parameters| async move {
+                    match query.get("from").map(|from| from.parse()) {
+                        Some(Ok(from)) => zones.diff(&origin, from),
+                        _ => bad_request("from must be the serial of a published version"),
+                    }
+                },
+            ),
+        )
+        .with_state(zones);
+
+    match token {
+        Some(token) => router.layer(middleware::from_fn_with_state(Arc::from(token), authorize)),
+        None => router,
+    }
+}
+
+/// Serve the zones API.
+pub async fn serve(address: SocketAddr, token: Option<String>, zones: Arc<PublishedZones>) {
+    server::serve(address, router(zones, token.as_deref())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::{self, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use k8s_openapi::serde_json::{self, json, Value};
+    use tower::ServiceExt as _;
+
+    use super::{router, ApiArgs, PublishedZones};
+
+    async fn request(router: &Router, request: Request<Body>) -> (StatusCode, String) {
+        let response = router.clone().oneshot(request).await.unwrap();
+
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, String) {
+        request(router, Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    async fn get_json(router: &Router, uri: &str) -> Value {
+        serde_json::from_str(&get(router, uri).await.1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_published_zones_and_diffs() {
+        let zones = Arc::new(PublishedZones::new(2));
+
+        zones.publish(
+            "default/zones",
+            [
+                ("example.org.", 1, "sha256:a", "www IN A 192.0.2.1\n"),
+                ("example.net.", 1, "sha256:b", "www IN A 192.0.2.2\n"),
+            ],
+            &["example.org.", "example.net."],
+        );
+        zones.publish(
+            "default/zones",
+            [("example.org.", 2, "sha256:c", "www IN A 192.0.2.3\n")],
+            &["example.org."],
+        );
+
+        let router = router(zones, None);
+
+        assert_eq!(
+            get_json(&router, "/zones").await,
+            json!([{
+                "origin": "example.org.",
+                "zonefile": "default/zones",
+                "serial": 2,
+                "hash": "sha256:c",
+            }])
+        );
+
+        assert_eq!(
+            get(&router, "/zones/example.org").await,
+            (StatusCode::OK, String::from("www IN A 192.0.2.3\n"))
+        );
+
+        assert_eq!(
+            get(&router, "/zones/example.net.").await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            get(&router, "/zones/example.org.?format=yaml").await.0,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(
+            get_json(&router, "/zones/example.org./diff?from=1").await,
+            json!({
+                "origin": "example.org.",
+                "from": 1,
+                "to": 2,
+                "added": ["www IN A 192.0.2.3"],
+                "removed": ["www IN A 192.0.2.1"],
+            })
+        );
+
+        assert_eq!(
+            get(&router, "/zones/example.org./diff?from=0").await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            get(&router, "/zones/example.org./diff").await.0,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_zones_which_were_not_rendered() {
+        let zones = Arc::new(PublishedZones::new(2));
+
+        zones.publish(
+            "default/zones",
+            [
+                ("example.org.", 1, "sha256:a", "www IN A 192.0.2.1\n"),
+                ("example.net.", 1, "sha256:b", "www IN A 192.0.2.2\n"),
+            ],
+            &["example.org.", "example.net."],
+        );
+
+        // The second zone keeps its last published contents.
+        zones.publish(
+            "default/zones",
+            [("example.org.", 2, "sha256:c", "www IN A 192.0.2.3\n")],
+            &["example.org.", "example.net."],
+        );
+        zones.publish("other/zones", [], &[]);
+
+        let router = router(zones.clone(), None);
+        assert_eq!(
+            get(&router, "/zones/example.net.").await,
+            (StatusCode::OK, String::from("www IN A 192.0.2.2\n"))
+        );
+
+        zones.forget("default/zones");
+        assert_eq!(get_json(&router, "/zones").await, json!([]));
+    }
+
+    #[tokio::test]
+    async fn serves_retained_zones_after_a_restart() {
+        // A restarted controller has published nothing yet, and renders nothing
+        // anew for a zone which keeps its last published contents.
+        let zones = Arc::new(PublishedZones::new(2));
+        zones.publish("default/zones", [], &["example.org."]);
+        zones.seed(
+            "default/zones",
+            [("example.org.", 1, "sha256:a", "www IN A 192.0.2.1\n")],
+        );
+
+        let router = router(zones.clone(), None);
+        assert_eq!(
+            get(&router, "/zones/example.org.").await,
+            (StatusCode::OK, String::from("www IN A 192.0.2.1\n"))
+        );
+
+        // Zones which are known already keep their history.
+        zones.publish(
+            "default/zones",
+            [("example.org.", 2, "sha256:b", "www IN A 192.0.2.2\n")],
+            &["example.org."],
+        );
+        zones.seed(
+            "default/zones",
+            [("example.org.", 2, "sha256:b", "www IN A 192.0.2.2\n")],
+        );
+        assert_eq!(
+            get_json(&router, "/zones/example.org./diff?from=1").await["to"],
+            json!(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn requires_the_token() {
+        let router = router(Arc::new(PublishedZones::new(1)), Some("s3cr3t"));
+
+        assert_eq!(get(&router, "/zones").await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            request(
+                &router,
+                Request::get("/zones")
+                    .header("Authorization", "Bearer wrong")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            request(
+                &router,
+                Request::get("/zones")
+                    .header("Authorization", "Bearer s3cr3t")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await,
+            (StatusCode::OK, String::from("[]"))
+        );
+
+        let args = |address: &str, token: Option<&str>| ApiArgs {
+            api_address: Some(address.parse().unwrap()),
+            api_token: token.map(String::from),
+            api_history: 1,
+        };
+        assert!(args("127.0.0.1:8081", None).address().is_ok());
+        assert!(args("[::1]:8081", None).address().is_ok());
+        assert!(args("0.0.0.0:8081", None).address().is_err());
+        assert!(args("0.0.0.0:8081", Some("s3cr3t")).address().is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+use std::{io::Write as _, path::PathBuf};
+
+use clap::Args;
+use k8s_openapi::{chrono::Utc, serde_json::json};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt as _};
+use tracing::log::*;
+
+use crate::diff::ZoneChange;
+
+#[derive(Debug, Clone, Args)]
+pub struct AuditArgs {
+    /// Append a JSON line describing every published zone change to
+    /// this file. Use `-` to write the audit trail to standard output, in
+    /// which case the controller logs to standard error instead.
+    #[arg(long, env = "ZONEFILE_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+}
+
+impl AuditArgs {
+    /// Whether the audit trail is written to standard output.
+    pub fn to_stdout(&self) -> bool {
+        self.audit_log
+            .as_ref()
+            .is_some_and(|path| path.as_os_str() == "-")
+    }
+}
+
+/// Append-only audit trail of published zone changes.
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn from_args(args: &AuditArgs) -> Option<Self> {
+        Some(AuditLog {
+            path: args.audit_log.clone()?,
+        })
+    }
+
+    /// Record a published change.
+    ///
+    /// The file is reopened for every entry, so that external
+    /// log rotation does not require restarting the controller.
+    pub async fn record(&self, controller: &str, change: &ZoneChange<'_>) {
+        let entry = json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "controller": controller,
+            "zonefile": change.zonefile,
+            "zone": change.zone,
+            "trigger": change.trigger,
+            "origin": change.origin,
+            "serial": {
+                "old": change.old_serial,
+                "new": change.new_serial,
+            },
+            "hash": change.hash,
+            "added": change.diff.added,
+            "removed": change.diff.removed,
+        });
+
+        if self.path.as_os_str() == "-" {
+            let mut stdout = std::io::stdout().lock();
+            if let Err(e) = writeln!(stdout, "{entry}").and_then(|()| stdout.flush()) {
+                error!(
+                    "failed to write audit log entry for zone {} to standard output: {e}",
+                    change.origin
+                );
+            }
+            return;
+        }
+
+        let result = async {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?
+                .write_all(format!("{entry}\n").as_bytes())
+                .await
+        }
+        .await;
+
+        if let Err(e) = result {
+            error!(
+                "failed to write audit log entry for zone {} to {}: {e}",
+                change.origin,
+                self.path.display()
+            );
+        }
+    }
+}
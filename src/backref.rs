@@ -0,0 +1,110 @@
+use clap::{Args, ValueEnum};
+use k8s_openapi::serde_json::json;
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::reflector::{ObjectRef, Store},
+    Api, Client, ResourceExt as _,
+};
+use kubizone_crds::v1alpha1::Zone;
+use tracing::log::*;
+use zonefile_crds::{ZoneFile, TARGET_ZONEFILE_LABEL};
+
+/// Where a [`Zone`] records the [`ZoneFile`] it is part of.
+///
+/// Backreferences are informational only, for tooling which relies on them.
+/// Changes to zones are mapped to zonefiles using [`zonefiles_referencing`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BackrefMode {
+    /// Label on the zone, referencing the zonefile.
+    Label,
+    /// Annotation on the zone, referencing the zonefile.
+    Annotation,
+    /// Zones are left untouched.
+    #[default]
+    None,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct BackrefArgs {
+    /// How zones are linked back to the zonefiles referencing them.
+    #[arg(
+        long,
+        env = "ZONEFILE_BACKREF",
+        value_enum,
+        default_value_t = BackrefMode::None
+    )]
+    pub backref: BackrefMode,
+
+    /// Key of the label or annotation holding the backreference.
+    #[arg(long, env = "ZONEFILE_BACKREF_KEY", default_value = TARGET_ZONEFILE_LABEL)]
+    pub backref_key: String,
+}
+
+impl BackrefArgs {
+    /// Record a backreference to the zonefile on the zone, unless it already has one.
+    pub async fn apply(
+        &self,
+        client: Client,
+        controller_name: &str,
+        zonefile: &ZoneFile,
+        zone: &Zone,
+    ) -> Result<(), kube::Error> {
+        let (field, existing) = match self.backref {
+            BackrefMode::Label => ("labels", zone.labels()),
+            BackrefMode::Annotation => ("annotations", zone.annotations()),
+            BackrefMode::None => return Ok(()),
+        };
+
+        let zonefile_ref = format!(
+            "{}.{}",
+            zonefile.name_any(),
+            zonefile.namespace().as_ref().unwrap()
+        );
+
+        if existing.get(&self.backref_key) != Some(&zonefile_ref) {
+            info!(
+                "updating zone {}'s {} to {zonefile_ref}",
+                zone.name_any(),
+                self.backref_key
+            );
+
+            Api::<Zone>::namespaced(client, zone.namespace().as_ref().unwrap())
+                .patch_metadata(
+                    &zone.name_any(),
+                    &PatchParams::apply(controller_name),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            field: {
+                                &self.backref_key: zonefile_ref
+                            },
+                        }
+                    })),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Zonefiles whose specs reference the zone, and which should
+/// therefore be reconciled when it changes. Since references are
+/// matched by name, this includes zonefiles waiting for the zone
+/// to be created.
+pub fn zonefiles_referencing(zone: &Zone, zonefiles: &Store<ZoneFile>) -> Vec<ObjectRef<ZoneFile>> {
+    zonefiles
+        .state()
+        .iter()
+        .filter(|zonefile| {
+            zonefile.spec.zone_refs.iter().any(|zone_ref| {
+                zone_ref.name == zone.name_any()
+                    && zone_ref
+                        .namespace
+                        .as_ref()
+                        .or(zonefile.namespace().as_ref())
+                        == zone.namespace().as_ref()
+            })
+        })
+        .map(|zonefile| ObjectRef::from_obj(zonefile.as_ref()))
+        .collect()
+}
@@ -0,0 +1,281 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use clap::Args;
+use k8s_openapi::{api::core::v1::ConfigMap, chrono::Utc};
+use kube::{
+    api::{DeleteParams, ListParams, Patch, PatchParams, PostParams},
+    core::ObjectMeta,
+    Api, Client, CustomResource, ResourceExt as _,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{
+    digest,
+    error::Error,
+    naming::{FileNaming, FILE_NAMING_ANNOTATION},
+    ownership,
+    reconciliation::{DEFAULT_CONTROLLER_NAME, PAUSED_ANNOTATION, ZONEFILE_LABEL},
+};
+
+/// Length of the timestamp suffixed to the names of backups, with its separator.
+const TIMESTAMP_SUFFIX_LENGTH: usize = "-20240101000000".len();
+
+/// Snapshot of the ConfigMap published by a [`ZoneFile`].
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "kubi.zone",
+    version = "v1alpha1",
+    kind = "ZoneBackup",
+    namespaced
+)]
+#[kube(printcolumn = r#"{"name":"zonefile", "jsonPath": ".spec.zonefile", "type": "string"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneBackupSpec {
+    /// Name of the ZoneFile the backup was taken from.
+    pub zonefile: String,
+    /// Name of the ConfigMap the zones were published to.
+    pub config_map_name: String,
+    /// Contents of the ConfigMap at the time of the backup,
+    /// with the contents of each zone keyed by its origin.
+    pub data: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct BackupArgs {
+    /// Snapshot the published zones of every ZoneFile into
+    /// ZoneBackup resources at this interval, in seconds.
+    #[arg(long, env = "ZONEFILE_BACKUP_INTERVAL")]
+    pub backup_interval: Option<u64>,
+
+    /// Number of backups to keep for each ZoneFile. Older backups are deleted.
+    #[arg(long, env = "ZONEFILE_BACKUP_RETENTION", default_value_t = 7)]
+    pub backup_retention: usize,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RestoreArgs {
+    /// Name of the ZoneBackup to restore.
+    pub backup: String,
+
+    /// Namespace of the ZoneBackup. Defaults to the namespace of the current context.
+    #[arg(short, long)]
+    pub namespace: Option<String>,
+
+    /// Field manager under which the ConfigMap is applied. This should match
+    /// the name of the controller instance which manages the zonefile.
+    #[arg(long, env = "ZONEFILE_CONTROLLER_NAME", default_value = DEFAULT_CONTROLLER_NAME)]
+    pub controller_name: String,
+
+    /// Mark the ConfigMap as owned by its zonefile with a label rather than an
+    /// owner reference. This should match the ownership mode of the controller.
+    #[arg(long, env = "ZONEFILE_LABEL_OWNERSHIP")]
+    pub label_ownership: bool,
+
+    /// Resume reconciling the zonefile once the backup is restored, which republishes
+    /// its zones from their current Records. Otherwise the zonefile is left paused,
+    /// keeping the restored zones until the pause annotation is removed.
+    #[arg(long)]
+    pub resume: bool,
+}
+
+/// Snapshot the ConfigMap of a single zonefile, and prune old backups.
+async fn backup_zonefile(
+    client: Client,
+    zonefile: &ZoneFile,
+    retention: usize,
+) -> Result<(), Error> {
+    let namespace = zonefile.namespace().unwrap();
+
+    let configmap_name = zonefile
+        .spec
+        .config_map_name
+        .as_ref()
+        .cloned()
+        .unwrap_or(zonefile.name_any());
+
+    let Some(data) = Api::<ConfigMap>::namespaced(client.clone(), &namespace)
+        .get_opt(&configmap_name)
+        .await?
+        .and_then(|config_map| Some(FileNaming::of(&config_map).unpublish(config_map.data?)))
+    else {
+        debug!(
+            "zonefile {} has not published any zones yet, skipping backup",
+            zonefile.name_any()
+        );
+        return Ok(());
+    };
+
+    let backups = Api::<ZoneBackup>::namespaced(client, &namespace);
+    let label = digest::truncate(&zonefile.name_any(), 63);
+    let list_params = ListParams::default().labels(&format!("{ZONEFILE_LABEL}={label}"));
+
+    let mut existing = backups.list(&list_params).await?.items;
+    existing.sort_by_key(|backup| backup.creation_timestamp().map(|time| time.0));
+
+    if existing.last().is_some_and(|latest| {
+        latest.spec.config_map_name == configmap_name && latest.spec.data == data
+    }) {
+        debug!(
+            "zonefile {} has not changed since its last backup, skipping backup",
+            zonefile.name_any()
+        );
+        return Ok(());
+    }
+
+    let backup = ZoneBackup {
+        metadata: ObjectMeta {
+            name: Some(format!(
+                "{}-{}",
+                digest::truncate(&zonefile.name_any(), 253 - TIMESTAMP_SUFFIX_LENGTH),
+                Utc::now().format("%Y%m%d%H%M%S")
+            )),
+            namespace: Some(namespace.clone()),
+            labels: Some(BTreeMap::from([(ZONEFILE_LABEL.to_string(), label)])),
+            ..ObjectMeta::default()
+        },
+        spec: ZoneBackupSpec {
+            zonefile: zonefile.name_any(),
+            config_map_name: configmap_name,
+            data,
+        },
+    };
+
+    let backup = backups.create(&PostParams::default(), &backup).await?;
+    info!("backed up zonefile {namespace}/{}", zonefile.name_any());
+
+    existing.push(backup);
+
+    for backup in existing.iter().rev().skip(retention) {
+        info!("pruning backup {namespace}/{}", backup.name_any());
+        backups
+            .delete(&backup.name_any(), &DeleteParams::default())
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Periodically back up every zonefile in the cluster.
+pub async fn run(client: Client, args: BackupArgs) {
+    let Some(interval) = args.backup_interval else {
+        return;
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(interval));
+
+    loop {
+        interval.tick().await;
+
+        let zonefiles = match Api::<ZoneFile>::all(client.clone())
+            .list(&ListParams::default())
+            .await
+        {
+            Ok(zonefiles) => zonefiles,
+            Err(e) => {
+                error!("failed to list zonefiles for backup: {e}");
+                continue;
+            }
+        };
+
+        for zonefile in &zonefiles {
+            if let Err(e) = backup_zonefile(client.clone(), zonefile, args.backup_retention).await {
+                error!("failed to back up zonefile {}: {e}", zonefile.name_any());
+            }
+        }
+    }
+}
+
+/// Republish the contents of a backup to its ConfigMap.
+pub async fn restore(client: Client, args: RestoreArgs) -> Result<(), Error> {
+    let namespace = args
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+
+    let backup = Api::<ZoneBackup>::namespaced(client.clone(), &namespace)
+        .get(&args.backup)
+        .await?;
+
+    let zonefiles = Api::<ZoneFile>::namespaced(client.clone(), &namespace);
+    let zonefile = zonefiles.get_opt(&backup.spec.zonefile).await?;
+
+    // Pause the zonefile first, so that the controller does not overwrite the restored zones.
+    let pause = |paused: bool| {
+        Patch::Merge(json!({
+            "metadata": {
+                "annotations": {
+                    PAUSED_ANNOTATION: paused.then_some("true"),
+                },
+            }
+        }))
+    };
+
+    if zonefile.is_some() {
+        zonefiles
+            .patch_metadata(&backup.spec.zonefile, &PatchParams::default(), &pause(true))
+            .await?;
+    }
+
+    // Zones are published under the zonefile's current naming convention.
+    let naming: FileNaming = zonefile
+        .as_ref()
+        .and_then(|zonefile| zonefile.annotations().get(FILE_NAMING_ANNOTATION))
+        .and_then(|naming| naming.parse().ok())
+        .unwrap_or_default();
+
+    let mut config_map = ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(backup.spec.config_map_name.clone()),
+            namespace: Some(namespace.clone()),
+            annotations: Some(BTreeMap::from([(
+                FILE_NAMING_ANNOTATION.to_string(),
+                naming.to_string(),
+            )])),
+            ..ObjectMeta::default()
+        },
+        data: Some(naming.publish(backup.spec.data.clone())),
+        ..Default::default()
+    };
+
+    // Keep the ConfigMap owned by its zonefile, if it still exists.
+    if let Some(zonefile) = &zonefile {
+        ownership::mark_owned(zonefile, args.label_ownership, &mut config_map.metadata);
+    }
+
+    Api::<ConfigMap>::namespaced(client, &namespace)
+        .patch(
+            &backup.spec.config_map_name,
+            &PatchParams::apply(&args.controller_name).force(),
+            &Patch::Apply(config_map),
+        )
+        .await?;
+
+    info!(
+        "restored backup {namespace}/{} to configmap {}",
+        args.backup, backup.spec.config_map_name
+    );
+
+    if zonefile.is_some() {
+        if args.resume {
+            zonefiles
+                .patch_metadata(
+                    &backup.spec.zonefile,
+                    &PatchParams::default(),
+                    &pause(false),
+                )
+                .await?;
+            info!("resumed zonefile {namespace}/{}", backup.spec.zonefile);
+        } else {
+            info!(
+                "zonefile {namespace}/{} is paused, remove its {PAUSED_ANNOTATION} annotation to resume",
+                backup.spec.zonefile
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,244 @@
+use std::str::FromStr;
+
+use k8s_openapi::chrono::{DateTime, Datelike as _, TimeDelta, Timelike as _, Utc};
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding the windows
+/// during which changed zones are not published, separated by semicolons.
+///
+/// Each window is a cron expression in UTC for its start, followed by its
+/// length in minutes, hours or days, as in `0 18 24 12 * 9d` for a freeze
+/// from the evening of December 24th until January 2nd.
+pub const BLACKOUT_WINDOWS_ANNOTATION: &str = "zonefile.kubi.zone/blackout-windows";
+
+/// Longest supported window. Windows are found by scanning back through the
+/// minutes of their length, and longer freezes are better expressed as several.
+const MAX_WINDOW_DAYS: i64 = 31;
+
+/// Values of a cron field which match, as a bit set.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut values = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step {step:?}"))?,
+            ),
+            None => (part, 1),
+        };
+
+        let value = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .ok_or_else(|| format!("invalid value {value:?}, expected {min} to {max}"))
+        };
+
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            // A single value with a step, as in 5/15, repeats until the end of the range.
+            None if part.contains('/') => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+
+        if first > last {
+            return Err(format!("invalid range {range:?}"));
+        }
+
+        for value in (first..=last).step_by(step as usize) {
+            values |= 1 << value;
+        }
+    }
+
+    Ok(values)
+}
+
+/// Cron schedule of the starts of a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of the month or the day of the week was left unrestricted.
+    /// Like cron, a day matches either of the two when both are restricted.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [minutes, hours, days, months, weekdays] = s.split_whitespace().collect::<Vec<_>>()[..]
+        else {
+            return Err(format!("invalid schedule {s:?}, expected five fields"));
+        };
+
+        // Sunday may be given as either 0 or 7.
+        let days_of_week = parse_field(weekdays, 0, 7)?;
+
+        Ok(Schedule {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: (days_of_week | days_of_week >> 7) & 0x7f,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+impl Schedule {
+    fn matches(&self, time: DateTime<Utc>) -> bool {
+        let day = self.days & 1 << time.day() != 0;
+        let weekday = self.weekdays & 1 << time.weekday().num_days_from_sunday() != 0;
+
+        let day = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        };
+
+        day && self.minutes & 1 << time.minute() != 0
+            && self.hours & 1 << time.hour() != 0
+            && self.months & 1 << time.month() != 0
+    }
+}
+
+/// Period during which changed zones are computed, but not published.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlackoutWindow {
+    schedule: Schedule,
+    length: TimeDelta,
+}
+
+impl FromStr for BlackoutWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let Some((schedule, length)) = s.rsplit_once(char::is_whitespace) else {
+            return Err(format!(
+                "invalid blackout window {s:?}, expected a schedule and a length"
+            ));
+        };
+
+        let (count, unit) = length.split_at(length.len().saturating_sub(1));
+        let length = match (count.parse::<i64>(), unit) {
+            (Ok(count), "m") => TimeDelta::minutes(count),
+            (Ok(count), "h") => TimeDelta::hours(count),
+            (Ok(count), "d") => TimeDelta::days(count),
+            _ => {
+                return Err(format!(
+                    "invalid length {length:?}, expected a number of minutes (m), hours (h) or days (d)"
+                ))
+            }
+        };
+
+        if length <= TimeDelta::zero() || length > TimeDelta::days(MAX_WINDOW_DAYS) {
+            return Err(format!(
+                "invalid length {length}, expected at most {MAX_WINDOW_DAYS} days"
+            ));
+        }
+
+        Ok(BlackoutWindow {
+            schedule: schedule.parse()?,
+            length,
+        })
+    }
+}
+
+impl BlackoutWindow {
+    /// End of the most recently started occurrence of the window, if it is still ongoing.
+    fn active_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let minute = now.with_second(0)?.with_nanosecond(0)?;
+
+        (0..self.length.num_minutes())
+            .map(|minutes| minute - TimeDelta::minutes(minutes))
+            .find(|start| self.schedule.matches(*start))
+            .map(|start| start + self.length)
+            .filter(|end| *end > now)
+    }
+}
+
+/// Parse the value of the blackout windows annotation.
+pub fn parse(value: &str) -> Result<Vec<BlackoutWindow>, String> {
+    value
+        .split(';')
+        .filter(|window| !window.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Time at which the latest ending of the ongoing windows ends, if any are ongoing.
+pub fn active_until(windows: &[BlackoutWindow], now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    windows
+        .iter()
+        .filter_map(|window| window.active_until(now))
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::chrono::{TimeZone as _, Utc};
+
+    use super::{active_until, parse, parse_field};
+
+    #[test]
+    fn cron_fields() {
+        assert_eq!(parse_field("*", 0, 3), Ok(0b1111));
+        assert_eq!(parse_field("1-3", 0, 59), Ok(0b1110));
+        assert_eq!(
+            parse_field("*/15", 0, 59),
+            Ok(1 | 1 << 15 | 1 << 30 | 1 << 45)
+        );
+        assert_eq!(parse_field("50/5", 0, 59), Ok(1 << 50 | 1 << 55));
+        assert_eq!(parse_field("1,5", 1, 12), Ok(1 << 1 | 1 << 5));
+
+        assert!(parse_field("60", 0, 59).is_err());
+        assert!(parse_field("5-1", 0, 59).is_err());
+        assert!(parse_field("*/0", 0, 59).is_err());
+    }
+
+    #[test]
+    fn blackout_windows() {
+        let time = |month, day, hour, minute| {
+            Utc.with_ymd_and_hms(2024, month, day, hour, minute, 0)
+                .unwrap()
+        };
+
+        // A holiday freeze, and a weekly freeze from Friday evening to Monday morning.
+        let windows = parse("0 18 24 12 * 9d; 0 16 * * 5 64h").unwrap();
+
+        assert_eq!(active_until(&windows, time(12, 24, 17, 59)), None);
+        assert_eq!(
+            active_until(&windows, time(12, 24, 18, 0)),
+            Some(Utc.with_ymd_and_hms(2025, 1, 2, 18, 0, 0).unwrap())
+        );
+        assert_eq!(
+            active_until(&windows, time(12, 31, 23, 59)),
+            Some(Utc.with_ymd_and_hms(2025, 1, 2, 18, 0, 0).unwrap())
+        );
+
+        // Friday, March 1st 2024.
+        assert_eq!(active_until(&windows, time(3, 1, 15, 0)), None);
+        assert_eq!(
+            active_until(&windows, time(3, 2, 12, 0)),
+            Some(time(3, 4, 8, 0))
+        );
+        assert_eq!(active_until(&windows, time(3, 4, 8, 0)), None);
+
+        assert!(parse("0 18 24 12 9d").is_err());
+        assert!(parse("0 18 24 12 * 60d").is_err());
+        assert_eq!(parse(" ").map(|windows| windows.len()), Ok(0));
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    error::Error as StdError,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use ::http::Request;
+use clap::Args;
+use kube::{client::Body, Client};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct BudgetArgs {
+    /// Maximum number of Kubernetes API requests made by a single reconciliation.
+    /// Further requests fail, and so does the reconciliation, guarding the API server
+    /// against zonefiles referencing an excessive number of resources. A reconciliation
+    /// over its budget fails before publishing anything, and the status updates
+    /// reporting what it published are exempt from the budget.
+    #[arg(long, env = "ZONEFILE_API_REQUEST_BUDGET")]
+    pub api_request_budget: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("reconciliation exceeded its budget of {0} api requests")]
+pub struct BudgetExceeded(pub usize);
+
+tokio::task_local! {
+    static BUDGET: Arc<RequestBudget>;
+    static EXEMPT: ();
+}
+
+/// Kubernetes API requests made by a reconciliation, and the number it may make.
+#[derive(Debug, Default)]
+pub struct RequestBudget {
+    used: AtomicUsize,
+    exempted: AtomicUsize,
+    limit: Option<usize>,
+}
+
+impl RequestBudget {
+    pub fn new(limit: Option<usize>) -> Arc<Self> {
+        Arc::new(RequestBudget {
+            used: AtomicUsize::new(0),
+            exempted: AtomicUsize::new(0),
+            limit,
+        })
+    }
+
+    /// Count a request, failing if it exceeds the budget, unless it is exempt from it.
+    fn spend(&self, exempt: bool) -> Result<(), BudgetExceeded> {
+        let used = self.used.fetch_add(1, Ordering::Relaxed) + 1;
+        if exempt {
+            self.exempted.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        match self.limit {
+            Some(limit) if used - self.exempted.load(Ordering::Relaxed) > limit => {
+                Err(BudgetExceeded(limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of requests made so far, including those refused for exceeding the
+    /// budget and those exempt from it.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Whether any request was refused for exceeding the budget.
+    pub fn exceeded(&self) -> Option<BudgetExceeded> {
+        self.limit
+            .filter(|limit| self.used() - self.exempted.load(Ordering::Relaxed) > *limit)
+            .map(BudgetExceeded)
+    }
+
+    /// Run the future, counting the requests it makes through a [`counted`] client.
+    pub async fn scope<F: Future>(self: &Arc<Self>, future: F) -> F::Output {
+        BUDGET.scope(self.clone(), future).await
+    }
+}
+
+/// Fail if the reconciliation calling this has exceeded its budget. Requests refused
+/// for exceeding it may have been retried or ignored, so this is checked before the
+/// reconciliation writes anything, rather than leaving it half published.
+pub fn ensure_within() -> Result<(), BudgetExceeded> {
+    match BUDGET.try_with(|budget| budget.exceeded()) {
+        Ok(Some(exceeded)) => Err(exceeded),
+        _ => Ok(()),
+    }
+}
+
+/// Run the future, exempting the requests it makes from the budget of the
+/// reconciliation, such as the status update which reports its outcome.
+pub async fn exempt<F: Future>(future: F) -> F::Output {
+    EXEMPT.scope((), future).await
+}
+
+/// Count a request against the budget of the reconciliation making it, if any.
+fn charge() -> Result<(), BudgetExceeded> {
+    let exempt = EXEMPT.try_with(|_| ()).is_ok();
+
+    BUDGET
+        .try_with(|budget| budget.spend(exempt))
+        .unwrap_or(Ok(()))
+}
+
+/// Wrap the client, so that the requests made through it count against the budget
+/// of the reconciliation making them. Requests made outside of any reconciliation,
+/// such as those of the watches, are not counted.
+pub fn counted(client: Client) -> Client {
+    let default_namespace = client.default_namespace().to_string();
+
+    Client::new(
+        tower::service_fn(move |request: Request<Body>| {
+            let client = client.clone();
+
+            async move {
+                if let Err(exceeded) = charge() {
+                    return Err(Box::new(exceeded) as Box<dyn StdError + Send + Sync>);
+                }
+
+                client.send(request).await.map_err(Into::into)
+            }
+        }),
+        default_namespace,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{charge, ensure_within, exempt, BudgetExceeded, RequestBudget, BUDGET};
+
+    #[tokio::test]
+    async fn counts_requests_within_scope() {
+        let budget = RequestBudget::new(Some(2));
+
+        let results = budget
+            .scope(async {
+                (0..3)
+                    .map(|_| BUDGET.with(|budget| budget.spend(false)))
+                    .collect::<Vec<_>>()
+            })
+            .await;
+
+        assert_eq!(results, vec![Ok(()), Ok(()), Err(BudgetExceeded(2))]);
+        assert_eq!(budget.used(), 3);
+        assert_eq!(budget.exceeded(), Some(BudgetExceeded(2)));
+
+        // Requests outside of the scope are not counted.
+        assert!(BUDGET.try_with(|budget| budget.spend(false)).is_err());
+
+        let unlimited = RequestBudget::new(None);
+        unlimited
+            .scope(async { BUDGET.with(|budget| budget.spend(false)) })
+            .await
+            .unwrap();
+        assert_eq!(unlimited.exceeded(), None);
+    }
+
+    #[tokio::test]
+    async fn exempts_requests() {
+        let budget = RequestBudget::new(Some(1));
+
+        let (within, exempted, after) = budget
+            .scope(async {
+                let within = charge();
+                let exempted = exempt(async { charge() }).await;
+                (within, exempted, ensure_within())
+            })
+            .await;
+
+        assert_eq!((within, exempted, after), (Ok(()), Ok(()), Ok(())));
+        assert_eq!(budget.used(), 2);
+        assert_eq!(budget.exceeded(), None);
+
+        let exhausted = budget
+            .scope(async {
+                let _ = charge();
+                ensure_within()
+            })
+            .await;
+        assert_eq!(exhausted, Err(BudgetExceeded(1)));
+    }
+}
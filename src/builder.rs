@@ -0,0 +1,450 @@
+use kubizone_common::{Class, FullyQualifiedDomainName};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    contact, normalize,
+    render::{self, build_zonefile_with_services, Comments, EncodingError, FormatArgs},
+    service::{self, ServiceRecord},
+    validation::{self, ValidationArgs, ValidationError},
+};
+
+pub use kubizone_common::Type;
+
+/// Fields of the SOA record of a zone built by a [`ZoneBuilder`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Soa {
+    /// Primary nameserver of the zone, relative to its origin or absolute.
+    pub nameserver: String,
+    /// Email address of the party responsible for the zone.
+    pub contact: String,
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    /// Negative caching TTL.
+    pub minimum: u32,
+    /// TTL of the SOA record itself.
+    pub ttl: u32,
+}
+
+impl Soa {
+    /// SOA record with the timers recommended by [RFC 1912 section 2.2](https://www.rfc-editor.org/rfc/rfc1912#section-2.2).
+    pub fn new(nameserver: impl Into<String>, contact: impl Into<String>, serial: u32) -> Self {
+        Soa {
+            nameserver: nameserver.into(),
+            contact: contact.into(),
+            serial,
+            refresh: 3600,
+            retry: 900,
+            expire: 1209600,
+            minimum: 3600,
+            ttl: 3600,
+        }
+    }
+}
+
+/// Record of a zone, as added through [`ZoneBuilder::record`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: Type,
+    pub ttl: u32,
+    pub rdata: String,
+}
+
+/// Zone before validation and rendering, which can be persisted or
+/// transmitted as is, and later built with [`ZoneBuilder::from`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Zone {
+    pub origin: String,
+    pub soa: Soa,
+    #[serde(default)]
+    pub records: Vec<Record>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BuildError {
+    #[error("zone has no origin")]
+    MissingOrigin,
+    #[error("invalid origin {origin:?}: {reason}")]
+    InvalidOrigin { origin: String, reason: String },
+    #[error("zone has no SOA record")]
+    MissingSoa,
+    #[error("invalid contact {0:?}, expected an email address")]
+    InvalidContact(String),
+    #[error("invalid name {name:?}: {reason}")]
+    InvalidName { name: String, reason: String },
+    #[error("name {name} is not within the zone {origin}")]
+    OutsideZone { name: String, origin: String },
+    #[error("invalid {type_} record for {name}: {error}")]
+    InvalidRecord {
+        name: String,
+        type_: Type,
+        error: ValidationError,
+    },
+    #[error("invalid zone contents: {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+/// Builds the text of a zone from its origin, SOA and records, validating and
+/// rendering them as the controller does, without any Kubernetes resources.
+///
+/// ```no_run
+/// use zonefile::builder::{Soa, Type, ZoneBuilder};
+///
+/// let zone = ZoneBuilder::new()
+///     .origin("example.org.")
+///     .soa(Soa::new("ns1", "hostmaster@example.org", 2024010101))
+///     .record("@", Type::NS, 3600, "ns1")
+///     .record("ns1", Type::A, 3600, "192.0.2.1")
+///     .record("www", Type::CNAME, 300, "@")
+///     .build()?;
+/// # Ok::<(), zonefile::builder::BuildError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ZoneBuilder {
+    origin: Option<String>,
+    soa: Option<Soa>,
+    records: Vec<Record>,
+    format: FormatArgs,
+    validation: ValidationArgs,
+}
+
+impl ZoneBuilder {
+    pub fn new() -> Self {
+        ZoneBuilder::default()
+    }
+
+    /// Fully qualified name of the zone, such as `example.org.`.
+    pub fn origin(mut self, origin: impl Into<String>) -> Self {
+        self.origin = Some(origin.into());
+        self
+    }
+
+    pub fn soa(mut self, soa: Soa) -> Self {
+        self.soa = Some(soa);
+        self
+    }
+
+    /// Add a record owned by the name, which is either relative to the origin,
+    /// `@` for the origin itself, or absolute. Names within the rdata are
+    /// likewise relative to the origin, unless they end with a dot.
+    pub fn record(
+        mut self,
+        name: impl Into<String>,
+        type_: Type,
+        ttl: u32,
+        rdata: impl Into<String>,
+    ) -> Self {
+        self.records.push(Record {
+            name: name.into(),
+            type_,
+            ttl,
+            rdata: rdata.into(),
+        });
+        self
+    }
+
+    pub fn format(mut self, format: FormatArgs) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Policies the records must satisfy, such as the allowed CAA issuers.
+    pub fn validation(mut self, validation: ValidationArgs) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Validate the records and render the zone, with its SOA record first.
+    pub fn build(self) -> Result<String, BuildError> {
+        let origin = self.origin.ok_or(BuildError::MissingOrigin)?;
+        let origin = FullyQualifiedDomainName::try_from(origin.as_str()).map_err(|e| {
+            BuildError::InvalidOrigin {
+                origin: origin.clone(),
+                reason: e.to_string(),
+            }
+        })?;
+
+        let soa = self.soa.ok_or(BuildError::MissingSoa)?;
+        let rname = contact::rname(&soa.contact)
+            .ok_or_else(|| BuildError::InvalidContact(soa.contact.clone()))?;
+
+        let mut entries = vec![ZoneEntry {
+            fqdn: origin.clone(),
+            type_: Type::SOA,
+            class: Class::IN,
+            ttl: soa.ttl,
+            rdata: format!(
+                "{} {rname} {} {} {} {} {}",
+                soa.nameserver, soa.serial, soa.refresh, soa.retry, soa.expire, soa.minimum
+            ),
+        }];
+
+        let mut services = Vec::new();
+        for record in self.records {
+            if let Some(owner) = service_owner(&origin, &record.name)? {
+                services.push(ServiceRecord {
+                    owner,
+                    type_: record.type_,
+                    class: Class::IN,
+                    ttl: record.ttl,
+                    rdata: record.rdata,
+                });
+                continue;
+            }
+
+            entries.push(ZoneEntry {
+                fqdn: owner(&origin, &record.name)?,
+                type_: record.type_,
+                class: Class::IN,
+                ttl: record.ttl,
+                rdata: record.rdata,
+            });
+        }
+
+        let (entries, invalid) = validation::validate_entries(&entries, &self.validation);
+        if let Some((entry, error)) = invalid.into_iter().next() {
+            return Err(BuildError::InvalidRecord {
+                name: entry.fqdn.to_string(),
+                type_: entry.type_,
+                error,
+            });
+        }
+
+        let (services, invalid) = service::validate(services, &self.validation);
+        if let Some((record, error)) = invalid.into_iter().next() {
+            return Err(BuildError::InvalidRecord {
+                name: record.owner,
+                type_: record.type_,
+                error,
+            });
+        }
+
+        let (mut entries, _) = normalize::dedup(normalize::canonicalize(&origin, entries));
+        normalize::sort_by_priority(&mut entries);
+        let services = service::canonicalize(&origin, services);
+
+        let rendered = build_zonefile_with_services(
+            &origin,
+            &entries,
+            &services,
+            &self.format,
+            &Comments::default(),
+        );
+        Ok(render::finish(&rendered, &self.format)?)
+    }
+}
+
+impl From<Zone> for ZoneBuilder {
+    fn from(zone: Zone) -> Self {
+        ZoneBuilder {
+            origin: Some(zone.origin),
+            soa: Some(zone.soa),
+            records: zone.records,
+            ..ZoneBuilder::default()
+        }
+    }
+}
+
+/// Owner name of a record, resolved against the origin, which it must fall within.
+fn owner(
+    origin: &FullyQualifiedDomainName,
+    name: &str,
+) -> Result<FullyQualifiedDomainName, BuildError> {
+    let absolute = match name {
+        "@" => return Ok(origin.clone()),
+        name if name.ends_with('.') => name.to_string(),
+        name => format!("{name}.{origin}"),
+    };
+
+    let fqdn = FullyQualifiedDomainName::try_from(normalize::to_ascii(&absolute).as_str())
+        .map_err(|e| BuildError::InvalidName {
+            name: name.to_string(),
+            reason: e.to_string(),
+        })?;
+
+    if fqdn != *origin && !fqdn.is_subdomain_of(origin) {
+        return Err(BuildError::OutsideZone {
+            name: fqdn.to_string(),
+            origin: origin.to_string(),
+        });
+    }
+
+    Ok(fqdn)
+}
+
+/// Absolute owner name of a record, if it holds service labels such as `_dmarc`,
+/// which zone entries cannot be owned by. It must fall within the origin.
+fn service_owner(
+    origin: &FullyQualifiedDomainName,
+    name: &str,
+) -> Result<Option<String>, BuildError> {
+    let absolute = match name {
+        name if name.ends_with('.') => name.to_string(),
+        name => format!("{name}.{origin}"),
+    };
+
+    let Some(owner) = service::service_name(&absolute) else {
+        return Ok(None);
+    };
+
+    if !service::within(&owner, origin) {
+        return Err(BuildError::OutsideZone {
+            name: owner,
+            origin: origin.to_string(),
+        });
+    }
+
+    Ok(Some(owner))
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use k8s_openapi::serde_json;
+
+    use crate::render::FormatArgs;
+
+    use super::{BuildError, Record, Soa, Type, Zone, ZoneBuilder};
+
+    fn zone() -> ZoneBuilder {
+        ZoneBuilder::new()
+            .origin("example.org.")
+            .soa(Soa::new("ns1", "hostmaster@example.org", 1))
+    }
+
+    #[test]
+    fn builds_zone() {
+        let zone = zone()
+            .record("@", Type::NS, 3600, "ns1")
+            .record("ns1", Type::A, 3600, "192.0.2.1")
+            .record("WWW.example.org.", Type::CNAME, 300, "@")
+            .format(FormatArgs {
+                no_align: true,
+                ..FormatArgs::default()
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            zone,
+            indoc! { "
+                $ORIGIN example.org.
+
+                @ 3600 IN SOA ns1.example.org. hostmaster.example.org. 1 3600 900 1209600 3600
+                @ 3600 IN NS ns1.example.org.
+                ns1 3600 IN A 192.0.2.1
+                www 300 IN CNAME example.org.
+            "}
+        );
+    }
+
+    #[test]
+    fn builds_service_records() {
+        let rendered = zone()
+            .record("@", Type::MX, 3600, "10 mail")
+            .record("_dmarc", Type::TXT, 3600, "\"v=DMARC1; p=reject\"")
+            .record("_acme-challenge.mail", Type::TXT, 60, "\"token\"")
+            .record("_SIP._TCP.example.org.", Type::SRV, 3600, "0 5 5060 mail")
+            .format(FormatArgs {
+                no_align: true,
+                ..FormatArgs::default()
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            rendered,
+            indoc! { r#"
+                $ORIGIN example.org.
+
+                @ 3600 IN SOA ns1.example.org. hostmaster.example.org. 1 3600 900 1209600 3600
+                @ 3600 IN MX 10 mail.example.org.
+                _acme-challenge.mail 60 IN TXT "token"
+                _dmarc 3600 IN TXT "v=DMARC1; p=reject"
+                _sip._tcp 3600 IN SRV 0 5 5060 mail.example.org.
+            "#}
+        );
+
+        assert!(matches!(
+            zone()
+                .record("_dmarc.example.com.", Type::TXT, 300, "\"v=DMARC1\"")
+                .build(),
+            Err(BuildError::OutsideZone { .. })
+        ));
+        assert!(matches!(
+            zone().record("_sip._udp", Type::SRV, 300, "0 5").build(),
+            Err(BuildError::InvalidRecord {
+                type_: Type::SRV,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_zones() {
+        assert_eq!(ZoneBuilder::new().build(), Err(BuildError::MissingOrigin));
+        assert_eq!(
+            ZoneBuilder::new().origin("example.org.").build(),
+            Err(BuildError::MissingSoa)
+        );
+        assert!(matches!(
+            zone()
+                .record("www.example.com.", Type::A, 300, "192.0.2.1")
+                .build(),
+            Err(BuildError::OutsideZone { .. })
+        ));
+        assert!(matches!(
+            zone().record("www", Type::A, 300, "not an address").build(),
+            Err(BuildError::InvalidRecord { type_: Type::A, .. })
+        ));
+    }
+
+    #[test]
+    fn deserializes_zones() {
+        let deserialized: Zone = serde_yaml::from_str(indoc! { "
+            origin: example.org.
+            soa:
+              nameserver: ns1
+              contact: hostmaster@example.org
+              serial: 1
+              refresh: 3600
+              retry: 900
+              expire: 1209600
+              minimum: 3600
+              ttl: 3600
+            records:
+              - name: '@'
+                type: NS
+                ttl: 3600
+                rdata: ns1
+        "})
+        .unwrap();
+
+        assert_eq!(
+            deserialized,
+            Zone {
+                origin: String::from("example.org."),
+                soa: Soa::new("ns1", "hostmaster@example.org", 1),
+                records: vec![Record {
+                    name: String::from("@"),
+                    type_: Type::NS,
+                    ttl: 3600,
+                    rdata: String::from("ns1"),
+                }],
+            }
+        );
+
+        let json = serde_json::to_string(&deserialized).unwrap();
+        assert_eq!(serde_json::from_str::<Zone>(&json).unwrap(), deserialized);
+
+        assert_eq!(
+            ZoneBuilder::from(deserialized).build(),
+            zone().record("@", Type::NS, 3600, "ns1").build()
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use kubizone_crds::v1alpha1::ZoneEntry;
+
+use crate::{
+    conditions::Observations,
+    negative::NegativeCachingArgs,
+    render::FormatArgs,
+    template::ZoneFileTemplateSpec,
+    validation::{ValidationArgs, WildcardPolicy},
+};
+
+/// A zone as rendered by an earlier reconciliation, along with
+/// the conditions which were observed while rendering it.
+#[derive(Clone)]
+pub struct RenderedZone {
+    pub entries: Vec<ZoneEntry>,
+    pub duplicates: usize,
+    pub rendered: String,
+    pub observations: Observations,
+}
+
+/// Inputs a zone is rendered from: the hash of its contents, as computed
+/// upstream, and everything else affecting its rendering.
+#[derive(Hash)]
+pub struct RenderInputs<'a> {
+    pub zone_hash: &'a str,
+    pub zone_ttl: u32,
+    pub zone_annotations: &'a BTreeMap<String, String>,
+    pub zonefile_annotations: &'a BTreeMap<String, String>,
+    pub format: &'a FormatArgs,
+    pub validation: &'a ValidationArgs,
+    pub negative: &'a NegativeCachingArgs,
+    pub template: &'a ZoneFileTemplateSpec,
+    pub wildcard_policy: WildcardPolicy,
+    pub default_nameservers: &'a [String],
+}
+
+/// Digest of the [`RenderInputs`] a zone was rendered from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderKey(blake3::Hash);
+
+/// Feeds everything hashed into it to BLAKE3, so that renderings are
+/// identified by a cryptographic digest of their inputs.
+struct Digest(blake3::Hasher);
+
+impl Hasher for Digest {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let digest = self.0.finalize();
+        u64::from_le_bytes(digest.as_bytes()[..8].try_into().unwrap())
+    }
+}
+
+impl RenderInputs<'_> {
+    pub fn key(&self) -> RenderKey {
+        let mut digest = Digest(blake3::Hasher::new());
+        self.hash(&mut digest);
+        RenderKey(digest.0.finalize())
+    }
+}
+
+/// Renderings of a zonefile's zones by origin, with the key of their inputs.
+type RenderedZones = HashMap<String, (RenderKey, RenderedZone)>;
+
+/// Most recent rendering of each zone, per zonefile, so that reconciliations
+/// triggered by changes to other zones, or by unrelated events, skip
+/// rendering the zones which are unchanged.
+///
+/// Only one rendering is kept per zone, and it is replaced as soon as the
+/// zone is rendered from different inputs. Renderings of zones the zonefile
+/// no longer publishes, and of deleted zonefiles, are evicted.
+#[derive(Default)]
+pub struct RenderCache(Mutex<HashMap<String, RenderedZones>>);
+
+impl RenderCache {
+    /// The rendering of the zone, if it was last rendered from the inputs identified by the key.
+    pub fn get(&self, zonefile: &str, origin: &str, key: RenderKey) -> Option<RenderedZone> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(zonefile)
+            .and_then(|zones| zones.get(origin))
+            .filter(|(cached, _)| *cached == key)
+            .map(|(_, zone)| zone.clone())
+    }
+
+    pub fn insert(&self, zonefile: &str, origin: &str, key: RenderKey, zone: RenderedZone) {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(zonefile.to_string())
+            .or_default()
+            .insert(origin.to_string(), (key, zone));
+    }
+
+    /// Evict the renderings of the zonefile's zones other than those of the origins.
+    pub fn retain(&self, zonefile: &str, origins: &[&str]) {
+        let mut cache = self.0.lock().unwrap();
+        if let Some(zones) = cache.get_mut(zonefile) {
+            zones.retain(|origin, _| origins.contains(&origin.as_str()));
+
+            if zones.is_empty() {
+                cache.remove(zonefile);
+            }
+        }
+    }
+
+    /// Evict the renderings of all the zones of a deleted zonefile.
+    pub fn forget(&self, zonefile: &str) {
+        self.0.lock().unwrap().remove(zonefile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        conditions::Observations,
+        negative::NegativeCachingArgs,
+        render::FormatArgs,
+        template::ZoneFileTemplateSpec,
+        validation::{ValidationArgs, WildcardPolicy},
+    };
+
+    use super::{RenderCache, RenderInputs, RenderedZone};
+
+    #[test]
+    fn keeps_latest_rendering() {
+        let cache = RenderCache::default();
+        let zone = |rendered: &str| RenderedZone {
+            entries: Vec::new(),
+            duplicates: 0,
+            rendered: rendered.to_string(),
+            observations: Observations::default(),
+        };
+
+        let annotations = BTreeMap::new();
+        let format = FormatArgs::default();
+        let validation = ValidationArgs::default();
+        let negative = NegativeCachingArgs::default();
+        let template = ZoneFileTemplateSpec::default();
+        let inputs = |zone_hash| RenderInputs {
+            zone_hash,
+            zone_ttl: 3600,
+            zone_annotations: &annotations,
+            zonefile_annotations: &annotations,
+            format: &format,
+            validation: &validation,
+            negative: &negative,
+            template: &template,
+            wildcard_policy: WildcardPolicy::Allow,
+            default_nameservers: &[],
+        };
+
+        let first = inputs("hash-1").key();
+        let second = inputs("hash-2").key();
+        assert_eq!(first, inputs("hash-1").key());
+        assert_ne!(first, second);
+        assert_ne!(
+            first,
+            RenderInputs {
+                wildcard_policy: WildcardPolicy::Deny,
+                ..inputs("hash-1")
+            }
+            .key()
+        );
+
+        cache.insert("default/zonefile", "example.org.", first, zone("v1"));
+        assert_eq!(
+            cache
+                .get("default/zonefile", "example.org.", first)
+                .map(|zone| zone.rendered),
+            Some(String::from("v1"))
+        );
+        assert!(cache
+            .get("default/zonefile", "example.org.", second)
+            .is_none());
+
+        cache.insert("default/zonefile", "example.org.", second, zone("v2"));
+        assert!(cache
+            .get("default/zonefile", "example.org.", first)
+            .is_none());
+        assert!(cache.get("default/other", "example.org.", second).is_none());
+    }
+
+    #[test]
+    fn evicts_unpublished_zones() {
+        let cache = RenderCache::default();
+        let key = RenderInputs {
+            zone_hash: "hash",
+            zone_ttl: 3600,
+            zone_annotations: &BTreeMap::new(),
+            zonefile_annotations: &BTreeMap::new(),
+            format: &FormatArgs::default(),
+            validation: &ValidationArgs::default(),
+            negative: &NegativeCachingArgs::default(),
+            template: &ZoneFileTemplateSpec::default(),
+            wildcard_policy: WildcardPolicy::Allow,
+            default_nameservers: &[],
+        }
+        .key();
+        let zone = || RenderedZone {
+            entries: Vec::new(),
+            duplicates: 0,
+            rendered: String::new(),
+            observations: Observations::default(),
+        };
+
+        cache.insert("default/zonefile", "example.org.", key, zone());
+        cache.insert("default/zonefile", "example.com.", key, zone());
+        cache.insert("default/other", "example.org.", key, zone());
+
+        cache.retain("default/zonefile", &["example.org."]);
+        assert!(cache.get("default/zonefile", "example.org.", key).is_some());
+        assert!(cache.get("default/zonefile", "example.com.", key).is_none());
+
+        cache.forget("default/zonefile");
+        assert!(cache.get("default/zonefile", "example.org.", key).is_none());
+        assert!(cache.get("default/other", "example.org.", key).is_some());
+    }
+}
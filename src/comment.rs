@@ -0,0 +1,129 @@
+use clap::Args;
+use futures::{channel::mpsc, StreamExt as _};
+use kube::{
+    runtime::{
+        reflector::{self, ObjectRef, Store},
+        watcher, WatchStreamExt as _,
+    },
+    Api, Client, ResourceExt as _,
+};
+use kubizone_crds::v1alpha1::{Record, ZoneEntry};
+use tokio::task::JoinSet;
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{acme, render::Comments};
+
+/// Annotation on a [`Record`] holding a comment, which is rendered after
+/// the record in the zone, to explain its purpose to those reading the
+/// zone on the DNS servers.
+pub const COMMENT_ANNOTATION: &str = "zonefile.kubi.zone/comment";
+
+#[derive(Debug, Clone, Args)]
+pub struct CommentArgs {
+    /// Render the comments of Records annotated with `zonefile.kubi.zone/comment`
+    /// after the records in the zones. Requires access to Records in all namespaces.
+    #[arg(long, env = "ZONEFILE_RECORD_COMMENTS")]
+    pub record_comments: bool,
+}
+
+/// Records of the cluster, kept up to date by [`CommentArgs::watch`].
+#[derive(Clone)]
+pub struct RecordComments {
+    store: Store<Record>,
+}
+
+impl RecordComments {
+    /// Comments of the records in the namespace of a zonefile, so that Records
+    /// elsewhere cannot annotate its zones. A comment is attached to the zone
+    /// entry matching the record it annotates, once both are canonicalized.
+    pub fn comments(&self, namespace: &str) -> Comments {
+        let mut comments = Comments::default();
+
+        for record in self.store.state() {
+            if record.namespace().as_deref() != Some(namespace) {
+                continue;
+            }
+
+            let (Some(fqdn), Some(comment)) =
+                (record.fqdn(), record.annotations().get(COMMENT_ANNOTATION))
+            else {
+                continue;
+            };
+
+            let entry = ZoneEntry {
+                fqdn: fqdn.clone(),
+                type_: record.spec.type_,
+                class: record.spec.class,
+                ttl: record.spec.ttl.unwrap_or_default(),
+                rdata: record.spec.rdata.clone(),
+            };
+
+            comments.insert(&entry, comment);
+        }
+
+        comments
+    }
+
+    /// Zonefiles in the namespace of the record which published a zone containing
+    /// it, and which should therefore be reconciled when its comment changes.
+    pub fn zonefiles_containing(
+        record: &Record,
+        zonefiles: &Store<ZoneFile>,
+    ) -> Vec<ObjectRef<ZoneFile>> {
+        let Some(fqdn) = record.fqdn() else {
+            return Vec::new();
+        };
+
+        zonefiles
+            .state()
+            .iter()
+            .filter(|zonefile| zonefile.namespace() == record.namespace())
+            .filter(|zonefile| {
+                zonefile.status.as_ref().is_some_and(|status| {
+                    acme::publishes_within(status.serial.keys().map(String::as_str), fqdn)
+                })
+            })
+            .map(|zonefile| ObjectRef::from_obj(zonefile.as_ref()))
+            .collect()
+    }
+}
+
+impl CommentArgs {
+    /// Start watching the records of the cluster, if enabled. Returns the
+    /// records, along with a stream of the records which changed.
+    pub fn watch(
+        &self,
+        client: Client,
+        config: watcher::Config,
+        tasks: &mut JoinSet<()>,
+    ) -> Option<(RecordComments, mpsc::UnboundedReceiver<Record>)> {
+        if !self.record_comments {
+            return None;
+        }
+
+        let (store, writer) = reflector::store();
+
+        let (changed, receiver) = mpsc::unbounded();
+        tasks.spawn(
+            reflector::reflector(
+                writer,
+                watcher(Api::<Record>::all(client), config).default_backoff(),
+            )
+            .for_each(move |event| {
+                match event {
+                    Ok(event) => {
+                        for record in event.into_iter_touched() {
+                            let _ = changed.unbounded_send(record);
+                        }
+                    }
+                    Err(e) => warn!("failed to watch records: {e}"),
+                }
+
+                futures::future::ready(())
+            }),
+        );
+
+        Some((RecordComments { store }, receiver))
+    }
+}
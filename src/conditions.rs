@@ -0,0 +1,123 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use k8s_openapi::{
+    apimachinery::pkg::apis::meta::v1::{Condition, Time},
+    chrono::Utc,
+};
+
+/// The zonefile was published, but some of its contents were
+/// excluded or are otherwise not as specified.
+pub const DEGRADED: &str = "Degraded";
+
+/// Some of the zonefile's zones exceed the limits configured
+/// for the controller, and were not published in full.
+pub const QUOTA_EXCEEDED: &str = "QuotaExceeded";
+
+/// Some of the zonefile's zones have lost their status upstream, and
+/// are served from their last published contents until it returns.
+pub const STALE: &str = "Stale";
+
+/// Some of the zonefile's zones changed in a suspicious way,
+/// such as losing many of their records at once.
+pub const ANOMALY: &str = "Anomaly";
+
+/// Some of the zonefile's zones are in other namespaces which do not grant
+/// access to the zonefile's namespace, or may not be read by its service
+/// account, and were not published.
+pub const REFERENCE_DENIED: &str = "ReferenceDenied";
+
+/// Some of the zones referenced by the zonefile do not exist yet. The zonefile
+/// is reconciled as soon as they are created, and published without them until then.
+pub const WAITING_FOR_ZONE: &str = "WaitingForZone";
+
+/// Records of some of the zonefile's zones violate lint rules with a severity of
+/// warning or error. Zones violating rules with a severity of error were not published.
+pub const LINT_VIOLATION: &str = "LintViolation";
+
+/// Condition types reported on every zonefile, along with the
+/// reason given when the condition is *not* observed.
+const KNOWN_CONDITIONS: &[(&str, &str)] = &[
+    (DEGRADED, "AsExpected"),
+    (QUOTA_EXCEEDED, "WithinQuota"),
+    (STALE, "UpToDate"),
+    (ANOMALY, "NoAnomalies"),
+    (REFERENCE_DENIED, "ReferencesGranted"),
+    (WAITING_FOR_ZONE, "ZonesFound"),
+    (LINT_VIOLATION, "NoLintViolations"),
+];
+
+/// Conditions observed during a single reconciliation.
+#[derive(Clone, Default)]
+pub struct Observations(BTreeMap<&'static str, (String, Vec<String>)>);
+
+impl Observations {
+    /// Mark the condition as observed. If the same condition is observed
+    /// multiple times, the first reason is kept and the messages are joined.
+    pub fn observe(&mut self, type_: &'static str, reason: &str, message: impl Into<String>) {
+        self.0
+            .entry(type_)
+            .or_insert_with(|| (reason.to_string(), Vec::new()))
+            .1
+            .push(message.into());
+    }
+
+    /// Add the conditions observed elsewhere, as if they had been observed here.
+    pub fn extend(&mut self, other: Observations) {
+        for (type_, (reason, messages)) in other.0 {
+            self.0
+                .entry(type_)
+                .or_insert_with(|| (reason, Vec::new()))
+                .1
+                .extend(messages);
+        }
+    }
+}
+
+/// Last reported conditions for each zonefile, used for keeping
+/// `lastTransitionTime` stable while a condition does not change.
+#[derive(Default)]
+pub struct Conditions(Mutex<HashMap<String, Vec<Condition>>>);
+
+impl Conditions {
+    /// Produce the full list of conditions for a zonefile from the observations.
+    pub fn update(
+        &self,
+        zonefile: &str,
+        generation: Option<i64>,
+        observations: Observations,
+    ) -> Vec<Condition> {
+        let mut previous = self.0.lock().unwrap();
+        let previous = previous.entry(zonefile.to_string()).or_default();
+
+        let conditions: Vec<_> = KNOWN_CONDITIONS
+            .iter()
+            .map(|(type_, default_reason)| {
+                let (status, reason, message) = match observations.0.get(type_) {
+                    Some((reason, messages)) => ("True", reason.clone(), messages.join("; ")),
+                    None => ("False", default_reason.to_string(), String::new()),
+                };
+
+                let last_transition_time = previous
+                    .iter()
+                    .find(|condition| condition.type_ == *type_ && condition.status == status)
+                    .map(|condition| condition.last_transition_time.clone())
+                    .unwrap_or_else(|| Time(Utc::now()));
+
+                Condition {
+                    type_: type_.to_string(),
+                    status: status.to_string(),
+                    reason,
+                    message,
+                    observed_generation: generation,
+                    last_transition_time,
+                }
+            })
+            .collect();
+
+        *previous = conditions.clone();
+        conditions
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+};
+
+use clap::{ArgMatches, Command};
+use serde_yaml::Value;
+
+use crate::error::Error;
+
+/// Environment variable naming the configuration file, as an alternative to `--config`.
+pub const CONFIG_ENV: &str = "ZONEFILE_CONFIG";
+
+/// YAML file holding controller options, keyed by the name of their long flag:
+///
+/// ```yaml
+/// metrics-address: 0.0.0.0:9090
+/// publish-stats: true
+/// max-records-per-zone: 10000
+/// ```
+///
+/// Options are parsed along with the command line, as if they were passed
+/// as flags, but flags and environment variables take precedence over the file.
+pub struct ConfigFile {
+    path: PathBuf,
+}
+
+/// An option of the command, as it may be given in the configuration file.
+struct ConfigOption {
+    short: Option<char>,
+    env: Option<OsString>,
+    takes_value: bool,
+}
+
+/// All options in the command and its subcommands, by their long flag.
+fn options(command: &Command) -> BTreeMap<String, ConfigOption> {
+    let mut found = BTreeMap::new();
+
+    for arg in command.get_arguments() {
+        if let Some(long) = arg.get_long() {
+            found.insert(
+                long.to_string(),
+                ConfigOption {
+                    short: arg.get_short(),
+                    env: arg.get_env().map(OsStr::to_os_string),
+                    takes_value: arg.get_action().takes_values(),
+                },
+            );
+        }
+    }
+
+    for subcommand in command.get_subcommands() {
+        found.extend(options(subcommand));
+    }
+
+    found
+}
+
+/// Whether the option is given on the command line, in its long or short form.
+fn given(args: &[OsString], long: &str, short: Option<char>) -> bool {
+    args.iter().filter_map(|arg| arg.to_str()).any(|arg| {
+        arg.strip_prefix("--")
+            .is_some_and(|flag| flag == long || flag.starts_with(&format!("{long}=")))
+            || short.is_some_and(|short| {
+                arg.strip_prefix('-')
+                    .is_some_and(|flag| !flag.starts_with('-') && flag.starts_with(short))
+            })
+    })
+}
+
+/// Add the options in the contents of a configuration file to the command line
+/// arguments, unless they are already given there or through their environment variable.
+fn arguments(
+    command: &Command,
+    contents: &str,
+    mut args: Vec<OsString>,
+    env_set: impl Fn(&OsStr) -> bool,
+) -> Result<Vec<OsString>, String> {
+    let file: Option<BTreeMap<String, Value>> =
+        serde_yaml::from_str(contents).map_err(|e| e.to_string())?;
+
+    let known = options(command);
+
+    // Options must come before any trailing arguments.
+    let mut position = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+
+    for (name, value) in file.unwrap_or_default() {
+        let Some(option) = known.get(&name) else {
+            return Err(format!("unknown option {name:?}"));
+        };
+
+        let value = match value {
+            Value::Null => continue,
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::String(value) => value,
+            _ => return Err(format!("option {name:?} must be a single value")),
+        };
+
+        if given(&args, &name, option.short) || option.env.as_deref().is_some_and(&env_set) {
+            continue;
+        }
+
+        let arg = if option.takes_value {
+            format!("--{name}={value}")
+        } else {
+            match value.as_str() {
+                "true" => format!("--{name}"),
+                "false" => continue,
+                _ => return Err(format!("option {name:?} must be true or false")),
+            }
+        };
+
+        args.insert(position, OsString::from(arg));
+        position += 1;
+    }
+
+    Ok(args)
+}
+
+impl ConfigFile {
+    /// Locate the configuration file named by `--config` or [`CONFIG_ENV`], if any.
+    ///
+    /// This happens ahead of parsing the command line, so that the file's
+    /// options can be parsed along with it.
+    pub fn locate() -> Option<Self> {
+        let mut args = std::env::args_os().skip(1);
+
+        let path = loop {
+            let Some(arg) = args.next() else {
+                break std::env::var_os(CONFIG_ENV)?;
+            };
+
+            if arg == "--config" {
+                break args.next()?;
+            }
+
+            if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+                break OsString::from(path);
+            }
+        };
+
+        Some(ConfigFile {
+            path: PathBuf::from(path),
+        })
+    }
+
+    /// Read the file, and add its options to the command line arguments of the process.
+    pub fn args(&self, command: &Command) -> Result<Vec<OsString>, Error> {
+        let contents = std::fs::read_to_string(&self.path)?;
+
+        arguments(command, &contents, std::env::args_os().collect(), |env| {
+            std::env::var_os(env).is_some()
+        })
+        .map_err(|e| Error::Config(format!("{}: {e}", self.path.display())))
+    }
+
+    /// Read the file again, and reparse the command line with the new options.
+    pub fn reload(&self, command: Command) -> Result<ArgMatches, Error> {
+        let args = self.args(&command)?;
+
+        command
+            .try_get_matches_from(args)
+            .map_err(|e| Error::Config(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{OsStr, OsString};
+
+    use clap::{Arg, ArgAction, Command};
+    use indoc::indoc;
+
+    use super::arguments;
+
+    fn command() -> Command {
+        Command::new("zonefile").subcommand(
+            Command::new("reconcile")
+                .arg(
+                    Arg::new("publish-stats")
+                        .long("publish-stats")
+                        .env("ZONEFILE_PUBLISH_STATS")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("max-records-per-zone")
+                        .long("max-records-per-zone")
+                        .env("ZONEFILE_MAX_RECORDS_PER_ZONE"),
+                )
+                .arg(
+                    Arg::new("notify-webhook")
+                        .long("notify-webhook")
+                        .env("ZONEFILE_NOTIFY_WEBHOOK"),
+                )
+                .arg(Arg::new("namespace").short('n').long("namespace")),
+        )
+    }
+
+    fn args(contents: &str, command_line: &[&str]) -> Result<Vec<String>, String> {
+        arguments(
+            &command(),
+            contents,
+            command_line.iter().map(OsString::from).collect(),
+            |env| env == OsStr::new("ZONEFILE_NOTIFY_WEBHOOK"),
+        )
+        .map(|args| {
+            args.into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn options_become_arguments() {
+        let contents = indoc! {"
+            publish-stats: true
+            max-records-per-zone: 10000
+            namespace: kube-system
+        "};
+
+        assert_eq!(
+            args(contents, &["zonefile", "reconcile"]).unwrap(),
+            vec![
+                "zonefile",
+                "reconcile",
+                "--max-records-per-zone=10000",
+                "--namespace=kube-system",
+                "--publish-stats",
+            ]
+        );
+
+        // The parsed options end up in the matches, like any others.
+        let matches = command()
+            .try_get_matches_from(args(contents, &["zonefile", "reconcile"]).unwrap())
+            .unwrap();
+        let (_, matches) = matches.subcommand().unwrap();
+        assert!(matches.get_flag("publish-stats"));
+        assert_eq!(
+            matches.get_one::<String>("max-records-per-zone").unwrap(),
+            "10000"
+        );
+    }
+
+    #[test]
+    fn flags_and_environment_take_precedence() {
+        assert_eq!(
+            args(
+                indoc! {"
+                    publish-stats: false
+                    max-records-per-zone: 10000
+                    namespace: kube-system
+                    notify-webhook: https://hooks.example.org/zonefile
+                "},
+                &[
+                    "zonefile",
+                    "reconcile",
+                    "--max-records-per-zone=5",
+                    "-n",
+                    "default"
+                ]
+            )
+            .unwrap(),
+            vec![
+                "zonefile",
+                "reconcile",
+                "--max-records-per-zone=5",
+                "-n",
+                "default"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(args("max-record-per-zone: 10", &["zonefile"]).is_err());
+        assert!(args("notify-webhook: [a, b]", &["zonefile"]).is_err());
+        assert!(args("publish-stats: sometimes", &["zonefile"]).is_err());
+    }
+
+    #[test]
+    fn empty_file() {
+        assert_eq!(args("", &["zonefile"]).unwrap(), vec!["zonefile"]);
+        assert_eq!(
+            args("# nothing yet\n", &["zonefile"]).unwrap(),
+            vec!["zonefile"]
+        );
+    }
+}
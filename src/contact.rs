@@ -0,0 +1,114 @@
+use kubizone_crds::{
+    kubizone_common::{FullyQualifiedDomainName, Type},
+    v1alpha1::ZoneEntry,
+};
+
+/// Annotation holding the email address of the party responsible for a zone,
+/// from which the RNAME of its SOA record is derived. It is read from the
+/// [`Zone`](kubizone_crds::v1alpha1::Zone) first, falling back to its
+/// [`ZoneFile`](zonefile_crds::ZoneFile).
+pub const CONTACT_ANNOTATION: &str = "zonefile.kubi.zone/contact";
+
+/// Convert an email address into the mailbox domain name used as the RNAME of
+/// SOA records, as described in [RFC 1035 section 8](https://www.rfc-editor.org/rfc/rfc1035#section-8).
+///
+/// Dots within the local part are escaped, so that `dns.team@example.org`
+/// becomes `dns\.team.example.org.` rather than the mailbox `dns@team.example.org`.
+pub fn rname(email: &str) -> Option<String> {
+    let (local, domain) = email.trim().rsplit_once('@')?;
+
+    if local.is_empty()
+        || local
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "@\\\"();".contains(c))
+    {
+        return None;
+    }
+
+    let domain = FullyQualifiedDomainName::try_from(format!("{domain}.").as_str()).ok()?;
+
+    Some(format!("{}.{domain}", local.replace('.', "\\.")))
+}
+
+/// Replace the RNAME of the zone's SOA record.
+///
+/// Returns `false` if the zone has no SOA record at its apex to replace it in.
+pub fn set_rname(
+    origin: &FullyQualifiedDomainName,
+    entries: &mut [ZoneEntry],
+    rname: &str,
+) -> bool {
+    let Some(soa) = entries
+        .iter_mut()
+        .find(|entry| entry.type_ == Type::SOA && entry.fqdn == *origin)
+    else {
+        return false;
+    };
+
+    let mut fields: Vec<&str> = soa.rdata.split_whitespace().collect();
+    let Some(field) = fields.get_mut(1) else {
+        return false;
+    };
+
+    *field = rname;
+    soa.rdata = fields.join(" ");
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_crds::{
+        kubizone_common::{Class, FullyQualifiedDomainName, Type},
+        v1alpha1::ZoneEntry,
+    };
+
+    use super::{rname, set_rname};
+
+    #[test]
+    fn email_to_rname() {
+        assert_eq!(
+            rname("hostmaster@example.org").as_deref(),
+            Some("hostmaster.example.org.")
+        );
+        assert_eq!(
+            rname("dns.team@example.org").as_deref(),
+            Some("dns\\.team.example.org.")
+        );
+        assert_eq!(
+            rname(" first.last@eu.example.org ").as_deref(),
+            Some("first\\.last.eu.example.org.")
+        );
+
+        assert!(rname("example.org").is_none());
+        assert!(rname("@example.org").is_none());
+        assert!(rname("dns team@example.org").is_none());
+        assert!(rname("hostmaster@").is_none());
+        assert!(rname("hostmaster@example..org").is_none());
+    }
+
+    #[test]
+    fn replaces_soa_rname() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let mut entries = vec![ZoneEntry {
+            fqdn: origin.clone(),
+            type_: Type::SOA,
+            class: Class::IN,
+            ttl: 360,
+            rdata: "ns1.example.org. hostmaster.example.org. 2024010101 86400 7200 3600000 360"
+                .to_string(),
+        }];
+
+        assert!(set_rname(&origin, &mut entries, "dns\\.team.example.org."));
+        assert_eq!(
+            entries[0].rdata,
+            "ns1.example.org. dns\\.team.example.org. 2024010101 86400 7200 3600000 360"
+        );
+
+        assert!(!set_rname(
+            &origin,
+            &mut entries[1..],
+            "dns\\.team.example.org."
+        ));
+    }
+}
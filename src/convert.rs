@@ -0,0 +1,302 @@
+use std::{fmt::Write as _, path::PathBuf};
+
+use clap::{Args, ValueEnum};
+use hickory_proto::{
+    rr::{Name, RData, Record},
+    serialize::{
+        binary::{BinEncodable as _, BinEncoder, EncodeMode},
+        txt::Parser,
+    },
+};
+use kubizone_common::FullyQualifiedDomainName;
+use kubizone_crds::v1alpha1::ZoneEntry;
+use serde_json::Value;
+use tracing::log::*;
+
+use crate::{
+    error::Error,
+    render::{self, build_zonefile_with_services, Comments, FormatArgs},
+    service::{self, ServiceRecord},
+    tinydns,
+};
+
+/// Zone file formats which zones can be converted between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Flavor {
+    /// RFC 1035 master files, as read by BIND, Knot and NSD.
+    Bind,
+    /// The data files compiled by tinydns-data.
+    Tinydns,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ConvertArgs {
+    /// Format of the zone file to convert.
+    #[arg(long, value_enum, default_value_t = Flavor::Bind)]
+    pub from: Flavor,
+
+    /// Format to convert the zone to.
+    #[arg(long, value_enum)]
+    pub to: Flavor,
+
+    /// Origin of the zone, if the file does not declare it with an `$ORIGIN` directive.
+    #[arg(long)]
+    pub origin: Option<String>,
+
+    /// Format of the converted zone, if it is converted to a bind zone file.
+    #[command(flatten)]
+    pub format: FormatArgs,
+
+    /// Zone file to convert.
+    pub file: PathBuf,
+}
+
+/// Parse a zone file into its origin and records.
+pub(crate) fn parse(
+    contents: &str,
+    file: PathBuf,
+    origin: Option<&str>,
+) -> Result<(Name, Vec<Record>), Error> {
+    let origin = origin
+        .map(Name::from_ascii)
+        .transpose()
+        .map_err(|e| Error::Config(format!("invalid origin: {e}")))?;
+
+    let (origin, records) = Parser::new(contents, Some(file), origin)
+        .parse()
+        .map_err(|e| Error::Parse(e.to_string()))?;
+
+    let records = records
+        .into_values()
+        .flat_map(|rrset| rrset.records_without_rrsigs().cloned().collect::<Vec<_>>())
+        .collect();
+
+    Ok((origin, records))
+}
+
+/// Quote a character string, escaping quotes, backslashes and unprintable bytes.
+pub(crate) fn quote(string: &[u8]) -> String {
+    let mut quoted = String::from('"');
+    for byte in string {
+        match byte {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(*byte as char);
+            }
+            0x20..=0x7e => quoted.push(*byte as char),
+            _ => {
+                let _ = write!(quoted, "\\{byte:03}");
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Write character strings in presentation format, quoting each of them.
+fn character_strings(strings: &[Box<[u8]>]) -> String {
+    strings
+        .iter()
+        .map(|string| quote(string))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Rdata as it is sent on the wire, with its domain names uncompressed.
+pub(crate) fn wire(rdata: &RData) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut encoder = BinEncoder::with_mode(&mut bytes, EncodeMode::Signing);
+    rdata.emit(&mut encoder).ok()?;
+    Some(bytes)
+}
+
+/// Presentation format of the rdata of a record. Types whose presentation
+/// format cannot be relied upon are written in the generic format of
+/// [RFC 3597](https://datatracker.ietf.org/doc/html/rfc3597#section-5).
+pub(crate) fn presentation(rdata: &RData) -> Option<String> {
+    match rdata {
+        RData::A(_)
+        | RData::AAAA(_)
+        | RData::NS(_)
+        | RData::CNAME(_)
+        | RData::PTR(_)
+        | RData::MX(_)
+        | RData::SRV(_)
+        | RData::SOA(_) => Some(rdata.to_string()),
+        RData::TXT(txt) => Some(character_strings(txt.txt_data())),
+        rdata => {
+            let bytes = wire(rdata)?;
+
+            let mut generic = format!("\\# {}", bytes.len());
+            if !bytes.is_empty() {
+                generic.push(' ');
+                for byte in bytes {
+                    let _ = write!(generic, "{byte:02X}");
+                }
+            }
+            Some(generic)
+        }
+    }
+}
+
+/// Turn parsed records into zone entries, as rendered by the controller, along with
+/// the records owned by names holding service labels, which entries cannot hold.
+fn entries(records: &[Record]) -> (Vec<ZoneEntry>, Vec<ServiceRecord>) {
+    let mut entries = Vec::with_capacity(records.len());
+    let mut services = Vec::new();
+
+    for record in records {
+        let converted = (|| {
+            let name = record.name().to_ascii();
+            let type_ =
+                serde_json::from_value(Value::String(record.record_type().to_string())).ok()?;
+            let class =
+                serde_json::from_value(Value::String(record.dns_class().to_string())).ok()?;
+            let rdata = presentation(record.data()?)?;
+
+            match FullyQualifiedDomainName::try_from(name.as_str()) {
+                Ok(fqdn) => entries.push(ZoneEntry {
+                    fqdn,
+                    type_,
+                    class,
+                    ttl: record.ttl(),
+                    rdata,
+                }),
+                Err(_) => services.push(ServiceRecord {
+                    owner: service::service_name(&name)?,
+                    type_,
+                    class,
+                    ttl: record.ttl(),
+                    rdata,
+                }),
+            }
+
+            Some(())
+        })();
+
+        if converted.is_none() {
+            warn!(
+                "unable to convert {} record for {}, leaving it out",
+                record.record_type(),
+                record.name()
+            );
+        }
+    }
+
+    (entries, services)
+}
+
+/// Convert a zone file between formats, printing the result.
+pub async fn convert(args: ConvertArgs) -> Result<(), Error> {
+    let contents = tokio::fs::read_to_string(&args.file).await?;
+
+    let (origin, records) = match args.from {
+        Flavor::Bind => parse(&contents, args.file.clone(), args.origin.as_deref())?,
+        Flavor::Tinydns => {
+            return Err(Error::Config(String::from(
+                "tinydns data files cannot be converted from yet",
+            )))
+        }
+    };
+
+    let converted = match args.to {
+        Flavor::Bind => {
+            let origin = FullyQualifiedDomainName::try_from(origin.to_ascii().as_str())
+                .map_err(|_| Error::Parse(format!("invalid origin {origin}")))?;
+
+            let (entries, services) = entries(&records);
+
+            render::finish(
+                &build_zonefile_with_services(
+                    &origin,
+                    &entries,
+                    &services,
+                    &args.format,
+                    &Comments::default(),
+                ),
+                &args.format,
+            )?
+        }
+        Flavor::Tinydns => tinydns::serialize(&records),
+    };
+
+    print!("{converted}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use indoc::indoc;
+    use kubizone_common::Type;
+
+    use super::{entries, parse};
+
+    #[test]
+    fn converts_records_to_entries() {
+        let (origin, records) = parse(
+            indoc! {r#"
+                $TTL 300
+                @ IN SOA ns1 hostmaster 1 7200 3600 1209600 300
+                @ IN TXT "v=spf1 \"quoted\" -all" "second"
+                @ IN CAA 0 issue "letsencrypt.org"
+                www IN A 192.0.2.1
+                _dmarc IN TXT "v=DMARC1; p=none"
+                _sip._tcp IN SRV 0 5 5060 www
+            "#},
+            PathBuf::from("db.example.org"),
+            Some("example.org."),
+        )
+        .unwrap();
+
+        assert_eq!(origin.to_ascii(), "example.org.");
+
+        let (entries, services) = entries(&records);
+        let rdata = |type_: Type| {
+            entries
+                .iter()
+                .find(|entry| entry.type_ == type_)
+                .unwrap()
+                .rdata
+                .clone()
+        };
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(rdata(Type::A), "192.0.2.1");
+        assert_eq!(rdata(Type::TXT), r#""v=spf1 \"quoted\" -all" "second""#);
+        assert_eq!(
+            rdata(Type::CAA),
+            "\\# 22 000569737375656C657473656E63727970742E6F7267"
+        );
+        assert_eq!(
+            rdata(Type::SOA),
+            "ns1.example.org. hostmaster.example.org. 1 7200 3600 1209600 300"
+        );
+
+        let mut services = services
+            .iter()
+            .map(|service| {
+                (
+                    service.owner.as_str(),
+                    service.type_,
+                    service.rdata.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        services.sort_by_key(|(owner, _, _)| *owner);
+
+        assert_eq!(
+            services,
+            vec![
+                ("_dmarc.example.org.", Type::TXT, r#""v=DMARC1; p=none""#),
+                (
+                    "_sip._tcp.example.org.",
+                    Type::SRV,
+                    "0 5 5060 www.example.org."
+                ),
+            ]
+        );
+    }
+}
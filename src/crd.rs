@@ -0,0 +1,269 @@
+use k8s_openapi::serde_json::{self, json, Value};
+use kube::CustomResourceExt as _;
+use zonefile_crds::ZoneFile;
+
+/// Names of most objects, such as ConfigMaps and Zones, must be DNS-1123 subdomains.
+const DNS_SUBDOMAIN: &str = r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*$";
+
+/// Namespace names must be DNS-1123 labels.
+const DNS_LABEL: &str = r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?$";
+
+/// Upper bound on the number of zones referenced by a zonefile, which keeps
+/// the cost of the uniqueness rule within the API server's budget.
+const MAX_ZONE_REFS: usize = 256;
+
+/// Merge the constraints into the schema at the given JSON pointer, if it exists.
+fn constrain(schema: &mut Value, pointer: &str, constraints: Value) {
+    if let (Some(Value::Object(schema)), Value::Object(constraints)) =
+        (schema.pointer_mut(pointer), constraints)
+    {
+        schema.extend(constraints);
+    }
+}
+
+/// Fields of the status written by the controller, beyond the hashes and serials
+/// declared by its type. Without a schema, the API server would prune them.
+fn status_properties() -> Value {
+    json!({
+        "zones": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "origin": { "type": "string" },
+                    "records": { "type": "integer" },
+                    "types": {
+                        "type": "object",
+                        "additionalProperties": { "type": "integer" },
+                    },
+                    "largestRRset": {
+                        "type": "object",
+                        "nullable": true,
+                        "properties": {
+                            "name": { "type": "string" },
+                            "type": { "type": "string" },
+                            "records": { "type": "integer" },
+                        },
+                    },
+                    "duplicates": { "type": "integer" },
+                    "size": { "type": "integer" },
+                    "signed": { "type": "boolean" },
+                },
+            },
+        },
+        "conditions": {
+            "type": "array",
+            "x-kubernetes-list-type": "map",
+            "x-kubernetes-list-map-keys": ["type"],
+            "items": {
+                "type": "object",
+                "required": ["type", "status", "reason", "message", "lastTransitionTime"],
+                "properties": {
+                    "type": { "type": "string" },
+                    "status": { "type": "string", "enum": ["True", "False", "Unknown"] },
+                    "reason": { "type": "string" },
+                    "message": { "type": "string" },
+                    "observedGeneration": { "type": "integer", "format": "int64" },
+                    "lastTransitionTime": { "type": "string", "format": "date-time" },
+                },
+            },
+        },
+        "outputs": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "target": { "type": "string" },
+                    "delivered": { "type": "boolean" },
+                    "error": { "type": "string", "nullable": true },
+                },
+            },
+        },
+        "contentHash": {
+            "type": "object",
+            "additionalProperties": { "type": "string" },
+        },
+        "frozen": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "origin": { "type": "string" },
+                    "serial": { "type": "integer" },
+                    "hash": { "type": "string" },
+                    "until": { "type": "string", "format": "date-time" },
+                },
+            },
+        },
+        "dualStackGaps": {
+            "type": "object",
+            "additionalProperties": {
+                "type": "array",
+                "items": { "type": "string" },
+            },
+        },
+        "ptrMismatches": {
+            "type": "object",
+            "additionalProperties": {
+                "type": "array",
+                "items": { "type": "string" },
+            },
+        },
+    })
+}
+
+/// The [`ZoneFile`] custom resource definition, extended with the validation
+/// of its spec which cannot be expressed by its types, so that invalid specs
+/// are rejected when they are admitted, rather than failing to reconcile,
+/// and with the schema of the status fields written by the controller.
+pub fn zonefile_crd() -> Value {
+    let mut crd = serde_json::to_value(ZoneFile::crd()).unwrap();
+
+    for version in crd
+        .pointer_mut("/spec/versions")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(Value::Object(schema)) =
+            version.pointer_mut("/schema/openAPIV3Schema/properties")
+        {
+            let status = schema
+                .entry("status")
+                .or_insert_with(|| json!({ "type": "object" }));
+
+            if let Some(status) = status.as_object_mut() {
+                if let (Value::Object(properties), Value::Object(written)) = (
+                    status.entry("properties").or_insert_with(|| json!({})),
+                    status_properties(),
+                ) {
+                    properties.extend(written);
+                }
+            }
+        }
+
+        let Some(spec) = version.pointer_mut("/schema/openAPIV3Schema/properties/spec") else {
+            continue;
+        };
+
+        // A zonefile without zones would only ever publish an empty ConfigMap.
+        if let Some(Value::Array(required)) = spec
+            .as_object_mut()
+            .map(|spec| spec.entry("required").or_insert_with(|| json!([])))
+        {
+            if !required.contains(&json!("zoneRefs")) {
+                required.push(json!("zoneRefs"));
+            }
+        }
+
+        // The rule's message names the field, which a failed pattern would not,
+        // and the maximum length bounds the cost of matching it.
+        constrain(
+            spec,
+            "/properties/configMapName",
+            json!({
+                "maxLength": 253,
+                "x-kubernetes-validations": [{
+                    "rule": format!("self.matches(r'{DNS_SUBDOMAIN}')"),
+                    "message": "configMapName must be a DNS-1123 subdomain",
+                }],
+            }),
+        );
+
+        constrain(
+            spec,
+            "/properties/zoneRefs",
+            json!({
+                "minItems": 1,
+                "maxItems": MAX_ZONE_REFS,
+                "x-kubernetes-validations": [{
+                    // `namespace` is a reserved word in CEL, and must be escaped.
+                    "rule": "self.all(zone, self.exists_one(other, other.name == zone.name && (has(other.__namespace__) ? other.__namespace__ : '') == (has(zone.__namespace__) ? zone.__namespace__ : '')))",
+                    "message": "zoneRefs must not reference the same zone more than once",
+                }],
+            }),
+        );
+
+        constrain(
+            spec,
+            "/properties/zoneRefs/items/properties/name",
+            json!({
+                "maxLength": 253,
+                "pattern": DNS_SUBDOMAIN,
+            }),
+        );
+
+        constrain(
+            spec,
+            "/properties/zoneRefs/items/properties/namespace",
+            json!({
+                "maxLength": 63,
+                "pattern": DNS_LABEL,
+            }),
+        );
+    }
+
+    crd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json, zonefile_crd, DNS_SUBDOMAIN};
+
+    #[test]
+    fn spec_fields_are_constrained() {
+        let crd = zonefile_crd();
+        let spec = crd
+            .pointer("/spec/versions/0/schema/openAPIV3Schema/properties/spec/properties")
+            .unwrap();
+
+        assert_eq!(
+            spec.pointer("/configMapName/x-kubernetes-validations/0/rule")
+                .unwrap(),
+            &json!(format!("self.matches(r'{DNS_SUBDOMAIN}')"))
+        );
+        assert_eq!(
+            spec.pointer("/zoneRefs/items/properties/name/pattern")
+                .unwrap(),
+            DNS_SUBDOMAIN
+        );
+        assert!(spec
+            .pointer("/zoneRefs/x-kubernetes-validations/0/rule")
+            .is_some());
+        assert_eq!(spec.pointer("/zoneRefs/minItems").unwrap(), 1);
+        assert!(crd
+            .pointer("/spec/versions/0/schema/openAPIV3Schema/properties/spec/required")
+            .and_then(|required| required.as_array())
+            .is_some_and(|required| required.contains(&json!("zoneRefs"))));
+        assert!(crd
+            .pointer(
+                "/spec/versions/0/schema/openAPIV3Schema/properties/spec/x-kubernetes-validations"
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn written_status_fields_have_a_schema() {
+        let crd = zonefile_crd();
+        let status = crd
+            .pointer("/spec/versions/0/schema/openAPIV3Schema/properties/status/properties")
+            .unwrap();
+
+        for field in [
+            "zones",
+            "conditions",
+            "outputs",
+            "contentHash",
+            "frozen",
+            "dualStackGaps",
+            "ptrMismatches",
+        ] {
+            assert!(status.get(field).is_some(), "{field} has no schema");
+        }
+
+        assert_eq!(
+            status.pointer("/conditions/items/properties/lastTransitionTime/format"),
+            Some(&json!("date-time"))
+        );
+    }
+}
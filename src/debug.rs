@@ -0,0 +1,37 @@
+use std::net::SocketAddr;
+
+use clap::Args;
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+#[cfg(not(tokio_unstable))]
+compile_error!(
+    "the debug-console feature instruments tokio's tasks, which requires building with RUSTFLAGS=\"--cfg tokio_unstable\""
+);
+
+#[derive(Debug, Clone, Args)]
+pub struct DebugArgs {
+    /// Serve diagnostics of the controller's async tasks to `tokio-console` on this
+    /// address, such as `127.0.0.1:6669`, for inspecting a stuck or leaking controller.
+    /// The diagnostics are not authenticated, and should not be exposed beyond the pod.
+    #[arg(long, env = "ZONEFILE_DEBUG_CONSOLE")]
+    pub debug_console: Option<SocketAddr>,
+}
+
+impl DebugArgs {
+    /// Layer recording the async tasks of the controller, if enabled. The
+    /// diagnostics are served from a thread of their own, so that they
+    /// remain available while the controller's own runtime is stuck.
+    pub fn console_layer<S>(&self) -> Option<impl Layer<S>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let address = self.debug_console?;
+
+        Some(
+            console_subscriber::ConsoleLayer::builder()
+                .server_addr(address)
+                .spawn(),
+        )
+    }
+}
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use clap::Args;
+use kube::{
+    runtime::reflector::{ObjectRef, Store},
+    CustomResource, ResourceExt as _,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{
+    error::Error,
+    output::Output,
+    template::{SoaDefaults, TtlPolicy},
+};
+
+/// Name of the [`ZoneFileDefaults`] consulted for the zonefiles in its namespace.
+/// Defaults under any other name are ignored.
+pub const NAMESPACE_DEFAULTS_NAME: &str = "default";
+
+/// Settings of the [`ZoneFile`]s in a namespace which neither they nor their
+/// template set, so that teams can share defaults without a template.
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[kube(
+    group = "kubi.zone",
+    version = "v1alpha1",
+    kind = "ZoneFileDefaults",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneFileDefaultsSpec {
+    /// Nameservers added to the apex of zones which have no NS records there,
+    /// unless the zonefile is annotated with nameservers of its own.
+    #[serde(default)]
+    pub nameservers: Vec<String>,
+
+    /// Values replacing those of the zones' SOA records, including their contact.
+    #[serde(default)]
+    pub soa: SoaDefaults,
+
+    /// Bounds on the TTLs of the zones' records, and overrides by type.
+    #[serde(default)]
+    pub ttl: TtlPolicy,
+}
+
+/// Zonefiles in the namespace of the defaults, which should therefore be reconciled when they change.
+pub fn zonefiles_defaulted_by(
+    defaults: &ZoneFileDefaults,
+    zonefiles: &Store<ZoneFile>,
+) -> Vec<ObjectRef<ZoneFile>> {
+    if defaults.name_any() != NAMESPACE_DEFAULTS_NAME {
+        return Vec::new();
+    }
+
+    zonefiles
+        .state()
+        .iter()
+        .filter(|zonefile| zonefile.namespace() == defaults.namespace())
+        .map(|zonefile| ObjectRef::from_obj(zonefile.as_ref()))
+        .collect()
+}
+
+/// Defaults of the namespace, as last seen by the watch of [`ZoneFileDefaults`].
+pub fn namespace_defaults(
+    defaults: &Store<ZoneFileDefaults>,
+    namespace: &str,
+) -> ZoneFileDefaultsSpec {
+    defaults
+        .get(&ObjectRef::new(NAMESPACE_DEFAULTS_NAME).within(namespace))
+        .map(|defaults| defaults.spec.clone())
+        .unwrap_or_default()
+}
+
+/// Time for which reconciliations wait for the defaults to be listed at startup.
+/// Should the watch never list them, such as when their CRD is not installed or
+/// may not be watched, the zonefiles are rendered without them instead.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Defaults of the namespace as [`namespace_defaults`] finds them, once the watch
+/// has listed them, or within the timeout, after which there may be none yet.
+pub async fn ready_namespace_defaults(
+    defaults: &Store<ZoneFileDefaults>,
+    namespace: &str,
+    timeout: Duration,
+) -> ZoneFileDefaultsSpec {
+    if tokio::time::timeout(timeout, defaults.wait_until_ready())
+        .await
+        .is_err()
+    {
+        warn!(
+            "defaults of namespace {namespace} have not been listed within {}s, rendering without them",
+            timeout.as_secs()
+        );
+    }
+
+    namespace_defaults(defaults, namespace)
+}
+
+/// Settings of zonefiles which neither they, their template nor the defaults
+/// of their namespace set, so that most zonefiles can leave them out.
+#[derive(Debug, Clone, Default, Args)]
+pub struct DefaultsArgs {
+    /// Email address of the party responsible for the zones, used unless the zone,
+    /// zonefile or template names a contact of its own.
+    #[arg(long, env = "ZONEFILE_DEFAULT_SOA_CONTACT")]
+    pub default_soa_contact: Option<String>,
+
+    /// Refresh timer of the zones' SOA records, unless set by the zonefile's template.
+    #[arg(long, env = "ZONEFILE_DEFAULT_SOA_REFRESH")]
+    pub default_soa_refresh: Option<u32>,
+
+    /// Retry timer of the zones' SOA records, unless set by the zonefile's template.
+    #[arg(long, env = "ZONEFILE_DEFAULT_SOA_RETRY")]
+    pub default_soa_retry: Option<u32>,
+
+    /// Expire timer of the zones' SOA records, unless set by the zonefile's template.
+    #[arg(long, env = "ZONEFILE_DEFAULT_SOA_EXPIRE")]
+    pub default_soa_expire: Option<u32>,
+
+    /// Outputs of zonefiles without a `zonefile.kubi.zone/outputs` annotation, in
+    /// the same comma-separated form. ConfigMaps cannot be among them, since every
+    /// zonefile would write to the same one.
+    #[arg(long, env = "ZONEFILE_DEFAULT_OUTPUTS")]
+    pub default_outputs: Option<String>,
+}
+
+impl DefaultsArgs {
+    /// Values of the template, falling back to the controller's defaults. The
+    /// negative caching TTL falls back to `--negative-ttl` when it is applied.
+    pub fn soa(&self, template: &SoaDefaults) -> SoaDefaults {
+        template.or(&SoaDefaults {
+            contact: self.default_soa_contact.clone(),
+            refresh: self.default_soa_refresh,
+            retry: self.default_soa_retry,
+            expire: self.default_soa_expire,
+            minimum: None,
+        })
+    }
+
+    /// Check that the default outputs are valid, and name no ConfigMap.
+    pub fn validate(&self) -> Result<(), Error> {
+        let Some(outputs) = &self.default_outputs else {
+            return Ok(());
+        };
+
+        let outputs = Output::parse_list(outputs)
+            .map_err(|output| Error::Config(format!("invalid default output {output:?}")))?;
+
+        for output in outputs {
+            if let Output::ConfigMap { name, .. } = output {
+                return Err(Error::Config(format!(
+                    "default outputs cannot include the ConfigMap {name}, since every zonefile would write to it"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, time::Duration};
+
+    use kube::runtime::{reflector, watcher};
+    use kubizone_common::Type;
+
+    use crate::template::{SoaDefaults, TtlPolicy};
+
+    use super::{
+        ready_namespace_defaults, DefaultsArgs, ZoneFileDefaults, ZoneFileDefaultsSpec,
+        NAMESPACE_DEFAULTS_NAME,
+    };
+
+    #[tokio::test]
+    async fn renders_without_defaults_which_are_never_listed() {
+        // The writer is kept, but never applies an event, as when the watch is forbidden.
+        let (store, mut writer) = reflector::store::<ZoneFileDefaults>();
+
+        let defaults = ready_namespace_defaults(&store, "dns", Duration::from_millis(10)).await;
+        assert!(defaults.nameservers.is_empty());
+
+        let mut listed = ZoneFileDefaults::new(
+            NAMESPACE_DEFAULTS_NAME,
+            ZoneFileDefaultsSpec {
+                nameservers: vec![String::from("ns1.example.org.")],
+                ..ZoneFileDefaultsSpec::default()
+            },
+        );
+        listed.metadata.namespace = Some(String::from("dns"));
+        writer.apply_watcher_event(&watcher::Event::Restarted(vec![listed]));
+
+        let defaults = ready_namespace_defaults(&store, "dns", Duration::from_millis(10)).await;
+        assert_eq!(defaults.nameservers, vec![String::from("ns1.example.org.")]);
+    }
+
+    #[test]
+    fn templates_override_defaults() {
+        let defaults = DefaultsArgs {
+            default_soa_contact: Some(String::from("dns@example.org")),
+            default_soa_refresh: Some(3600),
+            ..DefaultsArgs::default()
+        };
+
+        let soa = defaults.soa(&SoaDefaults {
+            refresh: Some(7200),
+            ..SoaDefaults::default()
+        });
+
+        assert_eq!(soa.contact.as_deref(), Some("dns@example.org"));
+        assert_eq!(soa.refresh, Some(7200));
+        assert_eq!(soa.retry, None);
+        assert_eq!(soa.minimum, None);
+
+        let ttl = TtlPolicy {
+            max: Some(3600),
+            overrides: BTreeMap::from([(Type::TXT, 300)]),
+            ..TtlPolicy::default()
+        }
+        .or(&TtlPolicy {
+            min: Some(60),
+            max: Some(86400),
+            overrides: BTreeMap::from([(Type::TXT, 60), (Type::NS, 86400)]),
+        });
+
+        assert_eq!((ttl.min, ttl.max), (Some(60), Some(3600)));
+        assert_eq!(
+            ttl.overrides,
+            BTreeMap::from([(Type::TXT, 300), (Type::NS, 86400)])
+        );
+    }
+
+    #[test]
+    fn rejects_config_map_default_outputs() {
+        let defaults = |outputs: &str| DefaultsArgs {
+            default_outputs: Some(outputs.to_string()),
+            ..DefaultsArgs::default()
+        };
+
+        assert!(defaults("https://zones.example.org/").validate().is_ok());
+        assert!(defaults("https://zones.example.org/, configmap:zones")
+            .validate()
+            .is_err());
+        assert!(defaults("ftp://zones.example.org/").validate().is_err());
+        assert!(DefaultsArgs::default().validate().is_ok());
+    }
+}
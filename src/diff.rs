@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+
+/// Records added to and removed from a zone between two renderings of it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ZoneDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Describes a change in the rendered contents of a single zone.
+pub struct ZoneChange<'a> {
+    pub zonefile: &'a str,
+    /// The [`Zone`](kubizone_crds::v1alpha1::Zone) which the rendered contents originate from.
+    pub zone: &'a str,
+    /// Field manager which most recently modified the zone, if known.
+    pub trigger: Option<&'a str>,
+    pub origin: &'a str,
+    pub old_serial: Option<u32>,
+    pub new_serial: u32,
+    pub hash: &'a str,
+    pub diff: &'a ZoneDiff,
+}
+
+impl ZoneDiff {
+    /// Compare two rendered zonefiles record by record.
+    pub fn between(old: &str, new: &str) -> Self {
+        let old = records(old);
+        let new = records(new);
+
+        ZoneDiff {
+            added: new.difference(&old).cloned().collect(),
+            removed: old.difference(&new).cloned().collect(),
+        }
+    }
+}
+
+/// Split a rendered zonefile into logical lines, stripping comments
+/// and joining records which span multiple lines within parentheses.
+fn logical_lines(zonefile: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+
+    for line in zonefile.lines() {
+        let mut quoted = false;
+        let mut escaped = false;
+
+        for character in line.chars() {
+            if escaped {
+                escaped = false;
+            } else if character == '\\' {
+                escaped = true;
+            } else if character == '"' {
+                quoted = !quoted;
+            } else if !quoted {
+                match character {
+                    ';' => break,
+                    '(' => {
+                        depth += 1;
+                        current.push(' ');
+                        continue;
+                    }
+                    ')' => {
+                        depth = depth.saturating_sub(1);
+                        current.push(' ');
+                        continue;
+                    }
+                    _ => (),
+                }
+            }
+
+            current.push(character);
+        }
+
+        if depth == 0 {
+            lines.push(std::mem::take(&mut current));
+        } else {
+            current.push(' ');
+        }
+    }
+
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+/// Owner name qualified by the origin, unless it is already fully qualified
+/// or no `$ORIGIN` has been declared yet.
+fn qualify(owner: &str, origin: &str) -> String {
+    if origin.is_empty() || owner.ends_with('.') {
+        owner.to_string()
+    } else if owner == "@" {
+        origin.to_string()
+    } else if origin == "." {
+        format!("{owner}.")
+    } else {
+        format!("{owner}.{origin}")
+    }
+}
+
+/// Extract the record lines from a rendered zonefile.
+///
+/// Whitespace is collapsed, so that a change in column alignment
+/// caused by a longer domain name does not register as a change
+/// to every record in the zone. Owners are qualified by the most
+/// recent `$ORIGIN`, so that equal relative names in different
+/// sections remain distinct. Lines which leave the owner name
+/// blank are attributed to the owner of the preceding record.
+fn records(zonefile: &str) -> BTreeSet<String> {
+    let mut origin = String::new();
+    let mut owner = String::new();
+    let mut records = BTreeSet::new();
+
+    for line in logical_lines(zonefile) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        if line.starts_with('$') {
+            if fields.next() == Some("$ORIGIN") {
+                origin = fields.next().unwrap_or_default().to_string();
+            }
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            owner = qualify(fields.next().unwrap_or_default(), &origin);
+        }
+
+        records.insert(
+            std::iter::once(owner.as_str())
+                .chain(fields)
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZoneDiff;
+
+    #[test]
+    fn realignment_is_not_a_change() {
+        let old = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            www  360      IN    A      127.0.0.1
+            @    360      IN    CNAME  www.example.org."#
+        };
+
+        let new = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            www      360      IN    A      127.0.0.1
+            @        360      IN    CNAME  www.example.org.
+            mail     360      IN    A      127.0.0.2"#
+        };
+
+        assert_eq!(
+            ZoneDiff::between(old, new),
+            ZoneDiff {
+                added: vec![String::from("mail.example.org. 360 IN A 127.0.0.2")],
+                removed: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn continuation_lines_keep_their_owner() {
+        let old = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            www  360      IN    A      127.0.0.1
+                 360      IN    AAAA   ::1"#
+        };
+
+        let new = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            www  360      IN    A      127.0.0.1
+            mail 360      IN    A      127.0.0.2
+                 360      IN    AAAA   ::1"#
+        };
+
+        assert_eq!(
+            ZoneDiff::between(old, new),
+            ZoneDiff {
+                added: vec![
+                    String::from("mail.example.org. 360 IN A 127.0.0.2"),
+                    String::from("mail.example.org. 360 IN AAAA ::1"),
+                ],
+                removed: vec![String::from("www.example.org. 360 IN AAAA ::1")],
+            }
+        );
+    }
+
+    #[test]
+    fn multiline_records_are_joined() {
+        let old = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            @    360      IN    SOA    ns1.example.org. hostmaster.example.org. (
+                                       1          ; serial
+                                       86400      ; refresh
+                                       7200       ; retry
+                                       3600000    ; expire
+                                       360 )      ; minimum
+            txt  360      IN    TXT    "a;b" ; comment"#
+        };
+
+        let new = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            @    360      IN    SOA    ns1.example.org. hostmaster.example.org. 2 86400 7200 3600000 360
+            txt  360      IN    TXT    "a;b""#
+        };
+
+        assert_eq!(
+            ZoneDiff::between(old, new),
+            ZoneDiff {
+                added: vec![String::from(
+                    "example.org. 360 IN SOA ns1.example.org. hostmaster.example.org. 2 86400 7200 3600000 360"
+                )],
+                removed: vec![String::from(
+                    "example.org. 360 IN SOA ns1.example.org. hostmaster.example.org. 1 86400 7200 3600000 360"
+                )],
+            }
+        );
+    }
+
+    #[test]
+    fn owners_are_qualified_by_their_origin() {
+        let old = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            @    360      IN    NS     ns1.example.org.
+
+            $ORIGIN a.example.org.
+            www  360      IN    A      192.0.2.1
+
+            $ORIGIN b.example.org.
+            www  360      IN    A      192.0.2.1"#
+        };
+
+        let new = indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            @    360      IN    NS     ns1.example.org.
+
+            $ORIGIN a.example.org.
+            www  360      IN    A      192.0.2.1"#
+        };
+
+        assert_eq!(
+            ZoneDiff::between(old, new),
+            ZoneDiff {
+                added: vec![],
+                removed: vec![String::from("www.b.example.org. 360 IN A 192.0.2.1")],
+            }
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt::Write as _;
+
+use clap::{Args, ValueEnum};
+use ring::digest::{digest, SHA256};
+
+/// Annotation on the published ConfigMap, holding a JSON object which maps each
+/// of its keys to the hash of its contents, so that consumers of the ConfigMap
+/// can verify that they received what the controller published.
+pub const HASHES_ANNOTATION: &str = "zonefile.kubi.zone/hashes";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct HashArgs {
+    /// Algorithm used to hash published zones, for the `contentHash` of the zonefile's
+    /// status and the hashes annotation of its ConfigMap.
+    #[arg(
+        long,
+        env = "ZONEFILE_HASH_ALGORITHM",
+        value_enum,
+        default_value_t = HashAlgorithm::Sha256
+    )]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+impl HashAlgorithm {
+    /// Hash the contents, prefixed by the name of the algorithm, as in `sha256:…`.
+    pub fn hash(self, contents: &str) -> String {
+        match self {
+            HashAlgorithm::Sha256 => {
+                format!(
+                    "sha256:{}",
+                    hex(digest(&SHA256, contents.as_bytes()).as_ref())
+                )
+            }
+            HashAlgorithm::Blake3 => {
+                format!("blake3:{}", blake3::hash(contents.as_bytes()).to_hex())
+            }
+        }
+    }
+}
+
+/// Shorten a name to at most `max` characters, such as the 253 of object names
+/// or the 63 of label values, replacing its end with a hash of the whole name
+/// so that shortened names stay distinct. Names which fit are left as they are.
+pub fn truncate(name: &str, max: usize) -> String {
+    if name.len() <= max {
+        return name.to_string();
+    }
+
+    let hash = hex(&digest(&SHA256, name.as_bytes()).as_ref()[..4]);
+    let prefix: String = name.chars().take(max - hash.len() - 1).collect();
+
+    // Names must start and end with an alphanumeric character.
+    format!(
+        "{}-{hash}",
+        prefix.trim_end_matches(|c: char| !c.is_ascii_alphanumeric())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate, HashAlgorithm};
+
+    #[test]
+    fn prefixed_hashes() {
+        assert_eq!(
+            HashAlgorithm::Sha256.hash(""),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            HashAlgorithm::Blake3.hash(""),
+            "blake3:af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+    }
+
+    #[test]
+    fn truncated_names() {
+        assert_eq!(truncate("zones", 63), "zones");
+
+        let long = "a".repeat(70);
+        let truncated = truncate(&long, 63);
+        assert_eq!(truncated.len(), 63);
+        assert!(truncated.starts_with(&"a".repeat(54)));
+        assert_ne!(truncated, truncate(&"a".repeat(71), 63));
+
+        // Separators are not left dangling before the hash.
+        let dashed = format!("{}-{}", "a".repeat(44), "b".repeat(20));
+        let truncated = truncate(&dashed, 54);
+        assert_eq!(truncated.len(), 53);
+        assert!(!truncated.contains("--"));
+    }
+}
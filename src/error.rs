@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::{
+    budget::BudgetExceeded, query::QueryError, render::EncodingError, validation::ValidationError,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Kube(#[from] kube::Error),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid manifest: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("invalid zone file: {0}")]
+    Parse(String),
+    #[error("failed to sign zone contents: {0}")]
+    Signing(String),
+    #[error("invalid TSIG key: {0}")]
+    Tsig(String),
+    #[error("failed to encrypt zone contents: {0}")]
+    Encryption(String),
+    #[error("pre-render webhook failed: {0}")]
+    PreRender(String),
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("reconciliations in progress did not finish within {0}s of shutting down")]
+    ShutdownTimeout(u64),
+    #[error("reconciliation panicked: {0}")]
+    Panic(String),
+    #[error("{0}")]
+    Budget(#[from] BudgetExceeded),
+    #[error("invalid zone contents: {0}")]
+    Encoding(#[from] EncodingError),
+    #[error("zone violates policy: {0}")]
+    Policy(#[from] ValidationError),
+    #[error("{0}")]
+    Query(#[from] QueryError),
+    #[error("{0} zone(s) differ from their golden files")]
+    GoldenMismatch(usize),
+    #[error("{0} record(s) failed the checks")]
+    CheckFailed(usize),
+    #[error("{resource} has fields owned by {}, pass --force-ownership to take them over", .managers.join(", "))]
+    Conflict {
+        resource: String,
+        managers: Vec<String>,
+    },
+}
+
+/// Field managers named in the message of a server-side apply conflict, such as
+/// `Apply failed with 1 conflict: conflict with "kubectl-edit" using v1: .data.example.org.`
+fn conflicting_managers(message: &str) -> Vec<String> {
+    let mut managers: Vec<String> = message
+        .split("with \"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"'))
+        .map(|(manager, _)| manager.to_string())
+        .collect();
+
+    managers.sort();
+    managers.dedup();
+    managers
+}
+
+impl Error {
+    /// Classify an error returned from a server-side apply of the resource,
+    /// turning conflicts into [`Error::Conflict`].
+    pub fn from_apply(error: kube::Error, resource: impl Into<String>) -> Self {
+        match error {
+            kube::Error::Api(response) if response.code == 409 && response.reason == "Conflict" => {
+                Error::Conflict {
+                    resource: resource.into(),
+                    managers: conflicting_managers(&response.message),
+                }
+            }
+            error => Error::Kube(error),
+        }
+    }
+
+    /// Time to wait before retrying a reconciliation which failed with this error,
+    /// or [`None`] if retrying cannot succeed until the resource itself changes.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::Kube(kube::Error::Api(response)) => match response.code {
+                // Conflicting writes usually resolve themselves right away.
+                409 => Some(Duration::from_secs(5)),
+                // Referenced resources which do not exist yet, or which we may
+                // not access, are unlikely to appear any time soon.
+                403 | 404 => Some(Duration::from_secs(300)),
+                // The request we built from the spec was rejected as invalid.
+                400 | 422 => None,
+                _ => Some(Duration::from_secs(60)),
+            },
+            // A panic is most likely caused by the contents of the resource,
+            // but backing off rather than giving up guards against flukes.
+            Error::Panic(_) => Some(Duration::from_secs(300)),
+            // Conflicts persist until the other manager lets go of the fields.
+            Error::Conflict { .. } => Some(Duration::from_secs(300)),
+            // The zonefile will make as many requests on the next attempt.
+            Error::Budget(_) => Some(Duration::from_secs(300)),
+            _ => Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use kube::core::ErrorResponse;
+
+    use super::{conflicting_managers, Error};
+
+    fn api_error(code: u16) -> Error {
+        Error::Kube(kube::Error::Api(ErrorResponse {
+            status: String::from("Failure"),
+            message: String::new(),
+            reason: String::new(),
+            code,
+        }))
+    }
+
+    #[test]
+    fn retry_classification() {
+        assert_eq!(api_error(409).retry_after(), Some(Duration::from_secs(5)));
+        assert_eq!(api_error(404).retry_after(), Some(Duration::from_secs(300)));
+        assert_eq!(api_error(422).retry_after(), None);
+        assert_eq!(api_error(500).retry_after(), Some(Duration::from_secs(60)));
+        assert_eq!(
+            Error::PreRender(String::from("timed out")).retry_after(),
+            Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn apply_conflicts() {
+        assert_eq!(
+            conflicting_managers(
+                r#"Apply failed with 1 conflict: conflict with "kubectl-edit" using v1: .data.example.org."#
+            ),
+            vec!["kubectl-edit"]
+        );
+
+        assert_eq!(
+            conflicting_managers(
+                "Apply failed with 3 conflicts: conflicts with \"helm\" using v1:\n- .data.a\n- .data.b\nconflicts with \"argocd-controller\" using v1:\n- .data.c"
+            ),
+            vec!["argocd-controller", "helm"]
+        );
+
+        let conflict = Error::from_apply(
+            kube::Error::Api(ErrorResponse {
+                status: String::from("Failure"),
+                message: String::from(
+                    r#"Apply failed with 1 conflict: conflict with "helm" using v1: .data.example.org."#,
+                ),
+                reason: String::from("Conflict"),
+                code: 409,
+            }),
+            "ConfigMap zones",
+        );
+
+        assert_eq!(
+            conflict.to_string(),
+            "ConfigMap zones has fields owned by helm, pass --force-ownership to take them over"
+        );
+        assert_eq!(conflict.retry_after(), Some(Duration::from_secs(300)));
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{api::ListParams, Api, Client, ResourceExt as _};
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{error::Error, naming::FileNaming};
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportArgs {
+    /// Names of the ZoneFiles to export.
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    pub zonefiles: Vec<String>,
+
+    /// Export every ZoneFile, across all namespaces unless `--namespace` is given.
+    #[arg(long)]
+    pub all: bool,
+
+    /// Namespace of the ZoneFiles. Defaults to the namespace of the current context.
+    #[arg(short, long)]
+    pub namespace: Option<String>,
+
+    /// Directory to write the zones to, as one `db.<origin>` file per zone.
+    #[arg(short, long, default_value = ".")]
+    pub directory: PathBuf,
+}
+
+/// Name of the file a zone is exported to.
+pub fn file_name(origin: &str) -> String {
+    match origin.trim_end_matches('.') {
+        "" => String::from("db.root"),
+        origin => format!("db.{origin}"),
+    }
+}
+
+async fn export_zonefile(
+    client: Client,
+    zonefile: &ZoneFile,
+    directory: &Path,
+) -> Result<(), Error> {
+    let configmap_name = zonefile
+        .spec
+        .config_map_name
+        .as_ref()
+        .cloned()
+        .unwrap_or(zonefile.name_any());
+
+    let Some(config_map) =
+        Api::<ConfigMap>::namespaced(client, zonefile.namespace().as_ref().unwrap())
+            .get_opt(&configmap_name)
+            .await?
+    else {
+        warn!(
+            "zonefile {} has not produced its configmap {configmap_name} yet, skipping",
+            zonefile.name_any()
+        );
+        return Ok(());
+    };
+
+    let naming = FileNaming::of(&config_map);
+
+    // Zones are keyed by their origin, which unlike the keys
+    // of signatures and statistics always ends with a dot.
+    for (origin, contents) in naming
+        .unpublish(config_map.data.unwrap_or_default())
+        .into_iter()
+        .filter(|(key, _)| key.ends_with('.'))
+    {
+        let path = directory.join(file_name(&origin));
+
+        if tokio::fs::try_exists(&path).await? {
+            warn!("overwriting {} with zone {origin}", path.display());
+        }
+
+        tokio::fs::write(&path, contents).await?;
+        info!("exported zone {origin} to {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Write the zones most recently published by the selected ZoneFiles to local files.
+pub async fn export(client: Client, args: ExportArgs) -> Result<(), Error> {
+    let namespace = args
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+
+    let zonefiles = if args.all {
+        let api = match &args.namespace {
+            Some(namespace) => Api::<ZoneFile>::namespaced(client.clone(), namespace),
+            None => Api::<ZoneFile>::all(client.clone()),
+        };
+
+        api.list(&ListParams::default()).await?.items
+    } else {
+        let api = Api::<ZoneFile>::namespaced(client.clone(), &namespace);
+
+        let mut zonefiles = Vec::with_capacity(args.zonefiles.len());
+        for name in &args.zonefiles {
+            zonefiles.push(api.get(name).await?);
+        }
+        zonefiles
+    };
+
+    tokio::fs::create_dir_all(&args.directory).await?;
+
+    for zonefile in &zonefiles {
+        export_zonefile(client.clone(), zonefile, &args.directory).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) which, when set to `true`,
+/// points CNAME records which lead to other CNAME records of the same zone directly
+/// at the final target, sparing resolvers the lookups of the intermediate hops.
+pub const FLATTEN_CNAMES_ANNOTATION: &str = "zonefile.kubi.zone/flatten-cnames";
+
+/// Targets of the CNAME records of the zone, by owner name. The entries are
+/// expected to be [canonicalized](crate::normalize::canonicalize), so that relative
+/// targets and targets differing in case from their owner names are followed.
+pub fn aliases(
+    entries: &[ZoneEntry],
+) -> BTreeMap<FullyQualifiedDomainName, FullyQualifiedDomainName> {
+    entries
+        .iter()
+        .filter(|entry| entry.type_ == Type::CNAME)
+        .filter_map(|entry| {
+            let target = FullyQualifiedDomainName::try_from(entry.rdata.trim()).ok()?;
+            Some((entry.fqdn.clone(), target))
+        })
+        .collect()
+}
+
+/// Names passed through when following the CNAME records from the name, starting
+/// with the name itself and ending with the final target, or [`None`] if the
+/// records form a loop.
+pub fn follow(
+    aliases: &BTreeMap<FullyQualifiedDomainName, FullyQualifiedDomainName>,
+    name: &FullyQualifiedDomainName,
+) -> Option<Vec<FullyQualifiedDomainName>> {
+    let mut chain = vec![name.clone()];
+
+    while let Some(target) = aliases.get(chain.last().unwrap()) {
+        if chain.contains(target) {
+            return None;
+        }
+
+        chain.push(target.clone());
+    }
+
+    Some(chain)
+}
+
+/// Point each CNAME record which leads to another CNAME record of the zone at
+/// the final target of the chain. Records forming loops are left unchanged.
+/// Like [`aliases`], this expects canonicalized entries.
+///
+/// Returns the owner names of the records forming loops.
+pub fn flatten_cnames(entries: &mut [ZoneEntry]) -> Vec<FullyQualifiedDomainName> {
+    let aliases = aliases(entries);
+    let mut looped = Vec::new();
+
+    for entry in entries
+        .iter_mut()
+        .filter(|entry| entry.type_ == Type::CNAME)
+    {
+        match follow(&aliases, &entry.fqdn) {
+            Some(chain) if chain.len() > 2 => entry.rdata = chain.last().unwrap().to_string(),
+            Some(_) => (),
+            None => looped.push(entry.fqdn.clone()),
+        }
+    }
+
+    looped
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::flatten_cnames;
+    use crate::normalize::canonicalize;
+
+    fn entry(fqdn: &str, type_: Type, rdata: &str) -> ZoneEntry {
+        ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(fqdn).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 300,
+            rdata: rdata.to_string(),
+        }
+    }
+
+    #[test]
+    fn flattens_chains() {
+        let mut entries = vec![
+            entry("www.example.org.", Type::A, "192.0.2.1"),
+            entry("cdn.example.org.", Type::CNAME, "www.example.org."),
+            entry("static.example.org.", Type::CNAME, "cdn.example.org."),
+            entry("assets.example.org.", Type::CNAME, "static.example.org."),
+            entry("external.example.org.", Type::CNAME, "example.net."),
+            entry("ping.example.org.", Type::CNAME, "pong.example.org."),
+            entry("pong.example.org.", Type::CNAME, "ping.example.org."),
+        ];
+
+        assert_eq!(
+            flatten_cnames(&mut entries),
+            vec![
+                FullyQualifiedDomainName::try_from("ping.example.org.").unwrap(),
+                FullyQualifiedDomainName::try_from("pong.example.org.").unwrap(),
+            ]
+        );
+
+        let targets: Vec<&str> = entries.iter().map(|entry| entry.rdata.as_str()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                "192.0.2.1",
+                "www.example.org.",
+                "www.example.org.",
+                "www.example.org.",
+                "example.net.",
+                "pong.example.org.",
+                "ping.example.org.",
+            ]
+        );
+    }
+
+    #[test]
+    fn flattens_canonicalized_chains() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let mut entries = canonicalize(
+            &origin,
+            vec![
+                entry("WWW.example.org.", Type::A, "192.0.2.1"),
+                entry("cdn.example.org.", Type::CNAME, "www"),
+                entry("Static.example.org.", Type::CNAME, "CDN.Example.Org."),
+                entry("assets.example.org.", Type::CNAME, "static"),
+            ],
+        );
+
+        assert!(flatten_cnames(&mut entries).is_empty());
+
+        let targets: Vec<&str> = entries.iter().map(|entry| entry.rdata.as_str()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                "192.0.2.1",
+                "www.example.org.",
+                "www.example.org.",
+                "www.example.org.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Strategies generating arbitrary valid zones and rendering options, for
+//! checking that every rendered zone parses back into the records it was
+//! built from. Enabled outside of the crate's tests by the `fuzz` feature.
+
+use std::{
+    fmt::Write as _,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+
+use proptest::{option, prelude::*};
+
+use crate::{
+    builder::{Record, Soa, Type, Zone, ZoneBuilder},
+    contact, convert,
+    render::FormatArgs,
+};
+
+const ORIGIN: &str = "example.org.";
+
+/// Label of a hostname. Hyphens at both the third and fourth position are
+/// reserved for punycode, and rejected.
+fn label() -> impl Strategy<Value = String> {
+    "[a-z0-9]([a-z0-9-]{0,8}[a-z0-9])?"
+        .prop_filter("reserved hyphens", |label| label.get(2..4) != Some("--"))
+}
+
+/// Name within the zone, relative to its origin, or `@` for the origin itself.
+/// Some are owned by service labels, such as `_dmarc`.
+fn name() -> impl Strategy<Value = String> {
+    (option::of(label()), prop::collection::vec(label(), 0..3)).prop_map(|(service, mut labels)| {
+        if let Some(service) = service {
+            labels.insert(0, format!("_{service}"));
+        }
+
+        if labels.is_empty() {
+            String::from("@")
+        } else {
+            labels.join(".")
+        }
+    })
+}
+
+/// Name below the origin, relative or absolute, or a name outside of the zone. The
+/// origin itself is left out, since the parser does not read `@` within rdata.
+fn target() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::collection::vec(label(), 1..3).prop_map(|labels| labels.join(".")),
+        prop::collection::vec(label(), 1..3)
+            .prop_map(|labels| format!("{}.{ORIGIN}", labels.join("."))),
+        label().prop_map(|label| format!("{label}.example.net.")),
+    ]
+}
+
+/// Character strings of a TXT record, quoted and escaped, holding any printable characters.
+fn character_strings() -> impl Strategy<Value = String> {
+    prop::collection::vec("[ -~]{0,40}", 1..4).prop_map(|strings| {
+        strings
+            .iter()
+            .map(|string| format!("\"{}\"", string.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect::<Vec<_>>()
+            .join(" ")
+    })
+}
+
+fn rdata() -> impl Strategy<Value = (Type, String)> {
+    prop_oneof![
+        any::<Ipv4Addr>().prop_map(|address| (Type::A, address.to_string())),
+        any::<Ipv6Addr>().prop_map(|address| (Type::AAAA, address.to_string())),
+        (any::<u16>(), target())
+            .prop_map(|(preference, exchange)| (Type::MX, format!("{preference} {exchange}"))),
+        character_strings().prop_map(|strings| (Type::TXT, strings)),
+    ]
+}
+
+/// Valid zone of the origin `example.org.`.
+pub fn zones() -> impl Strategy<Value = Zone> {
+    let soa = (any::<u32>(), 0..=i32::MAX as u32).prop_map(|(serial, ttl)| Soa {
+        ttl,
+        ..Soa::new("ns1", "hostmaster@example.org", serial)
+    });
+
+    let record =
+        (name(), rdata(), 0..=i32::MAX as u32).prop_map(|(name, (type_, rdata), ttl)| Record {
+            name,
+            type_,
+            ttl,
+            rdata,
+        });
+
+    (soa, prop::collection::vec(record, 0..32)).prop_map(|(soa, records)| Zone {
+        origin: String::from(ORIGIN),
+        soa,
+        records,
+    })
+}
+
+/// Any combination of the rendering options.
+pub fn formats() -> impl Strategy<Value = FormatArgs> {
+    (
+        any::<(bool, bool, bool, bool, bool)>(),
+        prop_oneof![
+            Just((None, false)),
+            Just((None, true)),
+            (0..64usize).prop_map(|width| (Some(width), false)),
+        ],
+        option::of(1..8usize),
+    )
+        .prop_map(
+            |(
+                (omit_class, default_ttl, group_owners, multiline_soa, relative_rdata),
+                (name_width, no_align),
+                sub_origin_threshold,
+            )| FormatArgs {
+                omit_class,
+                default_ttl,
+                group_owners,
+                multiline_soa,
+                name_width,
+                no_align,
+                relative_rdata,
+                sub_origin_threshold,
+                ..FormatArgs::default()
+            },
+        )
+}
+
+/// Records of a zone file in presentation format, sorted. Their TTLs are left out,
+/// since the parser carries the TTL of each record over to the following ones
+/// rather than applying `$TTL`, and gives SOA records their expire timer as TTL.
+fn records(contents: &str) -> Vec<String> {
+    let (_, records) = convert::parse(contents, PathBuf::from(ORIGIN), None)
+        .unwrap_or_else(|e| panic!("unable to parse zone: {e}\n{contents}"));
+
+    let mut records = records
+        .into_iter()
+        .map(|mut record| record.set_ttl(0).to_string())
+        .collect::<Vec<_>>();
+    records.sort();
+    records
+}
+
+/// Render the zone in the format and parse it again, asserting that it holds the
+/// same records as a zone file listing each of them as given, line by line.
+pub fn assert_round_trip(zone: &Zone, format: &FormatArgs) {
+    let rendered = ZoneBuilder::from(zone.clone())
+        .format(format.clone())
+        .build()
+        .unwrap();
+
+    let mut listed = format!(
+        "$ORIGIN {ORIGIN}\n@ {} IN SOA {} {} {} {} {} {} {}\n",
+        zone.soa.ttl,
+        zone.soa.nameserver,
+        contact::rname(&zone.soa.contact).unwrap(),
+        zone.soa.serial,
+        zone.soa.refresh,
+        zone.soa.retry,
+        zone.soa.expire,
+        zone.soa.minimum,
+    );
+    for record in &zone.records {
+        let _ = writeln!(
+            listed,
+            "{} {} IN {} {}",
+            record.name, record.ttl, record.type_, record.rdata
+        );
+    }
+
+    assert_eq!(records(&rendered), records(&listed), "{rendered}");
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{assert_round_trip, formats, zones};
+
+    proptest! {
+        #[test]
+        fn rendered_zones_round_trip(zone in zones(), format in formats()) {
+            assert_round_trip(&zone, &format);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::fmt::Display;
+
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use thiserror::Error;
+
+use crate::validation::{canonical_rdata, ValidationError};
+
+/// Annotation on a [`Zone`](kubizone_crds::v1alpha1::Zone) holding ranges of
+/// records, one per line, in the syntax of BIND's `$GENERATE` directive:
+///
+/// ```text
+/// 0-99 pod-$ A 10.0.0.$
+/// 0-255/2 ${0,3,d} 300 PTR host-$.example.org.
+/// ```
+///
+/// Each range is rendered as a `$GENERATE` directive, or expanded into
+/// individual records when `--expand-generate` is set.
+pub const GENERATE_ANNOTATION: &str = "zonefile.kubi.zone/generate";
+
+/// Upper bound on the number of records produced by a single range.
+pub const MAX_RANGE: u32 = 65536;
+
+/// Upper bound on the width which substitutions are zero-padded to.
+pub const MAX_WIDTH: usize = 255;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    #[error("missing {0}")]
+    MissingField(&'static str),
+    #[error("invalid range {0:?}, expected <start>-<stop>[/<step>]")]
+    InvalidRange(String),
+    #[error("range {0:?} produces more than {MAX_RANGE} records")]
+    RangeTooLarge(String),
+    #[error("unsupported type {0:?}, expected one of A, AAAA, CNAME, DNAME, NS or PTR")]
+    UnsupportedType(String),
+    #[error("invalid substitution {0:?}")]
+    InvalidSubstitution(String),
+    #[error("invalid owner name {0:?}")]
+    InvalidOwner(String),
+    #[error("invalid rdata {0:?}: {1}")]
+    InvalidRdata(String, ValidationError),
+}
+
+/// A range of records, produced by substituting each number
+/// within the range into the templates of their owner and rdata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generate {
+    start: u32,
+    stop: u32,
+    step: u32,
+    owner: String,
+    ttl: Option<u32>,
+    type_: Type,
+    rdata: String,
+}
+
+fn parse_range(range: &str) -> Result<(u32, u32, u32), GenerateError> {
+    let invalid = || GenerateError::InvalidRange(range.to_string());
+
+    let (range, step) = match range.split_once('/') {
+        Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+        None => (range, 1),
+    };
+
+    let (start, stop) = range.split_once('-').ok_or_else(invalid)?;
+    let (start, stop): (u32, u32) = (
+        start.parse().map_err(|_| invalid())?,
+        stop.parse().map_err(|_| invalid())?,
+    );
+
+    if start > stop || step == 0 {
+        return Err(invalid());
+    }
+
+    if (stop - start) / step >= MAX_RANGE {
+        return Err(GenerateError::RangeTooLarge(format!(
+            "{start}-{stop}/{step}"
+        )));
+    }
+
+    Ok((start, stop, step))
+}
+
+fn parse_type(type_: &str) -> Result<Type, GenerateError> {
+    match type_.to_ascii_uppercase().as_str() {
+        "A" => Ok(Type::A),
+        "AAAA" => Ok(Type::AAAA),
+        "CNAME" => Ok(Type::CNAME),
+        "DNAME" => Ok(Type::DNAME),
+        "NS" => Ok(Type::NS),
+        "PTR" => Ok(Type::PTR),
+        _ => Err(GenerateError::UnsupportedType(type_.to_string())),
+    }
+}
+
+/// Substitute the iterator into the template. `$` is replaced by the iterator,
+/// `${offset[,width[,base]]}` by the iterator plus the offset, zero-padded to the
+/// width, in decimal (`d`), octal (`o`) or hexadecimal (`x` or `X`), and `\$` by `$`.
+/// Widths above [`MAX_WIDTH`] are refused.
+fn substitute(template: &str, iterator: u32) -> Result<String, GenerateError> {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'$') => {
+                output.push(chars.next().unwrap());
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let modifier: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let invalid = || GenerateError::InvalidSubstitution(format!("${{{modifier}}}"));
+
+                let mut fields = modifier.split(',');
+                let offset: i64 = fields
+                    .next()
+                    .filter(|offset| !offset.is_empty())
+                    .map_or(Ok(0), str::parse)
+                    .map_err(|_| invalid())?;
+                let width: usize = fields
+                    .next()
+                    .map_or(Ok(0), str::parse)
+                    .ok()
+                    .filter(|width| *width <= MAX_WIDTH)
+                    .ok_or_else(invalid)?;
+                let base = fields.next().unwrap_or("d");
+                if fields.next().is_some() {
+                    return Err(invalid());
+                }
+
+                let value = u32::try_from(i64::from(iterator) + offset).map_err(|_| invalid())?;
+                output.push_str(&match base {
+                    "d" => format!("{value:0width$}"),
+                    "o" => format!("{value:0width$o}"),
+                    "x" => format!("{value:0width$x}"),
+                    "X" => format!("{value:0width$X}"),
+                    _ => return Err(invalid()),
+                });
+            }
+            '$' => output.push_str(&iterator.to_string()),
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+impl Generate {
+    /// Parse a range in the syntax of the `$GENERATE` directive,
+    /// `<range> <owner> [<ttl>] [IN] <type> <rdata>`.
+    pub fn parse(line: &str) -> Result<Self, GenerateError> {
+        let mut fields = line.split_whitespace().peekable();
+
+        let (start, stop, step) =
+            parse_range(fields.next().ok_or(GenerateError::MissingField("range"))?)?;
+        let owner = fields
+            .next()
+            .ok_or(GenerateError::MissingField("owner"))?
+            .to_string();
+
+        let ttl = fields.next_if(|ttl| ttl.parse::<u32>().is_ok());
+        fields.next_if(|class| class.eq_ignore_ascii_case("IN"));
+
+        let type_ = parse_type(fields.next().ok_or(GenerateError::MissingField("type"))?)?;
+
+        let rdata = fields.collect::<Vec<_>>().join(" ");
+        if rdata.is_empty() {
+            return Err(GenerateError::MissingField("rdata"));
+        }
+
+        let generate = Generate {
+            start,
+            stop,
+            step,
+            owner,
+            ttl: ttl.map(|ttl| ttl.parse().unwrap()),
+            type_,
+            rdata,
+        };
+
+        // Catch invalid substitutions up front, rather than when the range is expanded.
+        substitute(&generate.owner, start)?;
+        substitute(&generate.rdata, start)?;
+
+        Ok(generate)
+    }
+
+    /// Parse the ranges held by the [`GENERATE_ANNOTATION`], ignoring empty lines.
+    pub fn parse_all(ranges: &str) -> Result<Vec<Self>, GenerateError> {
+        ranges
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Generate::parse)
+            .collect()
+    }
+
+    /// Expand the range into individual records. Owner names which are not fully
+    /// qualified are relative to the origin, and records without an explicit TTL
+    /// use `default_ttl`. Fails if any of the records has invalid rdata for its type.
+    pub fn expand(
+        &self,
+        origin: &FullyQualifiedDomainName,
+        default_ttl: u32,
+    ) -> Result<Vec<ZoneEntry>, GenerateError> {
+        (self.start..=self.stop)
+            .step_by(self.step as usize)
+            .map(|iterator| {
+                let owner = substitute(&self.owner, iterator)?;
+                let fqdn = match owner.as_str() {
+                    "@" => Ok(origin.clone()),
+                    owner if owner.ends_with('.') => FullyQualifiedDomainName::try_from(owner),
+                    owner => FullyQualifiedDomainName::try_from(format!("{owner}.{origin}")),
+                }
+                .map_err(|_| GenerateError::InvalidOwner(owner.clone()))?;
+
+                let rdata = substitute(&self.rdata, iterator)?;
+                canonical_rdata(self.type_, &rdata)
+                    .map_err(|e| GenerateError::InvalidRdata(rdata.clone(), e))?;
+
+                Ok(ZoneEntry {
+                    fqdn,
+                    type_: self.type_,
+                    class: Class::IN,
+                    ttl: self.ttl.unwrap_or(default_ttl),
+                    rdata,
+                })
+            })
+            .collect()
+    }
+
+    /// The `$GENERATE` directive producing the range, with an explicit TTL so that
+    /// it does not depend on its position within the zone.
+    pub fn directive(&self, default_ttl: u32) -> String {
+        format!(
+            "$GENERATE {}-{}/{} {} {} IN {} {}",
+            self.start,
+            self.stop,
+            self.step,
+            self.owner,
+            self.ttl.unwrap_or(default_ttl),
+            self.type_,
+            self.rdata
+        )
+    }
+}
+
+impl Display for Generate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}/{} {}",
+            self.start, self.stop, self.step, self.owner
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{FullyQualifiedDomainName, Type};
+
+    use super::{substitute, Generate, GenerateError};
+
+    #[test]
+    fn substitutions() {
+        assert_eq!(substitute("pod-$", 7).unwrap(), "pod-7");
+        assert_eq!(substitute("pod-${10}", 7).unwrap(), "pod-17");
+        assert_eq!(substitute("pod-${0,3}", 7).unwrap(), "pod-007");
+        assert_eq!(substitute("pod-${-7,2,x}", 42).unwrap(), "pod-23");
+        assert_eq!(substitute("${0,4,X}", 255).unwrap(), "00FF");
+        assert_eq!(substitute("\\$-$", 1).unwrap(), "$-1");
+
+        assert!(substitute("${a}", 1).is_err());
+        assert!(substitute("${0,2,b}", 1).is_err());
+        assert!(substitute("${-2}", 1).is_err());
+
+        assert_eq!(substitute("${0,255}", 1).unwrap().len(), 255);
+        assert_eq!(
+            substitute("${0,256}", 1),
+            Err(GenerateError::InvalidSubstitution("${0,256}".to_string()))
+        );
+        assert!(matches!(
+            Generate::parse("0-9 pod-${0,1000000000} A 10.0.0.$"),
+            Err(GenerateError::InvalidSubstitution(_))
+        ));
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let generate = Generate::parse("0-99 pod-$ A 10.0.0.$").unwrap();
+        assert_eq!(generate.type_, Type::A);
+        assert_eq!(generate.ttl, None);
+        assert_eq!(
+            generate.directive(300),
+            "$GENERATE 0-99/1 pod-$ 300 IN A 10.0.0.$"
+        );
+
+        let generate = Generate::parse("1-9/2 $ 60 IN ptr host-$.example.org.").unwrap();
+        assert_eq!(generate.type_, Type::PTR);
+        assert_eq!(generate.ttl, Some(60));
+
+        assert_eq!(
+            Generate::parse("0-99 pod-$ MX 10 mail.example.org."),
+            Err(GenerateError::UnsupportedType("MX".to_string()))
+        );
+        assert!(matches!(
+            Generate::parse("9-0 pod-$ A 10.0.0.$"),
+            Err(GenerateError::InvalidRange(_))
+        ));
+        assert!(matches!(
+            Generate::parse("0-100000 pod-$ A 10.0.0.$"),
+            Err(GenerateError::RangeTooLarge(_))
+        ));
+        assert_eq!(
+            Generate::parse("0-99 pod-$ A"),
+            Err(GenerateError::MissingField("rdata"))
+        );
+    }
+
+    #[test]
+    fn expands_ranges() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entries = Generate::parse("0-4/2 pod-${0,2} A 10.0.0.${1}")
+            .unwrap()
+            .expand(&origin, 300)
+            .unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| format!("{} {} {}", entry.fqdn, entry.ttl, entry.rdata))
+                .collect::<Vec<_>>(),
+            vec![
+                "pod-00.example.org. 300 10.0.0.1",
+                "pod-02.example.org. 300 10.0.0.3",
+                "pod-04.example.org. 300 10.0.0.5",
+            ]
+        );
+
+        // Ranges producing rdata which is invalid for their type are refused as a whole.
+        assert!(matches!(
+            Generate::parse("250-260 pod-$ A 10.0.0.$")
+                .unwrap()
+                .expand(&origin, 300),
+            Err(GenerateError::InvalidRdata(rdata, _)) if rdata == "10.0.0.256"
+        ));
+        assert!(matches!(
+            Generate::parse("0-1 pod-$ PTR host-$..example.org.")
+                .unwrap()
+                .expand(&origin, 300),
+            Err(GenerateError::InvalidRdata(_, _))
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use crate::{diff::ZoneDiff, error::Error, export};
+
+#[derive(Debug, Clone, Args)]
+pub struct GoldenArgs {
+    /// Compare the rendered zones against the `db.<origin>` files in this
+    /// directory rather than printing them, failing if any of them differ.
+    #[arg(long)]
+    pub golden_dir: Option<PathBuf>,
+
+    /// Overwrite the files in the golden directory with the rendered zones.
+    #[arg(long, requires = "golden_dir")]
+    pub update: bool,
+}
+
+/// Difference between the golden file of a zone and its rendered contents,
+/// or [`None`] if they are identical. A missing golden file differs from
+/// any rendered contents.
+pub fn compare(golden: Option<&str>, rendered: &str) -> Option<ZoneDiff> {
+    match golden {
+        Some(golden) if golden == rendered => None,
+        Some(golden) => Some(ZoneDiff::between(golden, rendered)),
+        None => Some(ZoneDiff::between("", rendered)),
+    }
+}
+
+/// Check the rendered zone against its golden file in the directory, printing
+/// any differences, or update the golden file if `update` is set.
+///
+/// Returns whether the zone matched its golden file.
+pub async fn check(
+    directory: &Path,
+    origin: &str,
+    rendered: &str,
+    update: bool,
+) -> Result<bool, Error> {
+    let path = directory.join(export::file_name(origin));
+
+    let golden = if tokio::fs::try_exists(&path).await? {
+        Some(tokio::fs::read_to_string(&path).await?)
+    } else {
+        None
+    };
+
+    let Some(diff) = compare(golden.as_deref(), rendered) else {
+        return Ok(true);
+    };
+
+    if update {
+        tokio::fs::create_dir_all(directory).await?;
+        tokio::fs::write(&path, rendered).await?;
+        println!("updated {}", path.display());
+        return Ok(true);
+    }
+
+    match golden {
+        Some(_) => println!("zone {origin} differs from {}:", path.display()),
+        None => println!("zone {origin} has no golden file {}:", path.display()),
+    }
+
+    // Changes in alignment alone do not show up in the diff.
+    if diff.added.is_empty() && diff.removed.is_empty() {
+        println!("  formatting differs");
+    }
+
+    for removed in &diff.removed {
+        println!("- {removed}");
+    }
+
+    for added in &diff.added {
+        println!("+ {added}");
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare;
+
+    #[test]
+    fn compares_against_golden_files() {
+        let golden = "$ORIGIN example.org.\n\nwww 360 IN A 192.0.2.1\n";
+
+        assert_eq!(compare(Some(golden), golden), None);
+
+        let diff = compare(
+            Some(golden),
+            "$ORIGIN example.org.\n\nwww 360 IN A 192.0.2.2\n",
+        )
+        .unwrap();
+        assert_eq!(diff.removed, vec!["www.example.org. 360 IN A 192.0.2.1"]);
+        assert_eq!(diff.added, vec!["www.example.org. 360 IN A 192.0.2.2"]);
+
+        // Renderings must match byte for byte, even where the diff sees no change.
+        assert!(compare(
+            Some(golden),
+            "$ORIGIN example.org.\n\nwww  360 IN A 192.0.2.1\n"
+        )
+        .is_some_and(|diff| diff.added.is_empty() && diff.removed.is_empty()));
+
+        assert!(compare(None, golden).is_some_and(|diff| !diff.added.is_empty()));
+    }
+}
@@ -0,0 +1,45 @@
+use clap::Args;
+
+/// Annotation on a [`Zone`](kubizone_crds::v1alpha1::Zone) holding the
+/// comma-separated namespaces whose [`ZoneFile`](zonefile_crds::ZoneFile)s
+/// may reference it, or `*` for all of them. ZoneFiles in the namespace of
+/// the Zone itself may always reference it.
+///
+/// On a Namespace, it holds the namespaces whose zonefiles may deliver
+/// their zones to ConfigMap outputs within it.
+pub const ALLOWED_NAMESPACES_ANNOTATION: &str = "zonefile.kubi.zone/allowed-namespaces";
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct GrantArgs {
+    /// Publish zones referenced from other namespaces even when they do not grant
+    /// access to the referencing namespace through their `zonefile.kubi.zone/allowed-namespaces`
+    /// annotation, only reporting the reference. Meant for migrating existing clusters.
+    #[arg(long, env = "ZONEFILE_ALLOW_UNGRANTED_ZONE_REFS")]
+    pub allow_ungranted_zone_refs: bool,
+}
+
+/// Whether a zone with the given allowed namespaces annotation
+/// may be referenced by zonefiles in another namespace.
+pub fn is_granted(allowed_namespaces: Option<&str>, namespace: &str) -> bool {
+    allowed_namespaces.is_some_and(|allowed| {
+        allowed
+            .split(',')
+            .map(str::trim)
+            .any(|allowed| allowed == "*" || allowed == namespace)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_granted;
+
+    #[test]
+    fn grants_namespaces() {
+        assert!(!is_granted(None, "tenant-a"));
+        assert!(!is_granted(Some(""), "tenant-a"));
+        assert!(!is_granted(Some("tenant-ab"), "tenant-a"));
+
+        assert!(is_granted(Some("tenant-b, tenant-a"), "tenant-a"));
+        assert!(is_granted(Some("*"), "tenant-a"));
+    }
+}
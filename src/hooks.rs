@@ -0,0 +1,415 @@
+use std::{collections::BTreeSet, str::FromStr, time::Duration};
+
+use clap::Args;
+use k8s_openapi::{
+    api::{
+        batch::v1::{CronJob, Job},
+        core::v1::EnvVar,
+    },
+    serde_json::json,
+};
+use kube::{
+    api::{ListParams, Patch, PatchParams, PostParams},
+    core::ObjectMeta,
+    Api, Client, ResourceExt as _,
+};
+use kubizone_crds::{kubizone_common::FullyQualifiedDomainName, v1alpha1::ZoneEntry};
+use serde::Deserialize;
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{digest, error::Error, http::HttpClient, ownership, reconciliation::ZONEFILE_LABEL};
+
+/// Annotation on a [`ZoneFile`] holding the URL of a webhook which receives
+/// the entries of each zone before rendering, and may return a modified set.
+pub const PRE_RENDER_ANNOTATION: &str = "zonefile.kubi.zone/pre-render-webhook";
+
+/// Seconds to wait for the pre-render webhook to respond. Defaults to 10.
+pub const PRE_RENDER_TIMEOUT_ANNOTATION: &str = "zonefile.kubi.zone/pre-render-timeout";
+
+/// [`FailurePolicy`] applied when the pre-render webhook cannot be reached,
+/// or responds with an error.
+pub const PRE_RENDER_FAILURE_POLICY_ANNOTATION: &str =
+    "zonefile.kubi.zone/pre-render-failure-policy";
+
+const DEFAULT_PRE_RENDER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Behaviour when a webhook fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Fail the reconciliation, leaving the previously published zones in place.
+    #[default]
+    Fail,
+    /// Publish the zone as if no webhook was configured.
+    Ignore,
+}
+
+impl FromStr for FailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(FailurePolicy::Fail),
+            "ignore" => Ok(FailurePolicy::Ignore),
+            other => Err(format!(
+                "unknown failure policy {other:?}, expected fail or ignore"
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PreRenderResponse {
+    entries: Vec<ZoneEntry>,
+}
+
+/// Pass the entries of a zone through the zonefile's pre-render webhook, if it has one.
+///
+/// The webhook receives the zonefile, zone and origin along with the entries, and
+/// must respond with an object holding the `entries` to render in their place.
+/// The returned entries are validated like any other.
+pub async fn pre_render(
+    http: &HttpClient,
+    zonefile: &ZoneFile,
+    zone: &str,
+    origin: &FullyQualifiedDomainName,
+    entries: Vec<ZoneEntry>,
+) -> Result<Vec<ZoneEntry>, Error> {
+    let annotations = zonefile.annotations();
+
+    let Some(url) = annotations.get(PRE_RENDER_ANNOTATION) else {
+        return Ok(entries);
+    };
+
+    // Refused regardless of the failure policy, rather than rendering the zone unmodified.
+    let url = http.allow(url).map_err(Error::PreRender)?;
+
+    let timeout = match annotations.get(PRE_RENDER_TIMEOUT_ANNOTATION) {
+        Some(timeout) => timeout
+            .parse()
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| {
+                warn!(
+                    "invalid pre-render timeout {timeout:?} on zonefile {}, using the default",
+                    zonefile.name_any()
+                );
+                DEFAULT_PRE_RENDER_TIMEOUT
+            }),
+        None => DEFAULT_PRE_RENDER_TIMEOUT,
+    };
+
+    let failure_policy = match annotations.get(PRE_RENDER_FAILURE_POLICY_ANNOTATION) {
+        Some(policy) => policy.parse().unwrap_or_else(|e: String| {
+            warn!("{e} on zonefile {}, failing closed", zonefile.name_any());
+            FailurePolicy::Fail
+        }),
+        None => FailurePolicy::default(),
+    };
+
+    let result = async {
+        http.client()
+            .post(url)
+            .timeout(timeout)
+            .json(&json!({
+                "zonefile": format!("{}/{}", zonefile.namespace().unwrap(), zonefile.name_any()),
+                "zone": zone,
+                "origin": origin,
+                "entries": entries,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PreRenderResponse>()
+            .await
+    }
+    .await;
+
+    match result {
+        Ok(response) => Ok(response.entries),
+        Err(e) if failure_policy == FailurePolicy::Ignore => {
+            warn!("pre-render webhook for zone {zone} failed, rendering it unmodified: {e}");
+            Ok(entries)
+        }
+        Err(e) => Err(Error::PreRender(e.to_string())),
+    }
+}
+
+/// Annotation on a [`ZoneFile`] naming a CronJob in the same namespace, whose
+/// job template is launched whenever one of the zonefile's zones is published.
+///
+/// The CronJob must opt in by carrying the `zonefile.kubi.zone/zonefile` label
+/// naming the zonefile, and should be suspended, unless it is also meant to run
+/// on a schedule.
+pub const POST_SYNC_ANNOTATION: &str = "zonefile.kubi.zone/post-sync-cronjob";
+
+/// Interval at which queued post-sync jobs are started, as running jobs complete.
+const QUEUE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Args)]
+pub struct HookArgs {
+    /// Maximum number of post-sync jobs which may be running at once
+    /// for a single zonefile. Jobs for further changes are created
+    /// suspended, and started in order as the running jobs complete.
+    #[arg(long, env = "ZONEFILE_POST_SYNC_MAX_ACTIVE", default_value_t = 1)]
+    pub post_sync_max_active: usize,
+
+    /// Number of retries before a post-sync job is considered failed,
+    /// overriding the `backoffLimit` of the job template.
+    #[arg(long, env = "ZONEFILE_POST_SYNC_BACKOFF_LIMIT")]
+    pub post_sync_backoff_limit: Option<i32>,
+
+    /// Seconds after which finished post-sync jobs are deleted, unless
+    /// the job template sets its own `ttlSecondsAfterFinished`.
+    #[arg(long, env = "ZONEFILE_POST_SYNC_TTL", default_value_t = 3600)]
+    pub post_sync_ttl: i32,
+}
+
+/// Whether the job has completed or failed.
+fn finished(job: &Job) -> bool {
+    job.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .is_some_and(|conditions| {
+            conditions.iter().any(|condition| {
+                matches!(condition.type_.as_str(), "Complete" | "Failed")
+                    && condition.status == "True"
+            })
+        })
+}
+
+/// Whether the job is queued behind the running jobs of its zonefile.
+fn queued(job: &Job) -> bool {
+    job.spec
+        .as_ref()
+        .and_then(|spec| spec.suspend)
+        .unwrap_or_default()
+        && !finished(job)
+}
+
+/// Queued jobs which may be started without exceeding the maximum
+/// number of running jobs, oldest first.
+fn startable(jobs: &[Job], max_active: usize) -> Vec<&Job> {
+    let running = jobs
+        .iter()
+        .filter(|job| !queued(job) && !finished(job))
+        .count();
+
+    let mut queue: Vec<&Job> = jobs.iter().filter(|job| queued(job)).collect();
+    queue.sort_by_key(|job| job.creation_timestamp().map(|time| time.0));
+    queue.truncate(max_active.saturating_sub(running));
+    queue
+}
+
+/// Start the queued post-sync jobs of a zonefile which fit within its maximum of running jobs.
+async fn start_queued(jobs: &Api<Job>, zonefile: &str, max_active: usize) -> Result<(), Error> {
+    let label = digest::truncate(zonefile, 63);
+    let existing = jobs
+        .list(&ListParams::default().labels(&format!("{ZONEFILE_LABEL}={label}")))
+        .await?
+        .items;
+
+    for job in startable(&existing, max_active) {
+        jobs.patch(
+            &job.name_any(),
+            &PatchParams::default(),
+            &Patch::Merge(json!({ "spec": { "suspend": false } })),
+        )
+        .await?;
+        info!("started queued post-sync job {}", job.name_any());
+    }
+
+    Ok(())
+}
+
+/// Launch the post-sync job of the zonefile, if it has one, for a newly published zone.
+/// If the zonefile already has as many running jobs as allowed, the job is queued.
+pub async fn post_sync(
+    client: Client,
+    zonefile: &ZoneFile,
+    origin: &str,
+    serial: u32,
+    label_ownership: bool,
+    args: &HookArgs,
+) -> Result<(), Error> {
+    let Some(cronjob_name) = zonefile.annotations().get(POST_SYNC_ANNOTATION) else {
+        return Ok(());
+    };
+
+    let namespace = zonefile.namespace().unwrap();
+    let jobs = Api::<Job>::namespaced(client.clone(), &namespace);
+    let label = digest::truncate(&zonefile.name_any(), 63);
+
+    let cronjob = Api::<CronJob>::namespaced(client, &namespace)
+        .get(cronjob_name)
+        .await?;
+
+    if cronjob.labels().get(ZONEFILE_LABEL) != Some(&label) {
+        return Err(Error::Config(format!(
+            "cronjob {cronjob_name} is not labeled {ZONEFILE_LABEL}={label}, and may not be launched by the zonefile"
+        )));
+    }
+
+    let template = cronjob.spec.unwrap_or_default().job_template;
+    let mut spec = template.spec.unwrap_or_default();
+
+    if let Some(backoff_limit) = args.post_sync_backoff_limit {
+        spec.backoff_limit = Some(backoff_limit);
+    }
+
+    if spec.ttl_seconds_after_finished.is_none() {
+        spec.ttl_seconds_after_finished = Some(args.post_sync_ttl);
+    }
+
+    // Created suspended, and started once the zonefile has room for another running job.
+    spec.suspend = Some(true);
+
+    let env = [
+        ("ZONEFILE_NAME", zonefile.name_any()),
+        ("ZONEFILE_ORIGIN", origin.to_string()),
+        ("ZONEFILE_SERIAL", serial.to_string()),
+    ];
+
+    if let Some(pod) = spec.template.spec.as_mut() {
+        for container in pod
+            .containers
+            .iter_mut()
+            .chain(pod.init_containers.iter_mut().flatten())
+        {
+            container
+                .env
+                .get_or_insert_with(Vec::new)
+                .extend(env.iter().map(|(name, value)| EnvVar {
+                    name: name.to_string(),
+                    value: Some(value.clone()),
+                    ..EnvVar::default()
+                }));
+        }
+    }
+
+    let metadata = template.metadata.unwrap_or_default();
+
+    let mut labels = metadata.labels.unwrap_or_default();
+    labels.insert(ZONEFILE_LABEL.to_string(), label);
+
+    let mut annotations = metadata.annotations.unwrap_or_default();
+    annotations.insert("zonefile.kubi.zone/origin".to_string(), origin.to_string());
+    annotations.insert("zonefile.kubi.zone/serial".to_string(), serial.to_string());
+
+    // Job names end up in pod labels, which are limited to 63 characters.
+    let prefix = cronjob_name.chars().take(50).collect::<String>();
+
+    let mut job = Job {
+        metadata: ObjectMeta {
+            generate_name: Some(format!("{}-", prefix.trim_end_matches('-'))),
+            namespace: Some(namespace.clone()),
+            labels: Some(labels),
+            annotations: Some(annotations),
+            ..ObjectMeta::default()
+        },
+        spec: Some(spec),
+        ..Job::default()
+    };
+    ownership::mark_owned(zonefile, label_ownership, &mut job.metadata);
+
+    let job = jobs.create(&PostParams::default(), &job).await?;
+    info!(
+        "queued post-sync job {namespace}/{} for zone {origin} serial {serial}",
+        job.name_any()
+    );
+
+    start_queued(&jobs, &zonefile.name_any(), args.post_sync_max_active).await
+}
+
+/// Periodically start queued post-sync jobs, as the running jobs of their zonefiles complete.
+pub async fn run(client: Client, args: HookArgs) {
+    let mut interval = tokio::time::interval(QUEUE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let jobs = match Api::<Job>::all(client.clone())
+            .list(&ListParams::default().labels(ZONEFILE_LABEL))
+            .await
+        {
+            Ok(jobs) => jobs.items,
+            Err(e) => {
+                error!("failed to list post-sync jobs: {e}");
+                continue;
+            }
+        };
+
+        // Only zonefiles with queued jobs need their jobs listed again.
+        let zonefiles: BTreeSet<(String, String)> = jobs
+            .iter()
+            .filter(|job| queued(job))
+            .filter_map(|job| Some((job.namespace()?, job.labels().get(ZONEFILE_LABEL)?.clone())))
+            .collect();
+
+        for (namespace, zonefile) in zonefiles {
+            let jobs = Api::<Job>::namespaced(client.clone(), &namespace);
+            if let Err(e) = start_queued(&jobs, &zonefile, args.post_sync_max_active).await {
+                error!(
+                    "failed to start queued post-sync jobs of zonefile {namespace}/{zonefile}: {e}"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::{
+        api::batch::v1::{Job, JobCondition, JobSpec, JobStatus},
+        apimachinery::pkg::apis::meta::v1::Time,
+        chrono::{TimeZone as _, Utc},
+    };
+    use kube::{core::ObjectMeta, ResourceExt as _};
+
+    use super::startable;
+
+    fn job(name: &str, created: i64, suspended: bool, condition: Option<&str>) -> Job {
+        Job {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                creation_timestamp: Some(Time(Utc.timestamp_opt(created, 0).unwrap())),
+                ..ObjectMeta::default()
+            },
+            spec: Some(JobSpec {
+                suspend: Some(suspended),
+                ..JobSpec::default()
+            }),
+            status: Some(JobStatus {
+                conditions: condition.map(|type_| {
+                    vec![JobCondition {
+                        type_: type_.to_string(),
+                        status: "True".to_string(),
+                        ..JobCondition::default()
+                    }]
+                }),
+                ..JobStatus::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn starts_queued_jobs_in_order() {
+        let jobs = [
+            job("done", 0, false, Some("Complete")),
+            job("running", 1, false, None),
+            job("third", 4, true, None),
+            job("second", 3, true, None),
+            job("failed-while-queued", 2, true, Some("Failed")),
+        ];
+
+        let names = |max_active| {
+            startable(&jobs, max_active)
+                .into_iter()
+                .map(|job| job.name_any())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(names(1).is_empty());
+        assert_eq!(names(2), vec!["second"]);
+        assert_eq!(names(5), vec!["second", "third"]);
+    }
+}
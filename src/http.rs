@@ -0,0 +1,398 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use clap::Args;
+use reqwest::{
+    header::RETRY_AFTER, Certificate, Client, Identity, NoProxy, Proxy, RequestBuilder, Response,
+    StatusCode, Url,
+};
+use thiserror::Error;
+use tokio::time::Instant;
+use tracing::log::*;
+
+#[derive(Debug, Clone, Args)]
+pub struct HttpArgs {
+    /// PEM-encoded client certificate presented to HTTP endpoints, for mutual TLS.
+    #[arg(long, env = "ZONEFILE_HTTP_CLIENT_CERT", requires = "http_client_key")]
+    pub http_client_cert: Option<PathBuf>,
+
+    /// PEM-encoded private key belonging to `--http-client-cert`.
+    #[arg(long, env = "ZONEFILE_HTTP_CLIENT_KEY", requires = "http_client_cert")]
+    pub http_client_key: Option<PathBuf>,
+
+    /// PEM bundle of certificate authorities trusted when connecting
+    /// to HTTP endpoints, in addition to the system roots.
+    #[arg(long, env = "ZONEFILE_HTTP_CA_BUNDLE")]
+    pub http_ca_bundle: Option<PathBuf>,
+
+    /// Proxy through which HTTP endpoints are reached. Without it, the
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment variables apply.
+    #[arg(long, env = "ZONEFILE_HTTP_PROXY")]
+    pub http_proxy: Option<String>,
+
+    /// Comma-separated hosts, domains and IP ranges reached without going
+    /// through `--http-proxy`. Defaults to the `NO_PROXY` environment variable.
+    #[arg(long, env = "ZONEFILE_HTTP_NO_PROXY", requires = "http_proxy")]
+    pub http_no_proxy: Option<String>,
+
+    /// Seconds after which requests to HTTP endpoints, such as webhooks and
+    /// outputs, are abandoned, so that an unresponsive endpoint cannot hold up
+    /// the reconciliation waiting on it.
+    #[arg(long, env = "ZONEFILE_HTTP_TIMEOUT", default_value_t = 30)]
+    pub http_timeout: u64,
+
+    /// Maximum number of requests per second sent to each host by HTTP outputs.
+    /// Unlimited if left unset.
+    #[arg(long, env = "ZONEFILE_HTTP_OUTPUT_RPS", value_parser = parse_rps)]
+    pub http_output_rps: Option<f64>,
+
+    /// Number of times the reconciliation of a zonefile is requeued early to retry
+    /// its HTTP outputs while their host responds with 429 Too Many Requests,
+    /// backing off exponentially or for as long as the host asks through its
+    /// `Retry-After` header. Beyond that, they are retried as the zonefile is
+    /// reconciled again.
+    #[arg(long, env = "ZONEFILE_HTTP_OUTPUT_RETRIES", default_value_t = 5)]
+    pub http_output_retries: u32,
+
+    /// Comma-separated hosts which zonefiles may direct requests to, through their
+    /// pre-render webhooks and staging probes. A leading `*.` allows all subdomains
+    /// of a domain. Requests to other hosts, or over plain HTTP, are refused.
+    #[arg(long, env = "ZONEFILE_HTTP_ALLOWED_HOSTS", value_delimiter = ',')]
+    pub http_allowed_hosts: Vec<String>,
+}
+
+fn parse_rps(rps: &str) -> Result<f64, String> {
+    let rps: f64 = rps.parse().map_err(|e| format!("{e}"))?;
+
+    if rps > 0.0 && rps.is_finite() {
+        Ok(rps)
+    } else {
+        Err(String::from(
+            "expected a positive number of requests per second",
+        ))
+    }
+}
+
+/// Parse a URL given by a zonefile, refusing it unless
+/// it uses https and its host is among the allowed hosts.
+fn allowed_url(url: &str, allowed_hosts: &[String]) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|e| format!("invalid url {url:?}: {e}"))?;
+
+    if url.scheme() != "https" {
+        return Err(format!("url {url} does not use https"));
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("url {url} has no host"))?
+        .to_ascii_lowercase();
+
+    let allowed = allowed_hosts.iter().any(|allowed| {
+        let allowed = allowed.trim().to_ascii_lowercase();
+        match allowed.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|subdomain| subdomain.ends_with('.')),
+            None => host == allowed,
+        }
+    });
+
+    if !allowed {
+        return Err(format!("host {host} is not among the allowed http hosts"));
+    }
+
+    Ok(url)
+}
+
+#[derive(Error, Debug)]
+pub enum SendError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("{host} is throttling requests, retrying in {}s", .retry_in.as_secs())]
+    Throttled { host: String, retry_in: Duration },
+}
+
+/// Longest time waited before retrying a throttled request.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Time to wait before the retry following `attempt` failed attempts, as
+/// asked by the `Retry-After` header if it holds a number of seconds.
+fn backoff(attempt: u32, retry_after: Option<&str>) -> Duration {
+    retry_after
+        .and_then(|seconds| seconds.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| Duration::from_secs(1) * 2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+/// Spaces out requests to each host, so that they stay within a budget of requests per second.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    fn new(rps: f64) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / rps),
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserve the next free slot for a request to the host.
+    fn reserve(&self, host: &str, now: Instant) -> Instant {
+        let mut next = self.next.lock().unwrap();
+        let slot = next.get(host).map_or(now, |next| (*next).max(now));
+        next.insert(host.to_string(), slot + self.interval);
+        slot
+    }
+}
+
+impl HttpArgs {
+    fn files(&self) -> impl Iterator<Item = &Path> {
+        [
+            &self.http_client_cert,
+            &self.http_client_key,
+            &self.http_ca_bundle,
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::as_path)
+    }
+
+    /// Most recent modification time of any of the configured files.
+    fn last_modified(&self) -> Option<SystemTime> {
+        self.files()
+            .filter_map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+            })
+            .max()
+    }
+
+    fn build(&self) -> Result<Client, Box<dyn std::error::Error>> {
+        let mut builder = Client::builder()
+            .use_rustls_tls()
+            .timeout(Duration::from_secs(self.http_timeout));
+
+        if let Some(ca_bundle) = &self.http_ca_bundle {
+            for certificate in pem::parse_many(std::fs::read(ca_bundle)?)? {
+                builder =
+                    builder.add_root_certificate(Certificate::from_der(certificate.contents())?);
+            }
+        }
+
+        if let (Some(cert), Some(key)) = (&self.http_client_cert, &self.http_client_key) {
+            let mut identity = std::fs::read(key)?;
+            identity.extend(std::fs::read(cert)?);
+            builder = builder.identity(Identity::from_pem(&identity)?);
+        }
+
+        if let Some(proxy) = &self.http_proxy {
+            let no_proxy = match &self.http_no_proxy {
+                Some(no_proxy) => NoProxy::from_string(no_proxy),
+                None => NoProxy::from_env(),
+            };
+
+            builder = builder.proxy(Proxy::all(proxy)?.no_proxy(no_proxy));
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// HTTP client shared by all outbound integrations, so that they
+/// all honor the same proxy and certificate configuration.
+///
+/// Certificates are read from disk, and the client is rebuilt
+/// whenever they change, so that rotated certificates mounted
+/// from Secrets are picked up without restarting the controller.
+pub struct HttpClient {
+    args: HttpArgs,
+    current: Mutex<(Option<SystemTime>, Client)>,
+    limiter: Option<RateLimiter>,
+    /// Hosts which throttled the last request sent to them, with the number of
+    /// requests they throttled in a row, and the time until no more are sent.
+    throttled: Mutex<HashMap<String, (u32, Instant)>>,
+}
+
+impl HttpClient {
+    pub fn new(args: HttpArgs) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = args.build()?;
+
+        Ok(HttpClient {
+            current: Mutex::new((args.last_modified(), client)),
+            limiter: args.http_output_rps.map(RateLimiter::new),
+            throttled: Mutex::default(),
+            args,
+        })
+    }
+
+    /// Options which the client was built from.
+    pub fn args(&self) -> &HttpArgs {
+        &self.args
+    }
+
+    /// Check that a URL given by a zonefile may be requested.
+    pub fn allow(&self, url: &str) -> Result<Url, String> {
+        allowed_url(url, &self.args.http_allowed_hosts)
+    }
+
+    /// Get the current client, rebuilding it first if any of
+    /// the certificate files have changed.
+    pub fn client(&self) -> Client {
+        let mut current = self.current.lock().unwrap();
+
+        let last_modified = self.args.last_modified();
+        if last_modified != current.0 {
+            match self.args.build() {
+                Ok(client) => {
+                    info!("reloaded http client certificates");
+                    *current = (last_modified, client);
+                }
+                Err(e) => error!("failed to reload http client certificates: {e}"),
+            }
+        }
+
+        current.1.clone()
+    }
+
+    /// Send a request of an HTTP output to the host, within its budget of
+    /// requests per second. Requests are not retried while the host throttles
+    /// them, but refused until it is time to retry them, which the caller
+    /// should reschedule itself for through [`HttpClient::throttled_for`].
+    pub async fn send_limited(
+        &self,
+        host: &str,
+        request: impl Fn(&Client) -> RequestBuilder,
+    ) -> Result<Response, SendError> {
+        if let Some(until) = self
+            .throttled
+            .lock()
+            .unwrap()
+            .get(host)
+            .map(|(_, until)| *until)
+        {
+            let retry_in = until.saturating_duration_since(Instant::now());
+            if !retry_in.is_zero() {
+                return Err(SendError::Throttled {
+                    host: host.to_string(),
+                    retry_in,
+                });
+            }
+        }
+
+        if let Some(limiter) = &self.limiter {
+            tokio::time::sleep_until(limiter.reserve(host, Instant::now())).await;
+        }
+
+        let response = request(&self.client()).send().await?;
+
+        let mut throttled = self.throttled.lock().unwrap();
+        if response.status() != StatusCode::TOO_MANY_REQUESTS {
+            throttled.remove(host);
+            return Ok(response);
+        }
+
+        let attempt = throttled.get(host).map_or(0, |(attempt, _)| *attempt);
+        let retry_in = backoff(
+            attempt,
+            response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok()),
+        );
+        throttled.insert(host.to_string(), (attempt + 1, Instant::now() + retry_in));
+
+        Err(SendError::Throttled {
+            host: host.to_string(),
+            retry_in,
+        })
+    }
+
+    /// Time until requests to the host may be retried after it throttled them,
+    /// unless it throttled more than `--http-output-retries` of them in a row.
+    pub fn throttled_for(&self, host: &str) -> Option<Duration> {
+        let throttled = self.throttled.lock().unwrap();
+        let (attempts, until) = throttled.get(host)?;
+
+        (*attempts <= self.args.http_output_retries)
+            .then(|| until.saturating_duration_since(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{allowed_url, backoff, parse_rps, RateLimiter, MAX_BACKOFF};
+
+    #[test]
+    fn backs_off_exponentially() {
+        assert_eq!(backoff(0, None), Duration::from_secs(1));
+        assert_eq!(backoff(3, None), Duration::from_secs(8));
+        assert_eq!(backoff(10, None), MAX_BACKOFF);
+
+        assert_eq!(backoff(0, Some("7")), Duration::from_secs(7));
+        assert_eq!(backoff(0, Some("3600")), MAX_BACKOFF);
+        // Dates are not supported, and fall back to the exponential backoff.
+        assert_eq!(
+            backoff(1, Some("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[test]
+    fn positive_rps() {
+        assert_eq!(parse_rps("2.5"), Ok(2.5));
+
+        assert!(parse_rps("0").is_err());
+        assert!(parse_rps("-1").is_err());
+        assert!(parse_rps("inf").is_err());
+        assert!(parse_rps("NaN").is_err());
+        assert!(parse_rps("fast").is_err());
+    }
+
+    #[test]
+    fn spaces_out_requests_per_host() {
+        let limiter = RateLimiter::new(4.0);
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve("a.example.org", now), now);
+        assert_eq!(
+            limiter.reserve("a.example.org", now),
+            now + Duration::from_millis(250)
+        );
+        assert_eq!(limiter.reserve("b.example.org", now), now);
+
+        // Unused budget does not accumulate.
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve("a.example.org", later), later);
+    }
+
+    #[test]
+    fn allows_https_to_listed_hosts() {
+        let allowed = [
+            String::from("hooks.example.org"),
+            String::from("*.probes.example.org"),
+        ];
+
+        assert!(allowed_url("https://hooks.example.org/render", &allowed).is_ok());
+        assert!(allowed_url("https://HOOKS.example.org:8443/render", &allowed).is_ok());
+        assert!(allowed_url("https://a.probes.example.org/", &allowed).is_ok());
+
+        assert!(allowed_url("http://hooks.example.org/render", &allowed).is_err());
+        assert!(allowed_url("https://probes.example.org/", &allowed).is_err());
+        assert!(allowed_url("https://evilprobes.example.org/", &allowed).is_err());
+        assert!(allowed_url("https://hooks.example.org.evil.test/", &allowed).is_err());
+        assert!(allowed_url("https://169.254.169.254/", &allowed).is_err());
+        assert!(allowed_url("https://hooks.example.org/", &[]).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use clap::Args;
+use kube::{config::InferConfigError, Client, ResourceExt as _};
+use zonefile_crds::ZoneFile;
+
+use crate::{budget, error::Error};
+
+/// Annotation on a [`ZoneFile`] naming the ServiceAccount in its namespace
+/// which its zones are read as, when impersonation is enabled.
+pub const SERVICE_ACCOUNT_ANNOTATION: &str = "zonefile.kubi.zone/service-account";
+
+/// ServiceAccount impersonated for zonefiles which do not name one.
+const DEFAULT_SERVICE_ACCOUNT: &str = "default";
+
+#[derive(Debug, Clone, Args)]
+pub struct ImpersonationArgs {
+    /// Read the Zones referenced by each ZoneFile while impersonating a ServiceAccount
+    /// in its namespace, named by its `zonefile.kubi.zone/service-account` annotation
+    /// or `default`, so that zonefiles only publish zones the account may read.
+    #[arg(long, env = "ZONEFILE_IMPERSONATE_SERVICE_ACCOUNTS")]
+    pub impersonate_service_accounts: bool,
+
+    /// Names of the ServiceAccounts which zonefiles may be read as. Other accounts
+    /// named by zonefiles are refused, and the controller is only granted the
+    /// impersonation of these by `rbac --impersonate-service-account`.
+    #[arg(
+        long = "impersonate-service-account",
+        env = "ZONEFILE_IMPERSONATE_SERVICE_ACCOUNT",
+        value_delimiter = ',',
+        default_value = DEFAULT_SERVICE_ACCOUNT
+    )]
+    pub impersonated_service_accounts: Vec<String>,
+}
+
+impl ImpersonationArgs {
+    /// Read the controller's own client configuration, if impersonation is enabled.
+    pub async fn impersonation(&self) -> Result<Option<Impersonation>, InferConfigError> {
+        if !self.impersonate_service_accounts {
+            return Ok(None);
+        }
+
+        Ok(Some(Impersonation {
+            config: kube::Config::infer().await?,
+            allowed: self.impersonated_service_accounts.clone(),
+            clients: Mutex::default(),
+        }))
+    }
+}
+
+/// The user name Kubernetes assigns to the ServiceAccount. The API server adds
+/// the groups of ServiceAccounts itself when only their user is impersonated,
+/// so the controller never needs to impersonate groups.
+fn identity(namespace: &str, service_account: &str) -> String {
+    format!("system:serviceaccount:{namespace}:{service_account}")
+}
+
+/// ServiceAccount which the zonefile's zones are read as, if it is one of the allowed accounts.
+fn service_account<'a>(zonefile: &'a ZoneFile, allowed: &[String]) -> Result<&'a str, Error> {
+    let service_account = zonefile
+        .annotations()
+        .get(SERVICE_ACCOUNT_ANNOTATION)
+        .map_or(DEFAULT_SERVICE_ACCOUNT, String::as_str);
+
+    if !allowed.iter().any(|name| name == service_account) {
+        return Err(Error::Config(format!(
+            "service account {service_account} may not be impersonated, allowed are: {}",
+            allowed.join(", ")
+        )));
+    }
+
+    Ok(service_account)
+}
+
+/// Clients impersonating the ServiceAccounts of zonefiles.
+pub struct Impersonation {
+    config: kube::Config,
+    allowed: Vec<String>,
+    clients: Mutex<HashMap<(String, String), Client>>,
+}
+
+impl Impersonation {
+    /// Client impersonating the ServiceAccount which the zonefile's zones are read as,
+    /// counting its requests against the budget of the reconciliation making them.
+    pub fn client(&self, zonefile: &ZoneFile) -> Result<Client, Error> {
+        let namespace = zonefile.namespace().unwrap();
+        let service_account = service_account(zonefile, &self.allowed)?.to_string();
+
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(&(namespace.clone(), service_account.clone())) {
+            return Ok(client.clone());
+        }
+
+        let mut config = self.config.clone();
+        config.auth_info.impersonate = Some(identity(&namespace, &service_account));
+
+        // Zones read as the ServiceAccount count against the reconciliation's
+        // budget, like those read with the controller's own client.
+        let client = budget::counted(Client::try_from(config)?);
+        clients.insert((namespace, service_account), client.clone());
+
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use kube::ResourceExt as _;
+    use zonefile_crds::ZoneFile;
+
+    use crate::budget::RequestBudget;
+
+    use super::{identity, service_account, Impersonation, SERVICE_ACCOUNT_ANNOTATION};
+
+    fn zonefile() -> ZoneFile {
+        serde_yaml::from_str(indoc::indoc! { r#"
+            apiVersion: kubi.zone/v1alpha1
+            kind: ZoneFile
+            metadata:
+              name: zones
+              namespace: tenant-a
+            spec:
+              configMapName: zones
+              zoneRefs: []
+        "#})
+        .unwrap()
+    }
+
+    #[test]
+    fn service_account_identity() {
+        assert_eq!(
+            identity("tenant-a", "dns"),
+            "system:serviceaccount:tenant-a:dns"
+        );
+    }
+
+    #[test]
+    fn only_allowed_service_accounts() {
+        let allowed = [String::from("default"), String::from("dns")];
+
+        let mut zonefile = zonefile();
+        assert_eq!(service_account(&zonefile, &allowed).unwrap(), "default");
+
+        zonefile
+            .annotations_mut()
+            .insert(SERVICE_ACCOUNT_ANNOTATION.to_string(), String::from("dns"));
+        assert_eq!(service_account(&zonefile, &allowed).unwrap(), "dns");
+
+        zonefile.annotations_mut().insert(
+            SERVICE_ACCOUNT_ANNOTATION.to_string(),
+            String::from("cluster-admin"),
+        );
+        assert!(service_account(&zonefile, &allowed).is_err());
+    }
+
+    #[tokio::test]
+    async fn impersonated_requests_are_counted() {
+        let impersonation = Impersonation {
+            config: kube::Config::new("http://127.0.0.1:1".parse().unwrap()),
+            allowed: vec![String::from("default")],
+            clients: Mutex::default(),
+        };
+        let client = impersonation.client(&zonefile()).unwrap();
+
+        // The exhausted budget refuses the request before it is sent.
+        let budget = RequestBudget::new(Some(0));
+        let result = budget.scope(client.list_api_groups()).await;
+
+        assert!(result.is_err());
+        assert_eq!(budget.used(), 1);
+        assert!(budget.exceeded().is_some());
+    }
+}
@@ -0,0 +1,121 @@
+//! Controller publishing the zones of kubizone ZoneFiles to ConfigMaps and other
+//! outputs. The `zonefile` binary is a thin command line wrapper around it, and
+//! [`reconciliation::run`] embeds the controller in another binary.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+mod acme;
+mod age;
+mod anomaly;
+mod api;
+mod audit;
+mod backref;
+pub mod backup;
+mod blackout;
+mod budget;
+pub mod builder;
+mod cache;
+mod comment;
+mod conditions;
+pub mod config;
+mod contact;
+pub mod convert;
+pub mod crd;
+#[cfg(feature = "debug-console")]
+pub mod debug;
+pub mod defaults;
+mod diff;
+mod digest;
+pub mod error;
+pub mod export;
+mod flatten;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod fuzz;
+mod generate;
+mod golden;
+mod grant;
+mod hooks;
+mod http;
+mod impersonate;
+mod lint;
+mod metrics;
+mod naming;
+mod negative;
+mod normalize;
+mod notify;
+mod obsolete;
+mod oci;
+mod output;
+mod ownership;
+pub mod query;
+mod quota;
+pub mod rbac;
+pub mod reconciliation;
+pub mod render;
+mod saturation;
+mod schedule;
+mod serial;
+mod serve;
+mod server;
+mod service;
+mod shuffle;
+mod signing;
+pub mod simulate;
+mod snippet;
+mod source;
+mod staging;
+pub mod state;
+mod stats;
+mod status;
+mod stream;
+pub mod template;
+mod throttle;
+mod tinydns;
+mod trace;
+mod tsig;
+mod validation;
+pub mod version;
+mod warmup;
+mod watch;
+
+#[derive(Debug, Parser)]
+#[command(author, version, long_version = version::LONG_VERSION, about, long_about = None)]
+pub struct Args {
+    /// YAML file holding options for the command, keyed by their long flag.
+    /// Sending SIGHUP to the controller reloads it.
+    #[arg(long, global = true, env = config::CONFIG_ENV)]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    Reconcile(Box<reconciliation::Config>),
+    /// Write the zones published by ZoneFiles to local files.
+    Export(export::ExportArgs),
+    /// Show how proposed Records and Zones would change the published zones.
+    Simulate(simulate::SimulateArgs),
+    /// Render the zones described by local Record and Zone manifests, optionally
+    /// checking them against golden files.
+    Render(simulate::RenderArgs),
+    /// Query a nameserver and compare its answer to the zones described by local
+    /// Record and Zone manifests, for smoke testing deployments.
+    Query(query::QueryArgs),
+    /// Check the zones described by local Record and Zone manifests against the
+    /// lint rules, and check that their address and PTR records match each other.
+    Check(simulate::CheckArgs),
+    /// Convert a zone file between the supported flavors, without a cluster.
+    Convert(convert::ConvertArgs),
+    /// Republish the zones stored in a ZoneBackup.
+    Restore(backup::RestoreArgs),
+    /// Print the custom resource definitions used by this controller, with admission validation.
+    PrintCrds,
+    /// Print the roles and bindings required by the controller.
+    Rbac(rbac::RbacArgs),
+    /// Print the version of the controller, the build it came from and the custom resources it supports.
+    Version,
+}
@@ -0,0 +1,836 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use clap::{Args, ValueEnum};
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+
+use crate::{
+    flatten,
+    validation::{is_wildcard, tokenize},
+};
+
+#[derive(Debug, Clone, Args)]
+pub struct LintArgs {
+    /// Report names which have an A record but no AAAA record, or vice versa.
+    #[arg(long, env = "ZONEFILE_LINT_DUAL_STACK")]
+    pub lint_dual_stack: bool,
+
+    /// Report address records without a matching PTR record, and PTR records
+    /// without a matching address record, where both the forward and the
+    /// reverse zone belong to the same zonefile.
+    #[arg(long, env = "ZONEFILE_LINT_PTR_CONSISTENCY")]
+    pub lint_ptr_consistency: bool,
+
+    /// Severity of a lint rule, as `<rule>=<severity>`, such as `short-ttl=warning`.
+    /// Rules are off unless given a severity here, or by the zonefile's
+    /// `zonefile.kubi.zone/lint-rules` annotation in the same form.
+    #[arg(
+        long = "lint-rule",
+        env = "ZONEFILE_LINT_RULES",
+        value_delimiter = ',',
+        value_parser = parse_rule
+    )]
+    pub lint_rules: Vec<(Rule, Severity)>,
+
+    /// TTL below which records violate the `short-ttl` rule.
+    #[arg(long, env = "ZONEFILE_LINT_MIN_TTL", default_value_t = 60)]
+    pub lint_min_ttl: u32,
+
+    /// Length of text in bytes above which TXT records violate the `long-txt` rule.
+    #[arg(long, env = "ZONEFILE_LINT_MAX_TXT_LENGTH", default_value_t = 255)]
+    pub lint_max_txt_length: usize,
+}
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding a comma-separated
+/// list of `<rule>=<severity>` pairs, overriding the severities of the controller.
+pub const LINT_RULES_ANNOTATION: &str = "zonefile.kubi.zone/lint-rules";
+
+/// Rule checked against the records of each zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Rule {
+    /// Records with a TTL below the minimum.
+    ShortTtl,
+    /// Names with A records but no AAAA records.
+    MissingAaaa,
+    /// Wildcard records.
+    Wildcard,
+    /// Records of types which are obsolete, such as DLV.
+    DeprecatedType,
+    /// TXT records with more text than the maximum.
+    LongTxt,
+    /// CNAME records pointing at other CNAME records in the zone.
+    CnameChain,
+}
+
+impl Rule {
+    /// Reason reported in the `LintViolation` condition, and
+    /// the `rule` label of the lint findings metric.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Rule::ShortTtl => "ShortTtl",
+            Rule::MissingAaaa => "MissingAaaa",
+            Rule::Wildcard => "Wildcard",
+            Rule::DeprecatedType => "DeprecatedType",
+            Rule::LongTxt => "LongTxt",
+            Rule::CnameChain => "CnameChain",
+        }
+    }
+}
+
+/// Severity of a lint rule. Warnings are reported, while errors also keep the
+/// previously published contents of the zone, as if it failed validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Severity {
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+fn parse_rule(rule: &str) -> Result<(Rule, Severity), String> {
+    let (name, severity) = rule
+        .split_once('=')
+        .ok_or_else(|| format!("lint rule {rule:?} is not of the form <rule>=<severity>"))?;
+
+    Ok((
+        Rule::from_str(name.trim(), true)?,
+        Severity::from_str(severity.trim(), true)?,
+    ))
+}
+
+/// Parse a comma-separated list of `<rule>=<severity>` pairs.
+pub fn parse_rules(rules: &str) -> Result<Vec<(Rule, Severity)>, String> {
+    rules
+        .split(',')
+        .filter(|rule| !rule.trim().is_empty())
+        .map(parse_rule)
+        .collect()
+}
+
+/// Violation of a lint rule by a record of a zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Types which are obsolete, and should no longer be published.
+const DEPRECATED_TYPES: &[Type] = &[Type::DLV, Type::KEY, Type::SIG];
+
+impl LintArgs {
+    /// Severities of the rules, as configured for the controller and overridden by the zonefile.
+    pub fn severities(&self, overrides: &[(Rule, Severity)]) -> BTreeMap<Rule, Severity> {
+        let mut severities = BTreeMap::from_iter(self.lint_rules.iter().chain(overrides).copied());
+        severities.retain(|_, severity| *severity != Severity::Off);
+        severities
+    }
+
+    /// Check the [canonicalized](crate::normalize::canonicalize) records of a zone
+    /// against the rules which are not off.
+    pub fn lint(
+        &self,
+        severities: &BTreeMap<Rule, Severity>,
+        entries: &[ZoneEntry],
+    ) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut report = |rule: Rule, message: String| {
+            if let Some(severity) = severities.get(&rule) {
+                findings.push(Finding {
+                    rule,
+                    severity: *severity,
+                    message,
+                });
+            }
+        };
+
+        let aliases = flatten::aliases(entries);
+
+        for entry in entries {
+            if entry.ttl < self.lint_min_ttl {
+                report(
+                    Rule::ShortTtl,
+                    format!(
+                        "{} {} has a TTL of {}, below {}",
+                        entry.fqdn, entry.type_, entry.ttl, self.lint_min_ttl
+                    ),
+                );
+            }
+
+            if is_wildcard(entry) {
+                report(
+                    Rule::Wildcard,
+                    format!("{} {} is a wildcard record", entry.fqdn, entry.type_),
+                );
+            }
+
+            if DEPRECATED_TYPES.contains(&entry.type_) {
+                report(
+                    Rule::DeprecatedType,
+                    format!(
+                        "{} has a record of deprecated type {}",
+                        entry.fqdn, entry.type_
+                    ),
+                );
+            }
+
+            if entry.type_ == Type::TXT {
+                // Length of the text itself, without quotes or the whitespace between strings.
+                let length: usize = tokenize(&entry.rdata)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|string| string.trim_matches('"').len())
+                    .sum();
+
+                if length > self.lint_max_txt_length {
+                    report(
+                        Rule::LongTxt,
+                        format!(
+                            "{} has a TXT record of {length} bytes, more than {}",
+                            entry.fqdn, self.lint_max_txt_length
+                        ),
+                    );
+                }
+            }
+
+            if entry.type_ == Type::CNAME {
+                match flatten::follow(&aliases, &entry.fqdn) {
+                    Some(chain) if chain.len() > 2 => report(
+                        Rule::CnameChain,
+                        format!(
+                            "{} reaches {} through {} CNAME records",
+                            entry.fqdn,
+                            chain.last().unwrap(),
+                            chain.len() - 1
+                        ),
+                    ),
+                    Some(_) => (),
+                    None => report(
+                        Rule::CnameChain,
+                        format!("{} is part of a loop of CNAME records", entry.fqdn),
+                    ),
+                }
+            }
+        }
+
+        for gap in dual_stack_gaps(entries) {
+            if gap.missing == Type::AAAA {
+                report(Rule::MissingAaaa, gap.to_string());
+            }
+        }
+
+        findings
+    }
+}
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding a comma-separated
+/// list of nameservers, which are added as NS records at the apex of zones lacking them.
+pub const DEFAULT_NAMESERVERS_ANNOTATION: &str = "zonefile.kubi.zone/default-nameservers";
+
+/// Name which has address records of only one family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DualStackGap {
+    pub name: FullyQualifiedDomainName,
+    /// The address record type the name is lacking.
+    pub missing: Type,
+}
+
+impl std::fmt::Display for DualStackGap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} has no {} record", self.name, self.missing)
+    }
+}
+
+/// Find names in the zone which have A records but no AAAA records, or vice versa.
+pub fn dual_stack_gaps(entries: &[ZoneEntry]) -> Vec<DualStackGap> {
+    let mut families = BTreeMap::<&FullyQualifiedDomainName, (bool, bool)>::new();
+
+    for entry in entries {
+        match entry.type_ {
+            Type::A => families.entry(&entry.fqdn).or_default().0 = true,
+            Type::AAAA => families.entry(&entry.fqdn).or_default().1 = true,
+            _ => (),
+        }
+    }
+
+    families
+        .into_iter()
+        .filter_map(|(name, families)| {
+            let missing = match families {
+                (true, false) => Type::AAAA,
+                (false, true) => Type::A,
+                _ => return None,
+            };
+
+            Some(DualStackGap {
+                name: name.clone(),
+                missing,
+            })
+        })
+        .collect()
+}
+
+/// Address record or PTR record lacking its counterpart in the other direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PtrMismatch {
+    /// The name has an address without a PTR record pointing back at it.
+    MissingPtr {
+        name: FullyQualifiedDomainName,
+        address: IpAddr,
+    },
+    /// The PTR record of the address points at a name without the address.
+    OrphanedPtr {
+        address: IpAddr,
+        target: FullyQualifiedDomainName,
+    },
+}
+
+impl std::fmt::Display for PtrMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PtrMismatch::MissingPtr { name, address } => {
+                write!(f, "{name} has no PTR record for {address}")
+            }
+            PtrMismatch::OrphanedPtr { address, target } => {
+                write!(
+                    f,
+                    "PTR record of {address} points at {target}, which does not have the address"
+                )
+            }
+        }
+    }
+}
+
+/// Name of the PTR record of the address, such as `1.2.0.192.in-addr.arpa.`.
+fn reverse_name(address: IpAddr) -> String {
+    match address {
+        IpAddr::V4(address) => {
+            let [a, b, c, d] = address.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa.")
+        }
+        IpAddr::V6(address) => {
+            let mut name = String::with_capacity(72);
+            for byte in address.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0x0f, byte >> 4));
+            }
+            name.push_str("ip6.arpa.");
+            name
+        }
+    }
+}
+
+/// Address whose PTR record has the name, if the name is a complete reverse name.
+fn reverse_address(name: &FullyQualifiedDomainName) -> Option<IpAddr> {
+    let name = name.to_string().to_ascii_lowercase();
+
+    if let Some(octets) = name.strip_suffix(".in-addr.arpa.") {
+        let mut octets = octets
+            .split('.')
+            .map(|octet| octet.parse::<u8>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        octets.reverse();
+
+        return Some(IpAddr::V4(Ipv4Addr::from(
+            <[u8; 4]>::try_from(octets).ok()?,
+        )));
+    }
+
+    let nibbles = name
+        .strip_suffix(".ip6.arpa.")?
+        .split('.')
+        .map(|nibble| {
+            u8::from_str_radix(nibble, 16)
+                .ok()
+                .filter(|_| nibble.len() == 1)
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if nibbles.len() != 32 {
+        return None;
+    }
+
+    let mut octets = [0u8; 16];
+    for (index, pair) in nibbles.chunks(2).rev().enumerate() {
+        octets[index] = (pair[1] << 4) | pair[0];
+    }
+
+    Some(IpAddr::V6(Ipv6Addr::from(octets)))
+}
+
+/// Compare the address records of the zones with their PTR records. Only addresses
+/// whose reverse names fall within one of the zones are expected to have PTR records,
+/// and only PTR records pointing into one of the zones are expected to have addresses,
+/// so that forward or reverse zones managed elsewhere are not reported.
+///
+/// Mismatches are returned by the origin of the zone holding the offending record.
+pub fn ptr_mismatches(
+    zones: &[(FullyQualifiedDomainName, Vec<ZoneEntry>)],
+) -> BTreeMap<FullyQualifiedDomainName, Vec<PtrMismatch>> {
+    let managed = |name: &FullyQualifiedDomainName| {
+        zones
+            .iter()
+            .any(|(origin, _)| name == origin || name.is_subdomain_of(origin))
+    };
+
+    let mut addresses = BTreeSet::<(IpAddr, &FullyQualifiedDomainName)>::new();
+    let mut pointers = BTreeSet::<(IpAddr, FullyQualifiedDomainName)>::new();
+
+    for (_, entries) in zones {
+        for entry in entries {
+            match entry.type_ {
+                Type::A | Type::AAAA => {
+                    if let Ok(address) = entry.rdata.trim().parse() {
+                        addresses.insert((address, &entry.fqdn));
+                    }
+                }
+                Type::PTR => {
+                    if let (Some(address), Ok(target)) = (
+                        reverse_address(&entry.fqdn),
+                        FullyQualifiedDomainName::try_from(entry.rdata.trim().to_ascii_lowercase()),
+                    ) {
+                        pointers.insert((address, target));
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut mismatches = BTreeMap::<FullyQualifiedDomainName, Vec<PtrMismatch>>::new();
+
+    for (origin, entries) in zones {
+        for entry in entries {
+            let mismatch = match entry.type_ {
+                Type::A | Type::AAAA => {
+                    let Ok(address) = entry.rdata.trim().parse::<IpAddr>() else {
+                        continue;
+                    };
+
+                    let Ok(reverse) = FullyQualifiedDomainName::try_from(reverse_name(address))
+                    else {
+                        continue;
+                    };
+
+                    if !managed(&reverse) || pointers.contains(&(address, entry.fqdn.clone())) {
+                        continue;
+                    }
+
+                    PtrMismatch::MissingPtr {
+                        name: entry.fqdn.clone(),
+                        address,
+                    }
+                }
+                Type::PTR => {
+                    let (Some(address), Ok(target)) = (
+                        reverse_address(&entry.fqdn),
+                        FullyQualifiedDomainName::try_from(entry.rdata.trim().to_ascii_lowercase()),
+                    ) else {
+                        continue;
+                    };
+
+                    if !managed(&target) || addresses.contains(&(address, &target)) {
+                        continue;
+                    }
+
+                    PtrMismatch::OrphanedPtr { address, target }
+                }
+                _ => continue,
+            };
+
+            mismatches.entry(origin.clone()).or_default().push(mismatch);
+        }
+    }
+
+    mismatches
+}
+
+/// Make sure the zone has NS records at its apex, adding records for the default
+/// nameservers if it has none of its own, right after its SOA record.
+///
+/// Returns `false` if the zone is left without apex NS records, rendering it unusable.
+pub fn ensure_apex_nameservers(
+    origin: &FullyQualifiedDomainName,
+    entries: &mut Vec<ZoneEntry>,
+    default_nameservers: &[FullyQualifiedDomainName],
+    ttl: u32,
+) -> bool {
+    if entries
+        .iter()
+        .any(|entry| entry.type_ == Type::NS && entry.fqdn == *origin)
+    {
+        return true;
+    }
+
+    let position = entries
+        .iter()
+        .position(|entry| entry.type_ == Type::SOA && entry.fqdn == *origin)
+        .map_or(0, |soa| soa + 1);
+
+    entries.splice(
+        position..position,
+        default_nameservers.iter().map(|nameserver| ZoneEntry {
+            fqdn: origin.clone(),
+            type_: Type::NS,
+            class: Class::IN,
+            ttl,
+            rdata: nameserver.to_string(),
+        }),
+    );
+
+    !default_nameservers.is_empty()
+}
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) which, when set to `true`,
+/// copies missing glue records from the address records of the zonefile's other zones.
+pub const SYNTHESIZE_GLUE_ANNOTATION: &str = "zonefile.kubi.zone/synthesize-glue";
+
+/// Make sure that nameservers within the zone have address records in it,
+/// since resolvers could not otherwise reach them. Missing records are
+/// copied from `glue_sources`, where available.
+///
+/// Returns the nameservers which are still lacking address records.
+pub fn ensure_glue(
+    origin: &FullyQualifiedDomainName,
+    entries: &mut Vec<ZoneEntry>,
+    glue_sources: &[ZoneEntry],
+) -> Vec<FullyQualifiedDomainName> {
+    let addressed = entries
+        .iter()
+        .filter(|entry| matches!(entry.type_, Type::A | Type::AAAA))
+        .map(|entry| entry.fqdn.clone())
+        .collect::<BTreeSet<_>>();
+
+    let unglued = entries
+        .iter()
+        .filter(|entry| entry.type_ == Type::NS)
+        .filter_map(|entry| FullyQualifiedDomainName::try_from(entry.rdata.as_str()).ok())
+        .filter(|nameserver| nameserver == origin || nameserver.is_subdomain_of(origin))
+        .filter(|nameserver| !addressed.contains(nameserver))
+        .collect::<BTreeSet<_>>();
+
+    let mut missing = Vec::new();
+    for nameserver in unglued {
+        let glue = glue_sources
+            .iter()
+            .filter(|entry| entry.fqdn == nameserver)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if glue.is_empty() {
+            missing.push(nameserver);
+        } else {
+            entries.extend(glue);
+        }
+    }
+
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use std::collections::BTreeMap;
+
+    use clap::Parser as _;
+
+    use super::{
+        dual_stack_gaps, ensure_apex_nameservers, ensure_glue, parse_rules, ptr_mismatches,
+        reverse_address, reverse_name, DualStackGap, LintArgs, PtrMismatch, Rule, Severity,
+    };
+    use crate::normalize::canonicalize;
+
+    #[test]
+    fn reports_single_family_names() {
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("www.example.org.", Type::A, "192.0.2.1"),
+            entry("www.example.org.", Type::AAAA, "2001:db8::1"),
+            entry("v4.example.org.", Type::A, "192.0.2.2"),
+            entry("v6.example.org.", Type::AAAA, "2001:db8::2"),
+            entry("alias.example.org.", Type::CNAME, "www.example.org."),
+        ];
+
+        assert_eq!(
+            dual_stack_gaps(&entries),
+            vec![
+                DualStackGap {
+                    name: FullyQualifiedDomainName::try_from("v4.example.org.").unwrap(),
+                    missing: Type::AAAA,
+                },
+                DualStackGap {
+                    name: FullyQualifiedDomainName::try_from("v6.example.org.").unwrap(),
+                    missing: Type::A,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn injects_missing_apex_nameservers() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+        let nameservers = vec![
+            FullyQualifiedDomainName::try_from("ns1.example.net.").unwrap(),
+            FullyQualifiedDomainName::try_from("ns2.example.net.").unwrap(),
+        ];
+
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let mut entries = vec![
+            entry(
+                "example.org.",
+                Type::SOA,
+                "ns1.example.net. hostmaster.example.org. 1 86400 7200 3600000 360",
+            ),
+            entry("www.example.org.", Type::A, "192.0.2.1"),
+            entry("sub.example.org.", Type::NS, "ns.sub.example.org."),
+        ];
+
+        assert!(!ensure_apex_nameservers(
+            &origin,
+            &mut entries.clone(),
+            &[],
+            360
+        ));
+
+        assert!(ensure_apex_nameservers(
+            &origin,
+            &mut entries,
+            &nameservers,
+            360
+        ));
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[1].type_, Type::NS);
+        assert_eq!(entries[2].rdata, "ns2.example.net.");
+
+        // Zones with apex nameservers of their own are left alone.
+        assert!(ensure_apex_nameservers(
+            &origin,
+            &mut entries,
+            &nameservers,
+            360
+        ));
+        assert_eq!(entries.len(), 5);
+    }
+
+    #[test]
+    fn copies_missing_glue() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let mut entries = vec![
+            entry("example.org.", Type::NS, "ns1.example.org."),
+            entry("example.org.", Type::NS, "ns.example.net."),
+            entry("ns1.example.org.", Type::A, "192.0.2.1"),
+            entry("sub.example.org.", Type::NS, "ns.sub.example.org."),
+            entry("other.example.org.", Type::NS, "ns.other.example.org."),
+        ];
+
+        let glue_sources = vec![entry("ns.sub.example.org.", Type::AAAA, "2001:db8::53")];
+
+        assert_eq!(
+            ensure_glue(&origin, &mut entries, &glue_sources),
+            vec![FullyQualifiedDomainName::try_from("ns.other.example.org.").unwrap()]
+        );
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[5].rdata, "2001:db8::53");
+    }
+
+    #[test]
+    fn reverse_names() {
+        for address in ["192.0.2.1", "2001:db8::1"] {
+            let address = address.parse().unwrap();
+            let name = FullyQualifiedDomainName::try_from(reverse_name(address)).unwrap();
+            assert_eq!(reverse_address(&name), Some(address));
+        }
+
+        assert_eq!(
+            reverse_name("192.0.2.1".parse().unwrap()),
+            "1.2.0.192.in-addr.arpa."
+        );
+        assert_eq!(
+            reverse_address(&FullyQualifiedDomainName::try_from("2.0.192.in-addr.arpa.").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn reports_ptr_mismatches() {
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let forward = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+        let reverse = FullyQualifiedDomainName::try_from("2.0.192.in-addr.arpa.").unwrap();
+
+        let zones = vec![
+            (
+                forward.clone(),
+                vec![
+                    entry("www.example.org.", Type::A, "192.0.2.1"),
+                    entry("mail.example.org.", Type::A, "192.0.2.2"),
+                    // Reverse zones of other networks are managed elsewhere.
+                    entry("www.example.org.", Type::A, "198.51.100.1"),
+                    entry("www.example.org.", Type::AAAA, "2001:db8::1"),
+                ],
+            ),
+            (
+                reverse.clone(),
+                vec![
+                    entry("1.2.0.192.in-addr.arpa.", Type::PTR, "www.example.org."),
+                    entry("3.2.0.192.in-addr.arpa.", Type::PTR, "old.example.org."),
+                    // Names of other zones are managed elsewhere.
+                    entry("4.2.0.192.in-addr.arpa.", Type::PTR, "host.example.net."),
+                ],
+            ),
+        ];
+
+        let mismatches = ptr_mismatches(&zones);
+        assert_eq!(
+            mismatches[&forward],
+            vec![PtrMismatch::MissingPtr {
+                name: FullyQualifiedDomainName::try_from("mail.example.org.").unwrap(),
+                address: "192.0.2.2".parse().unwrap(),
+            }]
+        );
+        assert_eq!(
+            mismatches[&reverse],
+            vec![PtrMismatch::OrphanedPtr {
+                address: "192.0.2.3".parse().unwrap(),
+                target: FullyQualifiedDomainName::try_from("old.example.org.").unwrap(),
+            }]
+        );
+    }
+
+    #[derive(clap::Parser)]
+    struct Args {
+        #[command(flatten)]
+        lint: LintArgs,
+    }
+
+    #[test]
+    fn configurable_rules() {
+        let entry = |name: &str, type_: Type, ttl: u32, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl,
+            rdata: rdata.to_string(),
+        };
+
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        // The chain is followed through relative and mixed-case targets once canonicalized.
+        let entries = canonicalize(
+            &origin,
+            vec![
+                entry("www.example.org.", Type::A, 30, "192.0.2.1"),
+                entry("cdn.example.org.", Type::CNAME, 300, "www.example.org."),
+                entry("alias.example.org.", Type::CNAME, 300, "CDN"),
+                entry(
+                    "example.org.",
+                    Type::DLV,
+                    300,
+                    "60485 5 1 2BB183AF5F22588179A53B0A98631FAD1A292118",
+                ),
+                entry(
+                    "example.org.",
+                    Type::TXT,
+                    300,
+                    &format!("\"{}\" \"{}\"", "a".repeat(200), "b".repeat(100)),
+                ),
+            ],
+        );
+
+        let lint = Args::parse_from([
+            "zonefile",
+            "--lint-rule",
+            "short-ttl=warning,wildcard=info",
+            "--lint-rule",
+            "missing-aaaa=error",
+        ])
+        .lint;
+
+        assert_eq!(
+            lint.lint_rules,
+            vec![
+                (Rule::ShortTtl, Severity::Warning),
+                (Rule::Wildcard, Severity::Info),
+                (Rule::MissingAaaa, Severity::Error),
+            ]
+        );
+
+        // The zonefile turns off some rules and enables others.
+        let severities = lint.severities(
+            &parse_rules(
+                "missing-aaaa=off, deprecated-type=error,long-txt=warning,cname-chain=warning",
+            )
+            .unwrap(),
+        );
+
+        let findings = lint.lint(&severities, &entries);
+        let rules = findings
+            .iter()
+            .map(|finding| (finding.rule, finding.severity))
+            .collect::<BTreeMap<_, _>>();
+
+        assert_eq!(
+            rules,
+            BTreeMap::from([
+                (Rule::ShortTtl, Severity::Warning),
+                (Rule::DeprecatedType, Severity::Error),
+                (Rule::LongTxt, Severity::Warning),
+                (Rule::CnameChain, Severity::Warning),
+            ])
+        );
+        assert_eq!(findings.len(), 4);
+        assert_eq!(
+            findings[0].to_string(),
+            "warning: www.example.org. A has a TTL of 30, below 60"
+        );
+
+        assert!(parse_rules("short-ttl").is_err());
+        assert!(parse_rules("short-ttl=fatal").is_err());
+        assert!(lint.lint(&lint.severities(&[]), &entries[1..]).is_empty());
+    }
+}
@@ -1,30 +1,136 @@
-use clap::{command, Parser, Subcommand};
-use kube::Client;
-mod reconciliation;
-
-#[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    #[command(subcommand)]
-    pub command: Command,
-}
-
-#[derive(Debug, Subcommand)]
-enum Command {
-    Reconcile,
-}
+use clap::{CommandFactory as _, Parser as _};
+use kube::{Client, CustomResourceExt as _};
+use tracing_subscriber::{
+    filter::LevelFilter, fmt::writer::BoxMakeWriter, layer::SubscriberExt as _,
+    util::SubscriberInitExt as _, Layer as _,
+};
+use zonefile::{
+    backup, config, convert, crd, defaults, export, query, rbac, reconciliation, simulate, state,
+    template, version, Args, Command,
+};
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    // Options from the configuration file are parsed along with the command line.
+    let config_file = config::ConfigFile::locate();
+    let args = match &config_file {
+        Some(config_file) => match config_file.args(&Args::command()) {
+            Ok(args) => Args::parse_from(args),
+            Err(e) => {
+                eprintln!("failed to load configuration file: {e}");
+                std::process::exit(1);
+            }
+        },
+        None => Args::parse(),
+    };
+
+    // An audit trail written to standard output must not be interleaved with logs.
+    let writer = match &args.command {
+        Command::Reconcile(config) if config.audit.to_stdout() => {
+            BoxMakeWriter::new(std::io::stderr)
+        }
+        _ => BoxMakeWriter::new(std::io::stdout),
+    };
+
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_filter(LevelFilter::INFO),
+    );
 
-    let args = Args::parse();
+    #[cfg(feature = "debug-console")]
+    let subscriber = subscriber.with(match &args.command {
+        Command::Reconcile(config) => config.debug.console_layer(),
+        _ => None,
+    });
+
+    subscriber.init();
 
     match args.command {
-        Command::Reconcile => {
+        Command::Reconcile(config) => {
+            let client = Client::try_default().await.unwrap();
+
+            if let Err(e) = reconciliation::reconcile(client, *config, config_file).await {
+                eprintln!("reconciliation failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::Export(args) => {
+            let client = Client::try_default().await.unwrap();
+
+            if let Err(e) = export::export(client, args).await {
+                eprintln!("export failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::Simulate(args) => {
             let client = Client::try_default().await.unwrap();
 
-            reconciliation::reconcile(client).await;
+            if let Err(e) = simulate::simulate(client, args).await {
+                eprintln!("simulation failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::Render(args) => {
+            if let Err(e) = simulate::render(args).await {
+                eprintln!("render failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::Query(args) => {
+            if let Err(e) = query::query(args).await {
+                eprintln!("query failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::Check(args) => {
+            if let Err(e) = simulate::check(args).await {
+                eprintln!("check failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::Convert(args) => {
+            if let Err(e) = convert::convert(args).await {
+                eprintln!("conversion failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::Restore(args) => {
+            let client = Client::try_default().await.unwrap();
+
+            if let Err(e) = backup::restore(client, args).await {
+                eprintln!("restore failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Command::PrintCrds => {
+            println!(
+                "---\n{}",
+                serde_yaml::to_string(&crd::zonefile_crd()).unwrap()
+            );
+            println!(
+                "---\n{}",
+                serde_yaml::to_string(&backup::ZoneBackup::crd()).unwrap()
+            );
+            println!(
+                "---\n{}",
+                serde_yaml::to_string(&template::ZoneFileTemplate::crd()).unwrap()
+            );
+            println!(
+                "---\n{}",
+                serde_yaml::to_string(&defaults::ZoneFileDefaults::crd()).unwrap()
+            );
+            println!(
+                "---\n{}",
+                serde_yaml::to_string(&state::ZoneFileState::crd()).unwrap()
+            );
+        }
+        Command::Rbac(args) => {
+            if let Err(e) = rbac::rbac(args) {
+                eprintln!("rbac generation failed: {e}");
+                std::process::exit(1);
+            }
         }
+        Command::Version => version::print(),
     }
 }
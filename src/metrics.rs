@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use axum::{http::header::CONTENT_TYPE, routing::get, Router};
+use clap::Args;
+use prometheus::{
+    core::{Collector, MetricVec, MetricVecBuilder},
+    CounterVec, Encoder as _, GaugeVec, Opts, TextEncoder,
+};
+use tracing::log::*;
+
+use crate::server;
+
+#[derive(Debug, Clone, Args)]
+pub struct MetricsArgs {
+    /// Address on which to expose Prometheus metrics at `/metrics`.
+    #[arg(long, env = "ZONEFILE_METRICS_ADDRESS")]
+    pub metrics_address: Option<SocketAddr>,
+}
+
+/// A gauge family, registered with the default Prometheus registry on first use.
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    family: OnceLock<GaugeVec>,
+}
+
+/// A counter family, registered with the default Prometheus registry on first use.
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    family: OnceLock<CounterVec>,
+}
+
+pub static DUAL_STACK_GAPS: Gauge = Gauge::new(
+    "zonefile_dual_stack_gaps",
+    "Number of names in a rendered zone which have address records of only one family.",
+    &["zonefile", "origin", "missing"],
+);
+
+pub static ANOMALIES: Gauge = Gauge::new(
+    "zonefile_anomalies",
+    "Anomalies found in a zone when it was last rendered, by the check which found them.",
+    &["zonefile", "origin", "check"],
+);
+
+pub static LINT_FINDINGS: Gauge = Gauge::new(
+    "zonefile_lint_findings",
+    "Number of records in a zone violating a lint rule when it was last rendered, by rule and severity.",
+    &["zonefile", "origin", "rule", "severity"],
+);
+
+pub static RECONCILE_PANICS: Counter = Counter::new(
+    "zonefile_reconcile_panics_total",
+    "Number of reconciliations of a zonefile which were aborted by a panic.",
+    &["zonefile"],
+);
+
+pub static WATCH_LAG: Gauge = Gauge::new(
+    "zonefile_watch_lag_seconds",
+    "Seconds between the last change to a resource and the controller receiving its watch event, for the most recent event of each kind of resource.",
+    &["resource"],
+);
+
+pub static RECONCILE_QUEUE_DEPTH: Gauge = Gauge::new(
+    "zonefile_reconcile_queue_depth",
+    "Number of zonefiles whose reconciliation was triggered by a change, but has not started yet.",
+    &[],
+);
+
+pub static RECONCILE_API_REQUESTS: Gauge = Gauge::new(
+    "zonefile_reconcile_api_requests",
+    "Number of Kubernetes API requests made by the most recent reconciliation of the zonefile.",
+    &["zonefile"],
+);
+
+pub static RECONCILES_IN_FLIGHT: Gauge = Gauge::new(
+    "zonefile_reconciles_in_flight",
+    "Number of reconciliations of zonefiles currently running.",
+    &[],
+);
+
+pub static BUILD_INFO: Gauge = Gauge::new(
+    "zonefile_build_info",
+    "Always 1, labeled with the version, commit, build time and target of the running controller.",
+    &["version", "commit", "built", "target"],
+);
+
+pub static SECONDS_SINCE_LAST_SYNC: Gauge = Gauge::new(
+    "zonefile_seconds_since_last_successful_sync",
+    "Seconds since the zonefile was last reconciled successfully, or since it was first seen if it never was, as of the scrape.",
+    &["zonefile"],
+);
+
+/// Time of the last successful reconciliation of each zonefile. The staleness
+/// metric is computed from it whenever the metrics are rendered, so that it keeps
+/// growing while a zonefile is not reconciled at all.
+static LAST_SYNCS: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+/// Start tracking the staleness of the zonefile, unless it is already tracked.
+pub fn seen(zonefile: &str) {
+    LAST_SYNCS
+        .lock()
+        .unwrap()
+        .entry(zonefile.to_string())
+        .or_insert_with(Instant::now);
+}
+
+/// Record a successful reconciliation of the zonefile.
+pub fn synced(zonefile: &str) {
+    LAST_SYNCS
+        .lock()
+        .unwrap()
+        .insert(zonefile.to_string(), Instant::now());
+}
+
+/// Stop tracking the staleness of a deleted zonefile.
+pub fn forget(zonefile: &str) {
+    LAST_SYNCS.lock().unwrap().remove(zonefile);
+    SECONDS_SINCE_LAST_SYNC.remove_matching(&[("zonefile", zonefile)]);
+    RECONCILE_API_REQUESTS.remove_matching(&[("zonefile", zonefile)]);
+}
+
+/// Tracks a running operation in a gauge, until dropped.
+pub struct InFlight(&'static Gauge);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.family().with_label_values(&[]).dec();
+    }
+}
+
+/// Register a family with the default registry, which only fails
+/// if the name is already taken by a different family.
+fn register(name: &str, family: impl Collector + 'static) {
+    if let Err(e) = prometheus::register(Box::new(family)) {
+        warn!("failed to register metric {name}: {e}");
+    }
+}
+
+/// Values of the given labels, in the order the family declares them.
+fn label_values<'a>(declared: &[&str], labels: &[(&'static str, &'a str)]) -> Vec<&'a str> {
+    declared
+        .iter()
+        .map(|name| {
+            labels
+                .iter()
+                .find(|(key, _)| key == name)
+                .map_or("", |(_, value)| *value)
+        })
+        .collect()
+}
+
+/// Remove all series of the family which carry *all* of the given labels.
+fn remove_matching<T: MetricVecBuilder>(family: &MetricVec<T>, labels: &[(&'static str, &str)]) {
+    for metric_family in family.collect() {
+        for metric in metric_family.get_metric() {
+            let series: HashMap<&str, &str> = metric
+                .get_label()
+                .iter()
+                .map(|pair| (pair.get_name(), pair.get_value()))
+                .collect();
+
+            if labels
+                .iter()
+                .all(|(key, value)| series.get(key) == Some(value))
+            {
+                let _ = family.remove(&series);
+            }
+        }
+    }
+}
+
+impl Gauge {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Gauge {
+            name,
+            help,
+            labels,
+            family: OnceLock::new(),
+        }
+    }
+
+    fn family(&'static self) -> &'static GaugeVec {
+        self.family.get_or_init(|| {
+            let family = GaugeVec::new(Opts::new(self.name, self.help), self.labels).unwrap();
+            register(self.name, family.clone());
+            family
+        })
+    }
+
+    /// Set the value of the series identified by the labels.
+    pub fn set(&'static self, labels: &[(&'static str, &str)], value: f64) {
+        self.family()
+            .with_label_values(&label_values(self.labels, labels))
+            .set(value);
+    }
+
+    /// Increment the unlabeled series until the returned guard is dropped.
+    pub fn track(&'static self) -> InFlight {
+        self.family().with_label_values(&[]).inc();
+        InFlight(self)
+    }
+
+    /// Remove all series which carry *all* of the given labels.
+    pub fn remove_matching(&'static self, labels: &[(&'static str, &str)]) {
+        remove_matching(self.family(), labels);
+    }
+}
+
+impl Counter {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Counter {
+            name,
+            help,
+            labels,
+            family: OnceLock::new(),
+        }
+    }
+
+    fn family(&'static self) -> &'static CounterVec {
+        self.family.get_or_init(|| {
+            let family = CounterVec::new(Opts::new(self.name, self.help), self.labels).unwrap();
+            register(self.name, family.clone());
+            family
+        })
+    }
+
+    /// Increment the series identified by the labels.
+    pub fn inc(&'static self, labels: &[(&'static str, &str)]) {
+        self.family()
+            .with_label_values(&label_values(self.labels, labels))
+            .inc();
+    }
+}
+
+/// Render all registered metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    for (zonefile, last_sync) in LAST_SYNCS.lock().unwrap().iter() {
+        SECONDS_SINCE_LAST_SYNC.set(&[("zonefile", zonefile)], last_sync.elapsed().as_secs_f64());
+    }
+
+    let mut output = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut output) {
+        warn!("failed to encode metrics: {e}");
+    }
+
+    String::from_utf8(output).unwrap_or_default()
+}
+
+/// Serve the metrics endpoint.
+pub async fn serve(address: SocketAddr) {
+    let router = Router::new().route(
+        "/metrics",
+        get(|| async { ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], render()) }),
+    );
+
+    server::serve(address, router).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Counter, Gauge};
+
+    static SERIES: Gauge = Gauge::new(
+        "zonefile_test_series",
+        "Test series.",
+        &["zonefile", "origin"],
+    );
+    static EVENTS: Counter =
+        Counter::new("zonefile_test_events_total", "Test events.", &["zonefile"]);
+
+    #[test]
+    fn renders_and_removes_series() {
+        SERIES.set(
+            &[("origin", "example.org."), ("zonefile", "default/a")],
+            2.0,
+        );
+        SERIES.set(
+            &[("zonefile", "default/a"), ("origin", "example.net.")],
+            1.0,
+        );
+        SERIES.set(
+            &[("zonefile", "default/b"), ("origin", "example.org.")],
+            3.0,
+        );
+        EVENTS.inc(&[("zonefile", "default/a")]);
+        EVENTS.inc(&[("zonefile", "default/a")]);
+
+        let rendered = render();
+        assert!(rendered.contains("# TYPE zonefile_test_series gauge\n"));
+        assert!(rendered
+            .contains("zonefile_test_series{origin=\"example.org.\",zonefile=\"default/a\"} 2\n"));
+        assert!(rendered.contains("zonefile_test_events_total{zonefile=\"default/a\"} 2\n"));
+
+        SERIES.remove_matching(&[("zonefile", "default/a")]);
+
+        let rendered = render();
+        assert!(!rendered
+            .contains("zonefile_test_series{origin=\"example.org.\",zonefile=\"default/a\"}"));
+        assert!(!rendered.contains("origin=\"example.net.\""));
+        assert!(rendered
+            .contains("zonefile_test_series{origin=\"example.org.\",zonefile=\"default/b\"} 3\n"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::ResourceExt as _;
+
+/// Annotation selecting the [`FileNaming`] preset under which a
+/// [`ZoneFile`](zonefile_crds::ZoneFile) publishes its zones. The controller
+/// records the preset in the same annotation on the published ConfigMap, so
+/// that its contents can still be read after the preset is changed.
+pub const FILE_NAMING_ANNOTATION: &str = "zonefile.kubi.zone/file-naming";
+
+/// Naming convention of the ConfigMap keys holding the contents of each zone,
+/// matching the file names DNS servers are commonly configured with when the
+/// ConfigMap is mounted as a volume. Signatures and statistics are published
+/// under keys derived from the origin regardless.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileNaming {
+    /// The origin itself, as in `example.org.`
+    #[default]
+    Raw,
+    /// `db.example.org`, as is customary for BIND.
+    Bind,
+    /// `example.org.zone`, as is customary for NSD.
+    Nsd,
+}
+
+impl FromStr for FileNaming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(FileNaming::Raw),
+            "bind" => Ok(FileNaming::Bind),
+            "nsd" => Ok(FileNaming::Nsd),
+            other => Err(format!(
+                "unknown file naming {other:?}, expected raw, bind or nsd"
+            )),
+        }
+    }
+}
+
+impl Display for FileNaming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FileNaming::Raw => "raw",
+            FileNaming::Bind => "bind",
+            FileNaming::Nsd => "nsd",
+        })
+    }
+}
+
+impl FileNaming {
+    /// Naming the zones in the ConfigMap were published under, as recorded in its annotation.
+    pub fn of(config_map: &ConfigMap) -> Self {
+        config_map
+            .annotations()
+            .get(FILE_NAMING_ANNOTATION)
+            .and_then(|naming| naming.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Key under which the contents of the zone are published.
+    pub fn key(self, origin: &str) -> String {
+        let name = match origin.trim_end_matches('.') {
+            "" => "root",
+            name => name,
+        };
+
+        match self {
+            FileNaming::Raw => origin.to_string(),
+            FileNaming::Bind => format!("db.{name}"),
+            FileNaming::Nsd => format!("{name}.zone"),
+        }
+    }
+
+    /// Origin of the zone whose contents are published under the key,
+    /// or [`None`] if the key does not hold the contents of a zone.
+    pub fn origin(self, key: &str) -> Option<String> {
+        let name = match self {
+            FileNaming::Raw => return key.ends_with('.').then(|| key.to_string()),
+            FileNaming::Bind => key.strip_prefix("db.")?,
+            FileNaming::Nsd => key.strip_suffix(".zone")?,
+        };
+
+        match name {
+            "root" => Some(String::from(".")),
+            // Keys of signatures and statistics hold the origin, dot included.
+            name if name.is_empty() || name.contains("..") => None,
+            name => Some(format!("{name}.")),
+        }
+    }
+
+    /// Rename a key of data keyed by origin to follow the convention,
+    /// if it holds the contents of a zone.
+    pub fn rename(self, key: &str) -> String {
+        match FileNaming::Raw.origin(key) {
+            Some(origin) => self.key(&origin),
+            None => key.to_string(),
+        }
+    }
+
+    /// Rename the keys of zone contents in data keyed by origin to follow the convention.
+    pub fn publish<T>(self, data: BTreeMap<String, T>) -> BTreeMap<String, T> {
+        data.into_iter()
+            .map(|(key, value)| (self.rename(&key), value))
+            .collect()
+    }
+
+    /// Rename the keys of zone contents published under the convention back to their origins.
+    pub fn unpublish<T>(self, data: BTreeMap<String, T>) -> BTreeMap<String, T> {
+        data.into_iter()
+            .map(|(key, value)| match self.origin(&key) {
+                Some(origin) => (origin, value),
+                None => (key, value),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::FileNaming;
+
+    #[test]
+    fn names_zone_keys() {
+        assert_eq!(FileNaming::Raw.key("example.org."), "example.org.");
+        assert_eq!(FileNaming::Bind.key("example.org."), "db.example.org");
+        assert_eq!(FileNaming::Nsd.key("example.org."), "example.org.zone");
+        assert_eq!(FileNaming::Bind.key("."), "db.root");
+
+        assert_eq!("nsd".parse(), Ok(FileNaming::Nsd));
+        assert!("knot".parse::<FileNaming>().is_err());
+    }
+
+    #[test]
+    fn renames_only_zone_contents() {
+        let data = BTreeMap::from([
+            (String::from("example.org."), "zone"),
+            (String::from("example.org..sig"), "signature"),
+            (String::from("example.org..stats.json"), "statistics"),
+            (String::from("."), "root zone"),
+        ]);
+
+        for naming in [FileNaming::Raw, FileNaming::Bind, FileNaming::Nsd] {
+            assert_eq!(naming.unpublish(naming.publish(data.clone())), data);
+        }
+
+        assert_eq!(FileNaming::Nsd.rename("example.org."), "example.org.zone");
+        assert_eq!(
+            FileNaming::Nsd.rename("example.org..sig"),
+            "example.org..sig"
+        );
+
+        assert_eq!(
+            FileNaming::Bind
+                .publish(data)
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec![
+                "db.example.org",
+                "db.root",
+                "example.org..sig",
+                "example.org..stats.json",
+            ]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use clap::Args;
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+
+/// Annotation holding the negative caching TTL of a zone in seconds, which is
+/// published as the minimum field of its SOA record ([RFC 2308](https://www.rfc-editor.org/rfc/rfc2308#section-4)).
+/// It is read from the [`Zone`](kubizone_crds::v1alpha1::Zone) first, falling back
+/// to its [`ZoneFile`](zonefile_crds::ZoneFile) and then its template.
+pub const NEGATIVE_TTL_ANNOTATION: &str = "zonefile.kubi.zone/negative-ttl";
+
+#[derive(Debug, Clone, Default, Hash, Args)]
+pub struct NegativeCachingArgs {
+    /// Negative caching TTL in seconds of zones which do not set one through
+    /// their annotations, template or namespace defaults. If left unset, the
+    /// minimum field of the zones' SOA records is published as-is.
+    #[arg(long, env = "ZONEFILE_NEGATIVE_TTL")]
+    pub negative_ttl: Option<u32>,
+
+    /// Upper bound on the negative caching TTL of zones. Higher
+    /// values are lowered to it, and reported.
+    #[arg(long, env = "ZONEFILE_MAX_NEGATIVE_TTL")]
+    pub max_negative_ttl: Option<u32>,
+}
+
+/// Parse the value of the negative TTL annotation.
+pub fn parse(value: &str) -> Option<u32> {
+    value.trim().parse().ok()
+}
+
+impl NegativeCachingArgs {
+    /// Set the minimum field of the zone's SOA record to the negative TTL, falling
+    /// back to the controller's default, and lower it to the configured maximum.
+    ///
+    /// Returns the value which was lowered, if it exceeded the maximum.
+    pub fn apply(
+        &self,
+        origin: &FullyQualifiedDomainName,
+        entries: &mut [ZoneEntry],
+        negative_ttl: Option<u32>,
+    ) -> Option<u32> {
+        let soa = entries
+            .iter_mut()
+            .find(|entry| entry.type_ == Type::SOA && entry.fqdn == *origin)?;
+
+        let mut fields: Vec<String> = soa.rdata.split_whitespace().map(String::from).collect();
+        if fields.len() != 7 {
+            return None;
+        }
+
+        let ttl = negative_ttl
+            .or(self.negative_ttl)
+            .or_else(|| fields[6].parse().ok())?;
+
+        let exceeded = self.max_negative_ttl.filter(|max| ttl > *max);
+
+        fields[6] = exceeded.unwrap_or(ttl).to_string();
+        soa.rdata = fields.join(" ");
+
+        exceeded.map(|_| ttl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::NegativeCachingArgs;
+
+    #[test]
+    fn sets_and_bounds_negative_ttl() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let mut entries = vec![ZoneEntry {
+            fqdn: origin.clone(),
+            type_: Type::SOA,
+            class: Class::IN,
+            ttl: 360,
+            rdata: "ns1.example.org. hostmaster.example.org. 2024010101 86400 7200 3600000 86400"
+                .to_string(),
+        }];
+
+        let args = NegativeCachingArgs {
+            negative_ttl: Some(300),
+            max_negative_ttl: Some(3600),
+        };
+
+        assert_eq!(args.apply(&origin, &mut entries, None), None);
+        assert!(entries[0].rdata.ends_with(" 300"));
+
+        assert_eq!(args.apply(&origin, &mut entries, Some(900)), None);
+        assert!(entries[0].rdata.ends_with(" 900"));
+
+        assert_eq!(args.apply(&origin, &mut entries, Some(86400)), Some(86400));
+        assert!(entries[0].rdata.ends_with(" 3600"));
+
+        // Without a default, the zone's own value is still bounded.
+        entries[0].rdata =
+            "ns1.example.org. hostmaster.example.org. 2024010101 86400 7200 3600000 7200"
+                .to_string();
+        let args = NegativeCachingArgs {
+            negative_ttl: None,
+            max_negative_ttl: Some(3600),
+        };
+
+        assert_eq!(args.apply(&origin, &mut entries, None), Some(7200));
+        assert!(entries[0].rdata.ends_with(" 3600"));
+    }
+}
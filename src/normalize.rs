@@ -0,0 +1,314 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use unicode_normalization::UnicodeNormalization as _;
+
+use crate::validation::tokenize;
+
+/// Rewrite the rdata of each entry into a canonical textual form, so
+/// that semantically identical records always render identically.
+///
+/// * Owner names are lowercased.
+/// * Whitespace between fields is collapsed into a single space.
+/// * Domain names are lowercased and made absolute relative to `origin`.
+/// * Internationalized domain names are normalized to NFC and converted to punycode.
+/// * Addresses are written in their canonical (RFC 5952 for IPv6) form.
+pub fn canonicalize(origin: &FullyQualifiedDomainName, entries: Vec<ZoneEntry>) -> Vec<ZoneEntry> {
+    let origin = origin.to_string();
+
+    entries
+        .into_iter()
+        .map(|entry| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(to_ascii(&entry.fqdn.to_string()))
+                .unwrap_or_else(|_| entry.fqdn.clone()),
+            rdata: canonical_rdata(&origin, entry.type_, &entry.rdata),
+            ..entry
+        })
+        .collect()
+}
+
+/// Positions of the fields within the rdata of each type which hold domain names.
+fn hostname_fields(type_: Type) -> &'static [usize] {
+    match type_ {
+        Type::CNAME | Type::DNAME | Type::NS | Type::PTR => &[0],
+        Type::MX | Type::KX | Type::AFSDB | Type::SVCB | Type::HTTPS => &[1],
+        Type::SOA | Type::RP => &[0, 1],
+        Type::SRV => &[3],
+        Type::NAPTR => &[5],
+        _ => &[],
+    }
+}
+
+/// Domain names within the rdata of the entry.
+pub fn hostnames(entry: &ZoneEntry) -> Vec<String> {
+    let Ok(fields) = tokenize(&entry.rdata) else {
+        return Vec::new();
+    };
+
+    hostname_fields(entry.type_)
+        .iter()
+        .filter_map(|index| fields.get(*index).cloned())
+        .collect()
+}
+
+/// The rdata of a record of the type with each domain name within it
+/// replaced, or [`None`] if the rdata cannot be split into fields.
+pub fn map_hostnames(type_: Type, rdata: &str, map: impl Fn(&str) -> String) -> Option<String> {
+    let mut fields = tokenize(rdata).ok()?;
+
+    for index in hostname_fields(type_) {
+        if let Some(field) = fields.get_mut(*index) {
+            *field = map(field);
+        }
+    }
+
+    Some(fields.join(" "))
+}
+
+/// The rdata of a record of the type in canonical form, with the
+/// domain names within it qualified by `origin`.
+pub fn canonical_rdata(origin: &str, type_: Type, rdata: &str) -> String {
+    match type_ {
+        Type::A => {
+            if let Ok(address) = rdata.trim().parse::<Ipv4Addr>() {
+                return address.to_string();
+            }
+        }
+        Type::AAAA => {
+            if let Ok(address) = rdata.trim().parse::<Ipv6Addr>() {
+                return address.to_string();
+            }
+        }
+        _ => (),
+    }
+
+    // Rdata which cannot be split into fields is left for
+    // the zonefile's consumer to reject.
+    let Ok(mut fields) = tokenize(rdata) else {
+        return rdata.to_string();
+    };
+
+    for index in hostname_fields(type_) {
+        if let Some(field) = fields.get_mut(*index) {
+            *field = qualify(origin, field);
+        }
+    }
+
+    fields.join(" ")
+}
+
+/// Lowercase a domain name, converting internationalized names to punycode.
+///
+/// Names are normalized to NFC first, so that names which differ only
+/// in their composition of accented characters convert identically.
+/// Names which cannot be converted are only lowercased, and left for
+/// validation or the zonefile's consumer to reject.
+pub fn to_ascii(name: &str) -> String {
+    if name.is_ascii() {
+        return name.to_ascii_lowercase();
+    }
+
+    let name: String = name.nfc().collect();
+    idna::domain_to_ascii(&name).unwrap_or_else(|_| name.to_lowercase())
+}
+
+/// Lowercase a domain name, and make it absolute if it is relative to `origin`.
+fn qualify(origin: &str, name: &str) -> String {
+    let name = to_ascii(name);
+
+    if name == "@" {
+        origin.to_ascii_lowercase()
+    } else if name.ends_with('.') {
+        name
+    } else if origin == "." {
+        format!("{name}.")
+    } else {
+        format!("{name}.{}", origin.to_ascii_lowercase())
+    }
+}
+
+/// Priority of MX and SRV records, by which records of the same RRset
+/// are ordered. SRV records of equal priority are ordered by weight.
+fn priority(entry: &ZoneEntry) -> Option<(u16, u16)> {
+    let mut fields = entry.rdata.split_whitespace();
+
+    match entry.type_ {
+        Type::MX => Some((fields.next()?.parse().ok()?, 0)),
+        Type::SRV => Some((fields.next()?.parse().ok()?, fields.next()?.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// Order the records of each MX and SRV RRset by priority, so that the most
+/// preferred records are listed first. Each RRset keeps the positions it
+/// occupied in the zone, so records of other types are left in place.
+pub fn sort_by_priority(entries: &mut [ZoneEntry]) {
+    let mut rrsets = BTreeMap::<(FullyQualifiedDomainName, Type), Vec<usize>>::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        if priority(entry).is_some() {
+            rrsets
+                .entry((entry.fqdn.clone(), entry.type_))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    for positions in rrsets.into_values() {
+        let mut rrset: Vec<ZoneEntry> = positions.iter().map(|i| entries[*i].clone()).collect();
+        rrset.sort_by_key(priority);
+
+        for (position, entry) in positions.into_iter().zip(rrset) {
+            entries[position] = entry;
+        }
+    }
+}
+
+/// Remove entries which share name, type, class and rdata with an
+/// earlier entry, returning the remaining entries along with the
+/// number of duplicates removed.
+///
+/// Duplicates which differ only in TTL are removed as well, since
+/// they describe the same record. The first occurrence is kept.
+pub fn dedup(entries: Vec<ZoneEntry>) -> (Vec<ZoneEntry>, usize) {
+    let total = entries.len();
+
+    let mut seen = HashSet::new();
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| {
+            seen.insert((
+                entry.fqdn.clone(),
+                entry.type_,
+                entry.class,
+                entry.rdata.clone(),
+            ))
+        })
+        .collect();
+
+    let duplicates = total - entries.len();
+    (entries, duplicates)
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::{canonicalize, dedup, sort_by_priority};
+
+    #[test]
+    fn canonicalizes_rdata() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |type_: Type, rdata: &str| ZoneEntry {
+            fqdn: origin.clone(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = canonicalize(
+            &origin,
+            vec![
+                entry(Type::AAAA, "2001:DB8:0:0:0:0:0:1"),
+                entry(Type::CNAME, "WWW"),
+                entry(Type::CNAME, "Bu\u{0308}cher.example."),
+                entry(Type::MX, "10   Mail.Example.COM."),
+                entry(Type::NS, "@"),
+                entry(Type::SRV, "0 5 443 ."),
+                entry(Type::TXT, r#""hello   world"   "second""#),
+            ],
+        );
+
+        assert_eq!(entries[0].fqdn, origin);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.rdata.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "2001:db8::1",
+                "www.example.org.",
+                "xn--bcher-kva.example.",
+                "10 mail.example.com.",
+                "example.org.",
+                "0 5 443 .",
+                r#""hello   world" "second""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_exact_duplicates() {
+        let entry = |name: &str, ttl: u32, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_: Type::A,
+            class: Class::IN,
+            ttl,
+            rdata: rdata.to_string(),
+        };
+
+        let (entries, duplicates) = dedup(vec![
+            entry("www.example.org.", 360, "192.0.2.1"),
+            entry("www.example.org.", 360, "192.0.2.2"),
+            entry("www.example.org.", 60, "192.0.2.1"),
+            entry("mail.example.org.", 360, "192.0.2.1"),
+        ]);
+
+        assert_eq!(duplicates, 1);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.fqdn.to_string(), entry.ttl, entry.rdata.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (String::from("www.example.org."), 360, "192.0.2.1"),
+                (String::from("www.example.org."), 360, "192.0.2.2"),
+                (String::from("mail.example.org."), 360, "192.0.2.1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sorts_rrsets_by_priority() {
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let mut entries = vec![
+            entry("example.org.", Type::MX, "20 backup.example.org."),
+            entry("example.org.", Type::A, "192.0.2.1"),
+            entry("example.org.", Type::MX, "10 mail.example.org."),
+            entry("sip.example.org.", Type::SRV, "10 60 5060 b.example.org."),
+            entry("sip.example.org.", Type::SRV, "10 20 5060 a.example.org."),
+            entry("sip.example.org.", Type::SRV, "5 0 5060 c.example.org."),
+        ];
+
+        sort_by_priority(&mut entries);
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.rdata.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "10 mail.example.org.",
+                "192.0.2.1",
+                "20 backup.example.org.",
+                "5 0 5060 c.example.org.",
+                "10 20 5060 a.example.org.",
+                "10 60 5060 b.example.org.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use clap::{Args, ValueEnum};
+use k8s_openapi::serde_json::{json, Value};
+use tracing::log::*;
+
+use crate::{diff::ZoneChange, http::HttpClient};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NotifyFormat {
+    /// `{"text": ...}` payload, as expected by Slack-compatible incoming webhooks.
+    #[default]
+    Slack,
+    /// Structured JSON document describing the change.
+    Json,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct NotifyArgs {
+    /// URL which will receive a short summary whenever the
+    /// rendered contents of a zone changes.
+    #[arg(long, env = "ZONEFILE_NOTIFY_WEBHOOK")]
+    pub notify_webhook: Option<String>,
+
+    /// Payload format used when posting to the notification webhook.
+    #[arg(
+        long,
+        env = "ZONEFILE_NOTIFY_FORMAT",
+        value_enum,
+        default_value_t = NotifyFormat::Slack
+    )]
+    pub notify_format: NotifyFormat,
+}
+
+fn summary(change: &ZoneChange) -> String {
+    let old_serial = change
+        .old_serial
+        .map(|serial| serial.to_string())
+        .unwrap_or_else(|| String::from("none"));
+
+    format!(
+        "zone {} ({}) changed: serial {old_serial} → {}, {} record(s) added, {} record(s) removed",
+        change.origin,
+        change.zonefile,
+        change.new_serial,
+        change.diff.added.len(),
+        change.diff.removed.len()
+    )
+}
+
+pub fn payload(change: &ZoneChange, format: NotifyFormat) -> Value {
+    match format {
+        NotifyFormat::Slack => json!({
+            "text": summary(change),
+        }),
+        NotifyFormat::Json => json!({
+            "zonefile": change.zonefile,
+            "origin": change.origin,
+            "serial": {
+                "old": change.old_serial,
+                "new": change.new_serial,
+            },
+            "hash": change.hash,
+            "added": change.diff.added,
+            "removed": change.diff.removed,
+        }),
+    }
+}
+
+/// Posts [`ZoneChange`]s to the configured webhook.
+pub struct Notifier {
+    http: Arc<HttpClient>,
+    url: String,
+    format: NotifyFormat,
+}
+
+impl Notifier {
+    /// Construct a notifier from the command line arguments, if a webhook was configured.
+    pub fn from_args(args: &NotifyArgs, http: Arc<HttpClient>) -> Option<Self> {
+        Some(Notifier {
+            http,
+            url: args.notify_webhook.clone()?,
+            format: args.notify_format,
+        })
+    }
+
+    /// Post the change to the webhook.
+    ///
+    /// Failure to notify is logged, but never fails the reconciliation
+    /// which produced the change.
+    pub async fn notify(&self, change: &ZoneChange<'_>) {
+        let result = self
+            .http
+            .client()
+            .post(&self.url)
+            .json(&payload(change, self.format))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        match result {
+            Ok(_) => debug!("notified webhook of change to zone {}", change.origin),
+            Err(e) => warn!(
+                "failed to notify webhook of change to zone {}: {e}",
+                change.origin
+            ),
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::{fmt::Display, str::FromStr};
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) which determines how
+/// records of obsolete types within its snippets are treated. See [`ObsoleteTypePolicy`].
+///
+/// The records of zones cannot hold these types, so only snippets can carry them.
+/// Snippets referenced through `$INCLUDE` directives are loaded by the DNS server
+/// as they are stored, so their records are checked but never converted.
+pub const OBSOLETE_TYPES_ANNOTATION: &str = "zonefile.kubi.zone/obsolete-types";
+
+/// Obsolete record types, by mnemonic and type code. SPF was retired by RFC 7208
+/// in favour of TXT, MD and MF by RFC 973 in favour of MX, and WKS by RFC 1123.
+const OBSOLETE_TYPES: &[(&str, u16)] = &[("MD", 3), ("MF", 4), ("WKS", 11), ("SPF", 99)];
+
+/// Treatment of records of obsolete types.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ObsoleteTypePolicy {
+    /// Records are published, but a warning is logged.
+    #[default]
+    Warn,
+    /// Records are converted to the type which replaced theirs: SPF records to TXT,
+    /// MD records to MX records of preference 0 and MF records to MX records of
+    /// preference 10. Records which cannot be converted are published with a warning.
+    Convert,
+    /// Snippets holding records of obsolete types are left out, and the
+    /// zonefile is marked as degraded.
+    Reject,
+}
+
+impl FromStr for ObsoleteTypePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(ObsoleteTypePolicy::Warn),
+            "convert" => Ok(ObsoleteTypePolicy::Convert),
+            "reject" => Ok(ObsoleteTypePolicy::Reject),
+            other => Err(format!(
+                "unknown obsolete type policy {other:?}, expected warn, convert or reject"
+            )),
+        }
+    }
+}
+
+/// A record of an obsolete type within zone file text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObsoleteRecord {
+    /// Line of the record, counting from one.
+    pub line: usize,
+    /// Mnemonic of the record's type.
+    pub type_: &'static str,
+    /// Type the record was converted to, if it was.
+    pub converted_to: Option<&'static str>,
+}
+
+impl Display for ObsoleteRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {} holds an obsolete {} record",
+            self.line, self.type_
+        )?;
+
+        if let Some(type_) = self.converted_to {
+            write!(f, ", converted to {type_}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whitespace-separated fields of the line before any comment, along with their offsets.
+fn fields(line: &str) -> Vec<(usize, &str)> {
+    let line = line.split(';').next().unwrap_or_default();
+
+    let mut fields = Vec::new();
+    let mut start = None;
+    for (index, c) in line
+        .char_indices()
+        .chain(std::iter::once((line.len(), ' ')))
+    {
+        match (c.is_whitespace(), start) {
+            (true, Some(begin)) => {
+                fields.push((begin, &line[begin..index]));
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => (),
+        }
+    }
+    fields
+}
+
+fn is_class(field: &str) -> bool {
+    matches!(
+        field.to_ascii_uppercase().as_str(),
+        "IN" | "CH" | "HS" | "CS"
+    ) || field
+        .get(..5)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("CLASS"))
+}
+
+/// Offset and mnemonic of the obsolete type of the record starting on the line, if any.
+fn obsolete_type(line: &str) -> Option<(usize, &str, &'static str)> {
+    let fields = fields(line);
+
+    // Records of the owner name of the record above leave the owner out
+    // and start with whitespace.
+    let skip = usize::from(!line.starts_with(char::is_whitespace));
+
+    let (offset, field) = fields
+        .into_iter()
+        .skip(skip)
+        .find(|(_, field)| !field.starts_with(|c: char| c.is_ascii_digit()) && !is_class(field))?;
+
+    let upper = field.to_ascii_uppercase();
+    OBSOLETE_TYPES
+        .iter()
+        .find(|(mnemonic, code)| upper == *mnemonic || upper == format!("TYPE{code}"))
+        .map(|(mnemonic, _)| (offset, field, *mnemonic))
+}
+
+/// Change in depth of parentheses over the line, which continue
+/// records over several lines. Quoted text and comments are skipped.
+fn parentheses(line: &str) -> isize {
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => break,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            _ => (),
+        }
+    }
+    depth
+}
+
+/// Apply the policy to the records of obsolete types within zone file text.
+///
+/// Returns the text, with records converted if the policy says so, along with
+/// the records found, or the first record found if the policy rejects them.
+pub fn apply(
+    policy: ObsoleteTypePolicy,
+    contents: &str,
+) -> Result<(String, Vec<ObsoleteRecord>), ObsoleteRecord> {
+    let mut text = String::with_capacity(contents.len());
+    let mut records = Vec::new();
+    let mut depth = 0;
+
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        let continued = depth > 0;
+        depth = (depth + parentheses(line)).max(0);
+
+        let found = if continued || line.starts_with('$') {
+            None
+        } else {
+            obsolete_type(line)
+        };
+
+        let Some((offset, field, type_)) = found else {
+            text.push_str(line);
+            continue;
+        };
+
+        let rdata = line[offset + field.len()..].trim_start();
+        let replacement = match type_ {
+            "SPF" => Some(("TXT", "TXT")),
+            "MD" if !rdata.starts_with("\\#") => Some(("MX", "MX 0")),
+            "MF" if !rdata.starts_with("\\#") => Some(("MX", "MX 10")),
+            _ => None,
+        }
+        .filter(|_| policy == ObsoleteTypePolicy::Convert);
+
+        let mut record = ObsoleteRecord {
+            line: index + 1,
+            type_,
+            converted_to: None,
+        };
+
+        if policy == ObsoleteTypePolicy::Reject {
+            return Err(record);
+        }
+
+        match replacement {
+            Some((converted_to, replacement)) => {
+                text.push_str(&line[..offset]);
+                text.push_str(replacement);
+                text.push_str(&line[offset + field.len()..]);
+                record.converted_to = Some(converted_to);
+            }
+            None => text.push_str(line),
+        }
+
+        records.push(record);
+    }
+
+    Ok((text, records))
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::{apply, ObsoleteRecord, ObsoleteTypePolicy};
+
+    const SNIPPET: &str = indoc! {r#"
+        $TTL 300
+        @          IN SPF "v=spf1 -all"
+        md         300 IN A 192.0.2.1
+                   MD mail.example.org.
+        mail       MF relay.example.org. ; forwarder
+        legacy     IN WKS 192.0.2.1 6 25
+        txt        TXT ( "SPF"
+                         "MD" )
+        generic    TYPE99 \# 4 03616263
+    "#};
+
+    #[test]
+    fn warns_of_obsolete_types() {
+        let (text, records) = apply(ObsoleteTypePolicy::Warn, SNIPPET).unwrap();
+
+        assert_eq!(text, SNIPPET);
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.line, record.type_))
+                .collect::<Vec<_>>(),
+            vec![(2, "SPF"), (4, "MD"), (5, "MF"), (6, "WKS"), (9, "SPF")]
+        );
+    }
+
+    #[test]
+    fn converts_obsolete_types() {
+        let (text, records) = apply(ObsoleteTypePolicy::Convert, SNIPPET).unwrap();
+
+        assert_eq!(
+            text,
+            indoc! {r#"
+                $TTL 300
+                @          IN TXT "v=spf1 -all"
+                md         300 IN A 192.0.2.1
+                           MX 0 mail.example.org.
+                mail       MX 10 relay.example.org. ; forwarder
+                legacy     IN WKS 192.0.2.1 6 25
+                txt        TXT ( "SPF"
+                                 "MD" )
+                generic    TXT \# 4 03616263
+            "#}
+        );
+        assert_eq!(
+            records[3].to_string(),
+            "line 6 holds an obsolete WKS record"
+        );
+        assert_eq!(
+            records[0].to_string(),
+            "line 2 holds an obsolete SPF record, converted to TXT"
+        );
+    }
+
+    #[test]
+    fn rejects_obsolete_types() {
+        assert_eq!(
+            apply(ObsoleteTypePolicy::Reject, SNIPPET),
+            Err(ObsoleteRecord {
+                line: 2,
+                type_: "SPF",
+                converted_to: None,
+            })
+        );
+
+        let clean = "www IN A 192.0.2.1\n";
+        assert_eq!(
+            apply(ObsoleteTypePolicy::Reject, clean),
+            Ok((clean.to_string(), Vec::new()))
+        );
+    }
+
+    #[test]
+    fn obsolete_type_policy_parsing() {
+        assert_eq!("convert".parse(), Ok(ObsoleteTypePolicy::Convert));
+        assert!("drop".parse::<ObsoleteTypePolicy>().is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use k8s_openapi::{
+    api::core::v1::Secret,
+    serde_json::{self, Value},
+};
+use kube::{Api, Client};
+use oci_client::{
+    client::{Certificate, CertificateEncoding, ClientConfig, ClientProtocol, Config, ImageLayer},
+    manifest::{OciImageManifest, OciManifest},
+    secrets::RegistryAuth,
+    Client as OciClient, Reference,
+};
+use ring::digest::{digest, SHA256};
+
+use crate::{
+    http::{HttpArgs, HttpClient},
+    reconciliation::ZONEFILE_LABEL,
+};
+
+/// Artifact type of the pushed manifests.
+pub const ARTIFACT_TYPE: &str = "application/vnd.kubi.zone.zonefile.v1";
+
+/// Tag which is moved to the most recently pushed artifact, for consumers
+/// which follow a repository rather than pinning a particular artifact.
+pub const LATEST_TAG: &str = "latest";
+
+/// Annotation of each layer holding the key it was read from, as understood by `oras pull`.
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+/// Artifacts carry no configuration, and use the empty descriptor in its place.
+const EMPTY_MEDIA_TYPE: &str = "application/vnd.oci.empty.v1+json";
+
+/// Key within a `kubernetes.io/dockerconfigjson` Secret holding the registry credentials.
+const DOCKER_CONFIG_KEY: &str = ".dockerconfigjson";
+
+/// Repository within a registry, as in `oci://ghcr.io/example/zones`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    pub registry: String,
+    pub name: String,
+}
+
+impl Repository {
+    pub fn parse(reference: &str) -> Option<Self> {
+        let (registry, name) = reference.split_once('/')?;
+
+        let valid = !registry.is_empty()
+            && !name.is_empty()
+            && name.split('/').all(|component| {
+                !component.is_empty()
+                    && component.chars().all(|c| {
+                        c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-')
+                    })
+            });
+
+        valid.then(|| Repository {
+            registry: registry.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    fn reference(&self, tag: &str) -> Reference {
+        Reference::with_tag(self.registry.clone(), self.name.clone(), tag.to_string())
+    }
+}
+
+fn sha256(contents: &[u8]) -> String {
+    digest(&SHA256, contents)
+        .as_ref()
+        .iter()
+        .fold(String::from("sha256:"), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Media type of a key of the zonefile's ConfigMap.
+fn media_type(key: &str) -> &'static str {
+    if key.ends_with(".json") {
+        "application/json"
+    } else if key.ends_with('.') {
+        "text/dns"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Layers of an artifact holding each key of the data, titled by the key.
+fn layers(data: &BTreeMap<String, String>) -> Vec<ImageLayer> {
+    data.iter()
+        .map(|(key, contents)| {
+            ImageLayer::new(
+                contents.as_bytes().to_vec(),
+                media_type(key).to_string(),
+                Some(
+                    [(TITLE_ANNOTATION.to_string(), key.clone())]
+                        .into_iter()
+                        .collect(),
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Tag of the artifact, derived from the hash of its contents, so that
+/// pushing an unchanged bundle again is a no-op for the registry.
+fn tag(layers: &[ImageLayer]) -> String {
+    let contents = layers
+        .iter()
+        .map(|layer| {
+            let title = layer
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(TITLE_ANNOTATION))
+                .map(String::as_str)
+                .unwrap_or_default();
+
+            format!("{title}={}\n", sha256(&layer.data))
+        })
+        .collect::<String>();
+
+    sha256(contents.as_bytes()).replace(':', "-")[..39].to_string()
+}
+
+/// Read the username and password for the registry from a
+/// `kubernetes.io/dockerconfigjson` Secret.
+async fn credentials(
+    client: Client,
+    namespace: &str,
+    secret: &str,
+    registry: &str,
+) -> Result<(String, String), String> {
+    let secret = Api::<Secret>::namespaced(client, namespace)
+        .get(secret)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let config: Value = secret
+        .data
+        .as_ref()
+        .and_then(|data| data.get(DOCKER_CONFIG_KEY))
+        .and_then(|config| serde_json::from_slice(&config.0).ok())
+        .ok_or_else(|| format!("secret has no valid {DOCKER_CONFIG_KEY} key"))?;
+
+    let auth = &config["auths"][registry];
+
+    if let (Some(username), Some(password)) = (
+        auth.get("username").and_then(Value::as_str),
+        auth.get("password").and_then(Value::as_str),
+    ) {
+        return Ok((username.to_string(), password.to_string()));
+    }
+
+    auth.get("auth")
+        .and_then(Value::as_str)
+        .and_then(|auth| STANDARD.decode(auth).ok())
+        .and_then(|auth| String::from_utf8(auth).ok())
+        .and_then(|auth| {
+            auth.split_once(':')
+                .map(|(username, password)| (username.to_string(), password.to_string()))
+        })
+        .ok_or_else(|| format!("secret has no credentials for {registry}"))
+}
+
+/// Registry client honoring the CA bundle and proxy of the HTTP client, which
+/// reaches the insecure registries over plain HTTP and all others over HTTPS.
+fn client_config(args: &HttpArgs, insecure_registries: &[String]) -> Result<ClientConfig, String> {
+    let extra_root_certificates = match &args.http_ca_bundle {
+        Some(ca_bundle) => std::fs::read(ca_bundle)
+            .map_err(|e| e.to_string())
+            .and_then(|ca_bundle| pem::parse_many(ca_bundle).map_err(|e| e.to_string()))?
+            .into_iter()
+            .map(|certificate| Certificate {
+                encoding: CertificateEncoding::Der,
+                data: certificate.into_contents(),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(ClientConfig {
+        protocol: ClientProtocol::HttpsExcept(insecure_registries.to_vec()),
+        extra_root_certificates,
+        http_proxy: args.http_proxy.clone(),
+        https_proxy: args.http_proxy.clone(),
+        no_proxy: args.http_no_proxy.clone(),
+        ..ClientConfig::default()
+    })
+}
+
+/// Push the data as an artifact to the repository, tagged by the hash of its
+/// contents and as [`LATEST_TAG`], returning the tag of its contents.
+pub async fn push(
+    client: Client,
+    http: &HttpClient,
+    insecure_registries: &[String],
+    repository: &Repository,
+    credentials_secret: Option<&str>,
+    zonefile: (&str, &str),
+    data: &BTreeMap<String, String>,
+) -> Result<String, String> {
+    let (namespace, zonefile_name) = zonefile;
+
+    let auth = match credentials_secret {
+        Some(secret) => {
+            let (username, password) =
+                credentials(client, namespace, secret, &repository.registry).await?;
+            RegistryAuth::Basic(username, password)
+        }
+        None => RegistryAuth::Anonymous,
+    };
+
+    let layers = layers(data);
+    let config = Config::new(b"{}".to_vec(), EMPTY_MEDIA_TYPE.to_string(), None);
+
+    let mut manifest = OciImageManifest::build(
+        &layers,
+        &config,
+        Some(
+            [(
+                ZONEFILE_LABEL.to_string(),
+                format!("{namespace}/{zonefile_name}"),
+            )]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    manifest.artifact_type = Some(ARTIFACT_TYPE.to_string());
+
+    let tag = tag(&layers);
+    let registry = OciClient::new(client_config(http.args(), insecure_registries)?);
+
+    registry
+        .push(
+            &repository.reference(&tag),
+            &layers,
+            config,
+            &auth,
+            Some(manifest.clone()),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // The blobs were pushed along with the first tag, so the moving tag
+    // only needs the manifest, which the registry already holds.
+    registry
+        .push_manifest(
+            &repository.reference(LATEST_TAG),
+            &OciManifest::Image(manifest),
+        )
+        .await
+        .map_err(|e| format!("failed to tag {tag} as {LATEST_TAG}: {e}"))?;
+
+    Ok(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{layers, tag, Repository};
+
+    #[test]
+    fn parses_repositories() {
+        assert_eq!(
+            Repository::parse("ghcr.io/example/zones"),
+            Some(Repository {
+                registry: "ghcr.io".to_string(),
+                name: "example/zones".to_string(),
+            })
+        );
+
+        assert_eq!(
+            Repository::parse("localhost:5000/zones")
+                .unwrap()
+                .reference("latest")
+                .whole(),
+            "localhost:5000/zones:latest"
+        );
+
+        assert!(Repository::parse("ghcr.io").is_none());
+        assert!(Repository::parse("ghcr.io/Example/zones").is_none());
+    }
+
+    #[test]
+    fn layers_and_tag() {
+        let data = BTreeMap::from([
+            (
+                "example.org.".to_string(),
+                "$ORIGIN example.org.\n".to_string(),
+            ),
+            (
+                "example.org..stats.json".to_string(),
+                "{\"records\":0}".to_string(),
+            ),
+        ]);
+
+        let pushed = layers(&data);
+
+        assert_eq!(pushed.len(), 2);
+        assert_eq!(pushed[0].media_type, "text/dns");
+        assert_eq!(
+            pushed[0].annotations.as_ref().unwrap()["org.opencontainers.image.title"],
+            "example.org."
+        );
+        assert_eq!(pushed[1].media_type, "application/json");
+
+        let pushed_tag = tag(&pushed);
+        assert!(pushed_tag.starts_with("sha256-"));
+        assert_eq!(pushed_tag.len(), 39);
+
+        // The tag only changes along with the contents.
+        let mut changed = data.clone();
+        assert_eq!(tag(&layers(&changed)), pushed_tag);
+        changed.insert("example.org.".to_string(), String::new());
+        assert_ne!(tag(&layers(&changed)), pushed_tag);
+    }
+}
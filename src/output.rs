@@ -0,0 +1,682 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    sync::Mutex,
+    time::Duration,
+};
+
+use clap::Args;
+use hickory_proto::{
+    error::ProtoError,
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{dnssec::tsig::TSigner, Name, RecordType},
+    xfer::DnsResponse,
+};
+use k8s_openapi::{
+    api::core::v1::{ConfigMap, Namespace},
+    chrono::Utc,
+};
+use kube::{
+    api::{Patch, PatchParams},
+    core::ObjectMeta,
+    Api, Client, ResourceExt,
+};
+use tokio::net::{lookup_host, UdpSocket};
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{
+    grant::{self, ALLOWED_NAMESPACES_ANNOTATION},
+    http::HttpClient,
+    naming::FileNaming,
+    oci::{self, Repository},
+    ownership::{OWNED_BY_LABEL, OWNED_BY_NAMESPACE_LABEL},
+    reconciliation::ZONEFILE_LABEL,
+};
+
+/// Annotation on a [`ZoneFile`] holding a comma-separated
+/// list of targets which the zonefile's zones are delivered to, in addition to its
+/// own ConfigMap. See [`Output`] for the supported targets.
+pub const OUTPUTS_ANNOTATION: &str = "zonefile.kubi.zone/outputs";
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct OutputArgs {
+    /// Comma-separated secondaries which zonefiles may send NOTIFY messages to
+    /// through their `notify:` outputs, as `<host>[:<port>]`, the port defaulting
+    /// to 53. Secondaries which are not listed are never notified.
+    #[arg(long, env = "ZONEFILE_NOTIFY_ALLOWED_TARGETS", value_delimiter = ',')]
+    pub notify_allowed_targets: Vec<String>,
+
+    /// Registries, as `<host>[:<port>]`, which OCI outputs are pushed to over plain
+    /// HTTP rather than HTTPS, such as the local registries of kind and k3d.
+    #[arg(
+        long = "insecure-registry",
+        env = "ZONEFILE_INSECURE_REGISTRIES",
+        value_delimiter = ','
+    )]
+    pub insecure_registries: Vec<String>,
+}
+
+impl OutputArgs {
+    /// Whether the secondary, given as `<host>:<port>`, may be notified.
+    fn allows_notify(&self, address: &str) -> bool {
+        self.notify_allowed_targets.iter().any(|allowed| {
+            let allowed = allowed.trim().to_ascii_lowercase();
+            let address = address.to_ascii_lowercase();
+
+            match allowed.rsplit_once(':') {
+                Some(_) => allowed == address,
+                None => address == format!("{allowed}:53"),
+            }
+        })
+    }
+}
+
+/// Time to wait for a secondary to acknowledge a NOTIFY message.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Target which the published zones are delivered to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// `configmap:[<namespace>/]<name>`, another ConfigMap holding the same
+    /// keys as the zonefile's own, in its namespace unless specified.
+    ConfigMap {
+        namespace: Option<String>,
+        name: String,
+    },
+    /// `https://…`, an endpoint such as an S3 bucket, which receives each
+    /// changed key in a `PUT` request to `<url>/<key>`. Plain `http://` is
+    /// refused, since the zones would be sent unencrypted.
+    Http(String),
+    /// `notify:<host>[:<port>]`, a secondary nameserver which is sent a DNS NOTIFY
+    /// message whenever one of the zones changes, signed with the zonefile's TSIG key.
+    Notify(String),
+    /// `oci://<registry>/<repository>[?credentials=<secret>]`, a registry which
+    /// receives all keys as an OCI artifact, tagged by the hash of its contents
+    /// and as `latest`. Registries are reached over HTTPS unless listed in
+    /// `--insecure-registry`.
+    /// Credentials are read from a `kubernetes.io/dockerconfigjson` Secret
+    /// in the zonefile's namespace, if given.
+    Oci {
+        repository: Repository,
+        credentials: Option<String>,
+    },
+}
+
+impl Output {
+    /// Outputs of the zonefile, given by its annotation or else by the default
+    /// outputs, or the first of them which is invalid.
+    pub fn configured(
+        zonefile: &ZoneFile,
+        default_outputs: Option<&String>,
+    ) -> Result<Vec<Self>, String> {
+        match zonefile
+            .annotations()
+            .get(OUTPUTS_ANNOTATION)
+            .or(default_outputs)
+        {
+            Some(outputs) => Output::parse_list(outputs),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Host of an HTTP output, whose requests are rate limited together.
+    pub fn http_host(&self) -> Option<String> {
+        match self {
+            Output::Http(url) => reqwest::Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(String::from)),
+            _ => None,
+        }
+    }
+
+    /// Parse a comma-separated list of targets.
+    pub fn parse_list(outputs: &str) -> Result<Vec<Self>, String> {
+        outputs
+            .split(',')
+            .map(str::trim)
+            .filter(|output| !output.is_empty())
+            .map(|output| {
+                if let Some(config_map) = output.strip_prefix("configmap:") {
+                    let (namespace, name) = match config_map.split_once('/') {
+                        Some((namespace, name)) => (Some(namespace.to_string()), name),
+                        None => (None, config_map),
+                    };
+
+                    if name.is_empty() || namespace.as_ref().is_some_and(String::is_empty) {
+                        return Err(output.to_string());
+                    }
+
+                    Ok(Output::ConfigMap {
+                        namespace,
+                        name: name.to_string(),
+                    })
+                } else if output.starts_with("https://") {
+                    Ok(Output::Http(output.trim_end_matches('/').to_string()))
+                } else if let Some(address) = output.strip_prefix("notify:") {
+                    match address.rsplit_once(':') {
+                        _ if address.is_empty() => Err(output.to_string()),
+                        Some((_, port)) if port.parse::<u16>().is_err() => Err(output.to_string()),
+                        Some(_) => Ok(Output::Notify(address.to_string())),
+                        None => Ok(Output::Notify(format!("{address}:53"))),
+                    }
+                } else if let Some(reference) = output.strip_prefix("oci://") {
+                    let (reference, credentials) = match reference.split_once('?') {
+                        Some((reference, query)) => match query.strip_prefix("credentials=") {
+                            Some(secret) if !secret.is_empty() => {
+                                (reference, Some(secret.to_string()))
+                            }
+                            _ => return Err(output.to_string()),
+                        },
+                        None => (reference, None),
+                    };
+
+                    Ok(Output::Oci {
+                        repository: Repository::parse(reference)
+                            .ok_or_else(|| output.to_string())?,
+                        credentials,
+                    })
+                } else {
+                    Err(output.to_string())
+                }
+            })
+            .collect()
+    }
+
+    /// Check that the zonefile may deliver to the target. ConfigMaps in other
+    /// namespaces must be granted to the zonefile's namespace through the
+    /// `zonefile.kubi.zone/allowed-namespaces` annotation of their namespace,
+    /// HTTP endpoints must be among `--http-allowed-hosts`, and secondaries
+    /// among `--notify-allowed-targets`, which are only notified with TSIG.
+    pub async fn permitted(
+        &self,
+        client: Client,
+        http: &HttpClient,
+        args: &OutputArgs,
+        namespace: &str,
+        signed: bool,
+    ) -> Result<(), String> {
+        match self {
+            Output::ConfigMap {
+                namespace: Some(target_namespace),
+                ..
+            } if target_namespace != namespace => {
+                let allowed_namespaces = Api::<Namespace>::all(client)
+                    .get_opt(target_namespace)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .and_then(|target| {
+                        target
+                            .annotations()
+                            .get(ALLOWED_NAMESPACES_ANNOTATION)
+                            .cloned()
+                    });
+
+                if !grant::is_granted(allowed_namespaces.as_deref(), namespace) {
+                    return Err(format!(
+                        "namespace {target_namespace} does not grant access to namespace {namespace}"
+                    ));
+                }
+            }
+            Output::Http(url) => {
+                http.allow(url)?;
+            }
+            Output::Notify(address) => {
+                if !signed {
+                    return Err(String::from(
+                        "NOTIFY messages are only sent when signed with a TSIG key",
+                    ));
+                }
+
+                if !args.allows_notify(address) {
+                    return Err(format!("{address} is not among the allowed NOTIFY targets"));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Deliver the changed keys, or notify the secondary of the changed zones.
+    /// ConfigMaps are always marked as owned by the zonefile through labels,
+    /// since owner references cannot cross namespaces.
+    ///
+    /// The data is keyed by origin, and delivered under the zonefile's naming.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn deliver(
+        &self,
+        client: Client,
+        http: &HttpClient,
+        controller_name: &str,
+        args: &OutputArgs,
+        zonefile: (&str, &str),
+        naming: FileNaming,
+        tsig: Option<&TSigner>,
+        data: &BTreeMap<String, String>,
+        changed: &[&String],
+    ) -> Result<(), String> {
+        let (namespace, zonefile_name) = zonefile;
+
+        match self {
+            Output::ConfigMap {
+                namespace: target_namespace,
+                name,
+            } => {
+                let labels = BTreeMap::from([
+                    (ZONEFILE_LABEL.to_string(), zonefile_name.to_string()),
+                    (OWNED_BY_LABEL.to_string(), zonefile_name.to_string()),
+                    (OWNED_BY_NAMESPACE_LABEL.to_string(), namespace.to_string()),
+                ]);
+
+                let namespace = target_namespace.as_deref().unwrap_or(namespace);
+
+                let config_map = ConfigMap {
+                    metadata: ObjectMeta {
+                        name: Some(name.clone()),
+                        namespace: Some(namespace.to_string()),
+                        labels: Some(labels),
+                        ..ObjectMeta::default()
+                    },
+                    data: Some(naming.publish(data.clone())),
+                    ..Default::default()
+                };
+
+                Api::<ConfigMap>::namespaced(client, namespace)
+                    .patch(
+                        name,
+                        &PatchParams::apply(controller_name),
+                        &Patch::Apply(config_map),
+                    )
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            Output::Http(url) => {
+                let host = self.http_host().unwrap_or_default();
+
+                for key in changed {
+                    http.send_limited(&host, |client| {
+                        client
+                            .put(format!("{url}/{}", naming.rename(key)))
+                            .body(data[key.as_str()].clone())
+                    })
+                    .await
+                    .and_then(|response| Ok(response.error_for_status()?))
+                    .map_err(|e| format!("{key}: {e}"))?;
+                }
+            }
+            Output::Notify(address) => {
+                // Keys of the zones themselves are their origins, which unlike
+                // the keys of their signatures and statistics end with a dot.
+                for origin in changed.iter().filter(|key| key.ends_with('.')) {
+                    notify(address, origin, tsig)
+                        .await
+                        .map_err(|e| format!("{origin}: {e}"))?;
+                }
+            }
+            Output::Oci {
+                repository,
+                credentials,
+            } => {
+                let tag = oci::push(
+                    client,
+                    http,
+                    &args.insecure_registries,
+                    repository,
+                    credentials.as_deref(),
+                    zonefile,
+                    &naming.publish(data.clone()),
+                )
+                .await?;
+
+                info!("pushed zonefile {namespace}/{zonefile_name} to {self} as {tag}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Output::ConfigMap {
+                namespace: Some(namespace),
+                name,
+            } => write!(f, "configmap:{namespace}/{name}"),
+            Output::ConfigMap {
+                namespace: None,
+                name,
+            } => write!(f, "configmap:{name}"),
+            Output::Http(url) => f.write_str(url),
+            Output::Notify(address) => write!(f, "notify:{address}"),
+            Output::Oci {
+                repository,
+                credentials: Some(credentials),
+            } => write!(
+                f,
+                "oci://{}/{}?credentials={credentials}",
+                repository.registry, repository.name
+            ),
+            Output::Oci {
+                repository,
+                credentials: None,
+            } => write!(f, "oci://{}/{}", repository.registry, repository.name),
+        }
+    }
+}
+
+/// DNS NOTIFY message for the zone, as described in
+/// [RFC 1996](https://www.rfc-editor.org/rfc/rfc1996).
+fn notify_message(id: u16, origin: &str) -> Result<Message, ProtoError> {
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Notify)
+        .set_authoritative(true)
+        .add_query(Query::query(Name::from_ascii(origin)?, RecordType::SOA));
+
+    Ok(message)
+}
+
+/// Send a NOTIFY message for the zone to the secondary, and wait for its acknowledgement.
+/// The message is signed with the TSIG key, if given, and so must be the acknowledgement.
+async fn notify(address: &str, origin: &str, tsig: Option<&TSigner>) -> Result<(), std::io::Error> {
+    let address = lookup_host(address)
+        .await?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses found"))?;
+
+    let socket = UdpSocket::bind(if address.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    })
+    .await?;
+    socket.connect(address).await?;
+
+    let id = u16::from_be_bytes(
+        ring::rand::generate(&ring::rand::SystemRandom::new())
+            .map_err(|_| std::io::Error::other("failed to generate message id"))?
+            .expose(),
+    );
+
+    let mut message = notify_message(id, origin)?;
+    let verifier = match tsig {
+        Some(tsig) => message.finalize(tsig, Utc::now().timestamp() as u32)?,
+        None => None,
+    };
+    socket.send(&message.to_vec()?).await?;
+
+    let mut response = [0u8; 512];
+    let length = tokio::time::timeout(NOTIFY_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "no response"))??;
+
+    let response = match verifier {
+        Some(mut verifier) => verifier(&response[..length])
+            .map(DnsResponse::into_message)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+        None => Message::from_vec(&response[..length])
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+    };
+
+    if response.id() != id {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "response to another message",
+        ));
+    }
+
+    match response.response_code() {
+        ResponseCode::NoError => Ok(()),
+        rcode => Err(std::io::Error::other(format!("refused with rcode {rcode}"))),
+    }
+}
+
+/// Hashes of the keys most recently delivered to each target, per zonefile,
+/// so that only changed keys are delivered. Targets which failed are retried
+/// with every reconciliation until they succeed, and their last failure is
+/// kept for the status.
+///
+/// Unless persisted with `--persist-state`, this is kept in memory only,
+/// so a restarted controller delivers all keys to every target once.
+#[derive(Default)]
+pub struct DeliveredOutputs {
+    delivered: Mutex<HashMap<(String, String), BTreeMap<String, String>>>,
+    failures: Mutex<HashMap<(String, String), String>>,
+    /// Zonefiles whose persisted state was looked up.
+    restored: Mutex<HashSet<String>>,
+}
+
+impl DeliveredOutputs {
+    /// Keys whose hashes differ from those last delivered to the target.
+    pub fn changed<'a>(
+        &self,
+        zonefile: &str,
+        target: &str,
+        hashes: &'a BTreeMap<String, String>,
+    ) -> Vec<&'a String> {
+        let delivered = self.delivered.lock().unwrap();
+        let delivered = delivered.get(&(zonefile.to_string(), target.to_string()));
+
+        hashes
+            .iter()
+            .filter(|(key, hash)| delivered.and_then(|delivered| delivered.get(*key)) != Some(hash))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    pub fn delivered(&self, zonefile: &str, target: &str, hashes: &BTreeMap<String, String>) {
+        let key = (zonefile.to_string(), target.to_string());
+
+        self.failures.lock().unwrap().remove(&key);
+        self.delivered.lock().unwrap().insert(key, hashes.clone());
+    }
+
+    pub fn failed(&self, zonefile: &str, target: &str, error: String) {
+        self.failures
+            .lock()
+            .unwrap()
+            .insert((zonefile.to_string(), target.to_string()), error);
+    }
+
+    /// Error of the last delivery to the target, unless it succeeded.
+    pub fn failure(&self, zonefile: &str, target: &str) -> Option<String> {
+        self.failures
+            .lock()
+            .unwrap()
+            .get(&(zonefile.to_string(), target.to_string()))
+            .cloned()
+    }
+
+    /// Whether anything was delivered for the zonefile.
+    pub fn known(&self, zonefile: &str) -> bool {
+        self.delivered
+            .lock()
+            .unwrap()
+            .keys()
+            .any(|(delivered_zonefile, _)| delivered_zonefile == zonefile)
+    }
+
+    /// Whether the persisted state of the zonefile was looked up.
+    pub fn restored(&self, zonefile: &str) -> bool {
+        self.restored.lock().unwrap().contains(zonefile)
+    }
+
+    pub fn mark_restored(&self, zonefile: &str) {
+        self.restored.lock().unwrap().insert(zonefile.to_string());
+    }
+
+    /// Hashes of the keys delivered for the zonefile, by target.
+    pub fn of(&self, zonefile: &str) -> BTreeMap<String, BTreeMap<String, String>> {
+        self.delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((delivered_zonefile, _), _)| delivered_zonefile == zonefile)
+            .map(|((_, target), hashes)| (target.clone(), hashes.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use hickory_proto::op::{Message, OpCode};
+
+    use super::{notify_message, DeliveredOutputs, Output, OutputArgs};
+    use crate::{oci::Repository, tsig};
+
+    #[test]
+    fn parses_outputs() {
+        assert_eq!(
+            Output::parse_list(
+                "configmap:coredns, configmap:dns/zones, https://bucket.example.org/zones/, notify:ns2.example.org"
+            )
+            .unwrap(),
+            vec![
+                Output::ConfigMap {
+                    namespace: None,
+                    name: "coredns".to_string()
+                },
+                Output::ConfigMap {
+                    namespace: Some("dns".to_string()),
+                    name: "zones".to_string()
+                },
+                Output::Http("https://bucket.example.org/zones".to_string()),
+                Output::Notify("ns2.example.org:53".to_string()),
+            ]
+        );
+
+        assert!(Output::parse_list("notify:192.0.2.1:5353").is_ok());
+        assert!(Output::parse_list("notify:192.0.2.1:dns").is_err());
+        assert!(Output::parse_list("configmap:").is_err());
+        assert!(Output::parse_list("s3://bucket").is_err());
+        assert_eq!(
+            Output::parse_list("http://bucket.example.org/zones").unwrap_err(),
+            "http://bucket.example.org/zones"
+        );
+
+        assert_eq!(
+            Output::parse_list("oci://ghcr.io/example/zones?credentials=regcred").unwrap(),
+            vec![Output::Oci {
+                repository: Repository {
+                    registry: "ghcr.io".to_string(),
+                    name: "example/zones".to_string(),
+                },
+                credentials: Some("regcred".to_string()),
+            }]
+        );
+        assert!(Output::parse_list("oci://ghcr.io/example/zones?credentials=").is_err());
+        assert!(Output::parse_list("oci://ghcr.io").is_err());
+    }
+
+    #[test]
+    fn allows_listed_notify_targets() {
+        let args = OutputArgs {
+            notify_allowed_targets: vec![
+                "ns2.example.org".to_string(),
+                " 192.0.2.1:5353".to_string(),
+            ],
+            insecure_registries: Vec::new(),
+        };
+
+        assert!(args.allows_notify("ns2.example.org:53"));
+        assert!(args.allows_notify("NS2.example.org:53"));
+        assert!(args.allows_notify("192.0.2.1:5353"));
+
+        assert!(!args.allows_notify("ns2.example.org:5353"));
+        assert!(!args.allows_notify("192.0.2.1:53"));
+        assert!(!args.allows_notify("ns3.example.org:53"));
+        assert!(!OutputArgs::default().allows_notify("ns2.example.org:53"));
+    }
+
+    #[test]
+    fn notify_message_encoding() {
+        assert_eq!(
+            notify_message(0x1234, "example.org.")
+                .unwrap()
+                .to_vec()
+                .unwrap(),
+            [
+                &[0x12, 0x34, 0x24, 0x00, 0, 1, 0, 0, 0, 0, 0, 0][..],
+                b"\x07example\x03org\x00",
+                &[0, 6, 0, 1],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn signed_notify_messages() {
+        let tsig = tsig::signer(
+            "transfer.",
+            None,
+            "2vgqVbBZJ3fK4LFbfdSaGGbJ8Lgu0hXzCLK/ybqTLtM=",
+        )
+        .unwrap();
+
+        let mut message = notify_message(0x1234, "example.org.").unwrap();
+        message.finalize(&tsig, 1_700_000_000).unwrap();
+
+        let signed = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        assert_eq!(signed.op_code(), OpCode::Notify);
+        assert_eq!(signed.signature().len(), 1);
+        assert_eq!(signed.signature()[0].name().to_ascii(), "transfer.");
+    }
+
+    #[test]
+    fn only_changed_keys_are_delivered() {
+        let outputs = DeliveredOutputs::default();
+        let mut hashes = BTreeMap::from([
+            ("example.org.".to_string(), "sha256:a".to_string()),
+            ("example.net.".to_string(), "sha256:b".to_string()),
+        ]);
+
+        assert_eq!(
+            outputs
+                .changed("default/zones", "notify:ns2", &hashes)
+                .len(),
+            2
+        );
+
+        outputs.delivered("default/zones", "notify:ns2", &hashes);
+        assert!(outputs
+            .changed("default/zones", "notify:ns2", &hashes)
+            .is_empty());
+
+        hashes.insert("example.org.".to_string(), "sha256:c".to_string());
+        assert_eq!(
+            outputs.changed("default/zones", "notify:ns2", &hashes),
+            vec!["example.org."]
+        );
+        assert_eq!(
+            outputs
+                .changed("default/zones", "https://other", &hashes)
+                .len(),
+            2
+        );
+        outputs.failed("default/zones", "https://other", String::from("timed out"));
+        assert_eq!(
+            outputs.failure("default/zones", "https://other").as_deref(),
+            Some("timed out")
+        );
+        outputs.delivered("default/zones", "https://other", &hashes);
+        assert_eq!(outputs.failure("default/zones", "https://other"), None);
+
+        assert!(outputs.known("default/zones"));
+        assert!(!outputs.known("default/other"));
+
+        assert!(!outputs.restored("default/other"));
+        outputs.mark_restored("default/other");
+        assert!(outputs.restored("default/other"));
+        assert!(!outputs.known("default/other"));
+        assert_eq!(
+            outputs.of("default/zones").keys().collect::<Vec<_>>(),
+            vec!["https://other", "notify:ns2"]
+        );
+    }
+}
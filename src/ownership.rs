@@ -0,0 +1,370 @@
+use std::{collections::BTreeMap, fmt::Debug, time::Duration};
+
+use clap::Args;
+use k8s_openapi::{
+    api::{batch::v1::Job, core::v1::ConfigMap},
+    NamespaceResourceScope,
+};
+use kube::{
+    api::{DeleteParams, ListParams},
+    core::ObjectMeta,
+    Api, Client, Resource, ResourceExt,
+};
+use serde::de::DeserializeOwned;
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{output::Output, state::ZoneFileState};
+
+/// Label on objects generated in label ownership mode, naming the
+/// [`ZoneFile`] which they were generated for.
+pub const OWNED_BY_LABEL: &str = "zonefile.kubi.zone/owned-by";
+
+/// Label on ConfigMap outputs, naming the namespace of their [`ZoneFile`], since
+/// outputs may be in other namespaces. Outputs carry it along with the owned-by
+/// label whatever the ownership mode, since owner references cannot cross
+/// namespaces. Other objects are always in the namespace of their zonefile.
+pub const OWNED_BY_NAMESPACE_LABEL: &str = "zonefile.kubi.zone/owned-by-namespace";
+
+#[derive(Debug, Clone, Args)]
+pub struct OwnershipArgs {
+    /// Mark the objects generated for zonefiles, such as their ConfigMaps, with the
+    /// `zonefile.kubi.zone/owned-by` label instead of an owner reference, for GitOps
+    /// tools which prune objects with foreign owner references. Objects which are no
+    /// longer generated for their zonefile are then deleted by the controller itself.
+    #[arg(long, env = "ZONEFILE_LABEL_OWNERSHIP")]
+    pub label_ownership: bool,
+
+    /// Interval in seconds at which orphaned objects are deleted. Without
+    /// `--label-ownership`, only ConfigMap outputs are deleted by the controller.
+    #[arg(long, env = "ZONEFILE_GC_INTERVAL", default_value_t = 300)]
+    pub gc_interval: u64,
+}
+
+/// Mark an object generated for the zonefile in its namespace as owned by it, with
+/// the owned-by label in label ownership mode, or an owner reference otherwise.
+pub fn mark_owned(zonefile: &ZoneFile, label_ownership: bool, metadata: &mut ObjectMeta) {
+    if label_ownership {
+        metadata
+            .labels
+            .get_or_insert_with(BTreeMap::new)
+            .insert(OWNED_BY_LABEL.to_string(), zonefile.name_any());
+    } else {
+        metadata.owner_references = zonefile
+            .controller_owner_ref(&())
+            .map(|owner_reference| vec![owner_reference]);
+    }
+}
+
+/// Name of the ConfigMap which the zonefile is published to.
+pub fn config_map_name(zonefile: &ZoneFile) -> String {
+    zonefile
+        .spec
+        .config_map_name
+        .clone()
+        .unwrap_or_else(|| zonefile.name_any())
+}
+
+/// Zonefile named by the owned-by labels of the object, if it still exists.
+/// Returns `None` for objects without the labels, which are never orphans.
+fn owner<'a>(object: &impl ResourceExt, zonefiles: &'a [ZoneFile]) -> Option<Option<&'a ZoneFile>> {
+    let owner = object.labels().get(OWNED_BY_LABEL)?;
+    let namespace = object
+        .labels()
+        .get(OWNED_BY_NAMESPACE_LABEL)
+        .cloned()
+        .or_else(|| object.namespace());
+
+    Some(
+        zonefiles
+            .iter()
+            .find(|zonefile| zonefile.namespace() == namespace && zonefile.name_any() == *owner),
+    )
+}
+
+/// Whether the zonefile publishes to the ConfigMap, either as its own or as one of
+/// its outputs, resolved as they are when reconciling it. Zonefiles with invalid
+/// outputs are assumed to publish to it.
+fn publishes_to(
+    zonefile: &ZoneFile,
+    default_outputs: Option<&String>,
+    config_map: &ConfigMap,
+) -> bool {
+    let namespace = zonefile.namespace();
+
+    if namespace == config_map.namespace() && config_map_name(zonefile) == config_map.name_any() {
+        return true;
+    }
+
+    Output::configured(zonefile, default_outputs).map_or(true, |outputs| {
+        outputs.iter().any(|output| match output {
+            Output::ConfigMap {
+                namespace: output_namespace,
+                name,
+            } => {
+                output_namespace.as_ref().or(namespace.as_ref()) == config_map.namespace().as_ref()
+                    && *name == config_map.name_any()
+            }
+            _ => false,
+        })
+    })
+}
+
+/// Whether the labeled ConfigMap is no longer published by the zonefile named by its labels.
+fn orphaned(
+    config_map: &ConfigMap,
+    zonefiles: &[ZoneFile],
+    default_outputs: Option<&String>,
+) -> bool {
+    owner(config_map, zonefiles).is_some_and(|owner| {
+        !owner.is_some_and(|zonefile| publishes_to(zonefile, default_outputs, config_map))
+    })
+}
+
+/// Whether the zonefile named by the labels of the object no longer exists.
+fn abandoned(object: &impl ResourceExt, zonefiles: &[ZoneFile]) -> bool {
+    owner(object, zonefiles).is_some_and(|owner| owner.is_none())
+}
+
+/// List the objects of a kind carrying the label in all namespaces.
+async fn labeled<K>(client: Client, label: &str) -> Result<Vec<K>, kube::Error>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + Debug
+        + DeserializeOwned,
+{
+    Ok(Api::<K>::all(client)
+        .list(&ListParams::default().labels(label))
+        .await?
+        .items)
+}
+
+/// Delete the orphaned objects, logging rather than returning failures.
+async fn delete<'a, K>(client: Client, objects: impl Iterator<Item = &'a K>, params: &DeleteParams)
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + Debug
+        + DeserializeOwned
+        + 'a,
+{
+    let kind = K::kind(&()).to_lowercase();
+
+    for object in objects {
+        let namespace = object.namespace().unwrap_or_default();
+
+        info!("deleting orphaned {kind} {namespace}/{}", object.name_any());
+
+        if let Err(e) = Api::<K>::namespaced(client.clone(), &namespace)
+            .delete(&object.name_any(), params)
+            .await
+        {
+            error!(
+                "failed to delete orphaned {kind} {namespace}/{}: {e}",
+                object.name_any()
+            );
+        }
+    }
+}
+
+async fn collect_garbage(
+    client: Client,
+    label_ownership: bool,
+    persist_state: bool,
+    default_outputs: Option<String>,
+) -> Result<(), kube::Error> {
+    // Labeled objects are listed ahead of zonefiles, so that the objects of a
+    // zonefile created in between are not mistaken for orphans. Without label
+    // ownership, only outputs are labeled, and everything else is deleted
+    // along with its zonefile through its owner reference.
+    let config_maps = if label_ownership {
+        labeled::<ConfigMap>(client.clone(), OWNED_BY_LABEL).await?
+    } else {
+        // Outputs are only delivered across namespaces if the
+        // controller was granted access to ConfigMaps in all of them.
+        match labeled::<ConfigMap>(client.clone(), OWNED_BY_NAMESPACE_LABEL).await {
+            Ok(config_maps) => config_maps,
+            Err(kube::Error::Api(e)) if e.code == 403 => {
+                debug!("not collecting orphaned outputs: {e}");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    let states = if label_ownership && persist_state {
+        labeled::<ZoneFileState>(client.clone(), OWNED_BY_LABEL).await?
+    } else {
+        Vec::new()
+    };
+
+    // Post-sync jobs are only launched for zonefiles with hooks, which
+    // the controller need not have been granted access to jobs without.
+    let jobs = if label_ownership {
+        match labeled::<Job>(client.clone(), OWNED_BY_LABEL).await {
+            Ok(jobs) => jobs,
+            Err(kube::Error::Api(e)) if e.code == 403 => {
+                debug!("not collecting orphaned jobs: {e}");
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        }
+    } else {
+        Vec::new()
+    };
+
+    let zonefiles = Api::<ZoneFile>::all(client.clone())
+        .list(&ListParams::default())
+        .await?;
+
+    delete(
+        client.clone(),
+        config_maps
+            .iter()
+            .filter(|config_map| orphaned(config_map, &zonefiles.items, default_outputs.as_ref())),
+        &DeleteParams::default(),
+    )
+    .await;
+
+    delete(
+        client.clone(),
+        states
+            .iter()
+            .filter(|state| abandoned(*state, &zonefiles.items)),
+        &DeleteParams::default(),
+    )
+    .await;
+
+    // The pods of orphaned jobs are deleted along with them.
+    delete(
+        client,
+        jobs.iter().filter(|job| abandoned(*job, &zonefiles.items)),
+        &DeleteParams::background(),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Periodically delete labeled objects which are no longer generated,
+/// given the default outputs currently configured.
+pub async fn run(
+    client: Client,
+    args: OwnershipArgs,
+    persist_state: bool,
+    default_outputs: impl Fn() -> Option<String>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(args.gc_interval));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = collect_garbage(
+            client.clone(),
+            args.label_ownership,
+            persist_state,
+            default_outputs(),
+        )
+        .await
+        {
+            error!("failed to collect orphaned objects: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use kube::core::ObjectMeta;
+    use zonefile_crds::ZoneFile;
+
+    use super::{abandoned, orphaned, OWNED_BY_LABEL, OWNED_BY_NAMESPACE_LABEL};
+
+    fn config_map(name: &str, owner: Option<&str>) -> ConfigMap {
+        ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("default".to_string()),
+                labels: owner
+                    .map(|owner| BTreeMap::from([(OWNED_BY_LABEL.to_string(), owner.to_string())])),
+                ..ObjectMeta::default()
+            },
+            ..ConfigMap::default()
+        }
+    }
+
+    #[test]
+    fn finds_orphans() {
+        let zonefiles: [ZoneFile; 1] = [serde_yaml::from_str(indoc::indoc! { r#"
+            apiVersion: kubi.zone/v1alpha1
+            kind: ZoneFile
+            metadata:
+              name: zones
+              namespace: default
+              annotations:
+                zonefile.kubi.zone/outputs: configmap:dns/coredns-zones
+            spec:
+              configMapName: coredns-zones
+              zoneRefs: []
+        "#})
+        .unwrap()];
+
+        assert!(!orphaned(
+            &config_map("coredns-zones", Some("zones")),
+            &zonefiles,
+            None
+        ));
+
+        // Left behind after the zonefile's ConfigMap was renamed.
+        assert!(orphaned(
+            &config_map("zones", Some("zones")),
+            &zonefiles,
+            None
+        ));
+
+        // Left behind after the zonefile was deleted.
+        assert!(orphaned(
+            &config_map("coredns-zones", Some("other")),
+            &zonefiles,
+            None
+        ));
+
+        // Never touched without the label.
+        assert!(!orphaned(&config_map("zones", None), &zonefiles, None));
+
+        // Outputs in other namespaces name the namespace of their zonefile.
+        let mut output = config_map("coredns-zones", Some("zones"));
+        output.metadata.namespace = Some("dns".to_string());
+        assert!(orphaned(&output, &zonefiles, None));
+
+        output
+            .metadata
+            .labels
+            .as_mut()
+            .unwrap()
+            .insert(OWNED_BY_NAMESPACE_LABEL.to_string(), "default".to_string());
+        assert!(!orphaned(&output, &zonefiles, None));
+
+        output.metadata.name = Some("other-zones".to_string());
+        assert!(orphaned(&output, &zonefiles, None));
+
+        // Zonefiles without outputs of their own publish to the default outputs.
+        let mut zonefile = zonefiles[0].clone();
+        zonefile.metadata.annotations = None;
+        output.metadata.name = Some("default-zones".to_string());
+        assert!(orphaned(&output, &[zonefile.clone()], None));
+        assert!(!orphaned(
+            &output,
+            &[zonefile],
+            Some(&"configmap:dns/default-zones".to_string())
+        ));
+
+        // Other objects are orphaned only once their zonefile is deleted.
+        assert!(!abandoned(&config_map("zones", Some("zones")), &zonefiles));
+        assert!(abandoned(&config_map("zones", Some("other")), &zonefiles));
+        assert!(!abandoned(&config_map("zones", None), &zonefiles));
+    }
+}
@@ -0,0 +1,577 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::Args;
+use hickory_proto::{
+    error::ProtoError,
+    op::{Edns, Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{DNSClass, Name, Record, RecordType},
+    serialize::binary::{BinDecodable as _, BinDecoder},
+};
+use k8s_openapi::serde_json::{self, Value};
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{lookup_host, TcpStream, UdpSocket},
+};
+use tracing::log::*;
+
+use crate::{
+    convert,
+    error::Error,
+    normalize,
+    render::FormatArgs,
+    simulate::{self, LocalZone},
+    validation::ValidationArgs,
+};
+
+/// Types which can be queried, along with their codes on the wire.
+/// Their answers are presented as in rendered zones.
+const TYPE_CODES: [(Type, u16); 11] = [
+    (Type::A, 1),
+    (Type::NS, 2),
+    (Type::CNAME, 5),
+    (Type::SOA, 6),
+    (Type::PTR, 12),
+    (Type::MX, 15),
+    (Type::TXT, 16),
+    (Type::AAAA, 28),
+    (Type::SRV, 33),
+    (Type::DNAME, 39),
+    (Type::CAA, 257),
+];
+
+/// UDP payload size advertised through EDNS, which avoids fragmentation
+/// on most paths, as recommended by DNS Flag Day 2020.
+const EDNS_PAYLOAD: u16 = 1232;
+
+#[derive(Debug, Clone, Args)]
+pub struct QueryArgs {
+    /// Name to query.
+    #[arg(value_parser = parse_name)]
+    pub name: FullyQualifiedDomainName,
+
+    /// Type of the records to query, such as `A` or `MX`.
+    #[arg(value_name = "TYPE", value_parser = parse_type)]
+    pub type_: Type,
+
+    /// Server to query, as `<host>[:<port>]`.
+    #[arg(long)]
+    pub server: String,
+
+    /// Manifests containing the Records and Zones which the server should be serving.
+    #[arg(short = 'f', long = "filename", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Namespace of manifests which do not specify one.
+    #[arg(short, long, default_value = "default")]
+    pub namespace: String,
+
+    /// Seconds to wait for the server to answer.
+    #[arg(long, default_value_t = 5)]
+    pub timeout: u64,
+
+    #[command(flatten)]
+    pub validation: ValidationArgs,
+}
+
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error("failed to query server: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("no answer from server within {0:?}")]
+    Timeout(Duration),
+    #[error("malformed message: {0}")]
+    Malformed(#[from] ProtoError),
+    #[error("response from server does not answer the query")]
+    Unrelated,
+    #[error("server refused the query with rcode {0}")]
+    Refused(ResponseCode),
+    #[error("no zone in the manifests contains {0}")]
+    NoZone(String),
+    #[error("{0} record(s) differ from the rendered zone")]
+    Mismatch(usize),
+}
+
+fn parse_name(name: &str) -> Result<FullyQualifiedDomainName, String> {
+    let mut name = name.to_ascii_lowercase();
+    if !name.ends_with('.') {
+        name.push('.');
+    }
+
+    FullyQualifiedDomainName::try_from(name.as_str()).map_err(|e| e.to_string())
+}
+
+fn parse_type(type_: &str) -> Result<Type, String> {
+    serde_json::from_value(Value::String(type_.to_ascii_uppercase()))
+        .ok()
+        .filter(|type_| type_code(*type_).is_some())
+        .ok_or_else(|| format!("querying {type_} records is not supported"))
+}
+
+fn type_code(type_: Type) -> Option<u16> {
+    TYPE_CODES
+        .iter()
+        .find(|(known, _)| *known == type_)
+        .map(|(_, code)| *code)
+}
+
+fn code_type(code: u16) -> Option<Type> {
+    TYPE_CODES
+        .iter()
+        .find(|(_, known)| *known == code)
+        .map(|(type_, _)| *type_)
+}
+
+/// Query for the records of the type, without requesting recursion,
+/// since the server is expected to be authoritative for the name.
+fn query_message(
+    id: u16,
+    name: &FullyQualifiedDomainName,
+    type_: u16,
+) -> Result<Message, QueryError> {
+    let mut edns = Edns::new();
+    edns.set_max_payload(EDNS_PAYLOAD);
+
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false)
+        .add_query(Query::query(
+            Name::from_ascii(name.to_string())?,
+            RecordType::from(type_),
+        ))
+        .set_edns(edns);
+
+    Ok(message)
+}
+
+/// Decode the response of the server, which must answer the query.
+fn parse_response(query: &Message, response: &[u8]) -> Result<Message, QueryError> {
+    let response = Message::from_vec(response)?;
+
+    if response.id() != query.id()
+        || response.message_type() != MessageType::Response
+        || response.queries() != query.queries()
+    {
+        return Err(QueryError::Unrelated);
+    }
+
+    Ok(response)
+}
+
+/// Answer as a zone entry, with its rdata presented as in rendered zones, or
+/// [`None`] for answers of other classes and types, which cannot be compared.
+fn answer(record: &Record) -> Option<ZoneEntry> {
+    if record.dns_class() != DNSClass::IN {
+        return None;
+    }
+
+    let type_ = code_type(u16::from(record.record_type()))?;
+
+    let rdata = record.data()?;
+    let rdata = match type_ {
+        // Neither is presented by hickory as the zones present them.
+        Type::CAA => {
+            let bytes = convert::wire(rdata)?;
+            let (flags, rest) = bytes.split_first()?;
+            let (length, rest) = rest.split_first()?;
+            let tag = rest.get(..*length as usize)?;
+            let value = &rest[*length as usize..];
+
+            format!(
+                "{flags} {} {}",
+                String::from_utf8_lossy(tag),
+                convert::quote(value)
+            )
+        }
+        Type::DNAME => {
+            let bytes = convert::wire(rdata)?;
+            Name::read(&mut BinDecoder::new(&bytes)).ok()?.to_string()
+        }
+        _ => convert::presentation(rdata)?,
+    };
+
+    Some(ZoneEntry {
+        fqdn: FullyQualifiedDomainName::try_from(record.name().to_lowercase().to_ascii().as_str())
+            .ok()?,
+        type_,
+        class: Class::IN,
+        ttl: record.ttl(),
+        rdata,
+    })
+}
+
+/// Send the query over UDP, retrying over TCP if the response was truncated.
+async fn exchange(server: &str, query: &Message, timeout: Duration) -> Result<Message, QueryError> {
+    let address = lookup_host(server)
+        .await?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses found"))?;
+
+    let message = query.to_vec()?;
+
+    let socket = UdpSocket::bind(if address.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    })
+    .await?;
+    socket.connect(address).await?;
+    socket.send(&message).await?;
+
+    let mut response = vec![0u8; 65535];
+    let length = tokio::time::timeout(timeout, socket.recv(&mut response))
+        .await
+        .map_err(|_| QueryError::Timeout(timeout))??;
+
+    let response = parse_response(query, &response[..length])?;
+    if !response.truncated() {
+        return Ok(response);
+    }
+
+    debug!("response from {server} was truncated, retrying over tcp");
+    let exchange = async {
+        let mut stream = TcpStream::connect(address).await?;
+        stream
+            .write_all(&(message.len() as u16).to_be_bytes())
+            .await?;
+        stream.write_all(&message).await?;
+
+        let length = stream.read_u16().await? as usize;
+        let mut response = vec![0u8; length];
+        stream.read_exact(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+
+    let response = tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| QueryError::Timeout(timeout))??;
+
+    parse_response(query, &response)
+}
+
+/// Whether the name owns records in the zone, or is an empty non-terminal above such names.
+fn exists(zone: &LocalZone, name: &str) -> bool {
+    let suffix = match name {
+        "." => String::from("."),
+        name => format!(".{name}"),
+    };
+
+    zone.entries.iter().any(|entry| {
+        let owner = entry.fqdn.to_string();
+        owner == name || owner.ends_with(&suffix)
+    })
+}
+
+/// Records which the zone should answer the query with, including the CNAME record
+/// of the name when querying for other types, or [`None`] if the name does not exist.
+/// Names which do not exist are answered from the wildcard records of their closest
+/// encloser, the nearest of their ancestors which does exist, as in
+/// [RFC 4592 section 3.3.1](https://www.rfc-editor.org/rfc/rfc4592#section-3.3.1).
+fn expected_answers(
+    zone: &LocalZone,
+    name: &FullyQualifiedDomainName,
+    type_: Type,
+) -> Option<Vec<ZoneEntry>> {
+    let answers = |owner: &str| -> Vec<ZoneEntry> {
+        zone.entries
+            .iter()
+            .filter(|entry| {
+                entry.fqdn.to_string() == owner
+                    && (entry.type_ == type_
+                        || (entry.type_ == Type::CNAME && type_ != Type::CNAME))
+            })
+            .map(|entry| ZoneEntry {
+                fqdn: name.clone(),
+                ..entry.clone()
+            })
+            .collect()
+    };
+
+    let name = name.to_string();
+    if exists(zone, &name) {
+        return Some(answers(&name));
+    }
+
+    let mut encloser = name.as_str();
+    while !exists(zone, encloser) {
+        encloser = match encloser.split_once('.')? {
+            (_, "") => ".",
+            (_, parent) => parent,
+        };
+    }
+
+    let wildcard = match encloser {
+        "." => String::from("*."),
+        encloser => format!("*.{encloser}"),
+    };
+    exists(zone, &wildcard).then(|| answers(&wildcard))
+}
+
+fn present(entry: &ZoneEntry) -> String {
+    format!(
+        "{} {} {} {} {}",
+        entry.fqdn, entry.ttl, entry.class, entry.type_, entry.rdata
+    )
+}
+
+/// Query the server for the records of the name, and compare its answer
+/// to the zone rendered from the manifests, printing any differences.
+pub async fn query(args: QueryArgs) -> Result<(), Error> {
+    let zones = simulate::local_zones(
+        &args.files,
+        &args.namespace,
+        &args.validation,
+        &FormatArgs::default(),
+    )
+    .await?;
+
+    // The most specific zone containing the name is the one which answers for it.
+    let zone = zones
+        .iter()
+        .filter(|zone| args.name == zone.origin || args.name.is_subdomain_of(&zone.origin))
+        .max_by_key(|zone| zone.origin.len())
+        .ok_or_else(|| QueryError::NoZone(args.name.to_string()))?;
+
+    let expected = expected_answers(zone, &args.name, args.type_);
+    let exists = expected.is_some();
+    let expected = normalize::canonicalize(&zone.origin, expected.unwrap_or_default());
+
+    let id = u16::from_be_bytes(
+        ring::rand::generate(&ring::rand::SystemRandom::new())
+            .map_err(|_| std::io::Error::other("failed to generate message id"))?
+            .expose(),
+    );
+
+    let name = args.name.to_string();
+    let response = exchange(
+        &args.server,
+        &query_message(id, &args.name, type_code(args.type_).unwrap())?,
+        Duration::from_secs(args.timeout),
+    )
+    .await?;
+
+    let answered = response.response_code() == ResponseCode::NoError;
+    match response.response_code() {
+        ResponseCode::NoError => (),
+        ResponseCode::NXDomain if !exists => (),
+        ResponseCode::NXDomain => {
+            println!(
+                "- {name} exists in zone {}, but the server answered NXDOMAIN",
+                zone.origin
+            );
+            return Err(QueryError::Mismatch(expected.len().max(1)).into());
+        }
+        rcode => return Err(QueryError::Refused(rcode).into()),
+    }
+
+    if answered && !exists {
+        println!(
+            "+ {name} does not exist in zone {}, but the server answered for it",
+            zone.origin
+        );
+    }
+
+    // Servers may follow CNAME records within their zones, but only the
+    // records of the queried name itself are compared.
+    let answers = response
+        .answers()
+        .iter()
+        .filter_map(|record| {
+            let entry = answer(record);
+            if entry.is_none() {
+                debug!(
+                    "skipping {} {} record of {}",
+                    record.dns_class(),
+                    record.record_type(),
+                    record.name()
+                );
+            }
+            entry
+        })
+        .filter(|entry| entry.fqdn == args.name)
+        .collect();
+    let answers = normalize::canonicalize(&zone.origin, answers);
+
+    let mut expected: Vec<String> = expected.iter().map(present).collect();
+    let mut answers: Vec<String> = answers.iter().map(present).collect();
+    expected.sort();
+    answers.sort();
+
+    let missing: Vec<&String> = expected
+        .iter()
+        .filter(|record| !answers.contains(record))
+        .collect();
+    let unexpected: Vec<&String> = answers
+        .iter()
+        .filter(|record| !expected.contains(record))
+        .collect();
+
+    for record in &missing {
+        println!("- {record}");
+    }
+
+    for record in &unexpected {
+        println!("+ {record}");
+    }
+
+    let mismatches = missing.len() + unexpected.len() + usize::from(answered && !exists);
+    if mismatches > 0 {
+        return Err(QueryError::Mismatch(mismatches).into());
+    }
+
+    println!(
+        "{name} {}: {} record(s) match zone {}",
+        args.type_,
+        expected.len(),
+        zone.origin
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::{
+        op::{Message, MessageType, Query},
+        rr::{
+            rdata::{caa::CAA, MX, TXT},
+            Name, RData, Record, RecordType,
+        },
+    };
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use crate::simulate::LocalZone;
+
+    use super::{answer, expected_answers, parse_response, query_message, QueryError};
+
+    fn name(name: &str) -> FullyQualifiedDomainName {
+        FullyQualifiedDomainName::try_from(name).unwrap()
+    }
+
+    #[test]
+    fn query_message_encoding() {
+        let query = query_message(0x1234, &name("www.example.org."), 28).unwrap();
+        let decoded = Message::from_vec(&query.to_vec().unwrap()).unwrap();
+
+        assert_eq!(decoded.id(), 0x1234);
+        assert!(!decoded.recursion_desired());
+        assert_eq!(decoded.max_payload(), 1232);
+        assert_eq!(
+            decoded.queries(),
+            [Query::query(
+                Name::from_ascii("www.example.org.").unwrap(),
+                RecordType::AAAA
+            )]
+        );
+    }
+
+    #[test]
+    fn response_decoding() {
+        let query = query_message(0x1234, &name("example.org."), 15).unwrap();
+        let owner = Name::from_ascii("EXAMPLE.org.").unwrap();
+
+        let mut response = query.clone();
+        response
+            .set_message_type(MessageType::Response)
+            .set_authoritative(true)
+            .add_answers([
+                Record::from_rdata(
+                    owner.clone(),
+                    3600,
+                    RData::MX(MX::new(10, Name::from_ascii("MAIL.example.org.").unwrap())),
+                ),
+                Record::from_rdata(
+                    owner.clone(),
+                    60,
+                    RData::TXT(TXT::new(vec![
+                        String::from("v=spf1 -all"),
+                        String::from("a\"b"),
+                    ])),
+                ),
+                Record::from_rdata(
+                    owner,
+                    60,
+                    RData::CAA(CAA::new_issue(
+                        false,
+                        Some(Name::from_ascii("letsencrypt.org").unwrap()),
+                        Vec::new(),
+                    )),
+                ),
+            ]);
+
+        let decoded = parse_response(&query, &response.to_vec().unwrap()).unwrap();
+        let answers = decoded
+            .answers()
+            .iter()
+            .map(|record| answer(record).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(answers[0].fqdn, name("example.org."));
+        assert_eq!(answers[0].type_, Type::MX);
+        assert_eq!(answers[0].ttl, 3600);
+        assert_eq!(answers[0].rdata, "10 MAIL.example.org.");
+        assert_eq!(answers[1].rdata, r#""v=spf1 -all" "a\"b""#);
+        assert_eq!(answers[2].rdata, r#"0 issue "letsencrypt.org""#);
+
+        // Responses to other questions are rejected.
+        let other = query_message(0x1234, &name("example.org."), 1).unwrap();
+        assert!(matches!(
+            parse_response(&other, &response.to_vec().unwrap()),
+            Err(QueryError::Unrelated)
+        ));
+        assert!(matches!(
+            parse_response(&query, &[0x12, 0x34, 0, 0]),
+            Err(QueryError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn names_beneath_empty_non_terminals() {
+        let entry = |owner: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: name(owner),
+            type_,
+            class: Class::IN,
+            ttl: 300,
+            rdata: rdata.to_string(),
+        };
+
+        let zone = LocalZone {
+            namespace: String::from("default"),
+            name: String::from("example-org"),
+            origin: name("example.org."),
+            entries: vec![
+                entry("example.org.", Type::NS, "ns1.example.org."),
+                entry("host.sub.example.org.", Type::A, "192.0.2.2"),
+            ],
+            rendered: String::new(),
+        };
+
+        let answers = |query: &str, type_: Type| {
+            expected_answers(&zone, &name(query), type_).map(|answers| {
+                answers
+                    .into_iter()
+                    .map(|answer| (answer.fqdn.to_string(), answer.rdata))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            answers("host.sub.example.org.", Type::A),
+            Some(vec![(
+                String::from("host.sub.example.org."),
+                String::from("192.0.2.2")
+            )])
+        );
+
+        // The closest encloser of names beneath an empty non-terminal is the
+        // non-terminal itself, which has no wildcard.
+        assert_eq!(answers("www.sub.example.org.", Type::A), None);
+        assert_eq!(answers("sub.example.org.", Type::A), Some(Vec::new()));
+        assert_eq!(answers("a.b.example.org.", Type::A), None);
+    }
+}
@@ -0,0 +1,87 @@
+use clap::Args;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct QuotaArgs {
+    /// Maximum number of records in a single zone. Zones exceeding
+    /// it keep their previously published contents.
+    #[arg(long, env = "ZONEFILE_MAX_RECORDS_PER_ZONE")]
+    pub max_records_per_zone: Option<usize>,
+
+    /// Maximum number of zones referenced by a single ZoneFile.
+    /// Only the first zones up to the limit are published.
+    #[arg(long, env = "ZONEFILE_MAX_ZONES_PER_ZONEFILE")]
+    pub max_zones_per_zonefile: Option<usize>,
+
+    /// Maximum size in bytes of a single rendered zone. Zones exceeding
+    /// it keep their previously published contents.
+    #[arg(long, env = "ZONEFILE_MAX_ZONE_BYTES")]
+    pub max_zone_bytes: Option<usize>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum QuotaError {
+    #[error("{records} records exceed the limit of {limit}")]
+    Records { records: usize, limit: usize },
+    #[error("{bytes} bytes exceed the limit of {limit}")]
+    Bytes { bytes: usize, limit: usize },
+}
+
+impl QuotaError {
+    /// Reason reported in the `QuotaExceeded` condition.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            QuotaError::Records { .. } => "TooManyRecords",
+            QuotaError::Bytes { .. } => "ZoneTooLarge",
+        }
+    }
+}
+
+impl QuotaArgs {
+    /// Check a rendered zone against the configured limits.
+    pub fn check_zone(&self, records: usize, bytes: usize) -> Result<(), QuotaError> {
+        if let Some(limit) = self.max_records_per_zone.filter(|limit| records > *limit) {
+            return Err(QuotaError::Records { records, limit });
+        }
+
+        if let Some(limit) = self.max_zone_bytes.filter(|limit| bytes > *limit) {
+            return Err(QuotaError::Bytes { bytes, limit });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuotaArgs, QuotaError};
+
+    #[test]
+    fn zone_limits() {
+        let quota = QuotaArgs {
+            max_records_per_zone: Some(10),
+            max_zone_bytes: Some(1024),
+            ..Default::default()
+        };
+
+        assert_eq!(quota.check_zone(10, 1024), Ok(()));
+        assert_eq!(
+            quota.check_zone(11, 100),
+            Err(QuotaError::Records {
+                records: 11,
+                limit: 10
+            })
+        );
+        assert_eq!(
+            quota.check_zone(5, 2048),
+            Err(QuotaError::Bytes {
+                bytes: 2048,
+                limit: 1024
+            })
+        );
+        assert_eq!(
+            QuotaArgs::default().check_zone(usize::MAX, usize::MAX),
+            Ok(())
+        );
+    }
+}
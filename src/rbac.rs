@@ -0,0 +1,392 @@
+use clap::Args;
+use k8s_openapi::api::rbac::v1::{
+    ClusterRole, ClusterRoleBinding, PolicyRule, Role, RoleBinding, RoleRef, Subject,
+};
+use kube::core::ObjectMeta;
+use serde::Serialize;
+
+use crate::error::Error;
+
+#[derive(Debug, Args)]
+pub struct RbacArgs {
+    /// Name of the ServiceAccount which the controller runs as.
+    #[arg(long, default_value = "zonefile")]
+    pub service_account: String,
+
+    /// Namespace of the controller's ServiceAccount.
+    #[arg(short, long, default_value = "kubizone")]
+    pub namespace: String,
+
+    /// Namespaces containing ZoneFiles. When given, access to ConfigMaps and other
+    /// namespaced resources is only granted within them, rather than cluster-wide.
+    #[arg(long = "zonefile-namespace")]
+    pub zonefile_namespaces: Vec<String>,
+
+    /// Grant access to the Secrets holding signing keys, TSIG keys and registry credentials.
+    #[arg(long)]
+    pub signing: bool,
+
+    /// Grant access to ZoneBackups, for periodic backups.
+    #[arg(long)]
+    pub backups: bool,
+
+    /// Grant access to ZoneFileStates, for persisting delivered outputs.
+    #[arg(long)]
+    pub persist_state: bool,
+
+    /// Grant access to Jobs and CronJobs, for post-sync hooks.
+    #[arg(long)]
+    pub hooks: bool,
+
+    /// Grant write access to Zones, for backreferences.
+    #[arg(long)]
+    pub backrefs: bool,
+
+    /// Grant access to cert-manager Challenges, for publishing DNS-01 challenges.
+    #[arg(long)]
+    pub acme: bool,
+
+    /// Grant access to Ingresses, HTTPRoutes, Gateways and Services, and write
+    /// access to Records, for sourcing records from their hostnames.
+    #[arg(long)]
+    pub sources: bool,
+
+    /// Grant access to Records, for rendering their comments.
+    #[arg(long)]
+    pub record_comments: bool,
+
+    /// Grant access to Records, for publishing them within their validity windows.
+    #[arg(long)]
+    pub scheduled_records: bool,
+
+    /// Grant the impersonation of ServiceAccounts, for reading zones
+    /// as the ServiceAccounts of zonefiles.
+    #[arg(long)]
+    pub impersonation: bool,
+
+    /// Names of the ServiceAccounts which may be impersonated, matching the
+    /// controller's `--impersonate-service-account`.
+    #[arg(
+        long = "impersonate-service-account",
+        value_delimiter = ',',
+        default_value = "default"
+    )]
+    pub impersonated_service_accounts: Vec<String>,
+
+    /// Grant access to read Namespaces and list ConfigMaps in all namespaces,
+    /// for delivering ConfigMap outputs to other namespaces and deleting them
+    /// once they are no longer delivered to.
+    #[arg(long)]
+    pub outputs: bool,
+
+    /// Grant access to list ConfigMaps in all namespaces, along with ZoneFileStates
+    /// and Jobs if granted, for deleting orphaned objects when running with
+    /// `--label-ownership`.
+    #[arg(long)]
+    pub label_ownership: bool,
+}
+
+fn rule(api_group: &str, resources: &[&str], verbs: &[&str]) -> PolicyRule {
+    PolicyRule {
+        api_groups: Some(vec![api_group.to_string()]),
+        resources: Some(resources.iter().map(ToString::to_string).collect()),
+        verbs: verbs.iter().map(ToString::to_string).collect(),
+        ..PolicyRule::default()
+    }
+}
+
+impl RbacArgs {
+    /// Rules which must be granted cluster-wide, since the controller
+    /// watches zonefiles and zones in all namespaces.
+    fn cluster_rules(&self) -> Vec<PolicyRule> {
+        let zone_verbs: &[&str] = if self.backrefs {
+            &["get", "list", "watch", "patch"]
+        } else {
+            &["get", "list", "watch"]
+        };
+
+        let mut rules = vec![
+            rule("kubi.zone", &["zonefiles"], &["get", "list", "watch"]),
+            rule("kubi.zone", &["zones"], zone_verbs),
+            rule(
+                "kubi.zone",
+                &["zonefiletemplates"],
+                &["get", "list", "watch"],
+            ),
+            rule(
+                "kubi.zone",
+                &["zonefiledefaults"],
+                &["get", "list", "watch"],
+            ),
+        ];
+
+        if self.outputs {
+            rules.push(rule("", &["namespaces"], &["get"]));
+        }
+
+        if self.label_ownership || self.outputs {
+            rules.push(rule("", &["configmaps"], &["list"]));
+        }
+
+        if self.label_ownership && self.persist_state {
+            rules.push(rule("kubi.zone", &["zonefilestates"], &["list"]));
+        }
+
+        // Queued post-sync jobs are found across all namespaces.
+        if self.hooks {
+            rules.push(rule("batch", &["jobs"], &["list"]));
+        }
+
+        if self.record_comments || self.scheduled_records {
+            rules.push(rule("kubi.zone", &["records"], &["get", "list", "watch"]));
+        }
+
+        if self.acme {
+            rules.push(rule(
+                "acme.cert-manager.io",
+                &["challenges"],
+                &["get", "list", "watch"],
+            ));
+        }
+
+        // Sourced resources, and the records created for them, may be in any namespace.
+        if self.sources {
+            rules.push(rule(
+                "networking.k8s.io",
+                &["ingresses"],
+                &["get", "list", "watch"],
+            ));
+            rules.push(rule("", &["services"], &["get", "list", "watch"]));
+            rules.push(rule(
+                "gateway.networking.k8s.io",
+                &["httproutes", "gateways"],
+                &["get", "list", "watch"],
+            ));
+            rules.push(rule(
+                "kubi.zone",
+                &["records"],
+                &["list", "create", "patch", "delete"],
+            ));
+        }
+
+        rules
+    }
+
+    /// Rules for the resources within the namespaces of the zonefiles.
+    fn namespaced_rules(&self) -> Vec<PolicyRule> {
+        let mut rules = vec![
+            // The live status is read to skip patches which would not change it.
+            rule("kubi.zone", &["zonefiles/status"], &["get", "patch"]),
+            rule("", &["configmaps"], &["get", "patch", "delete"]),
+            rule("events.k8s.io", &["events"], &["create"]),
+        ];
+
+        if self.signing {
+            rules.push(rule("", &["secrets"], &["get"]));
+        }
+
+        if self.backups {
+            rules.push(rule(
+                "kubi.zone",
+                &["zonebackups"],
+                &["list", "create", "delete"],
+            ));
+        }
+
+        if self.persist_state {
+            let verbs: &[&str] = if self.label_ownership {
+                &["get", "create", "patch", "delete"]
+            } else {
+                &["get", "create", "patch"]
+            };
+            rules.push(rule("kubi.zone", &["zonefilestates"], verbs));
+        }
+
+        if self.hooks {
+            let verbs: &[&str] = if self.label_ownership {
+                &["list", "create", "patch", "delete"]
+            } else {
+                &["list", "create", "patch"]
+            };
+            rules.push(rule("batch", &["cronjobs"], &["get"]));
+            rules.push(rule("batch", &["jobs"], verbs));
+        }
+
+        // Only the named accounts, since zonefiles choose which one they are read as.
+        if self.impersonation {
+            rules.push(PolicyRule {
+                resource_names: Some(self.impersonated_service_accounts.clone()),
+                ..rule("", &["serviceaccounts"], &["impersonate"])
+            });
+        }
+
+        rules
+    }
+
+    fn subject(&self) -> Subject {
+        Subject {
+            kind: String::from("ServiceAccount"),
+            name: self.service_account.clone(),
+            namespace: Some(self.namespace.clone()),
+            ..Subject::default()
+        }
+    }
+}
+
+fn print(manifest: &impl Serialize) -> Result<(), Error> {
+    println!("---\n{}", serde_yaml::to_string(manifest)?);
+    Ok(())
+}
+
+/// Print the roles and bindings required by the controller.
+pub fn rbac(args: RbacArgs) -> Result<(), Error> {
+    let name = String::from("zonefile");
+
+    let mut cluster_rules = args.cluster_rules();
+    if args.zonefile_namespaces.is_empty() {
+        cluster_rules.extend(args.namespaced_rules());
+    }
+
+    print(&ClusterRole {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            ..ObjectMeta::default()
+        },
+        rules: Some(cluster_rules),
+        ..ClusterRole::default()
+    })?;
+
+    print(&ClusterRoleBinding {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            ..ObjectMeta::default()
+        },
+        role_ref: RoleRef {
+            api_group: String::from("rbac.authorization.k8s.io"),
+            kind: String::from("ClusterRole"),
+            name: name.clone(),
+        },
+        subjects: Some(vec![args.subject()]),
+    })?;
+
+    for namespace in &args.zonefile_namespaces {
+        print(&Role {
+            metadata: ObjectMeta {
+                name: Some(name.clone()),
+                namespace: Some(namespace.clone()),
+                ..ObjectMeta::default()
+            },
+            rules: Some(args.namespaced_rules()),
+        })?;
+
+        print(&RoleBinding {
+            metadata: ObjectMeta {
+                name: Some(name.clone()),
+                namespace: Some(namespace.clone()),
+                ..ObjectMeta::default()
+            },
+            role_ref: RoleRef {
+                api_group: String::from("rbac.authorization.k8s.io"),
+                kind: String::from("Role"),
+                name: name.clone(),
+            },
+            subjects: Some(vec![args.subject()]),
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RbacArgs;
+
+    fn args() -> RbacArgs {
+        RbacArgs {
+            service_account: String::from("zonefile"),
+            namespace: String::from("kubizone"),
+            zonefile_namespaces: Vec::new(),
+            signing: false,
+            backups: false,
+            persist_state: false,
+            hooks: false,
+            backrefs: false,
+            acme: false,
+            sources: false,
+            impersonation: false,
+            impersonated_service_accounts: vec![String::from("default")],
+            record_comments: false,
+            scheduled_records: false,
+            outputs: false,
+            label_ownership: false,
+        }
+    }
+
+    fn resources(rules: &[k8s_openapi::api::rbac::v1::PolicyRule]) -> Vec<String> {
+        rules
+            .iter()
+            .flat_map(|rule| rule.resources.clone().unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn optional_capabilities() {
+        let minimal = args();
+        assert!(!resources(&minimal.namespaced_rules()).contains(&String::from("secrets")));
+        assert!(minimal.namespaced_rules().iter().any(|rule| rule.resources
+            == Some(vec![String::from("zonefiles/status")])
+            && rule.verbs.contains(&String::from("get"))
+            && rule.verbs.contains(&String::from("patch"))));
+        assert!(minimal
+            .cluster_rules()
+            .iter()
+            .all(|rule| !rule.verbs.contains(&String::from("patch"))));
+
+        let full = RbacArgs {
+            signing: true,
+            hooks: true,
+            backrefs: true,
+            ..args()
+        };
+        assert!(resources(&full.namespaced_rules()).contains(&String::from("secrets")));
+        assert!(resources(&full.namespaced_rules()).contains(&String::from("jobs")));
+        assert!(resources(&full.cluster_rules()).contains(&String::from("jobs")));
+        assert!(full
+            .cluster_rules()
+            .iter()
+            .any(|rule| rule.verbs.contains(&String::from("patch"))));
+
+        let label_ownership = RbacArgs {
+            label_ownership: true,
+            ..full
+        };
+        assert!(resources(&label_ownership.cluster_rules()).contains(&String::from("jobs")));
+        assert!(label_ownership
+            .namespaced_rules()
+            .iter()
+            .any(|rule| rule.resources == Some(vec![String::from("jobs")])
+                && rule.verbs.contains(&String::from("delete"))));
+
+        let outputs = RbacArgs {
+            outputs: true,
+            ..args()
+        };
+        assert!(!resources(&minimal.cluster_rules()).contains(&String::from("namespaces")));
+        assert!(resources(&outputs.cluster_rules()).contains(&String::from("namespaces")));
+        assert!(resources(&outputs.cluster_rules()).contains(&String::from("configmaps")));
+
+        let impersonation = RbacArgs {
+            impersonation: true,
+            ..args()
+        };
+        assert!(impersonation
+            .cluster_rules()
+            .iter()
+            .chain(&impersonation.namespaced_rules())
+            .filter(|rule| rule.verbs.contains(&String::from("impersonate")))
+            .all(
+                |rule| rule.resources == Some(vec![String::from("serviceaccounts")])
+                    && rule.resource_names == Some(vec![String::from("default")])
+            ));
+    }
+}
@@ -1,232 +1,2823 @@
-use futures::StreamExt;
+use clap::{Args, CommandFactory as _, FromArgMatches as _};
+use futures::{
+    channel::oneshot,
+    future::{Either, Pending},
+    Future, FutureExt as _, Stream, StreamExt, TryStreamExt as _,
+};
 use kubizone_crds::{
-    kubizone_common::FullyQualifiedDomainName,
+    kubizone_common::{FullyQualifiedDomainName, Type},
     v1alpha1::{Zone, ZoneEntry},
 };
-use zonefile_crds::{ZoneFile, TARGET_ZONEFILE_LABEL};
+use zonefile_crds::ZoneFile;
 
-use k8s_openapi::{api::core::v1::ConfigMap, serde_json::json};
+use hickory_proto::rr::dnssec::tsig::TSigner;
+use k8s_openapi::{
+    api::core::v1::ConfigMap,
+    apimachinery::pkg::apis::meta::v1::Condition,
+    chrono::Utc,
+    serde_json::{self, json, Value},
+};
 use kube::{
-    api::{Patch, PatchParams},
+    api::{ApiResource, DynamicObject, Patch, PatchParams},
     core::ObjectMeta,
-    runtime::{controller::Action, watcher, Controller},
-    Api, Client, Resource as _, ResourceExt as _,
+    runtime::{
+        controller::{self, Action},
+        events::{Event, EventType, Recorder, Reporter},
+        reflector::{self, reflector, ObjectRef, Store},
+        watcher::{self, watcher},
+        Controller, WatchStreamExt as _,
+    },
+    Api, Client, Resource, ResourceExt as _,
+};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    panic::AssertUnwindSafe,
+    pin::pin,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task::JoinSet,
+};
+use tracing::{log::*, Instrument as _};
+
+use crate::{
+    acme::{self, AcmeArgs, Challenges},
+    age::{self, Recipient, AGE_RECIPIENTS_ANNOTATION},
+    anomaly::{Anomaly, AnomalyArgs, ACKNOWLEDGE_ANOMALIES_ANNOTATION},
+    api::{self, ApiArgs, PublishedZones},
+    audit::{AuditArgs, AuditLog},
+    backref::{self, BackrefArgs},
+    backup::{self, BackupArgs},
+    blackout::{self, BlackoutWindow, BLACKOUT_WINDOWS_ANNOTATION},
+    budget::{self, BudgetArgs, RequestBudget},
+    cache::{RenderCache, RenderInputs, RenderedZone},
+    comment::{CommentArgs, RecordComments},
+    conditions::{
+        Conditions, Observations, ANOMALY, DEGRADED, LINT_VIOLATION, QUOTA_EXCEEDED,
+        REFERENCE_DENIED, STALE, WAITING_FOR_ZONE,
+    },
+    config::ConfigFile,
+    contact::{self, CONTACT_ANNOTATION},
+    defaults::{self, DefaultsArgs, ZoneFileDefaults},
+    diff::{ZoneChange, ZoneDiff},
+    digest::{HashArgs, HASHES_ANNOTATION},
+    error::Error,
+    flatten::{self, FLATTEN_CNAMES_ANNOTATION},
+    generate::{Generate, GENERATE_ANNOTATION},
+    grant::{self, GrantArgs, ALLOWED_NAMESPACES_ANNOTATION},
+    hooks::{self, HookArgs},
+    http::{HttpArgs, HttpClient},
+    impersonate::{Impersonation, ImpersonationArgs},
+    lint::{
+        self, DualStackGap, Finding, LintArgs, PtrMismatch, Severity,
+        DEFAULT_NAMESERVERS_ANNOTATION, LINT_RULES_ANNOTATION, SYNTHESIZE_GLUE_ANNOTATION,
+    },
+    metrics::{self, MetricsArgs},
+    naming::{FileNaming, FILE_NAMING_ANNOTATION},
+    negative::{self, NegativeCachingArgs, NEGATIVE_TTL_ANNOTATION},
+    normalize,
+    notify::{Notifier, NotifyArgs},
+    obsolete::{self, ObsoleteTypePolicy, OBSOLETE_TYPES_ANNOTATION},
+    output::{DeliveredOutputs, Output, OutputArgs},
+    ownership::{self, OwnershipArgs},
+    quota::QuotaArgs,
+    render::{self, build_zonefile_with_services, Comments, FormatArgs},
+    saturation::{self, ReconcileQueue},
+    schedule::{ScheduleArgs, ScheduledRecords},
+    serial::{self, ALLOW_SERIAL_ROLLBACK_ANNOTATION},
+    serve::{self, ServeArgs, ServedZones},
+    service::{self, ServiceRecord},
+    shuffle::{self, SHUFFLE_RRSETS_ANNOTATION},
+    signing::Signer,
+    snippet::{
+        self, Snippet, SnippetRef, SNIPPETS_ANNOTATION, SNIPPET_INCLUDE_DIRECTORY_ANNOTATION,
+    },
+    source::{self, SourceArgs},
+    staging::{self, StagedZones, StagingPolicy},
+    state::{self, StateArgs},
+    stats::{StatsArgs, ZoneStats},
+    status,
+    stream::{EventStream, StreamArgs},
+    template::{self, ZoneFileTemplate, ZoneFileTemplateSpec, TEMPLATE_ANNOTATION},
+    throttle::{PublishThrottle, MIN_PUBLISH_INTERVAL_ANNOTATION},
+    trace::{ReconcileId, RECONCILE_ID_ANNOTATION},
+    tsig,
+    validation::{
+        self, ValidationArgs, ValidationError, WildcardPolicy, WILDCARD_POLICY_ANNOTATION,
+    },
+    version,
+    warmup::{Warmup, WarmupArgs},
+    watch::WatchArgs,
 };
-use std::{collections::BTreeMap, sync::Arc, time::Duration};
-use tracing::log::*;
+
+#[derive(Debug, Clone, Args)]
+pub struct Config {
+    /// Field manager under which the controller applies its changes. Independent
+    /// instances running in the same cluster should each use a different name.
+    #[arg(long, env = "ZONEFILE_CONTROLLER_NAME", default_value = DEFAULT_CONTROLLER_NAME)]
+    pub controller_name: String,
+
+    /// Take over fields of the published ConfigMaps which are owned by other
+    /// field managers, rather than reporting the conflict and retrying later.
+    #[arg(long, env = "ZONEFILE_FORCE_OWNERSHIP")]
+    pub force_ownership: bool,
+
+    /// Maximum number of zonefiles reconciled at once, or 0 for no limit.
+    #[arg(long, env = "ZONEFILE_CONCURRENCY", default_value_t = 0)]
+    pub concurrency: u16,
+
+    /// Label selector limiting the zonefiles reconciled by this instance,
+    /// so that several instances can divide the zonefiles between them.
+    #[arg(long, env = "ZONEFILE_SELECTOR")]
+    pub zonefile_selector: Option<String>,
+
+    /// Leave the tasks which act on the whole cluster rather than on the selected
+    /// zonefiles to another instance: backups, starting queued post-sync jobs,
+    /// sourcing records and collecting orphaned objects. When several instances
+    /// divide the zonefiles between them, all but one should pass this.
+    #[arg(long, env = "ZONEFILE_SKIP_CLUSTER_TASKS")]
+    pub skip_cluster_tasks: bool,
+
+    /// Seconds to wait for the reconciliations in progress to finish when
+    /// stopped by a signal, before exiting regardless. Should be shorter than
+    /// the pod's termination grace period.
+    #[arg(long, env = "ZONEFILE_SHUTDOWN_TIMEOUT", default_value_t = 25)]
+    pub shutdown_timeout: u64,
+
+    #[command(flatten)]
+    pub warmup: WarmupArgs,
+
+    #[command(flatten)]
+    pub ownership: OwnershipArgs,
+
+    #[command(flatten)]
+    pub backref: BackrefArgs,
+
+    #[command(flatten)]
+    pub watch: WatchArgs,
+
+    #[command(flatten)]
+    pub hash: HashArgs,
+
+    #[command(flatten)]
+    pub http: HttpArgs,
+
+    #[command(flatten)]
+    pub notify: NotifyArgs,
+
+    #[command(flatten)]
+    pub stream: StreamArgs,
+
+    #[command(flatten)]
+    pub api: ApiArgs,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    #[command(flatten)]
+    pub audit: AuditArgs,
+
+    #[command(flatten)]
+    pub validation: ValidationArgs,
+
+    #[command(flatten)]
+    pub lint: LintArgs,
+
+    #[command(flatten)]
+    pub metrics: MetricsArgs,
+
+    #[command(flatten)]
+    pub stats: StatsArgs,
+
+    #[command(flatten)]
+    pub format: FormatArgs,
+
+    #[command(flatten)]
+    pub backup: BackupArgs,
+
+    #[command(flatten)]
+    pub acme: AcmeArgs,
+
+    #[command(flatten)]
+    pub comment: CommentArgs,
+
+    #[command(flatten)]
+    pub schedule: ScheduleArgs,
+
+    #[command(flatten)]
+    pub grant: GrantArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub impersonation: ImpersonationArgs,
+
+    #[command(flatten)]
+    pub source: SourceArgs,
+
+    #[command(flatten)]
+    pub hooks: HookArgs,
+
+    #[command(flatten)]
+    pub quota: QuotaArgs,
+
+    #[command(flatten)]
+    pub anomaly: AnomalyArgs,
+
+    #[command(flatten)]
+    pub negative: NegativeCachingArgs,
+
+    #[command(flatten)]
+    pub defaults: DefaultsArgs,
+
+    #[command(flatten)]
+    pub budget: BudgetArgs,
+
+    #[command(flatten)]
+    pub state: StateArgs,
+
+    #[cfg(feature = "debug-console")]
+    #[command(flatten)]
+    pub debug: crate::debug::DebugArgs,
+}
+
+/// Options which can be changed by reloading the configuration file,
+/// and take effect on the next reconciliation.
+#[derive(Clone)]
+struct Settings {
+    validation: ValidationArgs,
+    lint: LintArgs,
+    stats: StatsArgs,
+    format: FormatArgs,
+    hooks: HookArgs,
+    quota: QuotaArgs,
+    anomaly: AnomalyArgs,
+    negative: NegativeCachingArgs,
+    grant: GrantArgs,
+    output: OutputArgs,
+    defaults: DefaultsArgs,
+    budget: BudgetArgs,
+}
+
+impl From<&Config> for Settings {
+    fn from(config: &Config) -> Self {
+        Settings {
+            validation: config.validation.clone(),
+            lint: config.lint.clone(),
+            stats: config.stats.clone(),
+            format: config.format.clone(),
+            hooks: config.hooks.clone(),
+            quota: config.quota.clone(),
+            anomaly: config.anomaly.clone(),
+            negative: config.negative.clone(),
+            grant: config.grant.clone(),
+            output: config.output.clone(),
+            defaults: config.defaults.clone(),
+            budget: config.budget.clone(),
+        }
+    }
+}
 
 struct Data {
     client: Client,
+    controller_name: String,
+    force_ownership: bool,
+    ownership: OwnershipArgs,
+    backref: BackrefArgs,
+    watch: WatchArgs,
+    hash: HashArgs,
+    http: Arc<HttpClient>,
+    notifier: Option<Notifier>,
+    stream: Option<EventStream>,
+    audit_log: Option<AuditLog>,
+    settings: RwLock<Settings>,
+    conditions: Conditions,
+    throttle: PublishThrottle,
+    staged: StagedZones,
+    outputs: DeliveredOutputs,
+    persist_state: bool,
+    challenges: Option<Challenges>,
+    comments: Option<RecordComments>,
+    scheduled: Option<ScheduledRecords>,
+    /// Defaults of each namespace, kept up to date by their watch.
+    defaults: Store<ZoneFileDefaults>,
+    impersonation: Option<Impersonation>,
+    published: Arc<PublishedZones>,
+    served: Option<Arc<ServedZones>>,
+    queue: Arc<ReconcileQueue>,
+    render_cache: RenderCache,
+    warmup: Warmup,
+}
+
+impl Data {
+    /// Parameters for applying the zonefile's ConfigMap.
+    fn apply_params(&self) -> PatchParams {
+        let params = PatchParams::apply(&self.controller_name);
+
+        if self.force_ownership {
+            params.force()
+        } else {
+            params
+        }
+    }
+}
+
+pub const DEFAULT_CONTROLLER_NAME: &str = "kubi.zone/zonefile";
+
+/// Number of zones of a zonefile fetched at the same time.
+const ZONE_FETCH_CONCURRENCY: usize = 8;
+
+/// Label on resources created on behalf of a [`ZoneFile`], such as
+/// backups and hook jobs, naming the zonefile they belong to.
+pub const ZONEFILE_LABEL: &str = "zonefile.kubi.zone/zonefile";
+
+/// Annotation on a [`ZoneFile`] which, while set to `true`, stops the controller
+/// from reconciling it, such as while a backup of its zones is being restored.
+pub const PAUSED_ANNOTATION: &str = "zonefile.kubi.zone/paused";
+
+/// Annotation on the published ConfigMap, holding a JSON object which maps the
+/// `namespace/name` of each zone to the origin it was published under, so that
+/// the zone's last published contents can still be found if it loses its status
+/// upstream, or cannot be found itself.
+pub const ORIGINS_ANNOTATION: &str = "zonefile.kubi.zone/origins";
+
+/// Key of the zone in the [`ORIGINS_ANNOTATION`].
+fn origin_key(namespace: &str, name: &str) -> String {
+    format!("{namespace}/{name}")
+}
+
+fn stats_json(origin: &str, stats: &ZoneStats) -> Value {
+    json!({
+        "origin": origin,
+        "records": stats.records,
+        "types": BTreeMap::from_iter(stats.types.iter().map(|(type_, count)| (type_.to_string(), count))),
+        "largestRRset": stats.largest_rrset.as_ref().map(|rrset| json!({
+            "name": rrset.name.to_string(),
+            "type": rrset.type_.to_string(),
+            "records": rrset.records,
+        })),
+        "duplicates": stats.duplicates,
+        "size": stats.size,
+        "signed": stats.signed,
+    })
+}
+
+/// Read the [`WildcardPolicy`] of the zonefile, falling back to
+/// the default if the annotation holds an unknown policy.
+pub fn wildcard_policy(zonefile: &ZoneFile, observations: &mut Observations) -> WildcardPolicy {
+    match zonefile.annotations().get(WILDCARD_POLICY_ANNOTATION) {
+        Some(policy) => policy.parse().unwrap_or_else(|e: String| {
+            observations.observe(DEGRADED, "InvalidWildcardPolicy", e);
+            WildcardPolicy::default()
+        }),
+        None => WildcardPolicy::default(),
+    }
+}
+
+/// Read the [`FileNaming`] of the zonefile, falling back to
+/// the default if the annotation holds an unknown preset.
+fn file_naming(zonefile: &ZoneFile, observations: &mut Observations) -> FileNaming {
+    match zonefile.annotations().get(FILE_NAMING_ANNOTATION) {
+        Some(naming) => naming.parse().unwrap_or_else(|e: String| {
+            observations.observe(DEGRADED, "InvalidFileNaming", e);
+            FileNaming::default()
+        }),
+        None => FileNaming::default(),
+    }
+}
+
+/// Read the blackout windows of the zonefile, ignoring all
+/// of them if any is invalid, since the freeze would be partial.
+fn blackout_windows(zonefile: &ZoneFile, observations: &mut Observations) -> Vec<BlackoutWindow> {
+    match zonefile.annotations().get(BLACKOUT_WINDOWS_ANNOTATION) {
+        Some(windows) => blackout::parse(windows).unwrap_or_else(|e| {
+            observations.observe(DEGRADED, "InvalidBlackoutWindows", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    }
+}
+
+/// Read the severities of the lint rules for the zonefile, falling back to
+/// those of the controller if the annotation holds an invalid rule.
+fn lint_severities(
+    zonefile: &ZoneFile,
+    lint: &LintArgs,
+    observations: &mut Observations,
+) -> BTreeMap<lint::Rule, Severity> {
+    match zonefile.annotations().get(LINT_RULES_ANNOTATION) {
+        Some(rules) => lint::parse_rules(rules)
+            .map(|rules| lint.severities(&rules))
+            .unwrap_or_else(|e| {
+                observations.observe(DEGRADED, "InvalidLintRules", e);
+                lint.severities(&[])
+            }),
+        None => lint.severities(&[]),
+    }
+}
+
+/// Report the lint findings of a zone in the zonefile's conditions and metrics.
+/// Findings with a severity of info are only counted in the metrics.
+fn report_findings(
+    zonefile_name: &str,
+    origin: &str,
+    findings: &[Finding],
+    observations: &mut Observations,
+) {
+    let mut counts = BTreeMap::<(lint::Rule, Severity), usize>::new();
+
+    for finding in findings {
+        *counts.entry((finding.rule, finding.severity)).or_default() += 1;
+
+        if finding.severity >= Severity::Warning {
+            observations.observe(
+                LINT_VIOLATION,
+                finding.rule.reason(),
+                format!("zone {origin}: {finding}"),
+            );
+        }
+    }
+
+    for ((rule, severity), count) in counts {
+        metrics::LINT_FINDINGS.set(
+            &[
+                ("zonefile", zonefile_name),
+                ("origin", origin),
+                ("rule", rule.reason()),
+                ("severity", &severity.to_string()),
+            ],
+            count as f64,
+        );
+    }
+}
+
+/// Report the anomalies found in a zone in the zonefile's conditions and metrics.
+fn report_anomalies(
+    zonefile_name: &str,
+    origin: &str,
+    anomalies: &[Anomaly],
+    observations: &mut Observations,
+) {
+    for anomaly in anomalies {
+        observations.observe(
+            ANOMALY,
+            anomaly.reason(),
+            format!("zone {origin}: {anomaly}"),
+        );
+        metrics::ANOMALIES.set(
+            &[
+                ("zonefile", zonefile_name),
+                ("origin", origin),
+                ("check", anomaly.reason()),
+            ],
+            1.0,
+        );
+    }
+}
+
+/// Read the default nameservers of the zonefile, falling back to those of its namespace,
+/// and skipping any which are not fully qualified.
+pub fn default_nameservers(
+    zonefile: &ZoneFile,
+    namespace_defaults: &[String],
+    observations: &mut Observations,
+) -> Vec<FullyQualifiedDomainName> {
+    let nameservers: Vec<&str> = match zonefile.annotations().get(DEFAULT_NAMESERVERS_ANNOTATION) {
+        Some(nameservers) => nameservers.split(',').collect(),
+        None => namespace_defaults.iter().map(String::as_str).collect(),
+    };
+
+    nameservers
+        .into_iter()
+        .map(str::trim)
+        .filter(|nameserver| !nameserver.is_empty())
+        .filter_map(
+            |nameserver| match FullyQualifiedDomainName::try_from(nameserver) {
+                Ok(nameserver) => Some(nameserver),
+                Err(e) => {
+                    observations.observe(
+                        DEGRADED,
+                        "InvalidDefaultNameservers",
+                        format!("default nameserver {nameserver:?} is invalid: {e}"),
+                    );
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// Read the [`ObsoleteTypePolicy`] of the zonefile, falling back to
+/// the default if the annotation holds an unknown policy.
+fn obsolete_type_policy(
+    zonefile: &ZoneFile,
+    observations: &mut Observations,
+) -> ObsoleteTypePolicy {
+    match zonefile.annotations().get(OBSOLETE_TYPES_ANNOTATION) {
+        Some(policy) => policy.parse().unwrap_or_else(|e: String| {
+            observations.observe(DEGRADED, "InvalidObsoleteTypePolicy", e);
+            ObsoleteTypePolicy::default()
+        }),
+        None => ObsoleteTypePolicy::default(),
+    }
+}
+
+/// Fetch the snippets referenced by the zonefile, skipping any which cannot be found
+/// or which hold records of obsolete types the zonefile rejects.
+async fn snippets(
+    config_maps: &Api<ConfigMap>,
+    zonefile: &ZoneFile,
+    observations: &mut Observations,
+) -> Result<Vec<Snippet>, Error> {
+    let Some(references) = zonefile.annotations().get(SNIPPETS_ANNOTATION) else {
+        return Ok(Vec::new());
+    };
+
+    let references = match SnippetRef::parse_list(references) {
+        Ok(references) => references,
+        Err(reference) => {
+            observations.observe(
+                DEGRADED,
+                "InvalidSnippets",
+                format!("snippet reference {reference:?} is invalid, expected <configmap>/<key>"),
+            );
+            return Ok(Vec::new());
+        }
+    };
+
+    let policy = obsolete_type_policy(zonefile, observations);
+    let mut snippets = Vec::with_capacity(references.len());
+    for reference in references {
+        let contents = config_maps
+            .get_opt(&reference.config_map)
+            .await?
+            .and_then(|config_map| config_map.data)
+            .and_then(|mut data| data.remove(&reference.key));
+
+        match contents.map(|contents| obsolete::apply(policy, &contents)) {
+            Some(Ok((contents, records))) => {
+                for record in records {
+                    match record.converted_to {
+                        Some(_) => info!("snippet {reference} {record}"),
+                        None => warn!("snippet {reference} {record}"),
+                    }
+                }
+
+                snippets.push(Snippet {
+                    reference,
+                    contents,
+                })
+            }
+            Some(Err(record)) => observations.observe(
+                DEGRADED,
+                "ObsoleteRecordType",
+                format!("snippet {reference} was left out: {record}"),
+            ),
+            None => observations.observe(
+                DEGRADED,
+                "MissingSnippet",
+                format!("snippet {reference} does not exist"),
+            ),
+        }
+    }
+
+    Ok(snippets)
+}
+
+/// Validate and normalize the entries of a zone, ready for rendering.
+///
+/// Returns the remaining entries, along with the number of duplicates omitted.
+/// Invalid records are left out, but a record violating a policy fails the
+/// whole zone, so that its previously published contents can be kept.
+pub fn prepare_entries(
+    zone: &str,
+    origin: &FullyQualifiedDomainName,
+    entries: &[ZoneEntry],
+    validation: &ValidationArgs,
+    wildcard_policy: WildcardPolicy,
+    observations: &mut Observations,
+) -> Result<(Vec<ZoneEntry>, usize), ValidationError> {
+    let (entries, invalid_entries) = validation::validate_entries(entries, validation);
+
+    if let Some((entry, error)) = invalid_entries
+        .iter()
+        .find(|(_, error)| error.is_policy_violation())
+    {
+        warn!(
+            "zone {zone} contains {} record for {} violating policy, keeping its previous contents: {error}",
+            entry.type_, entry.fqdn
+        );
+
+        observations.observe(
+            DEGRADED,
+            "PolicyViolation",
+            format!(
+                "zone {origin} was not published, its {} record for {} violates policy: {error}",
+                entry.type_, entry.fqdn
+            ),
+        );
+
+        return Err(error.clone());
+    }
+
+    for (entry, error) in invalid_entries {
+        warn!(
+            "zone {zone} contains invalid {} record for {}, excluding it: {error}",
+            entry.type_, entry.fqdn
+        );
+
+        observations.observe(
+            DEGRADED,
+            "InvalidRecord",
+            format!(
+                "{} record for {} was excluded: {error}",
+                entry.type_, entry.fqdn
+            ),
+        );
+    }
+
+    let entries = normalize::canonicalize(origin, entries);
+
+    for entry in &entries {
+        for name in std::iter::once(entry.fqdn.to_string()).chain(normalize::hostnames(entry)) {
+            for label in validation::mixed_script_labels(&name) {
+                warn!(
+                    "zone {zone} contains {} record for {} naming {name}, whose label {label:?} mixes scripts",
+                    entry.type_, entry.fqdn
+                );
+
+                observations.observe(
+                    DEGRADED,
+                    "MixedScriptName",
+                    format!(
+                        "{} record for {} names {name}, whose label {label:?} mixes scripts",
+                        entry.type_, entry.fqdn
+                    ),
+                );
+            }
+        }
+    }
+
+    let entries = match wildcard_policy {
+        WildcardPolicy::Allow => entries,
+        WildcardPolicy::Warn => {
+            for entry in entries
+                .iter()
+                .filter(|entry| validation::is_wildcard(entry))
+            {
+                warn!(
+                    "zone {zone} contains wildcard {} record for {}",
+                    entry.type_, entry.fqdn
+                );
+            }
+            entries
+        }
+        WildcardPolicy::Deny => {
+            let (wildcards, entries): (Vec<_>, Vec<_>) =
+                entries.into_iter().partition(validation::is_wildcard);
+
+            for entry in wildcards {
+                observations.observe(
+                    DEGRADED,
+                    "WildcardDenied",
+                    format!(
+                        "wildcard {} record for {} was excluded",
+                        entry.type_, entry.fqdn
+                    ),
+                );
+            }
+            entries
+        }
+    };
+
+    let (mut entries, duplicates) = normalize::dedup(entries);
+    if duplicates > 0 {
+        info!("zone {zone} contains {duplicates} duplicate record(s), omitting them");
+    }
+
+    normalize::sort_by_priority(&mut entries);
+
+    Ok((entries, duplicates))
 }
 
-pub const CONTROLLER_NAME: &str = "kubi.zone/zonefile";
+/// Everything besides its records which shapes the rendering of a zonefile's
+/// zones, so that the subcommands render zones as the controller publishes them.
+pub struct RenderOptions<'a> {
+    /// Annotations of the zonefile, some of which apply to all of its zones.
+    pub annotations: &'a BTreeMap<String, String>,
+    pub validation: &'a ValidationArgs,
+    pub wildcard_policy: WildcardPolicy,
+    pub format: &'a FormatArgs,
+    pub template: &'a ZoneFileTemplateSpec,
+    pub negative: &'a NegativeCachingArgs,
+    pub default_nameservers: &'a [FullyQualifiedDomainName],
+    /// Address records from which missing glue is copied.
+    pub glue_sources: &'a [ZoneEntry],
+    pub flatten_cnames: bool,
+    pub shuffle_rrsets: bool,
+    pub comments: &'a Comments,
+}
+
+/// Render the records of a zone, followed by the records owned by names holding
+/// service labels, such as those of challenges, before snippets are added.
+///
+/// Returns the records as rendered, the number of duplicates omitted and the
+/// rendered zone, or the error of a record violating a policy, in which case
+/// the zone keeps its previously published contents.
+pub fn render_zone(
+    zone: &Zone,
+    origin: &FullyQualifiedDomainName,
+    mut entries: Vec<ZoneEntry>,
+    services: Vec<ServiceRecord>,
+    options: &RenderOptions,
+    observations: &mut Observations,
+) -> Result<(Vec<ZoneEntry>, usize, String), ValidationError> {
+    let mut ranges = match zone.annotations().get(GENERATE_ANNOTATION) {
+        Some(ranges) => Generate::parse_all(ranges).unwrap_or_else(|e| {
+            observations.observe(
+                DEGRADED,
+                "InvalidGenerate",
+                format!("zone {origin} has an invalid range of records: {e}"),
+            );
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+
+    // Ranges are expanded even when rendered as directives, so that
+    // ranges producing invalid owner names are left out.
+    ranges.retain(|range| match range.expand(origin, zone.spec.ttl) {
+        Ok(expanded) => {
+            if options.format.expand_generate {
+                entries.extend(expanded);
+            }
+            true
+        }
+        Err(e) => {
+            observations.observe(
+                DEGRADED,
+                "InvalidGenerate",
+                format!("zone {origin} range {range} is invalid: {e}"),
+            );
+            false
+        }
+    });
+
+    let (mut entries, duplicates) = prepare_entries(
+        &zone.to_string(),
+        origin,
+        &entries,
+        options.validation,
+        options.wildcard_policy,
+        observations,
+    )?;
+
+    let (services, invalid_services) = service::validate(services, options.validation);
+    for (record, error) in invalid_services {
+        warn!(
+            "zone {zone} contains invalid {} record for {}, excluding it: {error}",
+            record.type_, record.owner
+        );
+
+        observations.observe(
+            DEGRADED,
+            "InvalidRecord",
+            format!(
+                "{} record for {} was excluded: {error}",
+                record.type_, record.owner
+            ),
+        );
+    }
+    let services = service::canonicalize(origin, services);
+
+    if !lint::ensure_apex_nameservers(
+        origin,
+        &mut entries,
+        options.default_nameservers,
+        zone.spec.ttl,
+    ) {
+        observations.observe(
+            DEGRADED,
+            "MissingApexNameservers",
+            format!("zone {origin} has no NS records at its apex"),
+        );
+    }
+
+    for nameserver in lint::ensure_glue(origin, &mut entries, options.glue_sources) {
+        observations.observe(
+            DEGRADED,
+            "MissingGlue",
+            format!("zone {origin} has no address records for its nameserver {nameserver}"),
+        );
+    }
+
+    let template = options.template;
+    template.soa.apply(origin, &mut entries);
+    // Comments are attached to the records as they were canonicalized, and the
+    // original TTLs of records overridden by the template are kept in them.
+    let mut comments = options.comments.canonicalize(origin);
+    for (entry, original) in template.ttl.apply(&mut entries) {
+        comments.append(&entry, &format!("ttl {original} overridden by template"));
+    }
+
+    let negative_ttl = match zone
+        .annotations()
+        .get(NEGATIVE_TTL_ANNOTATION)
+        .or(options.annotations.get(NEGATIVE_TTL_ANNOTATION))
+    {
+        Some(value) => negative::parse(value).or_else(|| {
+            observations.observe(
+                DEGRADED,
+                "InvalidNegativeTtl",
+                format!("zone {origin} has invalid negative TTL {value:?}, expected a number of seconds"),
+            );
+            template.soa.minimum
+        }),
+        None => template.soa.minimum,
+    };
+
+    if let Some(ttl) = options.negative.apply(origin, &mut entries, negative_ttl) {
+        observations.observe(
+            DEGRADED,
+            "NegativeTtlExceeded",
+            format!(
+                "zone {origin} negative TTL {ttl} exceeds the maximum of {}",
+                options.negative.max_negative_ttl.unwrap_or_default()
+            ),
+        );
+    }
+
+    if let Some(email) = zone
+        .annotations()
+        .get(CONTACT_ANNOTATION)
+        .or(options.annotations.get(CONTACT_ANNOTATION))
+        .or(template.soa.contact.as_ref())
+    {
+        match contact::rname(email) {
+            Some(rname) => {
+                if !contact::set_rname(origin, &mut entries, &rname) {
+                    warn!("zone {zone} has no SOA record to set the contact {email:?} in");
+                }
+            }
+            None => observations.observe(
+                DEGRADED,
+                "InvalidContact",
+                format!("zone {origin} has invalid contact {email:?}, expected an email address"),
+            ),
+        }
+    }
+
+    if options.flatten_cnames {
+        for name in flatten::flatten_cnames(&mut entries) {
+            observations.observe(
+                DEGRADED,
+                "CnameLoop",
+                format!("zone {origin} has a loop of CNAME records through {name}"),
+            );
+        }
+    }
+
+    if options.shuffle_rrsets {
+        shuffle::shuffle_rrsets(&mut entries);
+    }
+
+    let mut rendered =
+        build_zonefile_with_services(origin, &entries, &services, options.format, &comments);
+    if !options.format.expand_generate && !ranges.is_empty() {
+        rendered.push('\n');
+        for range in &ranges {
+            rendered.push_str(&format!("\n{}", range.directive(zone.spec.ttl)));
+        }
+    }
+
+    Ok((entries, duplicates, rendered))
+}
+
+/// Entries of the ConfigMap data belonging to the zone: its contents,
+/// along with any signature or statistics published alongside it.
+fn published_keys(data: &BTreeMap<String, String>, origin: &str) -> BTreeMap<String, String> {
+    data.iter()
+        .filter(|(key, _)| {
+            key.strip_prefix(origin)
+                .is_some_and(|suffix| suffix.is_empty() || suffix.starts_with('.'))
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// Keep serving the last published contents of a zone which has lost its
+/// status upstream, such as while its controller is restarting, rather than
+/// dropping it from the zonefile. Returns whether there were any contents.
+fn serve_stale(
+    zone: &Zone,
+    origin: &str,
+    previous_data: &BTreeMap<String, String>,
+    retained: &mut BTreeMap<String, String>,
+    observations: &mut Observations,
+) -> bool {
+    let published = published_keys(previous_data, origin);
+    if published.is_empty() {
+        return false;
+    }
+
+    warn!("zone {zone} has no status, serving its last published contents");
+    observations.observe(
+        STALE,
+        "ZoneStatusMissing",
+        format!("zone {origin} has no status, serving its last published contents"),
+    );
+
+    retained.extend(published);
+    true
+}
+
+/// Find the field manager which most recently changed the zone's
+/// spec or metadata, ignoring status updates made by its controller.
+fn last_manager(zone: &Zone) -> Option<String> {
+    zone.managed_fields()
+        .iter()
+        .filter(|entry| entry.subresource.is_none())
+        .max_by_key(|entry| entry.time.as_ref().map(|time| time.0))?
+        .manager
+        .clone()
+}
+
+/// Zone rendered anew for a zonefile, to be published in its ConfigMap.
+struct SerializedZone {
+    zone: String,
+    trigger: Option<String>,
+    origin: String,
+    serial: u32,
+    /// Hash of the zone, as computed by its controller.
+    hash: String,
+    /// Hash of the published contents, with the configured algorithm.
+    content_hash: String,
+    contents: String,
+    dual_stack_gaps: Vec<DualStackGap>,
+    stats: ZoneStats,
+}
+
+/// Everything a reconciliation arrived at for a zonefile, computed before
+/// any of it is written by [`publish_zonefile`].
+#[derive(Default)]
+struct RenderedZonefile {
+    /// The zonefile's ConfigMap, as last published.
+    previous: Option<ConfigMap>,
+    /// Data of the previous ConfigMap, keyed by origin.
+    previous_data: BTreeMap<String, String>,
+    /// Origin of each zone, including those served from their last published contents.
+    origins: BTreeMap<String, String>,
+    /// Zones referenced by the zonefile, which are marked with a backreference to it.
+    zones: Vec<Zone>,
+    serialized_zones: Vec<SerializedZone>,
+    /// Data of the ConfigMap, holding the serialized zones along with those
+    /// keeping their previously published contents.
+    data: BTreeMap<String, String>,
+    naming: FileNaming,
+    outputs: Vec<Output>,
+    /// Recipients the outputs are encrypted to, or [`None`] if
+    /// they are invalid, in which case nothing is delivered.
+    recipients: Option<Vec<Recipient>>,
+    tsig: Option<TSigner>,
+    /// Lint violations of zones whose contents changed, announced as Events.
+    lint_violations: Vec<String>,
+    observations: Observations,
+    /// Zones whose publication is deferred by a blackout window.
+    frozen: Vec<Value>,
+    lint_dual_stack: bool,
+    ptr_mismatches: Option<BTreeMap<FullyQualifiedDomainName, Vec<PtrMismatch>>>,
+    requeue: Duration,
+}
+
+impl RenderedZonefile {
+    /// Status of the zonefile once the zones are published, with all its fields
+    /// in a single merge patch, so that it can be skipped if the zonefile
+    /// already has it.
+    fn status(&self, conditions: Vec<Condition>, outputs: Option<Vec<Value>>) -> Value {
+        let serialized_zones = &self.serialized_zones;
+
+        json!({
+            "status": {
+                "hash": BTreeMap::from_iter(serialized_zones.iter().map(|serialized_zone| (&serialized_zone.origin, &serialized_zone.hash))),
+                "contentHash": BTreeMap::from_iter(serialized_zones.iter().map(|serialized_zone| (&serialized_zone.origin, &serialized_zone.content_hash))),
+                "serial": BTreeMap::from_iter(serialized_zones.iter().map(|serialized_zone| (&serialized_zone.origin, serialized_zone.serial))),
+                "zones": serialized_zones.iter().map(|serialized_zone| stats_json(&serialized_zone.origin, &serialized_zone.stats)).collect::<Vec<_>>(),
+                "conditions": conditions,
+                "outputs": outputs,
+                "frozen": (!self.frozen.is_empty()).then_some(&self.frozen),
+                "dualStackGaps": self.lint_dual_stack.then(|| BTreeMap::from_iter(serialized_zones.iter().map(|serialized_zone| (
+                    &serialized_zone.origin,
+                    serialized_zone.dual_stack_gaps.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                )))),
+                "ptrMismatches": self.ptr_mismatches.as_ref().map(|ptr_mismatches| BTreeMap::from_iter(ptr_mismatches.iter().map(|(origin, mismatches)| (
+                    origin.to_string(),
+                    mismatches.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                )))),
+            },
+        })
+    }
+}
+
+fn zonefile_name(zonefile: &ZoneFile) -> String {
+    format!(
+        "{}/{}",
+        zonefile.namespace().as_ref().unwrap(),
+        zonefile.name_any()
+    )
+}
+
+/// Render the zonefile's zones and arrive at its status, then publish them.
+async fn reconcile_zonefiles(
+    zonefile: Arc<ZoneFile>,
+    ctx: Arc<Data>,
+    id: ReconcileId,
+) -> Result<Action, Error> {
+    let settings = ctx.settings.read().unwrap().clone();
+
+    let rendered = render_zonefile(&zonefile, &ctx, &settings).await?;
+    publish_zonefile(&zonefile, &ctx, &settings, id, rendered).await
+}
+
+/// Compute everything the zonefile should publish, along with the status it
+/// arrives at, reading what it needs but writing nothing to the cluster.
+async fn render_zonefile(
+    zonefile: &ZoneFile,
+    ctx: &Data,
+    settings: &Settings,
+) -> Result<RenderedZonefile, Error> {
+    let zonefile_name = zonefile_name(zonefile);
+
+    // Publish a detached signature alongside each zone, if the
+    // zonefile references a signing key.
+    let signer = Signer::for_zonefile(ctx.client.clone(), zonefile).await?;
+
+    // NOTIFY messages sent to secondaries are signed, if the zonefile references a TSIG key.
+    let tsig = tsig::for_zonefile(ctx.client.clone(), zonefile).await?;
+
+    let configmap_name = ownership::config_map_name(zonefile);
+
+    let config_maps =
+        Api::<ConfigMap>::namespaced(ctx.client.clone(), zonefile.namespace().as_ref().unwrap());
+
+    let previous = config_maps.get_opt(&configmap_name).await?;
+
+    let previous_origins: BTreeMap<String, String> = previous
+        .as_ref()
+        .and_then(|config_map| config_map.annotations().get(ORIGINS_ANNOTATION))
+        .and_then(|origins| serde_json::from_str(origins).ok())
+        .unwrap_or_default();
+
+    // Zones are keyed by origin internally, whichever naming they were published under.
+    let previous_data = previous
+        .as_ref()
+        .and_then(|config_map| Some(FileNaming::of(config_map).unpublish(config_map.data.clone()?)))
+        .unwrap_or_default();
+
+    // Origin of each zone, including those served from their last published contents.
+    let mut origins = BTreeMap::new();
+
+    let mut serialized_zones = Vec::new();
+
+    // Entries of the rendered zones, compared across zones for PTR consistency.
+    let mut ptr_zones = Vec::new();
+    let mut lint_violations = Vec::new();
+    let mut observations = Observations::default();
+
+    // Zones which are not rendered anew, such as those exceeding their
+    // quota or lacking a status, keep their previously published contents.
+    let mut retained = BTreeMap::new();
+
+    let wildcard_policy = wildcard_policy(zonefile, &mut observations);
+    let naming = file_naming(zonefile, &mut observations);
+    // Defaults shared by the zonefiles of the namespace, which yield to their own
+    // annotations and templates, but take precedence over the controller's. They
+    // are waited for, so that no zonefile is published without them at startup,
+    // unless they cannot be listed at all.
+    let namespace_defaults = defaults::ready_namespace_defaults(
+        &ctx.defaults,
+        zonefile.namespace().as_ref().unwrap(),
+        defaults::READY_TIMEOUT,
+    )
+    .await;
+
+    let lint_severities = lint_severities(zonefile, &settings.lint, &mut observations);
+    let default_nameservers =
+        default_nameservers(zonefile, &namespace_defaults.nameservers, &mut observations);
+
+    // Origins whose serial may go backwards, as secondaries will otherwise ignore them.
+    let allowed_rollbacks = zonefile
+        .annotations()
+        .get(ALLOW_SERIAL_ROLLBACK_ANNOTATION)
+        .map(|origins| {
+            origins
+                .split(',')
+                .filter_map(|origin| FullyQualifiedDomainName::try_from(origin.trim()).ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    // Origins whose anomalies were acknowledged, and are published despite `--hold-anomalies`.
+    let acknowledged_anomalies = zonefile
+        .annotations()
+        .get(ACKNOWLEDGE_ANOMALIES_ANNOTATION)
+        .map(|origins| {
+            origins
+                .split(',')
+                .filter_map(|origin| FullyQualifiedDomainName::try_from(origin.trim()).ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let min_publish_interval = zonefile
+        .annotations()
+        .get(MIN_PUBLISH_INTERVAL_ANNOTATION)
+        .and_then(|interval| match interval.parse() {
+            Ok(seconds) => Some(Duration::from_secs(seconds)),
+            Err(_) => {
+                observations.observe(
+                    DEGRADED,
+                    "InvalidMinPublishInterval",
+                    format!("invalid minimum publish interval {interval:?}"),
+                );
+                None
+            }
+        });
+
+    let staging_policy = StagingPolicy::for_zonefile(zonefile, &ctx.http, &mut observations);
+
+    let now = Utc::now();
+    let blackout_until =
+        blackout::active_until(&blackout_windows(zonefile, &mut observations), now);
+    let mut frozen = Vec::new();
+
+    let mut template = match zonefile.annotations().get(TEMPLATE_ANNOTATION) {
+        Some(name) => match Api::<ZoneFileTemplate>::namespaced(
+            ctx.client.clone(),
+            zonefile.namespace().as_ref().unwrap(),
+        )
+        .get_opt(name)
+        .await?
+        {
+            Some(template) => template.spec,
+            None => {
+                observations.observe(
+                    DEGRADED,
+                    "MissingTemplate",
+                    format!("zonefile template {name} does not exist"),
+                );
+                ZoneFileTemplateSpec::default()
+            }
+        },
+        None => ZoneFileTemplateSpec::default(),
+    };
+
+    template.soa = settings
+        .defaults
+        .soa(&template.soa.or(&namespace_defaults.soa));
+    template.ttl = template.ttl.or(&namespace_defaults.ttl);
+    let format = template.format.apply(&settings.format);
+
+    let outputs = Output::configured(zonefile, settings.defaults.default_outputs.as_ref())
+        .unwrap_or_else(|output| {
+            observations.observe(
+                DEGRADED,
+                "InvalidOutputs",
+                format!("output {output:?} is invalid, expected configmap:[<namespace>/]<name>, an https:// url, notify:<host>[:<port>] or oci://<registry>/<repository>"),
+            );
+            Vec::new()
+        });
+
+    // Outputs which the zonefile may not deliver to are left out, rather than
+    // failing the reconciliation and with it the zonefile's own ConfigMap.
+    let mut permitted_outputs = Vec::with_capacity(outputs.len());
+    for output in outputs {
+        match output
+            .permitted(
+                ctx.client.clone(),
+                &ctx.http,
+                &settings.output,
+                zonefile.namespace().as_deref().unwrap_or_default(),
+                tsig.is_some(),
+            )
+            .await
+        {
+            Ok(()) => permitted_outputs.push(output),
+            Err(e) => observations.observe(
+                DEGRADED,
+                "DisallowedOutput",
+                format!("output {output} is not delivered to: {e}"),
+            ),
+        }
+    }
+    let outputs = permitted_outputs;
+
+    // Outputs are not delivered at all if the recipients they should
+    // be encrypted to are invalid, rather than delivered in plain text.
+    let recipients = match zonefile.annotations().get(AGE_RECIPIENTS_ANNOTATION) {
+        Some(recipients) => match Recipient::parse_list(recipients) {
+            Ok(recipients) => Some(recipients),
+            Err(recipient) => {
+                observations.observe(
+                    DEGRADED,
+                    "InvalidAgeRecipients",
+                    format!(
+                        "age recipient {recipient:?} is invalid, outputs will not be delivered"
+                    ),
+                );
+                None
+            }
+        },
+        None => Some(Vec::new()),
+    };
+
+    let snippets = snippets(&config_maps, zonefile, &mut observations).await?;
+    let snippet_include_directory = zonefile
+        .annotations()
+        .get(SNIPPET_INCLUDE_DIRECTORY_ANNOTATION)
+        .map(String::as_str);
+
+    let mut requeue = ctx.watch.resync_interval();
+
+    let zone_refs = match settings.quota.max_zones_per_zonefile {
+        Some(limit) if zonefile.spec.zone_refs.len() > limit => {
+            observations.observe(
+                QUOTA_EXCEEDED,
+                "TooManyZones",
+                format!(
+                    "zonefile references {} zones, only the first {limit} are published",
+                    zonefile.spec.zone_refs.len()
+                ),
+            );
+
+            &zonefile.spec.zone_refs[..limit]
+        }
+        _ => &zonefile.spec.zone_refs[..],
+    };
+
+    let zonefile_namespace = zonefile.namespace().unwrap();
+    let zone_reader = match &ctx.impersonation {
+        Some(impersonation) => impersonation.client(zonefile)?,
+        None => ctx.client.clone(),
+    };
+
+    // Zones are fetched concurrently, but processed in the order they are referenced.
+    let fetched = futures::stream::iter(zone_refs.iter().cloned())
+        .map(|zone_ref| {
+            let zone_namespace = zone_ref
+                .namespace
+                .clone()
+                .unwrap_or(zonefile_namespace.clone());
+            let zones = Api::<Zone>::namespaced(zone_reader.clone(), &zone_namespace);
+
+            async move {
+                let zone = zones.get(&zone_ref.name).await;
+                (zone_ref, zone_namespace, zone)
+            }
+        })
+        .buffered(ZONE_FETCH_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut zones = Vec::with_capacity(zone_refs.len());
+    for (zone_ref, zone_namespace, zone) in fetched {
+        let zone = match zone {
+            Ok(zone) => zone,
+            Err(kube::Error::Api(response)) if response.code == 403 => {
+                let message = format!(
+                    "zone {zone_namespace}/{} may not be read by the zonefile's service account: {}",
+                    zone_ref.name, response.message
+                );
+
+                warn!("zonefile {zonefile_name} may not read a zone, skipping it: {message}");
+                observations.observe(REFERENCE_DENIED, "ZoneReadForbidden", message);
+                continue;
+            }
+            // The zone watch reconciles the zonefile as soon as the zone is created.
+            // Until then, a zone which was published before keeps its last published
+            // contents, rather than being dropped from the zonefile by a brief 404.
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                info!(
+                    "zonefile {zonefile_name} references zone {zone_namespace}/{}, which does not exist yet",
+                    zone_ref.name
+                );
+
+                let key = origin_key(&zone_namespace, &zone_ref.name);
+                let published = previous_origins
+                    .get(&key)
+                    .map(|origin| published_keys(&previous_data, origin))
+                    .unwrap_or_default();
+
+                if published.is_empty() {
+                    observations.observe(
+                        WAITING_FOR_ZONE,
+                        "ZoneNotFound",
+                        format!("zone {zone_namespace}/{} does not exist yet", zone_ref.name),
+                    );
+                } else {
+                    observations.observe(
+                        WAITING_FOR_ZONE,
+                        "ZoneNotFound",
+                        format!(
+                            "zone {zone_namespace}/{} does not exist, serving its last published contents",
+                            zone_ref.name
+                        ),
+                    );
+                    origins.insert(key.clone(), previous_origins[&key].clone());
+                    retained.extend(published);
+                }
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if zone_namespace != zonefile_namespace
+            && !grant::is_granted(
+                zone.annotations()
+                    .get(ALLOWED_NAMESPACES_ANNOTATION)
+                    .map(String::as_str),
+                &zonefile_namespace,
+            )
+        {
+            let message = format!(
+                "zone {zone_namespace}/{} does not grant access to namespace {zonefile_namespace}",
+                zone_ref.name
+            );
+
+            if settings.grant.allow_ungranted_zone_refs {
+                warn!("zonefile {zonefile_name} references a zone in another namespace without a grant: {message}");
+            } else {
+                warn!("zonefile {zonefile_name} may not reference a zone in another namespace, skipping it: {message}");
+                observations.observe(REFERENCE_DENIED, "ZoneReferenceNotGranted", message);
+                continue;
+            }
+        }
+
+        zones.push(zone);
+    }
+
+    // Address records of all the zonefile's zones, from which missing glue is copied.
+    let synthesize_glue = zonefile
+        .annotations()
+        .get(SYNTHESIZE_GLUE_ANNOTATION)
+        .is_some_and(|synthesize| synthesize == "true");
+
+    let flatten_cnames = zonefile
+        .annotations()
+        .get(FLATTEN_CNAMES_ANNOTATION)
+        .is_some_and(|flatten| flatten == "true");
+
+    let shuffle_rrsets = zonefile
+        .annotations()
+        .get(SHUFFLE_RRSETS_ANNOTATION)
+        .is_some_and(|shuffle| shuffle == "true");
+
+    let glue_sources = if synthesize_glue {
+        zones
+            .iter()
+            .filter_map(|zone| zone.status.as_ref())
+            .flat_map(|status| &status.entries)
+            .filter(|entry| matches!(entry.type_, Type::A | Type::AAAA))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    metrics::ANOMALIES.remove_matching(&[("zonefile", &zonefile_name)]);
+    metrics::LINT_FINDINGS.remove_matching(&[("zonefile", &zonefile_name)]);
+
+    let challenges = ctx
+        .challenges
+        .as_ref()
+        .map(Challenges::pending)
+        .unwrap_or_default();
+    let comments = ctx
+        .comments
+        .as_ref()
+        .map(|comments| comments.comments(zonefile.namespace().as_deref().unwrap_or_default()))
+        .unwrap_or_default();
+    let managed_origins = zones
+        .iter()
+        .filter_map(|zone| zone.fqdn().cloned())
+        .collect::<Vec<_>>();
+
+    // Zones are rendered again only when their contents or anything else affecting
+    // their rendering changed. Pre-render hooks, pending challenges, record comments,
+    // scheduled records and synthesized glue depend on state outside the zone, so
+    // they bypass the cache.
+    let cacheable = !zonefile
+        .annotations()
+        .contains_key(hooks::PRE_RENDER_ANNOTATION)
+        && challenges.is_empty()
+        && ctx.comments.is_none()
+        && ctx.scheduled.is_none()
+        && !synthesize_glue;
+    let render_nameservers = default_nameservers
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    for zone in &zones {
+        let zone_key = origin_key(
+            zone.namespace().as_deref().unwrap_or_default(),
+            &zone.name_any(),
+        );
+
+        let Some(origin) = zone.fqdn() else {
+            debug!("zone {zone} has no fqdn, skipping.");
+
+            if let Some(origin) = previous_origins.get(&zone_key) {
+                if serve_stale(
+                    zone,
+                    origin,
+                    &previous_data,
+                    &mut retained,
+                    &mut observations,
+                ) {
+                    origins.insert(zone_key, origin.clone());
+                }
+            }
+            continue;
+        };
+
+        origins.insert(zone_key, origin.to_string());
+
+        if zone.hash().is_none() {
+            debug!("zone {zone} has not computed its hash yet, skipping");
+            serve_stale(
+                zone,
+                &origin.to_string(),
+                &previous_data,
+                &mut retained,
+                &mut observations,
+            );
+            continue;
+        }
+
+        let Some(serial) = zone.serial() else {
+            debug!("zone {zone} has not produced a serial yet, skipping");
+            serve_stale(
+                zone,
+                &origin.to_string(),
+                &previous_data,
+                &mut retained,
+                &mut observations,
+            );
+            continue;
+        };
+
+        let published_serial = zonefile
+            .status
+            .as_ref()
+            .and_then(|status| status.serial.get(&origin.to_string()).copied());
+
+        if let Some(published_serial) = published_serial.filter(|published_serial| {
+            serial::precedes(serial, *published_serial) && !allowed_rollbacks.contains(origin)
+        }) {
+            warn!(
+                "zone {zone} has serial {serial}, lower than the published serial {published_serial}, keeping its previous contents"
+            );
+            observations.observe(
+                DEGRADED,
+                "SerialRollback",
+                format!("zone {origin} serial {serial} is lower than the published serial {published_serial}, annotate the zonefile with {ALLOW_SERIAL_ROLLBACK_ANNOTATION} to publish it anyway"),
+            );
+            report_anomalies(
+                &zonefile_name,
+                &origin.to_string(),
+                &[Anomaly::SerialRollback {
+                    serial,
+                    published: published_serial,
+                }],
+                &mut observations,
+            );
+
+            retained.extend(published_keys(&previous_data, &origin.to_string()));
+            continue;
+        }
+
+        let cache_key = RenderInputs {
+            zone_hash: zone.hash().unwrap(),
+            zone_ttl: zone.spec.ttl,
+            zone_annotations: zone.annotations(),
+            zonefile_annotations: zonefile.annotations(),
+            format: &format,
+            validation: &settings.validation,
+            negative: &settings.negative,
+            template: &template,
+            wildcard_policy,
+            default_nameservers: &render_nameservers,
+        }
+        .key();
+        // Zones holding records which violate a policy are not rendered,
+        // and keep their previously published contents instead.
+        let mut violates_policy = false;
+        let cached = cacheable
+            .then(|| {
+                ctx.render_cache
+                    .get(&zonefile_name, &origin.to_string(), cache_key)
+            })
+            .flatten();
+
+        let RenderedZone {
+            entries,
+            duplicates,
+            rendered,
+            observations: zone_observations,
+        } = match cached {
+            Some(cached) => {
+                debug!("zone {zone} is unchanged, reusing its rendering");
+                cached
+            }
+            None => 'render: {
+                let mut observations = Observations::default();
+
+                // Scheduled records are left out until their window opens, and the
+                // zone is reconciled again whenever one of them opens or closes.
+                let windows = ctx
+                    .scheduled
+                    .as_ref()
+                    .map(|scheduled| scheduled.windows(zone))
+                    .unwrap_or_default();
+                let mut entries = zone.status.as_ref().unwrap().entries.clone();
+                if let Some(boundary) = windows.apply(&mut entries, now) {
+                    requeue = requeue.min((boundary - now).to_std().unwrap_or_default());
+                }
+
+                let entries =
+                    hooks::pre_render(&ctx.http, zonefile, &zone.to_string(), origin, entries)
+                        .await?;
+
+                let (entries, duplicates, rendered) = match render_zone(
+                    zone,
+                    origin,
+                    entries,
+                    acme::challenge_records(origin, &challenges, &managed_origins),
+                    &RenderOptions {
+                        annotations: zonefile.annotations(),
+                        validation: &settings.validation,
+                        wildcard_policy,
+                        format: &format,
+                        template: &template,
+                        negative: &settings.negative,
+                        default_nameservers: &default_nameservers,
+                        glue_sources: &glue_sources,
+                        flatten_cnames,
+                        shuffle_rrsets,
+                        comments: &comments,
+                    },
+                    &mut observations,
+                ) {
+                    Ok(rendered) => rendered,
+                    Err(_) => {
+                        violates_policy = true;
+                        break 'render RenderedZone {
+                            entries: Vec::new(),
+                            duplicates: 0,
+                            rendered: String::new(),
+                            observations,
+                        };
+                    }
+                };
+
+                let rendered_zone = RenderedZone {
+                    entries,
+                    duplicates,
+                    rendered,
+                    observations,
+                };
+                if cacheable {
+                    ctx.render_cache.insert(
+                        &zonefile_name,
+                        &origin.to_string(),
+                        cache_key,
+                        rendered_zone.clone(),
+                    );
+                }
+                rendered_zone
+            }
+        };
+        observations.extend(zone_observations);
+
+        if violates_policy {
+            retained.extend(published_keys(&previous_data, &origin.to_string()));
+            continue;
+        }
+
+        let dual_stack_gaps = if settings.lint.lint_dual_stack {
+            lint::dual_stack_gaps(&entries)
+        } else {
+            Vec::new()
+        };
+
+        let serialized_zone =
+            snippet::compose(rendered, origin, &snippets, snippet_include_directory);
+
+        let serialized_zone = match render::finish(&serialized_zone, &format) {
+            Ok(serialized_zone) => serialized_zone,
+            Err(e) => {
+                warn!("zone {zone} cannot be encoded, keeping its previous contents: {e}");
+                observations.observe(
+                    DEGRADED,
+                    "InvalidZoneEncoding",
+                    format!("zone {origin} cannot be encoded: {e}"),
+                );
+
+                retained.extend(published_keys(&previous_data, &origin.to_string()));
+                continue;
+            }
+        };
+
+        let findings = settings.lint.lint(&lint_severities, &entries);
+        report_findings(
+            &zonefile_name,
+            &origin.to_string(),
+            &findings,
+            &mut observations,
+        );
+
+        // Violations are announced once for each change to the zone, rather than
+        // with every reconciliation, as the condition already reports them.
+        let violations = findings
+            .iter()
+            .filter(|finding| finding.severity >= Severity::Warning)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        if !violations.is_empty()
+            && previous_data.get(&origin.to_string()) != Some(&serialized_zone)
+        {
+            lint_violations.push(format!("zone {origin}: {}", violations.join("; ")));
+        }
+
+        if findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Error)
+        {
+            warn!("zone {zone} violates lint rules, keeping its previous contents");
+
+            retained.extend(published_keys(&previous_data, &origin.to_string()));
+            continue;
+        }
+
+        if let Err(e) = settings
+            .quota
+            .check_zone(entries.len(), serialized_zone.len())
+        {
+            warn!("zone {zone} exceeds its quota, keeping its previous contents: {e}");
+            observations.observe(QUOTA_EXCEEDED, e.reason(), format!("zone {origin}: {e}"));
+
+            retained.extend(published_keys(&previous_data, &origin.to_string()));
+            continue;
+        }
+
+        let anomalies = settings.anomaly.check(
+            previous_data.get(&origin.to_string()).map(String::as_str),
+            &serialized_zone,
+            &entries,
+        );
+        report_anomalies(
+            &zonefile_name,
+            &origin.to_string(),
+            &anomalies,
+            &mut observations,
+        );
+
+        if settings.anomaly.hold_anomalies
+            && !anomalies.is_empty()
+            && !acknowledged_anomalies.contains(origin)
+        {
+            warn!("zone {zone} changed suspiciously, keeping its previous contents");
+            observations.observe(
+                DEGRADED,
+                "AnomalyHeld",
+                format!("zone {origin} changed suspiciously and keeps its previous contents, annotate the zonefile with {ACKNOWLEDGE_ANOMALIES_ANNOTATION} to publish it anyway"),
+            );
+
+            retained.extend(published_keys(&previous_data, &origin.to_string()));
+            continue;
+        }
+
+        if previous_data.get(&origin.to_string()) != Some(&serialized_zone) {
+            if let Some(until) = blackout_until {
+                info!("zone {zone} changed during a blackout window, deferring publication until {until}");
+
+                retained.extend(published_keys(&previous_data, &origin.to_string()));
+                frozen.push(json!({
+                    "origin": origin.to_string(),
+                    "serial": serial,
+                    "hash": ctx.hash.hash_algorithm.hash(&serialized_zone),
+                    "until": until.to_rfc3339(),
+                }));
+                requeue = requeue.min((until - now).to_std().unwrap_or_default());
+                continue;
+            }
+
+            if let Some(delay) = min_publish_interval.and_then(|interval| {
+                ctx.throttle
+                    .delay(&zonefile_name, &origin.to_string(), interval)
+            }) {
+                info!(
+                    "zone {zone} was published recently, deferring publication for {}s",
+                    delay.as_secs()
+                );
+
+                retained.extend(published_keys(&previous_data, &origin.to_string()));
+                requeue = requeue.min(delay);
+                continue;
+            }
+
+            if let Some(staging_policy) = &staging_policy {
+                let staged_for =
+                    ctx.staged
+                        .stage(&zonefile_name, &origin.to_string(), &serialized_zone);
+
+                let promote = staging_policy.soak.is_some_and(|soak| staged_for >= soak)
+                    || match &staging_policy.probe {
+                        Some(url) => {
+                            staging::probe(&ctx.http, url, &origin.to_string(), serial).await
+                        }
+                        None => false,
+                    };
+
+                if !promote {
+                    info!(
+                        "zone {zone} has been staged for {}s, awaiting promotion",
+                        staged_for.as_secs()
+                    );
+
+                    // The previously published keys include any earlier staged contents,
+                    // which are superseded by the current ones.
+                    retained.extend(published_keys(&previous_data, &origin.to_string()));
+                    retained.insert(staging::staging_key(&origin.to_string()), serialized_zone);
+                    requeue = requeue.min(staging_policy.recheck_after(staged_for));
+                    continue;
+                }
+
+                info!("promoting staged zone {zone}");
+            }
+        }
+
+        ctx.staged.clear(&zonefile_name, &origin.to_string());
+
+        let stats = ZoneStats {
+            duplicates,
+            ..ZoneStats::compute(&entries, &serialized_zone, signer.is_some())
+        };
+
+        serialized_zones.push(SerializedZone {
+            zone: zone.to_string(),
+            trigger: last_manager(zone),
+            origin: origin.to_string(),
+            serial,
+            hash: zone.hash().unwrap().to_string(),
+            content_hash: ctx.hash.hash_algorithm.hash(&serialized_zone),
+            contents: serialized_zone,
+            dual_stack_gaps,
+            stats,
+        });
+
+        if settings.lint.lint_ptr_consistency {
+            ptr_zones.push((origin.clone(), entries));
+        }
+    }
+
+    let ptr_mismatches = settings
+        .lint
+        .lint_ptr_consistency
+        .then(|| lint::ptr_mismatches(&ptr_zones));
+
+    let mut data = BTreeMap::from_iter(serialized_zones.iter().map(|serialized_zone| {
+        (
+            serialized_zone.origin.clone(),
+            serialized_zone.contents.clone(),
+        )
+    }));
+
+    // ECDSA signatures differ every time the same contents are signed, so the previous
+    // signature of unchanged contents is kept, as long as it was made with the same key.
+    if let Some(signer) = &signer {
+        for serialized_zone in &serialized_zones {
+            let key = format!("{}.sig", serialized_zone.origin);
+
+            let signature = match previous_data.get(&key) {
+                Some(signature)
+                    if previous_data.get(&serialized_zone.origin)
+                        == Some(&serialized_zone.contents)
+                        && signer.verifies(&serialized_zone.contents, signature) =>
+                {
+                    signature.clone()
+                }
+                _ => signer.sign(&serialized_zone.contents)?,
+            };
 
-fn build_zonefile(origin: &FullyQualifiedDomainName, entries: &[ZoneEntry]) -> String {
-    // We use the longest domain name in the list for
-    // aligning the text in the output zonefile
-    let longest_name_length = entries
-        .iter()
-        .map(|entry| entry.fqdn.to_string().len())
-        .max()
-        .unwrap_or_default();
+            data.insert(key, signature);
+        }
+    }
 
-    let serialized_records = entries
-        .iter()
-        .map(
-            |ZoneEntry {
-                 fqdn,
-                 type_,
-                 class,
-                 ttl,
-                 rdata,
-                 ..
-             }| {
-                let name = match fqdn.clone() - origin.clone() {
-                    Ok(partial) => partial.to_string(),
-                    Err(full) => full.to_string(),
-                };
+    if settings.stats.publish_stats {
+        for serialized_zone in &serialized_zones {
+            data.insert(
+                format!("{}.stats.json", serialized_zone.origin),
+                stats_json(&serialized_zone.origin, &serialized_zone.stats).to_string(),
+            );
+        }
+    }
 
-                let entry = if name.is_empty() { "@" } else { &name };
+    data.extend(retained);
 
-                format!(
-                    "{entry:<width$} {ttl:<8} {class:<5} {type_:<6} {rdata}",
-                    width = longest_name_length
-                )
-            },
-        )
-        .collect::<Vec<_>>()
-        .join("\n");
+    // Renderings of zones which the zonefile no longer references, or which were deleted.
+    ctx.render_cache.retain(
+        &zonefile_name,
+        &origins.values().map(String::as_str).collect::<Vec<_>>(),
+    );
 
-    format!("$ORIGIN {origin}\n\n{serialized_records}")
+    Ok(RenderedZonefile {
+        previous,
+        previous_data,
+        origins,
+        zones,
+        serialized_zones,
+        data,
+        naming,
+        outputs,
+        recipients,
+        tsig,
+        lint_violations,
+        observations,
+        frozen,
+        lint_dual_stack: settings.lint.lint_dual_stack,
+        ptr_mismatches,
+        requeue,
+    })
 }
 
-/// Applied a [`TARGET_ZONEFILE_LABEL`] label which references our zonefile.
-/// This label is monitored by our controller, causing reconciliation loops
-/// to fire for [`ZoneFile`]s referenced by [`Zone`]s, when the zone itself
-/// is updated.
-async fn apply_zonefile_backref(
-    client: Client,
+/// Write what the reconciliation arrived at: mark the zones with backreferences,
+/// apply the ConfigMap and the status, and deliver the outputs, followed by the
+/// notifications and hooks of the zones which changed.
+async fn publish_zonefile(
     zonefile: &ZoneFile,
-    zone: &Zone,
-) -> Result<(), kube::Error> {
-    let zonefile_ref = format!(
-        "{}.{}",
-        zonefile.name_any(),
-        zonefile.namespace().as_ref().unwrap()
+    ctx: &Data,
+    settings: &Settings,
+    id: ReconcileId,
+    mut rendered: RenderedZonefile,
+) -> Result<Action, Error> {
+    let zonefile_name = zonefile_name(zonefile);
+    let configmap_name = ownership::config_map_name(zonefile);
+    let config_maps =
+        Api::<ConfigMap>::namespaced(ctx.client.clone(), zonefile.namespace().as_ref().unwrap());
+
+    let mut observations = std::mem::take(&mut rendered.observations);
+    let mut requeue = rendered.requeue;
+    let RenderedZonefile {
+        previous,
+        previous_data,
+        origins,
+        zones,
+        serialized_zones,
+        data,
+        naming,
+        outputs,
+        recipients,
+        tsig,
+        lint_violations,
+        ..
+    } = &rendered;
+    let naming = *naming;
+
+    for note in lint_violations {
+        publish_warning(ctx, zonefile, "LintViolation", note.clone()).await;
+    }
+
+    let hashes = BTreeMap::from_iter(
+        data.iter()
+            .map(|(key, contents)| (key.clone(), ctx.hash.hash_algorithm.hash(contents))),
     );
 
-    if zone.labels().get(TARGET_ZONEFILE_LABEL) != Some(&zonefile_ref) {
-        info!(
-            "updating zone {}'s {TARGET_ZONEFILE_LABEL} to {zonefile_ref}",
-            zonefile.name_any()
-        );
+    let mut config_map = ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(configmap_name.clone()),
+            namespace: zonefile.namespace(),
+            annotations: Some(BTreeMap::from([
+                (
+                    HASHES_ANNOTATION.to_string(),
+                    json!(naming.publish(hashes.clone())).to_string(),
+                ),
+                (ORIGINS_ANNOTATION.to_string(), json!(origins).to_string()),
+                (FILE_NAMING_ANNOTATION.to_string(), naming.to_string()),
+                (RECONCILE_ID_ANNOTATION.to_string(), id.to_string()),
+            ])),
+            ..ObjectMeta::default()
+        },
+        data: Some(naming.publish(data.clone())),
+        ..Default::default()
+    };
+    ownership::mark_owned(
+        zonefile,
+        ctx.ownership.label_ownership,
+        &mut config_map.metadata,
+    );
 
-        Api::<Zone>::namespaced(client, zone.namespace().as_ref().unwrap())
-            .patch_metadata(
-                &zone.name_any(),
-                &PatchParams::apply(CONTROLLER_NAME),
-                &Patch::Merge(json!({
-                    "metadata": {
-                        "labels": {
-                            TARGET_ZONEFILE_LABEL: zonefile_ref
-                        },
-                    }
-                })),
-            )
+    // Reconciliations which change nothing, such as periodic resyncs, skip the write,
+    // which also leaves the id of the reconciliation which made the last change.
+    let unchanged = previous.as_ref().is_some_and(|previous| {
+        previous.data == config_map.data
+            && [
+                HASHES_ANNOTATION,
+                ORIGINS_ANNOTATION,
+                FILE_NAMING_ANNOTATION,
+            ]
+            .iter()
+            .all(|key| previous.annotations().get(*key) == config_map.annotations().get(*key))
+            && config_map
+                .owner_references()
+                .iter()
+                .all(|owner| previous.owner_references().contains(owner))
+            && config_map
+                .labels()
+                .iter()
+                .all(|(key, value)| previous.labels().get(key) == Some(value))
+    });
+
+    // Nothing has been written so far, so a reconciliation which exceeded its budget
+    // fails here, rather than once some of its writes were refused along the way.
+    budget::ensure_within()?;
+
+    for zone in zones {
+        ctx.backref
+            .apply(ctx.client.clone(), &ctx.controller_name, zonefile, zone)
             .await?;
     }
 
-    Ok(())
-}
+    if unchanged {
+        debug!("ConfigMap {configmap_name} is up to date");
+    } else if let Err(e) = config_maps
+        .patch(
+            &configmap_name,
+            &ctx.apply_params(),
+            &Patch::Apply(config_map),
+        )
+        .await
+    {
+        let e = Error::from_apply(e, format!("ConfigMap {configmap_name}"));
+        if let Error::Conflict { .. } = e {
+            publish_warning(ctx, zonefile, "FieldConflict", e.to_string()).await;
+        }
 
-async fn reconcile_zonefiles(
-    zonefile: Arc<ZoneFile>,
-    ctx: Arc<Data>,
-) -> Result<Action, kube::Error> {
-    struct SerializedZone {
-        origin: String,
-        serial: u32,
-        hash: String,
-        contents: String,
+        return Err(e);
     }
 
-    let mut serialized_zones = Vec::new();
+    // Additional outputs are delivered once the zonefile's own ConfigMap and
+    // its status are published. Their failures are reported, but do not hold
+    // back the others.
+    //
+    // Staged zones are promoted within the zonefile's own ConfigMap, and never delivered.
+    let namespace = zonefile.namespace().unwrap();
+    let delivered_data: BTreeMap<String, String> = data
+        .iter()
+        .filter(|(key, _)| !staging::is_staging_key(key))
+        .map(|(key, contents)| (key.clone(), contents.clone()))
+        .collect();
+    let output_hashes: BTreeMap<String, String> = hashes
+        .iter()
+        .filter(|(key, _)| !staging::is_staging_key(key))
+        .map(|(key, hash)| (key.clone(), hash.clone()))
+        .collect();
+
+    let (outputs, output_data) = match recipients {
+        Some(recipients) if !recipients.is_empty() => {
+            let encrypted = delivered_data
+                .iter()
+                .map(|(key, contents)| {
+                    age::encrypt(recipients, contents).map(|encrypted| (key.clone(), encrypted))
+                })
+                .collect::<Result<BTreeMap<_, _>, _>>()
+                .map_err(|_| {
+                    Error::Encryption(format!("failed to encrypt outputs of {zonefile_name}"))
+                })?;
+
+            (outputs.as_slice(), encrypted)
+        }
+        Some(_) => (outputs.as_slice(), delivered_data),
+        None => (&[][..], BTreeMap::new()),
+    };
+
+    // Without its persisted state, a restarted controller would deliver everything again.
+    if ctx.persist_state && !outputs.is_empty() {
+        if let Err(e) =
+            state::restore(ctx.client.clone(), zonefile, &zonefile_name, &ctx.outputs).await
+        {
+            warn!("failed to restore the delivered outputs of zonefile {zonefile_name}: {e}");
+        }
+    }
 
-    for zone_ref in &zonefile.spec.zone_refs {
-        let zone = Api::<Zone>::namespaced(
+    // Targets which have keys left to deliver are reported as such, along with
+    // the last failure to deliver them, until they are delivered.
+    let output_statuses = || {
+        outputs
+            .iter()
+            .map(|output| {
+                let target = output.to_string();
+
+                json!({
+                    "delivered": ctx.outputs.changed(&zonefile_name, &target, &output_hashes).is_empty(),
+                    "error": ctx.outputs.failure(&zonefile_name, &target),
+                    "target": target,
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for output in outputs {
+        if let Some(e) = ctx.outputs.failure(&zonefile_name, &output.to_string()) {
+            observations.observe(
+                DEGRADED,
+                "OutputFailed",
+                format!("failed to deliver to {output}: {e}"),
+            );
+        }
+    }
+
+    // All fields of the status are set in a single patch, which is skipped
+    // if the zonefile already has the status the reconciliation arrived at.
+    let status = rendered.status(
+        ctx.conditions
+            .update(&zonefile_name, zonefile.metadata.generation, observations),
+        (!outputs.is_empty()).then(output_statuses),
+    );
+
+    // The ConfigMap is published by now, and secondaries may already have transferred
+    // its zones, so it is kept as it is should the status fail to update. Only the
+    // status is retried, and the zones' notifications and hooks still follow below,
+    // as the next reconciliation will find their contents already published.
+    // The live status is read in full, since the zonefile's type only declares
+    // some of the fields the controller writes. The status reports what was
+    // published, so its requests are exempt from the budget.
+    let zonefiles =
+        Api::<ZoneFile>::namespaced(ctx.client.clone(), zonefile.namespace().as_ref().unwrap());
+    let written = budget::exempt(async {
+        let live_status = Api::<DynamicObject>::namespaced_with(
             ctx.client.clone(),
-            &zone_ref
-                .namespace
-                .as_ref()
-                .or(zonefile.namespace().as_ref())
-                .cloned()
-                .unwrap(),
+            zonefile.namespace().as_ref().unwrap(),
+            &ApiResource::erase::<ZoneFile>(&()),
         )
-        .get(&zone_ref.name)
-        .await?;
+        .get_status(&zonefile.name_any())
+        .await
+        .map(|live| json!({ "status": live.data.get("status") }));
 
-        apply_zonefile_backref(ctx.client.clone(), &zonefile, &zone).await?;
+        let mut written = Ok(());
+        if live_status.is_ok_and(|live| status::is_current(&live, &status)) {
+            debug!("status of zonefile {zonefile_name} is up to date");
+        } else {
+            for attempt in 0..STATUS_ATTEMPTS {
+                if attempt > 0 {
+                    tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                }
 
-        let Some(origin) = zone.fqdn() else {
-            debug!("zone {zone} has no fqdn, skipping.");
-            continue;
-        };
+                written = zonefiles
+                    .patch_status(
+                        &zonefile.name_any(),
+                        &PatchParams::apply(&ctx.controller_name),
+                        &Patch::Merge(&status),
+                    )
+                    .await
+                    .map(|_| ());
 
-        let Some(hash) = zone.hash() else {
-            debug!("zone {zone} has not computed its hash yet, skipping");
+                match &written {
+                    Ok(()) => break,
+                    Err(e) => {
+                        warn!("failed to update the status of zonefile {zonefile_name}: {e}")
+                    }
+                }
+            }
+        }
+        written
+    })
+    .await;
+
+    let mut attempted = false;
+    let mut delivered_any = false;
+    for output in outputs {
+        let target = output.to_string();
+        let changed = ctx.outputs.changed(&zonefile_name, &target, &output_hashes);
+
+        if changed.is_empty() {
             continue;
-        };
+        }
 
-        let Some(serial) = zone.serial() else {
-            debug!("zone {zone} has not produced a serial yet, skipping");
+        attempted = true;
+        match output
+            .deliver(
+                ctx.client.clone(),
+                &ctx.http,
+                &ctx.controller_name,
+                &settings.output,
+                (&namespace, &zonefile.name_any()),
+                naming,
+                tsig.as_ref(),
+                &output_data,
+                &changed,
+            )
+            .await
+        {
+            Ok(()) => {
+                delivered_any = true;
+                ctx.outputs
+                    .delivered(&zonefile_name, &target, &output_hashes);
+            }
+            Err(e) => {
+                warn!("failed to deliver zonefile {zonefile_name} to {target}: {e}");
+                ctx.outputs.failed(&zonefile_name, &target, e.to_string());
+
+                // Outputs whose host throttled them are retried once it allows it.
+                if let Some(delay) = output
+                    .http_host()
+                    .and_then(|host| ctx.http.throttled_for(&host))
+                {
+                    requeue = requeue.min(delay);
+                }
+            }
+        }
+    }
+
+    let published_any = serialized_zones.iter().any(|serialized_zone| {
+        previous_data.get(&serialized_zone.origin) != Some(&serialized_zone.contents)
+    });
+
+    if ctx.persist_state && (published_any || delivered_any) {
+        if let Err(e) = state::persist(
+            ctx.client.clone(),
+            &ctx.apply_params(),
+            zonefile,
+            &zonefile_name,
+            ctx.ownership.label_ownership,
+            &ctx.outputs,
+        )
+        .await
+        {
+            warn!("failed to persist the state of zonefile {zonefile_name}: {e}");
+        }
+    }
+
+    // The outcome of the deliveries is only known once the status is written.
+    if attempted {
+        if let Err(e) = budget::exempt(
+            Api::<ZoneFile>::namespaced(ctx.client.clone(), zonefile.namespace().as_ref().unwrap())
+                .patch_status(
+                    &zonefile.name_any(),
+                    &PatchParams::apply(&ctx.controller_name),
+                    &Patch::Merge(json!({ "status": { "outputs": output_statuses() } })),
+                ),
+        )
+        .await
+        {
+            warn!("failed to update the delivered outputs of zonefile {zonefile_name}: {e}");
+        }
+    }
+
+    if settings.lint.lint_dual_stack {
+        metrics::DUAL_STACK_GAPS.remove_matching(&[("zonefile", &zonefile_name)]);
+
+        for serialized_zone in serialized_zones {
+            for missing in [Type::A, Type::AAAA] {
+                let gaps = serialized_zone
+                    .dual_stack_gaps
+                    .iter()
+                    .filter(|gap| gap.missing == missing)
+                    .count();
+
+                metrics::DUAL_STACK_GAPS.set(
+                    &[
+                        ("zonefile", &zonefile_name),
+                        ("origin", &serialized_zone.origin),
+                        ("missing", &missing.to_string()),
+                    ],
+                    gaps as f64,
+                );
+            }
+        }
+    }
+
+    // Zones kept at their previously published contents, rather than rendered anew.
+    let retained_zones = origins
+        .values()
+        .filter(|origin| {
+            !serialized_zones
+                .iter()
+                .any(|serialized_zone| serialized_zone.origin == **origin)
+        })
+        .filter_map(|origin| Some((origin.as_str(), data.get(origin)?.as_str())))
+        .collect::<Vec<_>>();
+
+    ctx.published.publish(
+        &zonefile_name,
+        serialized_zones.iter().map(|serialized_zone| {
+            (
+                serialized_zone.origin.as_str(),
+                serialized_zone.serial,
+                serialized_zone.content_hash.as_str(),
+                serialized_zone.contents.as_str(),
+            )
+        }),
+        &origins.values().map(String::as_str).collect::<Vec<_>>(),
+    );
+    ctx.published.seed(
+        &zonefile_name,
+        retained_zones.iter().map(|(origin, contents)| {
+            (
+                *origin,
+                zonefile
+                    .status
+                    .as_ref()
+                    .and_then(|status| status.serial.get(*origin).copied())
+                    .unwrap_or_default(),
+                hashes[*origin].as_str(),
+                *contents,
+            )
+        }),
+    );
+
+    if let Some(served) = &ctx.served {
+        served
+            .publish(
+                &zonefile_name,
+                serialized_zones.iter().map(|serialized_zone| {
+                    (
+                        serialized_zone.origin.as_str(),
+                        serialized_zone.contents.as_str(),
+                    )
+                }),
+                &origins.values().map(String::as_str).collect::<Vec<_>>(),
+            )
+            .await;
+
+        served
+            .seed(
+                &zonefile_name,
+                retained_zones
+                    .iter()
+                    .map(|(origin, contents)| (*origin, *contents)),
+            )
+            .await;
+    }
+
+    for serialized_zone in serialized_zones {
+        let previous_contents = previous_data.get(&serialized_zone.origin);
+
+        if previous_contents == Some(&serialized_zone.contents) {
             continue;
+        }
+
+        ctx.throttle
+            .published(&zonefile_name, &serialized_zone.origin);
+
+        let diff = ZoneDiff::between(
+            previous_contents.map(String::as_str).unwrap_or_default(),
+            &serialized_zone.contents,
+        );
+
+        let change = ZoneChange {
+            zonefile: &zonefile_name,
+            zone: &serialized_zone.zone,
+            trigger: serialized_zone.trigger.as_deref(),
+            origin: &serialized_zone.origin,
+            old_serial: zonefile
+                .status
+                .as_ref()
+                .and_then(|status| status.serial.get(&serialized_zone.origin).copied()),
+            new_serial: serialized_zone.serial,
+            hash: &serialized_zone.hash,
+            diff: &diff,
         };
 
-        let serialized_zone = build_zonefile(origin, &zone.status.as_ref().unwrap().entries);
+        if let Some(audit_log) = &ctx.audit_log {
+            audit_log.record(&ctx.controller_name, &change).await;
+        }
 
-        serialized_zones.push(SerializedZone {
-            origin: origin.to_string(),
-            serial,
-            hash: hash.to_string(),
-            contents: serialized_zone,
-        });
+        if let Some(notifier) = &ctx.notifier {
+            notifier.notify(&change).await;
+        }
+
+        if let Some(stream) = &ctx.stream {
+            stream.publish(&change).await;
+        }
+
+        if let Err(e) = hooks::post_sync(
+            ctx.client.clone(),
+            zonefile,
+            &serialized_zone.origin,
+            serialized_zone.serial,
+            ctx.ownership.label_ownership,
+            &settings.hooks,
+        )
+        .await
+        {
+            warn!(
+                "failed to launch post-sync job for zone {}: {e}",
+                serialized_zone.origin
+            );
+        }
     }
 
-    let owner_reference = zonefile.controller_owner_ref(&()).unwrap();
-    let configmap_name = zonefile
-        .spec
-        .config_map_name
-        .as_ref()
-        .cloned()
-        .unwrap_or(zonefile.name_any());
+    // The status is written again by the retried reconciliation.
+    written?;
 
-    let config_map = ConfigMap {
-        metadata: ObjectMeta {
-            name: Some(configmap_name.clone()),
-            namespace: zonefile.namespace(),
-            owner_references: Some(vec![owner_reference]),
-            ..ObjectMeta::default()
-        },
-        data: Some(BTreeMap::from_iter(serialized_zones.iter().map(
-            |serialized_zone| {
-                (
-                    serialized_zone.origin.clone(),
-                    serialized_zone.contents.clone(),
-                )
-            },
-        ))),
-        ..Default::default()
+    Ok(Action::requeue(requeue))
+}
+
+/// Number of times the status of a zonefile is written before its reconciliation
+/// fails, since its ConfigMap has already been published by then.
+const STATUS_ATTEMPTS: u32 = 3;
+
+/// Watch the resources, recording the lag of each change, and backing
+/// off before restarting the watch after an error.
+pub(crate) fn watch_events<K>(
+    api: Api<K>,
+    config: watcher::Config,
+) -> impl Stream<Item = Result<watcher::Event<K>, watcher::Error>> + Send
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send + 'static,
+{
+    watcher(api, config).default_backoff().inspect_ok(|event| {
+        if let watcher::Event::Applied(object) | watcher::Event::Deleted(object) = event {
+            saturation::observe_watch_lag(K::kind(&()).as_ref(), object.meta());
+        }
+    })
+}
+
+/// Reconcile the zonefile, containing any panic to this reconciliation so
+/// that it is retried like any other error, instead of aborting the controller.
+async fn reconcile_isolated(zonefile: Arc<ZoneFile>, ctx: Arc<Data>) -> Result<Action, Error> {
+    ctx.queue.pop(&ObjectRef::from_obj(zonefile.as_ref()));
+
+    let zonefile_name = format!(
+        "{}/{}",
+        zonefile.namespace().unwrap_or_default(),
+        zonefile.name_any()
+    );
+    metrics::seen(&zonefile_name);
+
+    // The reconciliations of all zonefiles on startup are spread out, rather than written at once.
+    if let Some(delay) = ctx.warmup.delay(&zonefile_name) {
+        debug!(
+            "postponing startup reconciliation by {}ms",
+            delay.as_millis()
+        );
+        return Ok(Action::requeue(delay));
+    }
+
+    if zonefile
+        .annotations()
+        .get(PAUSED_ANNOTATION)
+        .is_some_and(|paused| paused == "true")
+    {
+        debug!("zonefile {zonefile_name} is paused, skipping reconciliation");
+        return Ok(Action::await_change());
+    }
+
+    let _in_flight = metrics::RECONCILES_IN_FLIGHT.track();
+
+    // Log lines of the reconciliation carry its id, which is also stamped
+    // on the ConfigMap it publishes, to trace the output back to them.
+    let id = ReconcileId::next();
+    let span = tracing::info_span!(
+        "reconcile",
+        id = %id,
+        zonefile = %zonefile_name,
+    );
+
+    let limit = ctx.settings.read().unwrap().budget.api_request_budget;
+    let budget = RequestBudget::new(limit);
+
+    let panic = match AssertUnwindSafe(budget.scope(reconcile_zonefiles(
+        zonefile.clone(),
+        ctx.clone(),
+        id,
+    )))
+    .catch_unwind()
+    .instrument(span.clone())
+    .await
+    {
+        Ok(result) => {
+            metrics::RECONCILE_API_REQUESTS
+                .set(&[("zonefile", &zonefile_name)], budget.used() as f64);
+
+            // Requests refused for exceeding the budget may have been retried or
+            // ignored along the way, so the reconciliation fails regardless.
+            let result = match budget.exceeded() {
+                Some(exceeded) => Err(Error::from(exceeded)),
+                None => result,
+            };
+
+            if result.is_ok() {
+                metrics::synced(&zonefile_name);
+            }
+            return result;
+        }
+        Err(panic) => panic,
     };
 
-    Api::<ConfigMap>::namespaced(ctx.client.clone(), zonefile.namespace().as_ref().unwrap())
-        .patch(
-            &configmap_name,
-            &PatchParams::apply(CONTROLLER_NAME),
-            &Patch::Apply(config_map),
-        )
-        .await?;
-
-    Api::<ZoneFile>::namespaced(ctx.client.clone(), zonefile.namespace().as_ref().unwrap())
-        .patch_status(
-            &zonefile.name_any(),
-            &PatchParams::apply(CONTROLLER_NAME),
-            &Patch::Merge(json!({
-                "status": {
-                    "hash": BTreeMap::from_iter(serialized_zones.iter().map(|serialized_zone| (&serialized_zone.origin, &serialized_zone.hash))),
-                    "serial": BTreeMap::from_iter(serialized_zones.iter().map(|serialized_zone| (&serialized_zone.origin, serialized_zone.serial))),
-                },
-            })),
-        )
-        .await?;
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown panic"));
+
+    metrics::RECONCILE_PANICS.inc(&[("zonefile", &zonefile_name)]);
+
+    publish_warning(&ctx, &zonefile, "ReconcilePanicked", message.clone())
+        .instrument(span)
+        .await;
+
+    Err(Error::Panic(message))
+}
+
+/// Publish a warning Event for the zonefile.
+async fn publish_warning(ctx: &Data, zonefile: &ZoneFile, reason: &str, note: String) {
+    let recorder = Recorder::new(
+        ctx.client.clone(),
+        Reporter {
+            controller: ctx.controller_name.clone(),
+            instance: None,
+        },
+        zonefile.object_ref(&()),
+    );
+
+    if let Err(e) = recorder
+        .publish(Event {
+            type_: EventType::Warning,
+            reason: reason.to_string(),
+            note: Some(note),
+            action: String::from("Reconcile"),
+            secondary: None,
+        })
+        .await
+    {
+        warn!(
+            "failed to publish {reason} event for zonefile {}: {e}",
+            zonefile.name_any()
+        );
+    }
+}
+
+fn zonefile_error_policy(zone: Arc<ZoneFile>, error: &Error, _ctx: Arc<Data>) -> Action {
+    match error.retry_after() {
+        Some(delay) => {
+            error!(
+                "zonefile {} reconciliation encountered error, retrying in {}s: {error}",
+                zone.name_any(),
+                delay.as_secs()
+            );
+            Action::requeue(delay)
+        }
+        None => {
+            error!(
+                "zonefile {} reconciliation encountered permanent error, waiting for it to change: {error}",
+                zone.name_any()
+            );
+            Action::await_change()
+        }
+    }
+}
+
+/// Reload the configuration file whenever the controller receives a SIGHUP.
+async fn reload_on_hangup(config_file: ConfigFile, ctx: Arc<Data>) {
+    let mut hangups = signal(SignalKind::hangup()).unwrap();
+
+    while hangups.recv().await.is_some() {
+        let config = config_file
+            .reload(crate::Args::command())
+            .and_then(|matches| {
+                let (_, matches) = matches.subcommand().unwrap();
+                Config::from_arg_matches(matches).map_err(|e| Error::Config(e.to_string()))
+            })
+            .and_then(|config| config.defaults.validate().map(|_| config));
 
-    Ok(Action::requeue(Duration::from_secs(300)))
+        match config {
+            Ok(config) => {
+                *ctx.settings.write().unwrap() = Settings::from(&config);
+                info!("reloaded configuration file, changes to options other than validation, linting, statistics, formatting, hooks, quotas, anomaly checks, negative caching, cross-namespace references and defaults take effect on restart");
+            }
+            Err(e) => {
+                error!("failed to reload configuration file, keeping the current options: {e}")
+            }
+        }
+    }
+}
+
+/// Resolves on SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).unwrap();
+
+    futures::future::select(
+        tokio::signal::ctrl_c().map(|_| ()).boxed(),
+        terminate.recv().map(|_| ()).boxed(),
+    )
+    .await;
 }
 
-fn zonefile_error_policy(zone: Arc<ZoneFile>, error: &kube::Error, _ctx: Arc<Data>) -> Action {
-    error!(
-        "zonefile {} reconciliation encountered error: {error}",
-        zone.name_any()
+/// Stop starting new reconciliations on SIGINT or SIGTERM, and resolve once the deadline
+/// passes or another signal is received, if those in progress have not finished by then.
+async fn drain_on_signal(drain: oneshot::Sender<()>, deadline: Duration) {
+    shutdown_signal().await;
+    info!(
+        "shutting down, waiting up to {}s for reconciliations in progress to finish",
+        deadline.as_secs()
     );
-    Action::requeue(Duration::from_secs(60))
+    let _ = drain.send(());
+
+    futures::future::select(
+        tokio::time::sleep(deadline).boxed(),
+        shutdown_signal().boxed(),
+    )
+    .await;
+}
+
+/// Run the controller until it is stopped by a signal.
+pub async fn reconcile(
+    client: Client,
+    config: Config,
+    config_file: Option<ConfigFile>,
+) -> Result<(), Error> {
+    run(client, config, config_file, None::<Pending<()>>).await
 }
 
-pub async fn reconcile(client: Client) {
-    let zonefiles = Api::<ZoneFile>::all(client.clone());
+/// Run the controller until the shutdown future resolves, after finishing the reconciliations
+/// in progress. Without one, the controller stops on SIGINT or SIGTERM instead, and fails
+/// if the reconciliations in progress do not finish within the shutdown timeout. The tasks
+/// started alongside the controller, such as its servers and watches, stop with it.
+///
+/// Fails without starting if the configuration is invalid.
+pub async fn run(
+    client: Client,
+    config: Config,
+    config_file: Option<ConfigFile>,
+    shutdown: Option<impl Future<Output = ()> + Send + Sync + 'static>,
+) -> Result<(), Error> {
+    let client = budget::counted(client);
+
+    config.defaults.validate()?;
+
+    let http = Arc::new(
+        HttpClient::new(config.http.clone())
+            .map_err(|e| Error::Config(format!("invalid http client options: {e}")))?,
+    );
+
+    let impersonation = config
+        .impersonation
+        .impersonation()
+        .await
+        .map_err(|e| Error::Config(format!("unable to impersonate service accounts: {e}")))?;
+
+    // Tasks running alongside the controller, which are aborted once it stops.
+    let mut tasks = JoinSet::new();
+
+    let served = serve::serve(&config.serve, &mut tasks).await?;
+
+    if let Some(address) = config.metrics.metrics_address {
+        version::record();
+        tasks.spawn(metrics::serve(address));
+    }
+
+    if !config.skip_cluster_tasks {
+        tasks.spawn(backup::run(client.clone(), config.backup.clone()));
+        tasks.spawn(hooks::run(client.clone(), config.hooks.clone()));
+        tasks.spawn(source::run(
+            client.clone(),
+            config.source.clone(),
+            config.controller_name.clone(),
+            config.watch.watcher_config(),
+        ));
+    }
+
+    let challenges = config
+        .acme
+        .watch(client.clone(), config.watch.watcher_config(), &mut tasks);
+
+    let comments = config
+        .comment
+        .watch(client.clone(), config.watch.watcher_config(), &mut tasks);
+
+    let scheduled =
+        config
+            .schedule
+            .watch(client.clone(), config.watch.watcher_config(), &mut tasks);
 
-    let zone_controller = Controller::new(zonefiles, watcher::Config::default())
-        .watches(
-            Api::<Zone>::all(client.clone()),
-            watcher::Config::default(),
-            kubizone_crds::watch_reference(TARGET_ZONEFILE_LABEL),
+    // Namespace defaults are read from the store fed by their watch below.
+    let (namespace_defaults, defaults_writer) = reflector::store();
+
+    let data = Arc::new(Data {
+        client: client.clone(),
+        controller_name: config.controller_name.clone(),
+        force_ownership: config.force_ownership,
+        ownership: config.ownership.clone(),
+        backref: config.backref.clone(),
+        watch: config.watch.clone(),
+        hash: config.hash.clone(),
+        http: http.clone(),
+        notifier: Notifier::from_args(&config.notify, http.clone()),
+        stream: EventStream::from_args(&config.stream)?,
+        audit_log: AuditLog::from_args(&config.audit),
+        settings: RwLock::new(Settings::from(&config)),
+        conditions: Conditions::default(),
+        throttle: PublishThrottle::default(),
+        staged: StagedZones::default(),
+        outputs: DeliveredOutputs::default(),
+        persist_state: config.state.persist_state,
+        challenges: challenges
+            .as_ref()
+            .map(|(challenges, _)| challenges.clone()),
+        comments: comments.as_ref().map(|(comments, _)| comments.clone()),
+        scheduled: scheduled.as_ref().map(|(scheduled, _)| scheduled.clone()),
+        defaults: namespace_defaults,
+        impersonation,
+        published: Arc::new(PublishedZones::new(config.api.api_history)),
+        served,
+        queue: Arc::default(),
+        render_cache: RenderCache::default(),
+        warmup: config.warmup.warmup(),
+    });
+
+    if let Some(address) = config.api.address()? {
+        tasks.spawn(api::serve(
+            address,
+            config.api.api_token.clone(),
+            data.published.clone(),
+        ));
+    }
+
+    if let Some(config_file) = config_file {
+        tasks.spawn(reload_on_hangup(config_file, data.clone()));
+    }
+
+    // Orphaned outputs are found through the default outputs in effect at the time.
+    if !config.skip_cluster_tasks {
+        let settings = data.clone();
+        tasks.spawn(ownership::run(
+            client.clone(),
+            config.ownership.clone(),
+            config.state.persist_state,
+            move || {
+                settings
+                    .settings
+                    .read()
+                    .unwrap()
+                    .defaults
+                    .default_outputs
+                    .clone()
+            },
+        ));
+    }
+
+    // The watches are set up by hand rather than by the controller, so that
+    // the lag of their events and the resulting reconciliations can be measured.
+    let zonefile_watcher_config = match &config.zonefile_selector {
+        Some(selector) => config.watch.watcher_config().labels(selector),
+        None => config.watch.watcher_config(),
+    };
+
+    let (zonefile_store, writer) = reflector::store();
+    let queue = data.queue.clone();
+    let deleted = data.queue.clone();
+    let published = data.published.clone();
+    let cache = data.clone();
+    let zonefiles = reflector(
+        writer,
+        watch_events(
+            Api::<ZoneFile>::all(client.clone()),
+            zonefile_watcher_config,
+        ),
+    )
+    .inspect_ok(move |event| {
+        if let watcher::Event::Deleted(zonefile) = event {
+            let zonefile_name = format!(
+                "{}/{}",
+                zonefile.namespace().unwrap_or_default(),
+                zonefile.name_any()
+            );
+            metrics::forget(&zonefile_name);
+            published.forget(&zonefile_name);
+            cache.render_cache.forget(&zonefile_name);
+            // Deleted zonefiles are never reconciled, and would otherwise stay queued.
+            deleted.pop(&ObjectRef::from_obj(zonefile));
+        }
+    })
+    .applied_objects()
+    .inspect_ok(move |zonefile| queue.push([ObjectRef::from_obj(zonefile)]));
+
+    let zone_controller = Controller::for_stream(zonefiles, zonefile_store);
+
+    let zonefile_store = zone_controller.store();
+    let template_store = zone_controller.store();
+    let zone_queue = data.queue.clone();
+    let template_queue = data.queue.clone();
+    let defaults_store = zone_controller.store();
+    let defaults_queue = data.queue.clone();
+
+    let zone_controller = zone_controller
+        .watches_stream(
+            watch_events(
+                Api::<Zone>::all(client.clone()),
+                config.watch.watcher_config(),
+            )
+            .touched_objects(),
+            move |zone| {
+                let zonefiles = backref::zonefiles_referencing(&zone, &zonefile_store);
+                zone_queue.push(zonefiles.clone());
+                zonefiles
+            },
         )
-        .shutdown_on_signal()
-        .run(
-            reconcile_zonefiles,
-            zonefile_error_policy,
-            Arc::new(Data {
-                client: client.clone(),
-            }),
+        .watches_stream(
+            watch_events(
+                Api::<ZoneFileTemplate>::all(client.clone()),
+                config.watch.watcher_config(),
+            )
+            .touched_objects(),
+            move |template| {
+                let zonefiles = template::zonefiles_using(&template, &template_store);
+                template_queue.push(zonefiles.clone());
+                zonefiles
+            },
         )
+        .watches_stream(
+            reflector(
+                defaults_writer,
+                watch_events(
+                    Api::<ZoneFileDefaults>::all(client.clone()),
+                    config.watch.watcher_config(),
+                ),
+            )
+            .touched_objects(),
+            move |defaults| {
+                let zonefiles = defaults::zonefiles_defaulted_by(&defaults, &defaults_store);
+                defaults_queue.push(zonefiles.clone());
+                zonefiles
+            },
+        );
+
+    // Challenges only concern the zonefiles publishing the zones containing them.
+    let zone_controller = match challenges {
+        Some((challenges, changed)) => {
+            let zonefile_store = zone_controller.store();
+            let challenge_queue = data.queue.clone();
+
+            zone_controller.watches_stream_with(
+                changed.map(Ok::<_, watcher::Error>),
+                move |challenge| {
+                    let zonefiles = challenges.zonefiles_publishing(&challenge, &zonefile_store);
+                    challenge_queue.push(zonefiles.clone());
+                    zonefiles
+                },
+                acme::challenge_resource(),
+            )
+        }
+        None => zone_controller,
+    };
+
+    // Comments are not part of the zones, so changes to them must trigger
+    // reconciliations of the zonefiles publishing the records they annotate.
+    let zone_controller = match comments {
+        Some((_, changed)) => {
+            let zonefile_store = zone_controller.store();
+            let comment_queue = data.queue.clone();
+
+            zone_controller.watches_stream(changed.map(Ok::<_, watcher::Error>), move |record| {
+                let zonefiles = RecordComments::zonefiles_containing(&record, &zonefile_store);
+                comment_queue.push(zonefiles.clone());
+                zonefiles
+            })
+        }
+        None => zone_controller,
+    };
+
+    // So are the validity windows of scheduled records.
+    let zone_controller = match scheduled {
+        Some((_, changed)) => zone_controller.reconcile_all_on(changed),
+        None => zone_controller,
+    };
+
+    let zone_controller =
+        zone_controller.with_config(controller::Config::default().concurrency(config.concurrency));
+
+    // Resolves once the reconciliations in progress are given up on.
+    let mut deadline = None;
+    let zone_controller = match shutdown {
+        Some(shutdown) => zone_controller.graceful_shutdown_on(shutdown),
+        None => {
+            let (drain, drained) = oneshot::channel();
+            deadline =
+                Some(drain_on_signal(drain, Duration::from_secs(config.shutdown_timeout)).boxed());
+            zone_controller.graceful_shutdown_on(drained.map(|_| ()))
+        }
+    };
+
+    let zone_controller = zone_controller
+        .run(reconcile_isolated, zonefile_error_policy, data)
         .for_each(|res| async move {
             match res {
                 Ok(o) => info!("reconciled {:?}", o),
@@ -234,47 +2825,101 @@ pub async fn reconcile(client: Client) {
             }
         });
 
-    zone_controller.await;
+    let result = match deadline {
+        Some(deadline) => match futures::future::select(pin!(zone_controller), deadline).await {
+            Either::Left(_) => Ok(()),
+            Either::Right(_) => Err(Error::ShutdownTimeout(config.shutdown_timeout)),
+        },
+        None => {
+            zone_controller.await;
+            Ok(())
+        }
+    };
+
+    tasks.shutdown().await;
+
+    result
 }
 
 #[cfg(test)]
 mod tests {
-    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
-    use kubizone_crds::v1alpha1::ZoneEntry;
+    use std::collections::BTreeMap;
 
-    use super::build_zonefile;
+    use k8s_openapi::serde_json::json;
 
-    #[test]
-    fn zonefile_construction() {
-        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
-
-        let entries = vec![
-            ZoneEntry {
-                fqdn: FullyQualifiedDomainName::try_from("www.example.org.").unwrap(),
-                type_: Type::A,
-                class: Class::IN,
-                ttl: 360,
-                rdata: "127.0.0.1".to_string(),
-            },
-            ZoneEntry {
-                fqdn: FullyQualifiedDomainName::try_from("example.org.").unwrap(),
-                type_: Type::CNAME,
-                class: Class::IN,
-                ttl: 360,
-                rdata: "www.example.org.".to_string(),
-            },
-        ];
+    use super::{RenderedZonefile, SerializedZone};
+    use crate::{stats::ZoneStats, status};
 
-        let zonefile = build_zonefile(&origin, &entries);
+    fn serialized_zone(origin: &str, serial: u32) -> SerializedZone {
+        let contents = format!("$ORIGIN {origin}\n");
 
-        assert_eq!(
-            zonefile,
-            indoc::indoc! { r#"
-            $ORIGIN example.org.
+        SerializedZone {
+            zone: String::from("dns/example"),
+            trigger: None,
+            origin: origin.to_string(),
+            serial,
+            hash: format!("zone-{serial}"),
+            content_hash: format!("contents-{serial}"),
+            dual_stack_gaps: Vec::new(),
+            stats: ZoneStats::compute(&[], &contents, false),
+            contents,
+        }
+    }
 
-            www              360      IN A 127.0.0.1
-            @                360      IN CNAME www.example.org."#
-            }
+    #[test]
+    fn computes_the_status_of_published_zones() {
+        let rendered = RenderedZonefile {
+            serialized_zones: vec![serialized_zone("example.org.", 2)],
+            lint_dual_stack: true,
+            ..RenderedZonefile::default()
+        };
+
+        let status = rendered.status(Vec::new(), None);
+        assert_eq!(status["status"]["serial"], json!({ "example.org.": 2 }));
+        assert_eq!(
+            status["status"]["dualStackGaps"],
+            json!({ "example.org.": [] })
         );
+        // Fields of features which are off, or have nothing to report, are cleared.
+        assert_eq!(status["status"]["frozen"], json!(null));
+        assert_eq!(status["status"]["ptrMismatches"], json!(null));
+
+        let mut live = status.clone();
+        assert!(status::is_current(&live, &status));
+
+        // Statuses which lack a zone, or list one as frozen, are corrected.
+        live["status"]["serial"] = json!({ "example.org.": 1 });
+        assert!(!status::is_current(&live, &status));
+
+        let mut live = status.clone();
+        live["status"]["frozen"] = json!([{ "origin": "example.org.", "serial": 2 }]);
+        assert!(!status::is_current(&live, &status));
+
+        // Zones kept at their previous contents are left as they are in the live status.
+        let mut live = status.clone();
+        live["status"]["serial"]["example.net."] = json!(7);
+        assert!(status::is_current(&live, &status));
+    }
+
+    #[test]
+    fn status_reports_deferred_zones_and_outputs() {
+        let frozen = json!({
+            "origin": "example.org.",
+            "serial": 3,
+            "until": "2024-06-01T00:00:00+00:00",
+        });
+        let rendered = RenderedZonefile {
+            frozen: vec![frozen.clone()],
+            ptr_mismatches: Some(BTreeMap::new()),
+            ..RenderedZonefile::default()
+        };
+
+        let outputs = vec![json!({ "target": "configmap:dns/zones", "delivered": true })];
+        let status = rendered.status(Vec::new(), Some(outputs.clone()));
+
+        assert_eq!(status["status"]["frozen"], json!([frozen]));
+        assert_eq!(status["status"]["outputs"], json!(outputs));
+        assert_eq!(status["status"]["ptrMismatches"], json!({}));
+        assert_eq!(status["status"]["serial"], json!({}));
     }
 }
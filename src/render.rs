@@ -0,0 +1,1048 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+};
+
+use clap::{Args, ValueEnum};
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{normalize, service::ServiceRecord};
+
+/// Sequence terminating each line of a rendered zone.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    /// For consumers which only load zones with Windows line endings.
+    Crlf,
+}
+
+/// Characters a rendered zone may hold.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Charset {
+    #[default]
+    Utf8,
+    Ascii,
+}
+
+/// Produced when a rendered zone holds characters its consumers cannot load.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    #[error("line {line} contains control character {character:?}")]
+    ControlCharacter { line: usize, character: char },
+    #[error("line {line} contains non-ASCII character {character:?}")]
+    NonAscii { line: usize, character: char },
+}
+
+#[derive(Debug, Clone, Default, Hash, Args)]
+pub struct FormatArgs {
+    /// Leave out the class column when every record in the zone is of class IN.
+    #[arg(long, env = "ZONEFILE_OMIT_CLASS")]
+    pub omit_class: bool,
+
+    /// Declare the most common TTL of each zone using a `$TTL` directive,
+    /// and leave out the TTL column for records which use it.
+    #[arg(long, env = "ZONEFILE_DEFAULT_TTL")]
+    pub default_ttl: bool,
+
+    /// Print the owner name only on the first of consecutive records
+    /// sharing the same owner, leaving it blank on the following lines.
+    #[arg(long, env = "ZONEFILE_GROUP_OWNERS")]
+    pub group_owners: bool,
+
+    /// Split SOA records across multiple lines within parentheses,
+    /// annotating each of the numeric fields with a comment.
+    #[arg(long, env = "ZONEFILE_MULTILINE_SOA")]
+    pub multiline_soa: bool,
+
+    /// Expand ranges of records into individual records, rather than rendering
+    /// them as `$GENERATE` directives, for servers which do not support them.
+    #[arg(long, env = "ZONEFILE_EXPAND_GENERATE")]
+    pub expand_generate: bool,
+
+    /// Pad owner names to this many columns, rather than to the length of the
+    /// longest name in the zone, which takes an extra pass over its records.
+    /// Longer names push the rest of their line to the right.
+    #[arg(long, env = "ZONEFILE_NAME_WIDTH", conflicts_with = "no_align")]
+    pub name_width: Option<usize>,
+
+    /// Separate the columns of each record by a single space, rather than
+    /// aligning them, for zones which are only read by machines.
+    #[arg(long, env = "ZONEFILE_NO_ALIGN")]
+    pub no_align: bool,
+
+    /// Write domain names within the rdata of records relative to the origin when they
+    /// fall within the zone, as in `CNAME www` or `MX 10 @`, rather than in full.
+    #[arg(long, env = "ZONEFILE_RELATIVE_RDATA")]
+    pub relative_rdata: bool,
+
+    /// Render the records of each subtree of a zone holding at least this many
+    /// records in a section of its own, introduced by a `$ORIGIN` directive for
+    /// the subtree, so that their owner names are written relative to it.
+    #[arg(long, env = "ZONEFILE_SUB_ORIGIN_THRESHOLD")]
+    pub sub_origin_threshold: Option<usize>,
+
+    /// Line ending of rendered zones. Every line, including the last, is terminated by it.
+    #[arg(long, env = "ZONEFILE_LINE_ENDING", value_enum, default_value_t = LineEnding::Lf)]
+    pub line_ending: LineEnding,
+
+    /// Characters rendered zones may hold. Zones holding others are not published.
+    #[arg(long, env = "ZONEFILE_CHARSET", value_enum, default_value_t = Charset::Utf8)]
+    pub charset: Charset,
+}
+
+/// Comments rendered after the records they are attached to.
+#[derive(Debug, Clone, Default)]
+pub struct Comments(HashMap<(String, String, String, String), (ZoneEntry, String)>);
+
+impl Comments {
+    fn key(entry: &ZoneEntry) -> (String, String, String, String) {
+        (
+            entry.fqdn.to_string(),
+            entry.type_.to_string(),
+            entry.class.to_string(),
+            entry.rdata.clone(),
+        )
+    }
+
+    /// Attach the comment to the record. Comments must fit on the
+    /// line of the record, so line breaks are replaced by spaces.
+    pub fn insert(&mut self, entry: &ZoneEntry, comment: &str) {
+        let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        if !comment.is_empty() {
+            self.0.insert(Self::key(entry), (entry.clone(), comment));
+        }
+    }
+
+    /// Attach the comment to the record, after any comment it already has.
+    pub fn append(&mut self, entry: &ZoneEntry, comment: &str) {
+        let comment = match self.get(entry) {
+            Some(existing) => format!("{existing}; {comment}"),
+            None => comment.to_string(),
+        };
+
+        self.insert(entry, &comment);
+    }
+
+    fn get(&self, entry: &ZoneEntry) -> Option<&str> {
+        self.0
+            .get(&Self::key(entry))
+            .map(|(_, comment)| comment.as_str())
+    }
+
+    /// Comments on the records within the zone, attached to the records as they are
+    /// [canonicalized](normalize::canonicalize) before the zone is rendered.
+    pub fn canonicalize(&self, origin: &FullyQualifiedDomainName) -> Comments {
+        let (entries, comments): (Vec<_>, Vec<_>) = self
+            .0
+            .values()
+            .filter(|(entry, _)| entry.fqdn == *origin || entry.fqdn.is_subdomain_of(origin))
+            .cloned()
+            .unzip();
+
+        let mut canonical = Comments::default();
+        for (entry, comment) in normalize::canonicalize(origin, entries)
+            .iter()
+            .zip(comments)
+        {
+            canonical.insert(entry, &comment);
+        }
+
+        canonical
+    }
+}
+
+/// Terminate every line of the rendered zone, including the last one, with the line
+/// ending, and check that it holds no characters its consumers cannot load. Line
+/// breaks carried in from elsewhere, such as snippets, are normalized as well.
+pub fn finish(zone: &str, format: &FormatArgs) -> Result<String, EncodingError> {
+    let line_ending = match format.line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::Crlf => "\r\n",
+    };
+
+    let mut finished = String::with_capacity(zone.len() + zone.len() / 32);
+    for (index, line) in zone.lines().enumerate() {
+        if let Some(character) = line
+            .chars()
+            .find(|character| character.is_control() && *character != '\t')
+        {
+            return Err(EncodingError::ControlCharacter {
+                line: index + 1,
+                character,
+            });
+        }
+
+        if format.charset == Charset::Ascii {
+            if let Some(character) = line.chars().find(|character| !character.is_ascii()) {
+                return Err(EncodingError::NonAscii {
+                    line: index + 1,
+                    character,
+                });
+            }
+        }
+
+        finished.push_str(line);
+        finished.push_str(line_ending);
+    }
+
+    Ok(finished)
+}
+
+/// Name relative to the origin: `@` for the apex itself, the labels below the
+/// origin for names within the zone, and the absolute name for any other name,
+/// such as those of a parent zone when the origin is itself a subdomain.
+fn relative_name(origin: &FullyQualifiedDomainName, name: &FullyQualifiedDomainName) -> String {
+    if name == origin {
+        return String::from("@");
+    }
+
+    match name.clone() - origin.clone() {
+        Ok(partial) => partial.to_string(),
+        Err(full) => full.to_string(),
+    }
+}
+
+/// Absolute name relative to the origin, as [`relative_name`] writes it, for names
+/// holding service labels such as `_sip._tcp`, which are rejected as domain names.
+fn relative_text(origin: &FullyQualifiedDomainName, name: &str) -> String {
+    let origin = origin.to_string();
+
+    if name == origin {
+        String::from("@")
+    } else {
+        name.strip_suffix(&origin)
+            .and_then(|labels| labels.strip_suffix('.'))
+            .filter(|labels| !labels.is_empty())
+            .unwrap_or(name)
+            .to_string()
+    }
+}
+
+/// Rdata of a record of the type with the domain names within the zone written
+/// relative to the origin.
+fn relative_rdata(origin: &FullyQualifiedDomainName, type_: Type, rdata: &str) -> Option<String> {
+    normalize::map_hostnames(
+        type_,
+        rdata,
+        |field| match FullyQualifiedDomainName::try_from(field) {
+            Ok(name) => relative_name(origin, &name),
+            Err(_) => relative_text(origin, field),
+        },
+    )
+}
+
+/// Columns of a record preceding its rdata: its owner name, TTL, class and type.
+fn columns(
+    format: &FormatArgs,
+    name_width: usize,
+    omit_class: bool,
+    name: &str,
+    ttl: &str,
+    class: &Class,
+    type_: &Type,
+) -> String {
+    if format.no_align {
+        let mut columns = format!("{name} ");
+        if !ttl.is_empty() {
+            let _ = write!(columns, "{ttl} ");
+        }
+        if !omit_class {
+            let _ = write!(columns, "{class} ");
+        }
+        let _ = write!(columns, "{type_} ");
+        columns
+    } else if omit_class {
+        format!("{name:<width$} {ttl:<8} {type_:<6} ", width = name_width)
+    } else {
+        format!(
+            "{name:<width$} {ttl:<8} {class:<5} {type_:<6} ",
+            width = name_width
+        )
+    }
+}
+
+/// Origin each entry is rendered relative to: the deepest subtree of the zone holding
+/// at least `threshold` entries which the entry falls below, or else the zone's origin.
+fn sub_origins(
+    origin: &FullyQualifiedDomainName,
+    entries: &[ZoneEntry],
+    threshold: Option<usize>,
+) -> Vec<FullyQualifiedDomainName> {
+    let Some(threshold) = threshold else {
+        return vec![origin.clone(); entries.len()];
+    };
+
+    // Subtrees between the origin and the owner name of each entry, deepest first.
+    let subtrees = entries
+        .iter()
+        .map(|entry| {
+            let Ok(partial) = entry.fqdn.clone() - origin.clone() else {
+                return Vec::new();
+            };
+
+            let labels = partial.to_string();
+            let labels = labels.split('.').collect::<Vec<_>>();
+
+            (1..labels.len())
+                .map(|depth| match origin.to_string().as_str() {
+                    "." => format!("{}.", labels[depth..].join(".")),
+                    origin => format!("{}.{origin}", labels[depth..].join(".")),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut sizes = HashMap::<&str, usize>::new();
+    for subtree in subtrees.iter().flatten() {
+        *sizes.entry(subtree).or_default() += 1;
+    }
+
+    subtrees
+        .iter()
+        .map(|subtrees| {
+            subtrees
+                .iter()
+                .find(|subtree| sizes[subtree.as_str()] >= threshold)
+                .and_then(|subtree| FullyQualifiedDomainName::try_from(subtree.as_str()).ok())
+                .unwrap_or_else(|| origin.clone())
+        })
+        .collect()
+}
+
+/// The most frequently used TTL among the entries. Ties are broken by picking the lowest TTL.
+fn most_common_ttl(entries: &[ZoneEntry]) -> Option<u32> {
+    let mut ttls = BTreeMap::<u32, usize>::new();
+    for entry in entries {
+        *ttls.entry(entry.ttl).or_default() += 1;
+    }
+
+    ttls.into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(ttl, _)| ttl)
+}
+
+/// Render the rdata of an SOA record in parenthesized form, with each numeric field on
+/// its own line indented by `indent` columns. Returns `None` if the rdata is malformed.
+fn multiline_soa(indent: usize, rdata: &str) -> Option<String> {
+    let fields = rdata.split_whitespace().collect::<Vec<_>>();
+    let [mname, rname, serial, refresh, retry, expire, minimum] = fields.as_slice() else {
+        return None;
+    };
+
+    let indent = " ".repeat(indent);
+    let minimum = format!("{minimum} )");
+
+    Some(format!(
+        "{mname} {rname} (\n\
+         {indent}{serial:<10} ; serial\n\
+         {indent}{refresh:<10} ; refresh\n\
+         {indent}{retry:<10} ; retry\n\
+         {indent}{expire:<10} ; expire\n\
+         {indent}{minimum:<10} ; minimum"
+    ))
+}
+
+pub fn build_zonefile(
+    origin: &FullyQualifiedDomainName,
+    entries: &[ZoneEntry],
+    format: &FormatArgs,
+    comments: &Comments,
+) -> String {
+    build_zonefile_with_services(origin, entries, &[], format, comments)
+}
+
+/// Render the zone as [`build_zonefile`] does, followed by the records owned by
+/// names holding service labels, aligned with the others and owned by names
+/// written relative to the origin.
+pub fn build_zonefile_with_services(
+    origin: &FullyQualifiedDomainName,
+    entries: &[ZoneEntry],
+    services: &[ServiceRecord],
+    format: &FormatArgs,
+    comments: &Comments,
+) -> String {
+    // We use the longest domain name in the list for aligning the text
+    // in the output zonefile, unless a width is given or alignment is off.
+    let name_width = format.name_width.unwrap_or_else(|| {
+        if format.no_align {
+            return 0;
+        }
+
+        entries
+            .iter()
+            .map(|entry| entry.fqdn.to_string().len())
+            .chain(services.iter().map(|service| service.owner.len()))
+            .max()
+            .unwrap_or_default()
+    });
+
+    let omit_class = format.omit_class
+        && entries.iter().all(|entry| entry.class == Class::IN)
+        && services.iter().all(|service| service.class == Class::IN);
+
+    let default_ttl = format
+        .default_ttl
+        .then(|| most_common_ttl(entries))
+        .flatten();
+
+    let origins = sub_origins(origin, entries, format.sub_origin_threshold);
+
+    let mut serialized_records = entries
+        .iter()
+        .enumerate()
+        .map(
+            |(
+                index,
+                record @ ZoneEntry {
+                    fqdn,
+                    type_,
+                    class,
+                    ttl,
+                    rdata,
+                    ..
+                },
+            )| {
+                let record_origin = &origins[index];
+                let name = relative_name(record_origin, fqdn);
+
+                let continuation =
+                    format.group_owners && index > 0 && entries[index - 1].fqdn == *fqdn;
+
+                let entry = if continuation { "" } else { &name };
+
+                let ttl = if Some(*ttl) == default_ttl {
+                    String::new()
+                } else {
+                    ttl.to_string()
+                };
+
+                let prefix = columns(format, name_width, omit_class, entry, &ttl, class, type_);
+
+                let rdata = format
+                    .relative_rdata
+                    .then(|| relative_rdata(record_origin, *type_, rdata))
+                    .flatten()
+                    .unwrap_or_else(|| rdata.clone());
+
+                let rdata = (format.multiline_soa && *type_ == Type::SOA)
+                    .then(|| multiline_soa(prefix.len(), &rdata))
+                    .flatten()
+                    .unwrap_or(rdata);
+
+                let line = match comments.get(record) {
+                    Some(comment) => format!("{prefix}{rdata} ; {comment}"),
+                    None => format!("{prefix}{rdata}"),
+                };
+
+                match index.checked_sub(1).map(|previous| &origins[previous]) {
+                    None if record_origin != origin => format!("$ORIGIN {record_origin}\n{line}"),
+                    Some(previous) if previous != record_origin => {
+                        format!("\n$ORIGIN {record_origin}\n{line}")
+                    }
+                    _ => line,
+                }
+            },
+        )
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Whatever follows the records, such as `$GENERATE` directives,
+    // is relative to the origin of the zone again.
+    if origins.last().is_some_and(|last| last != origin) {
+        let _ = write!(serialized_records, "\n\n$ORIGIN {origin}");
+    }
+
+    for (index, service) in services.iter().enumerate() {
+        let name = if format.group_owners && index > 0 && services[index - 1].owner == service.owner
+        {
+            String::new()
+        } else {
+            relative_text(origin, &service.owner)
+        };
+
+        let ttl = if Some(service.ttl) == default_ttl {
+            String::new()
+        } else {
+            service.ttl.to_string()
+        };
+
+        let rdata = format
+            .relative_rdata
+            .then(|| relative_rdata(origin, service.type_, &service.rdata))
+            .flatten()
+            .unwrap_or_else(|| service.rdata.clone());
+
+        if !serialized_records.is_empty() {
+            serialized_records.push('\n');
+        }
+        let _ = write!(
+            serialized_records,
+            "{}{rdata}",
+            columns(
+                format,
+                name_width,
+                omit_class,
+                &name,
+                &ttl,
+                &service.class,
+                &service.type_
+            )
+        );
+    }
+
+    match default_ttl {
+        Some(ttl) => format!("$ORIGIN {origin}\n$TTL {ttl}\n\n{serialized_records}"),
+        None => format!("$ORIGIN {origin}\n\n{serialized_records}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::{
+        build_zonefile, build_zonefile_with_services, finish, Charset, Comments, EncodingError,
+        FormatArgs, LineEnding,
+    };
+    use crate::service::ServiceRecord;
+
+    #[test]
+    fn zonefile_construction() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entries = vec![
+            ZoneEntry {
+                fqdn: FullyQualifiedDomainName::try_from("www.example.org.").unwrap(),
+                type_: Type::A,
+                class: Class::IN,
+                ttl: 360,
+                rdata: "127.0.0.1".to_string(),
+            },
+            ZoneEntry {
+                fqdn: FullyQualifiedDomainName::try_from("example.org.").unwrap(),
+                type_: Type::CNAME,
+                class: Class::IN,
+                ttl: 360,
+                rdata: "www.example.org.".to_string(),
+            },
+        ];
+
+        let zonefile = build_zonefile(
+            &origin,
+            &entries,
+            &FormatArgs::default(),
+            &Comments::default(),
+        );
+
+        assert_eq!(
+            zonefile,
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            www              360      IN A 127.0.0.1
+            @                360      IN CNAME www.example.org."#
+            }
+        );
+    }
+
+    #[test]
+    fn compact_zonefile_construction() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, type_: Type, ttl: u32, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("www.example.org.", Type::A, 360, "127.0.0.1"),
+            entry("example.org.", Type::CNAME, 360, "www.example.org."),
+            entry("mail.example.org.", Type::A, 60, "127.0.0.2"),
+        ];
+
+        let zonefile = build_zonefile(
+            &origin,
+            &entries,
+            &FormatArgs {
+                omit_class: true,
+                default_ttl: true,
+                ..Default::default()
+            },
+            &Comments::default(),
+        );
+
+        assert_eq!(
+            zonefile,
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+            $TTL 360
+
+            www                        A 127.0.0.1
+            @                          CNAME www.example.org.
+            mail              60       A 127.0.0.2"#
+            }
+        );
+    }
+
+    #[test]
+    fn grouped_owner_names() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("www.example.org.", Type::A, "127.0.0.1"),
+            entry("www.example.org.", Type::AAAA, "::1"),
+            entry("example.org.", Type::CNAME, "www.example.org."),
+        ];
+
+        let zonefile = build_zonefile(
+            &origin,
+            &entries,
+            &FormatArgs {
+                group_owners: true,
+                ..Default::default()
+            },
+            &Comments::default(),
+        );
+
+        assert_eq!(
+            zonefile,
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            www              360      IN A 127.0.0.1
+                             360      IN AAAA ::1
+            @                360      IN CNAME www.example.org."#
+            }
+        );
+    }
+
+    #[test]
+    fn alignment() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, ttl: u32, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_: Type::A,
+            class: Class::IN,
+            ttl,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("www.example.org.", 360, "192.0.2.1"),
+            entry("www.example.org.", 60, "192.0.2.2"),
+            entry("example.org.", 360, "192.0.2.3"),
+        ];
+
+        let fixed = build_zonefile(
+            &origin,
+            &entries,
+            &FormatArgs {
+                name_width: Some(4),
+                ..Default::default()
+            },
+            &Comments::default(),
+        );
+
+        assert_eq!(
+            fixed,
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            www  360      IN A 192.0.2.1
+            www  60       IN A 192.0.2.2
+            @    360      IN A 192.0.2.3"#
+            }
+        );
+
+        let unaligned = build_zonefile(
+            &origin,
+            &entries,
+            &FormatArgs {
+                no_align: true,
+                default_ttl: true,
+                group_owners: true,
+                ..Default::default()
+            },
+            &Comments::default(),
+        );
+
+        assert_eq!(
+            unaligned,
+            "$ORIGIN example.org.\n$TTL 360\n\nwww IN A 192.0.2.1\n 60 IN A 192.0.2.2\n@ IN A 192.0.2.3"
+        );
+    }
+
+    #[test]
+    fn record_comments() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_: Type::A,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("legacy.example.org.", "192.0.2.1"),
+            entry("legacy.example.org.", "192.0.2.2"),
+        ];
+
+        let mut comments = Comments::default();
+        comments.insert(
+            &entries[0],
+            "kept for the old billing system,\n  see ticket 42",
+        );
+        comments.append(&entries[0], "ttl 60 overridden by template");
+        comments.insert(&entries[1], " ");
+        comments.append(&entries[1], "ttl 3600 overridden by template");
+
+        let zonefile = build_zonefile(&origin, &entries, &FormatArgs::default(), &comments);
+
+        assert_eq!(
+            zonefile,
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            legacy              360      IN A 192.0.2.1 ; kept for the old billing system, see ticket 42; ttl 60 overridden by template
+            legacy              360      IN A 192.0.2.2 ; ttl 3600 overridden by template"#
+            }
+        );
+    }
+
+    #[test]
+    fn comments_follow_canonical_records() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_: Type::AAAA,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let mut comments = Comments::default();
+        comments.insert(&entry("www.example.org.", "2001:DB8:0::1"), "web");
+        comments.insert(&entry("www.example.com.", "2001:db8::2"), "elsewhere");
+
+        let comments = comments.canonicalize(&origin);
+        assert_eq!(
+            comments.get(&entry("www.example.org.", "2001:db8::1")),
+            Some("web")
+        );
+        assert_eq!(
+            comments.get(&entry("www.example.com.", "2001:db8::2")),
+            None
+        );
+    }
+
+    #[test]
+    fn multiline_soa_record() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entries = vec![ZoneEntry {
+            fqdn: origin.clone(),
+            type_: Type::SOA,
+            class: Class::IN,
+            ttl: 360,
+            rdata: "ns1.example.org. hostmaster.example.org. 2024010101 86400 7200 3600000 360"
+                .to_string(),
+        }];
+
+        let zonefile = build_zonefile(
+            &origin,
+            &entries,
+            &FormatArgs {
+                multiline_soa: true,
+                ..Default::default()
+            },
+            &Comments::default(),
+        );
+
+        assert_eq!(
+            zonefile,
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            @            360      IN SOA ns1.example.org. hostmaster.example.org. (
+                                         2024010101 ; serial
+                                         86400      ; refresh
+                                         7200       ; retry
+                                         3600000    ; expire
+                                         360 )      ; minimum"#
+            }
+        );
+    }
+
+    #[test]
+    fn relative_names_and_rdata() {
+        // A zone delegated from example.org., referring to names of its parent.
+        let origin = FullyQualifiedDomainName::try_from("sub.example.org.").unwrap();
+
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("sub.example.org.", Type::NS, "ns1.sub.example.org."),
+            entry("sub.example.org.", Type::NS, "ns.example.org."),
+            entry("sub.example.org.", Type::MX, "10 sub.example.org."),
+            entry("www.sub.example.org.", Type::CNAME, "www.example.org."),
+            entry(
+                "api.sub.example.org.",
+                Type::CNAME,
+                "lb.eu.sub.example.org.",
+            ),
+            entry(
+                "acme.sub.example.org.",
+                Type::CNAME,
+                "_acme-challenge.sub.example.org.",
+            ),
+            entry(
+                "sip.sub.example.org.",
+                Type::CNAME,
+                "_sip._tcp.example.org.",
+            ),
+            entry("ns1.sub.example.org.", Type::A, "192.0.2.1"),
+        ];
+
+        let format = FormatArgs {
+            no_align: true,
+            ..FormatArgs::default()
+        };
+
+        assert_eq!(
+            build_zonefile(&origin, &entries, &format, &Comments::default()),
+            indoc::indoc! { r#"
+            $ORIGIN sub.example.org.
+
+            @ 360 IN NS ns1.sub.example.org.
+            @ 360 IN NS ns.example.org.
+            @ 360 IN MX 10 sub.example.org.
+            www 360 IN CNAME www.example.org.
+            api 360 IN CNAME lb.eu.sub.example.org.
+            acme 360 IN CNAME _acme-challenge.sub.example.org.
+            sip 360 IN CNAME _sip._tcp.example.org.
+            ns1 360 IN A 192.0.2.1"#
+            }
+        );
+
+        let format = FormatArgs {
+            relative_rdata: true,
+            ..format
+        };
+
+        assert_eq!(
+            build_zonefile(&origin, &entries, &format, &Comments::default()),
+            indoc::indoc! { r#"
+            $ORIGIN sub.example.org.
+
+            @ 360 IN NS ns1
+            @ 360 IN NS ns.example.org.
+            @ 360 IN MX 10 @
+            www 360 IN CNAME www.example.org.
+            api 360 IN CNAME lb.eu
+            acme 360 IN CNAME _acme-challenge
+            sip 360 IN CNAME _sip._tcp.example.org.
+            ns1 360 IN A 192.0.2.1"#
+            }
+        );
+    }
+
+    #[test]
+    fn service_records() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("example.org.", Type::MX, "10 mail.example.org."),
+            entry("mail.example.org.", Type::A, "192.0.2.1"),
+        ];
+
+        let service = |owner: &str, type_: Type, rdata: &str| ServiceRecord {
+            owner: owner.to_string(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let services = vec![
+            service("_acme-challenge.mail.example.org.", Type::TXT, "\"token\""),
+            service("_dmarc.example.org.", Type::TXT, "\"v=DMARC1; p=none\""),
+            service(
+                "_sip._tcp.example.org.",
+                Type::SRV,
+                "0 5 5060 mail.example.org.",
+            ),
+            service(
+                "_sip._tcp.example.org.",
+                Type::SRV,
+                "10 5 5060 sip.example.net.",
+            ),
+        ];
+
+        assert_eq!(
+            build_zonefile_with_services(
+                &origin,
+                &entries,
+                &services,
+                &FormatArgs {
+                    group_owners: true,
+                    relative_rdata: true,
+                    ..Default::default()
+                },
+                &Comments::default(),
+            ),
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            @                                 360      IN MX 10 mail
+            mail                              360      IN A 192.0.2.1
+            _acme-challenge.mail              360      IN TXT "token"
+            _dmarc                            360      IN TXT "v=DMARC1; p=none"
+            _sip._tcp                         360      IN SRV 0 5 5060 mail
+                                              360      IN SRV 10 5 5060 sip.example.net."#
+            }
+        );
+    }
+
+    #[test]
+    fn sub_origin_sections() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("example.org.", Type::NS, "ns1.example.org."),
+            entry("www.example.org.", Type::A, "192.0.2.1"),
+            entry("a.svc.cluster.example.org.", Type::A, "10.0.0.1"),
+            entry("b.svc.cluster.example.org.", Type::A, "10.0.0.2"),
+            entry("one.db.svc.cluster.example.org.", Type::A, "10.0.0.3"),
+            entry(
+                "c.svc.cluster.example.org.",
+                Type::CNAME,
+                "a.svc.cluster.example.org.",
+            ),
+            entry("mail.example.org.", Type::A, "192.0.2.2"),
+        ];
+
+        let format = FormatArgs {
+            no_align: true,
+            relative_rdata: true,
+            sub_origin_threshold: Some(3),
+            ..FormatArgs::default()
+        };
+
+        assert_eq!(
+            build_zonefile(&origin, &entries, &format, &Comments::default()),
+            indoc::indoc! { r#"
+            $ORIGIN example.org.
+
+            @ 360 IN NS ns1
+            www 360 IN A 192.0.2.1
+
+            $ORIGIN svc.cluster.example.org.
+            a 360 IN A 10.0.0.1
+            b 360 IN A 10.0.0.2
+            one.db 360 IN A 10.0.0.3
+            c 360 IN CNAME a
+
+            $ORIGIN example.org.
+            mail 360 IN A 192.0.2.2"#
+            }
+        );
+
+        // Directives following the records are relative to the zone's origin.
+        assert!(
+            build_zonefile(&origin, &entries[..6], &format, &Comments::default())
+                .ends_with("c 360 IN CNAME a\n\n$ORIGIN example.org.")
+        );
+    }
+
+    #[test]
+    fn finished_zones() {
+        let zone = "$ORIGIN example.org.\n\nwww IN TXT \"caf\u{e9}\"\r\n@ IN NS ns1";
+
+        assert_eq!(
+            finish(zone, &FormatArgs::default()),
+            Ok(String::from(
+                "$ORIGIN example.org.\n\nwww IN TXT \"caf\u{e9}\"\n@ IN NS ns1\n"
+            ))
+        );
+
+        let crlf = FormatArgs {
+            line_ending: LineEnding::Crlf,
+            ..FormatArgs::default()
+        };
+        assert_eq!(
+            finish("@ IN NS ns1\n", &crlf),
+            Ok(String::from("@ IN NS ns1\r\n"))
+        );
+
+        let ascii = FormatArgs {
+            charset: Charset::Ascii,
+            ..FormatArgs::default()
+        };
+        assert_eq!(
+            finish(zone, &ascii),
+            Err(EncodingError::NonAscii {
+                line: 3,
+                character: '\u{e9}'
+            })
+        );
+
+        assert_eq!(
+            finish("@ IN TXT \"a\u{0}b\"", &FormatArgs::default()),
+            Err(EncodingError::ControlCharacter {
+                line: 1,
+                character: '\u{0}'
+            })
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::{core::ObjectMeta, runtime::reflector::ObjectRef};
+use zonefile_crds::ZoneFile;
+
+use crate::metrics;
+
+/// Time of the most recent change to the object, as far as its metadata tells.
+///
+/// Managed fields are timestamped on every write by their manager, with
+/// a resolution of one second, which is the best the API server offers.
+fn last_change(meta: &ObjectMeta) -> Option<DateTime<Utc>> {
+    meta.managed_fields
+        .iter()
+        .flatten()
+        .filter_map(|entry| entry.time.as_ref())
+        .chain(&meta.deletion_timestamp)
+        .chain(&meta.creation_timestamp)
+        .map(|time| time.0)
+        .max()
+}
+
+/// Record the delay between the last change to an object and the controller
+/// receiving the watch event for it. Objects which are only listed, as when
+/// a watch is restarted, should not be observed, since they may have been
+/// unchanged for a long time.
+pub fn observe_watch_lag(kind: &str, meta: &ObjectMeta) {
+    let Some(changed) = last_change(meta) else {
+        return;
+    };
+
+    let lag = (Utc::now() - changed).num_milliseconds().max(0) as f64 / 1000.0;
+    metrics::WATCH_LAG.set(&[("resource", kind)], lag);
+}
+
+/// Zonefiles whose reconciliation was triggered by a change to them or to
+/// the resources they depend on, but has not started yet.
+///
+/// Like the controller's own queue, a zonefile is counted once no matter how
+/// many changes triggered it. Scheduled retries and periodic reconciliations
+/// are not counted until they start.
+#[derive(Default)]
+pub struct ReconcileQueue {
+    pending: Mutex<HashSet<ObjectRef<ZoneFile>>>,
+}
+
+impl ReconcileQueue {
+    pub fn push(&self, zonefiles: impl IntoIterator<Item = ObjectRef<ZoneFile>>) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.extend(zonefiles);
+
+        metrics::RECONCILE_QUEUE_DEPTH.set(&[], pending.len() as f64);
+    }
+
+    /// Mark the reconciliation of the zonefile as started.
+    pub fn pop(&self, zonefile: &ObjectRef<ZoneFile>) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.remove(zonefile);
+
+        metrics::RECONCILE_QUEUE_DEPTH.set(&[], pending.len() as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::{
+        apimachinery::pkg::apis::meta::v1::{ManagedFieldsEntry, Time},
+        chrono::{TimeZone as _, Utc},
+    };
+    use kube::core::ObjectMeta;
+
+    use super::last_change;
+
+    #[test]
+    fn finds_last_change() {
+        let time = |minute| Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, 0).unwrap();
+
+        let mut meta = ObjectMeta {
+            creation_timestamp: Some(Time(time(0))),
+            ..ObjectMeta::default()
+        };
+        assert_eq!(last_change(&meta), Some(time(0)));
+
+        meta.managed_fields = Some(vec![
+            ManagedFieldsEntry {
+                time: Some(Time(time(5))),
+                ..ManagedFieldsEntry::default()
+            },
+            ManagedFieldsEntry {
+                time: Some(Time(time(3))),
+                ..ManagedFieldsEntry::default()
+            },
+        ]);
+        assert_eq!(last_change(&meta), Some(time(5)));
+
+        meta.deletion_timestamp = Some(Time(time(7)));
+        assert_eq!(last_change(&meta), Some(time(7)));
+
+        assert_eq!(last_change(&ObjectMeta::default()), None);
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+
+use clap::Args;
+use futures::{channel::mpsc, StreamExt as _};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::{
+    runtime::{
+        reflector::{self, Store},
+        watcher,
+    },
+    Api, Client, ResourceExt as _,
+};
+use kubizone_crds::{
+    kubizone_common::FullyQualifiedDomainName,
+    v1alpha1::{Record, Zone, ZoneEntry},
+};
+use tokio::task::JoinSet;
+use tracing::log::*;
+
+use crate::{normalize, reconciliation::watch_events};
+
+/// Annotation on a [`Record`] holding the RFC 3339 time before which
+/// the record is left out of the zone, as in `2024-06-01T00:00:00Z`.
+pub const NOT_BEFORE_ANNOTATION: &str = "zonefile.kubi.zone/not-before";
+
+/// Annotation on a [`Record`] holding the RFC 3339 time from which
+/// the record is left out of the zone again.
+pub const NOT_AFTER_ANNOTATION: &str = "zonefile.kubi.zone/not-after";
+
+#[derive(Debug, Clone, Args)]
+pub struct ScheduleArgs {
+    /// Publish Records annotated with `zonefile.kubi.zone/not-before` or
+    /// `zonefile.kubi.zone/not-after` only within their validity window, for
+    /// planned migrations and temporary verification records. Requires access
+    /// to Records in all namespaces.
+    #[arg(long, env = "ZONEFILE_SCHEDULED_RECORDS")]
+    pub scheduled_records: bool,
+}
+
+/// Period during which a record is published.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Window {
+    pub not_before: Option<DateTime<Utc>>,
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+fn parse_time(annotation: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("invalid {annotation} {value:?}: {e}"))
+}
+
+impl Window {
+    /// Validity window of the record, if it is annotated with one.
+    pub fn of(record: &Record) -> Result<Option<Self>, String> {
+        let annotation = |annotation| {
+            record
+                .annotations()
+                .get(annotation)
+                .map(|value| parse_time(annotation, value))
+                .transpose()
+        };
+
+        let window = Window {
+            not_before: annotation(NOT_BEFORE_ANNOTATION)?,
+            not_after: annotation(NOT_AFTER_ANNOTATION)?,
+        };
+
+        if let (Some(not_before), Some(not_after)) = (window.not_before, window.not_after) {
+            if not_after <= not_before {
+                return Err(format!(
+                    "{NOT_AFTER_ANNOTATION} {not_after} is not later than {NOT_BEFORE_ANNOTATION} {not_before}"
+                ));
+            }
+        }
+
+        Ok((window != Window::default()).then_some(window))
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.not_before
+            .into_iter()
+            .all(|not_before| not_before <= time)
+            && self.not_after.into_iter().all(|not_after| time < not_after)
+    }
+
+    /// Next time after `time` at which the record appears or disappears.
+    pub fn next_boundary(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        [self.not_before, self.not_after]
+            .into_iter()
+            .flatten()
+            .filter(|boundary| *boundary > time)
+            .min()
+    }
+}
+
+/// Validity windows of the records of a zone, by the canonical form of the
+/// zone entry matching each record, so that entries match the records they
+/// came from however either of them is written.
+#[derive(Debug, Clone, Default)]
+pub struct RecordWindows {
+    /// Origin of the zone, which relative names within rdata are qualified with.
+    origin: String,
+    windows: HashMap<(String, String, String, String), Window>,
+}
+
+impl RecordWindows {
+    pub fn new(origin: &FullyQualifiedDomainName) -> Self {
+        RecordWindows {
+            origin: origin.to_string(),
+            windows: HashMap::new(),
+        }
+    }
+
+    fn key(&self, entry: &ZoneEntry) -> (String, String, String, String) {
+        (
+            normalize::to_ascii(&entry.fqdn.to_string()),
+            entry.type_.to_string(),
+            entry.class.to_string(),
+            normalize::canonical_rdata(&self.origin, entry.type_, &entry.rdata),
+        )
+    }
+
+    pub fn insert(&mut self, entry: &ZoneEntry, window: Window) {
+        self.windows.insert(self.key(entry), window);
+    }
+
+    /// Remove the entries outside of their validity window at the time. Returns
+    /// the next time at which any of the entries appears or disappears.
+    pub fn apply(
+        &self,
+        entries: &mut Vec<ZoneEntry>,
+        time: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let mut next = None;
+
+        entries.retain(|entry| {
+            let Some(window) = self.windows.get(&self.key(entry)) else {
+                return true;
+            };
+
+            next = next.into_iter().chain(window.next_boundary(time)).min();
+            window.contains(time)
+        });
+
+        next
+    }
+}
+
+/// Records of the cluster, kept up to date by [`ScheduleArgs::watch`].
+#[derive(Clone)]
+pub struct ScheduledRecords {
+    store: Store<Record>,
+}
+
+/// Whether the record belongs to the zone of the namespace, name and origin: either
+/// because it references the zone, or because it is in the namespace of the zone
+/// without referencing any, and its name falls within the origin.
+fn owned_by(
+    record: &Record,
+    fqdn: &FullyQualifiedDomainName,
+    namespace: &str,
+    name: &str,
+    origin: &FullyQualifiedDomainName,
+) -> bool {
+    let record_namespace = record.namespace().unwrap_or_default();
+
+    match &record.spec.zone_ref {
+        Some(zone_ref) => {
+            zone_ref.name == name
+                && zone_ref.namespace.as_deref().unwrap_or(&record_namespace) == namespace
+        }
+        None => record_namespace == namespace && (fqdn == origin || fqdn.is_subdomain_of(origin)),
+    }
+}
+
+impl ScheduledRecords {
+    /// Validity windows of the records belonging to the zone, so that Records
+    /// elsewhere cannot remove entries from it. Windows apply to the zone entries
+    /// matching the records they annotate once both are canonicalized, and are
+    /// applied before pre-render hooks, so entries rewritten by a hook are still
+    /// scheduled. Records with invalid windows are published regardless.
+    pub fn windows(&self, zone: &Zone) -> RecordWindows {
+        let (Some(namespace), Some(origin)) = (zone.namespace(), zone.fqdn()) else {
+            return RecordWindows::default();
+        };
+        let name = zone.name_any();
+
+        let mut windows = RecordWindows::new(origin);
+
+        for record in self.store.state() {
+            let Some(fqdn) = record.fqdn() else {
+                continue;
+            };
+
+            if !owned_by(&record, fqdn, &namespace, &name, origin) {
+                continue;
+            }
+
+            let window = match Window::of(&record) {
+                Ok(Some(window)) => window,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(
+                        "record {}/{} has an invalid validity window: {e}",
+                        record.namespace().unwrap_or_default(),
+                        record.name_any()
+                    );
+                    continue;
+                }
+            };
+
+            let entry = ZoneEntry {
+                fqdn: fqdn.clone(),
+                type_: record.spec.type_,
+                class: record.spec.class,
+                ttl: record.spec.ttl.unwrap_or_default(),
+                rdata: record.spec.rdata.clone(),
+            };
+
+            windows.insert(&entry, window);
+        }
+
+        windows
+    }
+}
+
+impl ScheduleArgs {
+    /// Start watching the records of the cluster, if enabled. Returns the
+    /// records, along with a stream which yields whenever they change.
+    pub fn watch(
+        &self,
+        client: Client,
+        config: watcher::Config,
+        tasks: &mut JoinSet<()>,
+    ) -> Option<(ScheduledRecords, mpsc::UnboundedReceiver<()>)> {
+        if !self.scheduled_records {
+            return None;
+        }
+
+        let (store, writer) = reflector::store();
+
+        let (changed, receiver) = mpsc::unbounded();
+        tasks.spawn(
+            reflector::reflector(writer, watch_events(Api::<Record>::all(client), config))
+                .for_each(move |event| {
+                    match event {
+                        Ok(_) => {
+                            let _ = changed.unbounded_send(());
+                        }
+                        Err(e) => warn!("failed to watch records: {e}"),
+                    }
+
+                    futures::future::ready(())
+                }),
+        );
+
+        Some((ScheduledRecords { store }, receiver))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::{
+        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+        chrono::{DateTime, Utc},
+    };
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::{Record, RecordSpec, ZoneEntry, ZoneRef};
+
+    use super::{owned_by, RecordWindows, Window};
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn entry(fqdn: &str, rdata: &str) -> ZoneEntry {
+        ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(fqdn).unwrap(),
+            type_: Type::A,
+            class: Class::IN,
+            ttl: 300,
+            rdata: rdata.to_string(),
+        }
+    }
+
+    #[test]
+    fn publishes_records_within_their_window() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+        let mut windows = RecordWindows::new(&origin);
+        windows.insert(
+            &entry("new.example.org.", "192.0.2.2"),
+            Window {
+                not_before: Some(time("2024-06-01T00:00:00Z")),
+                not_after: None,
+            },
+        );
+        windows.insert(
+            &entry("old.example.org.", "192.0.2.1"),
+            Window {
+                not_before: None,
+                not_after: Some(time("2024-06-01T12:00:00Z")),
+            },
+        );
+
+        let entries = vec![
+            entry("www.example.org.", "192.0.2.3"),
+            entry("new.example.org.", "192.0.2.2"),
+            entry("old.example.org.", "192.0.2.1"),
+        ];
+
+        let published = |now: &str| {
+            let mut entries = entries.clone();
+            let next = windows.apply(&mut entries, time(now));
+            let names: Vec<String> = entries.iter().map(|entry| entry.fqdn.to_string()).collect();
+            (names, next)
+        };
+
+        assert_eq!(
+            published("2024-05-31T00:00:00Z"),
+            (
+                vec![
+                    String::from("www.example.org."),
+                    String::from("old.example.org.")
+                ],
+                Some(time("2024-06-01T00:00:00Z"))
+            )
+        );
+        assert_eq!(
+            published("2024-06-01T06:00:00Z"),
+            (
+                vec![
+                    String::from("www.example.org."),
+                    String::from("new.example.org."),
+                    String::from("old.example.org.")
+                ],
+                Some(time("2024-06-01T12:00:00Z"))
+            )
+        );
+        assert_eq!(
+            published("2024-06-01T12:00:00Z"),
+            (
+                vec![
+                    String::from("www.example.org."),
+                    String::from("new.example.org.")
+                ],
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn matches_canonical_entries() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+        let mut windows = RecordWindows::new(&origin);
+        let mx = |name: &str, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_: Type::MX,
+            class: Class::IN,
+            ttl: 300,
+            rdata: rdata.to_string(),
+        };
+
+        windows.insert(
+            &mx("Mail.Example.org.", "10   MX1"),
+            Window {
+                not_before: None,
+                not_after: Some(time("2024-06-01T00:00:00Z")),
+            },
+        );
+
+        let mut entries = vec![
+            mx("mail.example.org.", "10 mx1.example.org."),
+            mx("mail.example.org.", "20 mx2.example.org."),
+        ];
+        windows.apply(&mut entries, time("2024-06-01T00:00:00Z"));
+
+        let rdata: Vec<&str> = entries.iter().map(|entry| entry.rdata.as_str()).collect();
+        assert_eq!(rdata, vec!["20 mx2.example.org."]);
+    }
+
+    #[test]
+    fn records_belong_to_their_zone() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+        let fqdn = FullyQualifiedDomainName::try_from("www.example.org.").unwrap();
+
+        let record = |namespace: &str, zone_ref: Option<ZoneRef>| Record {
+            metadata: ObjectMeta {
+                name: Some(String::from("www")),
+                namespace: Some(namespace.to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: RecordSpec {
+                zone_ref,
+                ..RecordSpec::default()
+            },
+            status: None,
+        };
+        let zone_ref = |namespace: Option<&str>| ZoneRef {
+            name: String::from("example-org"),
+            namespace: namespace.map(String::from),
+        };
+
+        let owned = |record: &Record| owned_by(record, &fqdn, "dns", "example-org", &origin);
+
+        assert!(owned(&record("dns", None)));
+        assert!(owned(&record("dns", Some(zone_ref(None)))));
+        assert!(owned(&record("tenant", Some(zone_ref(Some("dns"))))));
+
+        // Records of other tenants, or referencing other zones, do not belong to it.
+        assert!(!owned(&record("tenant", None)));
+        assert!(!owned(&record("tenant", Some(zone_ref(None)))));
+        assert!(!owned(&record(
+            "dns",
+            Some(ZoneRef {
+                name: String::from("example-net"),
+                namespace: None,
+            })
+        )));
+        assert!(!owned_by(
+            &record("dns", None),
+            &FullyQualifiedDomainName::try_from("www.example.net.").unwrap(),
+            "dns",
+            "example-org",
+            &origin
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding a comma-separated
+/// list of origins whose serial may be published even though it is lower than the
+/// serial published before. It should be removed once the zones have been published.
+pub const ALLOW_SERIAL_ROLLBACK_ANNOTATION: &str = "zonefile.kubi.zone/allow-serial-rollback";
+
+/// Whether serial `a` precedes serial `b`, using the serial number
+/// arithmetic of [RFC 1982](https://www.rfc-editor.org/rfc/rfc1982),
+/// under which serials wrap around rather than overflow.
+pub fn precedes(a: u32, b: u32) -> bool {
+    a != b && b.wrapping_sub(a) < 1 << 31
+}
+
+#[cfg(test)]
+mod tests {
+    use super::precedes;
+
+    #[test]
+    fn serial_arithmetic() {
+        assert!(precedes(1, 2));
+        assert!(!precedes(2, 1));
+        assert!(!precedes(2, 2));
+
+        // Serials wrap around.
+        assert!(precedes(u32::MAX, 0));
+        assert!(!precedes(0, u32::MAX));
+
+        assert!(precedes(2024010100, 2024010101));
+        assert!(!precedes(2024010101, 2024010100));
+    }
+}
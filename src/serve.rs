@@ -0,0 +1,409 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use clap::Args;
+use hickory_proto::{
+    rr::{LowerName, Name},
+    rustls::tls_server,
+    serialize::txt::Parser,
+};
+use hickory_server::{
+    authority::{Authority as _, Catalog, ZoneType},
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+    store::in_memory::InMemoryAuthority,
+    ServerFuture,
+};
+use tokio::{
+    net::{TcpListener, UdpSocket},
+    sync::RwLock,
+    task::JoinSet,
+};
+use tracing::log::*;
+
+use crate::error::Error;
+
+/// Connections which do not send a request within this time are closed.
+const TCP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Args)]
+pub struct ServeArgs {
+    /// Address on which to answer queries for the published zones over UDP and TCP.
+    #[arg(long, env = "ZONEFILE_DNS_ADDRESS")]
+    pub dns_address: Option<SocketAddr>,
+
+    /// Address on which to answer queries for the published zones over TLS,
+    /// usually on port 853. Requires --tls-certificate and --tls-key.
+    #[arg(long, env = "ZONEFILE_DOT_ADDRESS", requires_all = ["tls_certificate", "tls_key"])]
+    pub dot_address: Option<SocketAddr>,
+
+    /// Address on which to answer queries for the published zones over HTTPS,
+    /// at `/dns-query`. Requires --tls-certificate and --tls-key.
+    #[arg(long, env = "ZONEFILE_DOH_ADDRESS", requires_all = ["tls_certificate", "tls_key"])]
+    pub doh_address: Option<SocketAddr>,
+
+    /// PEM file holding the certificate chain presented to DNS-over-TLS and
+    /// DNS-over-HTTPS clients.
+    #[arg(long, env = "ZONEFILE_TLS_CERTIFICATE")]
+    pub tls_certificate: Option<PathBuf>,
+
+    /// PEM file holding the private key of the certificate.
+    #[arg(long, env = "ZONEFILE_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+}
+
+impl ServeArgs {
+    fn enabled(&self) -> bool {
+        self.dns_address.is_some() || self.dot_address.is_some() || self.doh_address.is_some()
+    }
+}
+
+/// Zones answered for by the built-in nameserver, kept up to date with the
+/// zones published by the controller.
+#[derive(Default)]
+pub struct ServedZones {
+    catalog: RwLock<Catalog>,
+    /// Zonefile publishing each served origin.
+    zonefiles: Mutex<BTreeMap<String, String>>,
+}
+
+impl ServedZones {
+    /// Serve the zones rendered for the zonefile, and stop serving any zones
+    /// which are not among the origins it still publishes. Zones published at
+    /// their last contents rather than rendered anew are served as they are.
+    pub async fn publish<'a>(
+        &self,
+        zonefile: &str,
+        published: impl IntoIterator<Item = (&'a str, &'a str)>,
+        origins: &[&str],
+    ) {
+        let mut authorities = Vec::new();
+        for (origin, contents) in published {
+            match authority(origin, contents) {
+                Ok(authority) => authorities.push((origin.to_string(), authority)),
+                Err(e) => warn!("not serving zone {origin} of {zonefile}: {e}"),
+            }
+        }
+
+        let removed = {
+            let mut zonefiles = self.zonefiles.lock().unwrap();
+            let removed: Vec<String> = zonefiles
+                .iter()
+                .filter(|(origin, owner)| {
+                    *owner == zonefile
+                        && !origins.contains(&origin.as_str())
+                        && !authorities.iter().any(|(served, _)| served == *origin)
+                })
+                .map(|(origin, _)| origin.clone())
+                .collect();
+
+            zonefiles.retain(|origin, _| !removed.contains(origin));
+            for (origin, _) in &authorities {
+                zonefiles.insert(origin.clone(), zonefile.to_string());
+            }
+
+            removed
+        };
+
+        let mut catalog = self.catalog.write().await;
+        for origin in removed {
+            if let Ok(name) = Name::from_ascii(&origin) {
+                catalog.remove(&LowerName::new(&name));
+            }
+        }
+
+        for (origin, authority) in authorities {
+            catalog.upsert(authority.origin().clone(), Box::new(Arc::new(authority)));
+            debug!("serving zone {origin} of {zonefile}");
+        }
+    }
+
+    /// Serve the zones which the zonefile keeps publishing at their last contents,
+    /// unless they are served already. Such zones are not rendered anew, so after
+    /// a restart, they would otherwise go unanswered until they are.
+    pub async fn seed<'a>(
+        &self,
+        zonefile: &str,
+        retained: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) {
+        let mut authorities = Vec::new();
+        {
+            let mut zonefiles = self.zonefiles.lock().unwrap();
+            for (origin, contents) in retained {
+                if zonefiles.contains_key(origin) {
+                    continue;
+                }
+
+                match authority(origin, contents) {
+                    Ok(authority) => {
+                        zonefiles.insert(origin.to_string(), zonefile.to_string());
+                        authorities.push((origin.to_string(), authority));
+                    }
+                    Err(e) => warn!("not serving zone {origin} of {zonefile}: {e}"),
+                }
+            }
+        }
+
+        let mut catalog = self.catalog.write().await;
+        for (origin, authority) in authorities {
+            catalog.upsert(authority.origin().clone(), Box::new(Arc::new(authority)));
+            debug!("serving zone {origin} of {zonefile} at its last published contents");
+        }
+    }
+}
+
+/// Parse the rendered contents of a zone into an authority answering for it.
+fn authority(origin: &str, contents: &str) -> Result<InMemoryAuthority, String> {
+    let origin = Name::from_ascii(origin).map_err(|e| e.to_string())?;
+
+    let (origin, records) = Parser::new(contents, None, Some(origin))
+        .parse()
+        .map_err(|e| e.to_string())?;
+
+    InMemoryAuthority::new(origin, records, ZoneType::Primary, false)
+}
+
+/// Answers queries from the served zones, which are replaced as they are published.
+struct Handler(Arc<ServedZones>);
+
+#[async_trait::async_trait]
+impl RequestHandler for Handler {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        self.0
+            .catalog
+            .read()
+            .await
+            .handle_request(request, response_handle)
+            .await
+    }
+}
+
+/// Start the nameserver on the configured addresses, if any, as one of the tasks.
+pub async fn serve(
+    args: &ServeArgs,
+    tasks: &mut JoinSet<()>,
+) -> Result<Option<Arc<ServedZones>>, Error> {
+    if !args.enabled() {
+        return Ok(None);
+    }
+
+    let zones = Arc::new(ServedZones::default());
+    let mut server = ServerFuture::new(Handler(zones.clone()));
+
+    let bind_failed = |address: SocketAddr, e: std::io::Error| {
+        Error::Config(format!("failed to listen on {address}: {e}"))
+    };
+
+    if let Some(address) = args.dns_address {
+        server.register_socket(
+            UdpSocket::bind(address)
+                .await
+                .map_err(|e| bind_failed(address, e))?,
+        );
+        server.register_listener(
+            TcpListener::bind(address)
+                .await
+                .map_err(|e| bind_failed(address, e))?,
+            TCP_TIMEOUT,
+        );
+        info!("serving zones over DNS on {address}");
+    }
+
+    if args.dot_address.is_some() || args.doh_address.is_some() {
+        let (Some(certificate), Some(key)) = (&args.tls_certificate, &args.tls_key) else {
+            return Err(Error::Config(String::from(
+                "--tls-certificate and --tls-key are required to serve zones over TLS or HTTPS",
+            )));
+        };
+
+        let certificate_and_key = || {
+            Ok::<_, Error>((
+                tls_server::read_cert(certificate)
+                    .map_err(|e| Error::Config(format!("invalid TLS certificate: {e}")))?,
+                tls_server::read_key_from_pem(key)
+                    .map_err(|e| Error::Config(format!("invalid TLS key: {e}")))?,
+            ))
+        };
+
+        if let Some(address) = args.dot_address {
+            server
+                .register_tls_listener(
+                    TcpListener::bind(address)
+                        .await
+                        .map_err(|e| bind_failed(address, e))?,
+                    TCP_TIMEOUT,
+                    certificate_and_key()?,
+                )
+                .map_err(|e| Error::Config(e.to_string()))?;
+            info!("serving zones over DNS-over-TLS on {address}");
+        }
+
+        if let Some(address) = args.doh_address {
+            server
+                .register_https_listener(
+                    TcpListener::bind(address)
+                        .await
+                        .map_err(|e| bind_failed(address, e))?,
+                    TCP_TIMEOUT,
+                    certificate_and_key()?,
+                    None,
+                )
+                .map_err(|e| Error::Config(e.to_string()))?;
+            info!("serving zones over DNS-over-HTTPS on {address}");
+        }
+    }
+
+    tasks.spawn(async move {
+        if let Err(e) = server.block_until_done().await {
+            error!("nameserver stopped: {e}");
+        }
+    });
+
+    Ok(Some(zones))
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::rr::{LowerName, Name, RecordType};
+    use hickory_server::authority::LookupOptions;
+    use indoc::indoc;
+
+    use super::ServedZones;
+
+    #[tokio::test]
+    async fn serves_published_zones() {
+        let zones = ServedZones::default();
+        let contents = indoc! {"
+            $ORIGIN example.org.
+            $TTL 300
+
+            @ IN SOA ns1.example.org. hostmaster.example.org. 1 7200 3600 1209600 3600
+            @ IN NS ns1.example.org.
+            www IN A 192.0.2.1
+        "};
+
+        zones
+            .publish(
+                "default/zones",
+                [("example.org.", contents), ("example.net.", "not a zone")],
+                &["example.org.", "example.net."],
+            )
+            .await;
+
+        let origin = LowerName::new(&Name::from_ascii("example.org.").unwrap());
+        let name = LowerName::new(&Name::from_ascii("www.example.org.").unwrap());
+
+        {
+            let catalog = zones.catalog.read().await;
+            let authority = catalog.find(&name).unwrap();
+            assert_eq!(authority.origin(), &origin);
+
+            let lookup = authority
+                .lookup(&name, RecordType::A, LookupOptions::default())
+                .await
+                .unwrap();
+            assert_eq!(lookup.iter().count(), 1);
+
+            // Zones which do not parse are left out.
+            let unparsable = LowerName::new(&Name::from_ascii("example.net.").unwrap());
+            assert!(catalog.find(&unparsable).is_none());
+        }
+
+        zones.publish("default/zones", [], &[]).await;
+        assert!(zones.catalog.read().await.find(&name).is_none());
+    }
+
+    #[tokio::test]
+    async fn keeps_serving_retained_zones() {
+        let zones = ServedZones::default();
+        let contents = indoc! {"
+            $ORIGIN example.org.
+            $TTL 300
+
+            @ IN SOA ns1.example.org. hostmaster.example.org. 1 7200 3600 1209600 3600
+            @ IN NS ns1.example.org.
+        "};
+
+        zones
+            .publish(
+                "default/zones",
+                [("example.org.", contents)],
+                &["example.org."],
+            )
+            .await;
+
+        // Zones which are held, fail to render or are deferred are not rendered anew,
+        // but remain published at their last contents.
+        let origin = LowerName::new(&Name::from_ascii("example.org.").unwrap());
+        zones.publish("default/zones", [], &["example.org."]).await;
+        assert!(zones.catalog.read().await.find(&origin).is_some());
+
+        zones.publish("default/zones", [], &[]).await;
+        assert!(zones.catalog.read().await.find(&origin).is_none());
+    }
+
+    #[tokio::test]
+    async fn serves_retained_zones_after_a_restart() {
+        let contents = indoc! {"
+            $ORIGIN example.org.
+            $TTL 300
+
+            @ IN SOA ns1.example.org. hostmaster.example.org. 1 7200 3600 1209600 3600
+            @ IN NS ns1.example.org.
+            www IN A 192.0.2.1
+        "};
+
+        // A restarted controller has served nothing yet, and renders nothing
+        // anew for a zone which keeps its last published contents.
+        let zones = ServedZones::default();
+        zones.publish("default/zones", [], &["example.org."]).await;
+        zones
+            .seed("default/zones", [("example.org.", contents)])
+            .await;
+
+        let name = LowerName::new(&Name::from_ascii("www.example.org.").unwrap());
+        {
+            let catalog = zones.catalog.read().await;
+            let lookup = catalog
+                .find(&name)
+                .unwrap()
+                .lookup(&name, RecordType::A, LookupOptions::default())
+                .await
+                .unwrap();
+            assert_eq!(lookup.iter().count(), 1);
+        }
+
+        // Zones which are served already are not replaced by their retained contents.
+        let without_www = indoc! {"
+            $ORIGIN example.org.
+            $TTL 300
+
+            @ IN SOA ns1.example.org. hostmaster.example.org. 1 7200 3600 1209600 3600
+            @ IN NS ns1.example.org.
+        "};
+        zones
+            .seed("default/zones", [("example.org.", without_www)])
+            .await;
+        {
+            let catalog = zones.catalog.read().await;
+            let lookup = catalog
+                .find(&name)
+                .unwrap()
+                .lookup(&name, RecordType::A, LookupOptions::default())
+                .await
+                .unwrap();
+            assert_eq!(lookup.iter().count(), 1);
+        }
+
+        zones.publish("default/zones", [], &[]).await;
+        assert!(zones.catalog.read().await.find(&name).is_none());
+    }
+}
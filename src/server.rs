@@ -0,0 +1,23 @@
+use std::net::SocketAddr;
+
+use axum::Router;
+use tokio::net::TcpListener;
+use tracing::log::*;
+
+/// Serve the router's HTTP endpoints on the given address, logging
+/// rather than failing if the address cannot be listened on.
+pub async fn serve(address: SocketAddr, router: Router) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to listen on {address}: {e}");
+            return;
+        }
+    };
+
+    info!("listening on {address}");
+
+    if let Err(e) = axum::serve(listener, router).await {
+        error!("server on {address} failed: {e}");
+    }
+}
@@ -0,0 +1,183 @@
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+
+use crate::{
+    normalize,
+    validation::{self, ValidationArgs, ValidationError},
+};
+
+/// A record owned by a name holding service labels, such as `_dmarc`,
+/// `_acme-challenge` or `_sip._tcp` (RFC 8552). Zone entries cannot hold such
+/// records, since their domain name type rejects underscores, so they are kept
+/// apart from the entries of a zone and rendered after them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceRecord {
+    /// Absolute owner name, in lowercase.
+    pub owner: String,
+    pub type_: Type,
+    pub class: Class,
+    pub ttl: u32,
+    pub rdata: String,
+}
+
+/// The absolute name in lowercase, if it holds service labels. Each of its labels
+/// must be a hostname label, which those of services prefix with an underscore.
+pub fn service_name(name: &str) -> Option<String> {
+    let name = name.to_ascii_lowercase();
+    let labels = name.strip_suffix('.')?;
+
+    if name.len() > 254 {
+        return None;
+    }
+
+    let mut services = 0;
+    for label in labels.split('.') {
+        let hostname = match label.strip_prefix('_') {
+            Some(hostname) => {
+                services += 1;
+                hostname
+            }
+            None => label,
+        };
+
+        if hostname.is_empty()
+            || label.len() > 63
+            || hostname.starts_with('-')
+            || hostname.ends_with('-')
+            || !hostname
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return None;
+        }
+    }
+
+    (services > 0).then_some(name)
+}
+
+/// Whether the absolute name falls within the zone of the origin.
+pub fn within(name: &str, origin: &FullyQualifiedDomainName) -> bool {
+    let origin = origin.to_string().to_ascii_lowercase();
+
+    origin == "." || name == origin || name.ends_with(&format!(".{origin}"))
+}
+
+/// Validate the rdata of the records, returning the valid records with their rdata in
+/// canonical form, along with the invalid records and the reason they were rejected.
+pub fn validate(
+    records: Vec<ServiceRecord>,
+    args: &ValidationArgs,
+) -> (Vec<ServiceRecord>, Vec<(ServiceRecord, ValidationError)>) {
+    let mut valid = Vec::with_capacity(records.len());
+    let mut invalid = Vec::new();
+
+    for record in records {
+        match validation::validate_rdata(record.type_, &record.rdata, args) {
+            Ok(rdata) => valid.push(ServiceRecord { rdata, ..record }),
+            Err(error) => invalid.push((record, error)),
+        }
+    }
+
+    (valid, invalid)
+}
+
+/// Rewrite the rdata of the records into canonical form, as
+/// [`normalize::canonicalize`] does for entries, and sort them by owner
+/// name and type, leaving out exact duplicates.
+pub fn canonicalize(
+    origin: &FullyQualifiedDomainName,
+    records: Vec<ServiceRecord>,
+) -> Vec<ServiceRecord> {
+    let origin = origin.to_string();
+
+    let mut records = records
+        .into_iter()
+        .map(|record| ServiceRecord {
+            rdata: normalize::canonical_rdata(&origin, record.type_, &record.rdata),
+            ..record
+        })
+        .collect::<Vec<_>>();
+
+    records.sort_by_key(|record| (record.owner.clone(), record.type_.to_string()));
+    records.dedup();
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+
+    use crate::validation::{ValidationArgs, ValidationError};
+
+    use super::{canonicalize, service_name, validate, within, ServiceRecord};
+
+    fn record(owner: &str, type_: Type, rdata: &str) -> ServiceRecord {
+        ServiceRecord {
+            owner: owner.to_string(),
+            type_,
+            class: Class::IN,
+            ttl: 300,
+            rdata: rdata.to_string(),
+        }
+    }
+
+    #[test]
+    fn accepts_service_labels() {
+        assert_eq!(
+            service_name("_DMARC.example.org."),
+            Some(String::from("_dmarc.example.org."))
+        );
+        assert!(service_name("_acme-challenge.www.example.org.").is_some());
+        assert!(service_name("_sip._tcp.example.org.").is_some());
+        assert!(service_name("selector._domainkey.example.org.").is_some());
+
+        // Names without service labels are ordinary domain names.
+        assert_eq!(service_name("www.example.org."), None);
+        // Names must be absolute, and labels well formed.
+        assert_eq!(service_name("_dmarc"), None);
+        assert_eq!(service_name("_.example.org."), None);
+        assert_eq!(service_name("__dmarc.example.org."), None);
+        assert_eq!(service_name("_dmarc-.example.org."), None);
+        assert_eq!(service_name("_dm arc.example.org."), None);
+    }
+
+    #[test]
+    fn validates_and_canonicalizes_records() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        assert!(within("_dmarc.example.org.", &origin));
+        assert!(!within("_dmarc.example.net.", &origin));
+
+        let (valid, invalid) = validate(
+            vec![
+                record("_sip._tcp.example.org.", Type::SRV, "0 5 5060 SIP"),
+                record("_dmarc.example.org.", Type::TXT, "\"v=DMARC1;  p=none\""),
+                record("_dmarc.example.org.", Type::TXT, "\"v=DMARC1;  p=none\""),
+                record("_sip._udp.example.org.", Type::SRV, "0 5"),
+            ],
+            &ValidationArgs::default(),
+        );
+
+        assert_eq!(
+            invalid
+                .iter()
+                .map(|(record, error)| (record.owner.as_str(), error))
+                .collect::<Vec<_>>(),
+            vec![(
+                "_sip._udp.example.org.",
+                &ValidationError::MissingField("port")
+            )]
+        );
+
+        assert_eq!(
+            canonicalize(&origin, valid),
+            vec![
+                record("_dmarc.example.org.", Type::TXT, "\"v=DMARC1;  p=none\""),
+                record(
+                    "_sip._tcp.example.org.",
+                    Type::SRV,
+                    "0 5 5060 sip.example.org."
+                ),
+            ]
+        );
+    }
+}
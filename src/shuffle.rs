@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use ring::digest::{Context, SHA256};
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) which, when set to `true`,
+/// shuffles the records within each RRset of its zones, for crude load distribution
+/// across servers which answer with the records in the order of the zonefile.
+pub const SHUFFLE_RRSETS_ANNOTATION: &str = "zonefile.kubi.zone/shuffle-rrsets";
+
+/// Shuffle the records within each RRset. Each RRset keeps the positions it
+/// occupied in the zone, so the zone's layout is unchanged.
+///
+/// The shuffle is seeded by the records themselves, so the same records are always
+/// shuffled the same way. A zone is only shuffled anew when its records change, and
+/// reconciliations of unchanged zones do not publish them again in another order.
+/// The seed is a SHA-256 digest of the records, which unlike the standard library's
+/// hashers is stable across Rust releases.
+pub fn shuffle_rrsets(entries: &mut [ZoneEntry]) {
+    let mut context = Context::new(&SHA256);
+    for entry in entries.iter() {
+        context.update(
+            format!(
+                "{} {} {} {} {}\n",
+                entry.fqdn, entry.ttl, entry.class, entry.type_, entry.rdata
+            )
+            .as_bytes(),
+        );
+    }
+
+    let digest = context.finish();
+    let seed: [u8; 8] = digest.as_ref()[..8].try_into().unwrap();
+
+    // xorshift64, which is plenty for ordering records, and must not start from zero.
+    let mut state = u64::from_le_bytes(seed) | 1;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut rrsets = BTreeMap::<(FullyQualifiedDomainName, Type, Class), Vec<usize>>::new();
+    for (index, entry) in entries.iter().enumerate() {
+        rrsets
+            .entry((entry.fqdn.clone(), entry.type_, entry.class))
+            .or_default()
+            .push(index);
+    }
+
+    for positions in rrsets.into_values() {
+        // Fisher-Yates over the positions of the RRset.
+        for i in (1..positions.len()).rev() {
+            let j = (next() % (i as u64 + 1)) as usize;
+            entries.swap(positions[i], positions[j]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::shuffle_rrsets;
+
+    fn entry(fqdn: &str, type_: Type, rdata: &str) -> ZoneEntry {
+        ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(fqdn).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 300,
+            rdata: rdata.to_string(),
+        }
+    }
+
+    fn rdata(entries: &[ZoneEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.rdata.as_str()).collect()
+    }
+
+    #[test]
+    fn shuffles_within_rrsets() {
+        let mut entries = vec![entry("example.org.", Type::NS, "ns1.example.org.")];
+        entries
+            .extend((1..=20).map(|i| entry("www.example.org.", Type::A, &format!("192.0.2.{i}"))));
+        entries.push(entry("www.example.org.", Type::AAAA, "2001:db8::1"));
+
+        let mut shuffled = entries.clone();
+        shuffle_rrsets(&mut shuffled);
+
+        // The same records are shuffled the same way.
+        let mut again = entries.clone();
+        shuffle_rrsets(&mut again);
+        assert_eq!(rdata(&shuffled), rdata(&again));
+
+        assert_ne!(rdata(&shuffled), rdata(&entries));
+        assert_eq!(shuffled[0].rdata, entries[0].rdata);
+        assert_eq!(shuffled[21].rdata, entries[21].rdata);
+
+        let mut addresses = rdata(&shuffled[1..21]);
+        addresses.sort();
+        let mut expected = rdata(&entries[1..21]);
+        expected.sort();
+        assert_eq!(addresses, expected);
+
+        // The order depends on nothing but the records, so that it survives
+        // toolchain upgrades without changing the published zone.
+        assert_eq!(
+            rdata(&shuffled[1..6]),
+            [
+                "192.0.2.2",
+                "192.0.2.7",
+                "192.0.2.5",
+                "192.0.2.3",
+                "192.0.2.6"
+            ]
+        );
+
+        // Changing the records shuffles them anew.
+        let mut changed = entries.clone();
+        changed[1].ttl = 60;
+        shuffle_rrsets(&mut changed);
+        assert_ne!(rdata(&changed), rdata(&shuffled));
+    }
+}
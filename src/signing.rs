@@ -0,0 +1,134 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use k8s_openapi::api::core::v1::Secret;
+use kube::{Api, Client, ResourceExt as _};
+use ring::{
+    rand::SystemRandom,
+    signature::{
+        EcdsaKeyPair, KeyPair as _, UnparsedPublicKey, ECDSA_P256_SHA256_ASN1,
+        ECDSA_P256_SHA256_ASN1_SIGNING,
+    },
+};
+use zonefile_crds::ZoneFile;
+
+use crate::error::Error;
+
+/// Annotation on a [`ZoneFile`] naming a Secret in the same namespace,
+/// which holds the key used for signing the rendered zones.
+pub const SIGNING_KEY_ANNOTATION: &str = "zonefile.kubi.zone/signing-key";
+
+/// Key within the signing Secret which holds the PEM-encoded
+/// PKCS#8 ECDSA P-256 private key.
+pub const SIGNING_KEY_SECRET_KEY: &str = "signing.key";
+
+/// Signs rendered zone contents, producing base64-encoded ASN.1 signatures
+/// over the SHA-256 digest of the contents, in the same format as
+/// `cosign sign-blob`, so they can be checked using `cosign verify-blob`.
+pub struct Signer {
+    key_pair: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl Signer {
+    pub fn from_pem(pem: &[u8]) -> Result<Self, Error> {
+        let pem = pem::parse(pem).map_err(|e| Error::Signing(e.to_string()))?;
+        let rng = SystemRandom::new();
+
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pem.contents(), &rng)
+                .map_err(|e| Error::Signing(e.to_string()))?;
+
+        Ok(Signer { key_pair, rng })
+    }
+
+    /// Fetch the signing key referenced by the zonefile's
+    /// [`SIGNING_KEY_ANNOTATION`], if it has one.
+    pub async fn for_zonefile(client: Client, zonefile: &ZoneFile) -> Result<Option<Self>, Error> {
+        let Some(secret_name) = zonefile.annotations().get(SIGNING_KEY_ANNOTATION) else {
+            return Ok(None);
+        };
+
+        let secret = Api::<Secret>::namespaced(client, zonefile.namespace().as_ref().unwrap())
+            .get(secret_name)
+            .await?;
+
+        let Some(key) = secret
+            .data
+            .as_ref()
+            .and_then(|data| data.get(SIGNING_KEY_SECRET_KEY))
+        else {
+            return Err(Error::Signing(format!(
+                "secret {secret_name} has no {SIGNING_KEY_SECRET_KEY} key"
+            )));
+        };
+
+        Self::from_pem(&key.0).map(Some)
+    }
+
+    pub fn sign(&self, contents: &str) -> Result<String, Error> {
+        let signature = self
+            .key_pair
+            .sign(&self.rng, contents.as_bytes())
+            .map_err(|_| Error::Signing(String::from("signing operation failed")))?;
+
+        Ok(STANDARD.encode(signature.as_ref()))
+    }
+
+    /// Whether the signature was made over the contents with this key.
+    pub fn verifies(&self, contents: &str, signature: &str) -> bool {
+        let Ok(signature) = STANDARD.decode(signature) else {
+            return false;
+        };
+
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, self.key_pair.public_key().as_ref())
+            .verify(contents.as_bytes(), &signature)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use ring::{
+        rand::SystemRandom,
+        signature::{
+            EcdsaKeyPair, KeyPair as _, UnparsedPublicKey, ECDSA_P256_SHA256_ASN1,
+            ECDSA_P256_SHA256_ASN1_SIGNING,
+        },
+    };
+
+    use super::Signer;
+
+    #[test]
+    fn signature_verifies() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+
+        let pem = pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref()));
+        let signer = Signer::from_pem(pem.as_bytes()).unwrap();
+
+        let contents = "$ORIGIN example.org.\n\nwww 360 IN A 127.0.0.1";
+        let signature = STANDARD.decode(signer.sign(contents).unwrap()).unwrap();
+
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key_pair.public_key().as_ref())
+            .verify(contents.as_bytes(), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn verifies_own_signatures() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let pem = pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref()));
+        let signer = Signer::from_pem(pem.as_bytes()).unwrap();
+
+        let contents = "$ORIGIN example.org.\n\nwww 360 IN A 127.0.0.1";
+        let signature = signer.sign(contents).unwrap();
+
+        assert!(signer.verifies(contents, &signature));
+        assert!(!signer.verifies("$ORIGIN example.org.\n", &signature));
+        assert!(!signer.verifies(contents, "not base64"));
+    }
+}
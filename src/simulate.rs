@@ -0,0 +1,642 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::Args;
+use kube::{api::ListParams, Api, Client, ResourceExt as _};
+use kubizone_crds::{
+    kubizone_common::{DomainName, FullyQualifiedDomainName},
+    v1alpha1::{Record, Zone, ZoneEntry},
+};
+
+use serde::Deserialize as _;
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{
+    comment::COMMENT_ANNOTATION,
+    conditions::Observations,
+    diff::ZoneDiff,
+    error::Error,
+    flatten::FLATTEN_CNAMES_ANNOTATION,
+    golden::{self, GoldenArgs},
+    lint::{self, LintArgs, Severity},
+    negative::NegativeCachingArgs,
+    reconciliation::{default_nameservers, render_zone, wildcard_policy, RenderOptions},
+    render::{self, Comments, FormatArgs},
+    shuffle::SHUFFLE_RRSETS_ANNOTATION,
+    template::ZoneFileTemplateSpec,
+    validation::{ValidationArgs, WildcardPolicy},
+};
+
+#[derive(Debug, Clone, Args)]
+pub struct SimulateArgs {
+    /// Manifests containing the proposed Records and Zones.
+    #[arg(short = 'f', long = "filename", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Namespace of manifests which do not specify one.
+    /// Defaults to the namespace of the current context.
+    #[arg(short, long)]
+    pub namespace: Option<String>,
+
+    #[command(flatten)]
+    pub validation: ValidationArgs,
+
+    #[command(flatten)]
+    pub format: FormatArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RenderArgs {
+    /// Manifests containing the Records and Zones to render.
+    #[arg(short = 'f', long = "filename", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Namespace of manifests which do not specify one.
+    #[arg(short, long, default_value = "default")]
+    pub namespace: String,
+
+    #[command(flatten)]
+    pub validation: ValidationArgs,
+
+    #[command(flatten)]
+    pub format: FormatArgs,
+
+    #[command(flatten)]
+    pub golden: GoldenArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CheckArgs {
+    /// Manifests containing the Records and Zones to check.
+    #[arg(short = 'f', long = "filename", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Namespace of manifests which do not specify one.
+    #[arg(short, long, default_value = "default")]
+    pub namespace: String,
+
+    #[command(flatten)]
+    pub validation: ValidationArgs,
+
+    #[command(flatten)]
+    pub lint: LintArgs,
+}
+
+/// Contents of a zone, as it is now and with the proposed changes applied.
+struct SimulatedZone {
+    /// The zone as it is now, unless it is only proposed.
+    live_zone: Option<Zone>,
+    zone: Zone,
+    live_origin: FullyQualifiedDomainName,
+    origin: FullyQualifiedDomainName,
+    live: Vec<ZoneEntry>,
+    proposed: Vec<ZoneEntry>,
+    changed: bool,
+}
+
+/// Read all Records and Zones from the manifest files. Other resources are ignored.
+async fn read_manifests(
+    files: &[PathBuf],
+    namespace: &str,
+) -> Result<(Vec<Record>, Vec<Zone>), Error> {
+    let mut records = Vec::new();
+    let mut zones = Vec::new();
+
+    for file in files {
+        let contents = tokio::fs::read_to_string(file).await?;
+
+        for document in serde_yaml::Deserializer::from_str(&contents) {
+            let manifest = serde_yaml::Value::deserialize(document)?;
+
+            match manifest.get("kind").and_then(serde_yaml::Value::as_str) {
+                Some("Record") => {
+                    let mut record: Record = serde_yaml::from_value(manifest)?;
+                    record
+                        .metadata
+                        .namespace
+                        .get_or_insert(namespace.to_string());
+                    records.push(record);
+                }
+                Some("Zone") => {
+                    let mut zone: Zone = serde_yaml::from_value(manifest)?;
+                    zone.metadata.namespace.get_or_insert(namespace.to_string());
+                    zones.push(zone);
+                }
+                kind => debug!("ignoring {kind:?} manifest in {}", file.display()),
+            }
+        }
+    }
+
+    Ok((records, zones))
+}
+
+/// Resolve a domain name against the zone it references, if it is not already fully qualified.
+fn qualify(
+    domain_name: &DomainName,
+    zone_ref: Option<(String, String)>,
+    zones: &BTreeMap<(String, String), SimulatedZone>,
+) -> Option<FullyQualifiedDomainName> {
+    match domain_name {
+        DomainName::Full(fqdn) => Some(fqdn.clone()),
+        DomainName::Partial(pqdn) => {
+            let parent = zones.get(&zone_ref?)?;
+            Some(pqdn.with_origin(&parent.origin))
+        }
+    }
+}
+
+/// Zone which a record belongs in. Records without a zone reference
+/// end up in the most specific zone containing them.
+fn target_zone(
+    fqdn: &FullyQualifiedDomainName,
+    zone_ref: Option<(String, String)>,
+    zones: &BTreeMap<(String, String), SimulatedZone>,
+) -> Option<(String, String)> {
+    zone_ref.filter(|key| zones.contains_key(key)).or_else(|| {
+        zones
+            .iter()
+            .filter(|(_, zone)| *fqdn == zone.origin || fqdn.is_subdomain_of(&zone.origin))
+            .max_by_key(|(_, zone)| zone.origin.len())
+            .map(|(key, _)| key.clone())
+    })
+}
+
+/// Overlay the proposed Records and Zones on the live cluster state, and print
+/// the changes this would cause to the zones published by each ZoneFile.
+pub async fn simulate(client: Client, args: SimulateArgs) -> Result<(), Error> {
+    let namespace = args
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+
+    let (proposed_records, proposed_zones) = read_manifests(&args.files, &namespace).await?;
+
+    let mut zones = BTreeMap::new();
+    for zone in Api::<Zone>::all(client.clone())
+        .list(&ListParams::default())
+        .await?
+    {
+        let Some(origin) = zone.fqdn().cloned() else {
+            continue;
+        };
+
+        let entries = zone
+            .status
+            .as_ref()
+            .map(|status| status.entries.clone())
+            .unwrap_or_default();
+
+        zones.insert(
+            (zone.namespace().unwrap(), zone.name_any()),
+            SimulatedZone {
+                live_zone: Some(zone.clone()),
+                zone,
+                live_origin: origin.clone(),
+                origin,
+                live: entries.clone(),
+                proposed: entries,
+                changed: false,
+            },
+        );
+    }
+
+    // Proposed zones replace their live versions, and those
+    // which do not exist yet start out empty.
+    for zone in &proposed_zones {
+        let key = (zone.namespace().unwrap(), zone.name_any());
+
+        let zone_ref = zone.spec.zone_ref.as_ref().map(|zone_ref| {
+            (
+                zone_ref.namespace.clone().unwrap_or(key.0.clone()),
+                zone_ref.name.clone(),
+            )
+        });
+
+        let Some(origin) = qualify(&zone.spec.domain_name, zone_ref, &zones) else {
+            warn!("unable to determine the fqdn of proposed zone {zone}, skipping");
+            continue;
+        };
+
+        if let Some(live) = zones.get_mut(&key) {
+            if live.origin != origin {
+                // Entries outside the new origin would be moved to other zones.
+                live.proposed
+                    .retain(|entry| entry.fqdn == origin || entry.fqdn.is_subdomain_of(&origin));
+                live.origin = origin;
+                live.changed = true;
+            }
+
+            if live.zone.spec.ttl != zone.spec.ttl || live.zone.annotations() != zone.annotations()
+            {
+                live.changed = true;
+            }
+
+            live.zone = zone.clone();
+            continue;
+        }
+
+        zones.insert(
+            key,
+            SimulatedZone {
+                live_zone: None,
+                zone: zone.clone(),
+                live_origin: origin.clone(),
+                origin,
+                live: Vec::new(),
+                proposed: Vec::new(),
+                changed: true,
+            },
+        );
+    }
+
+    let mut comments = Comments::default();
+    for record in &proposed_records {
+        let record_namespace = record.namespace().unwrap();
+
+        // Remove the entry produced by the current version of the record, if any.
+        if let Some(live) = Api::<Record>::namespaced(client.clone(), &record_namespace)
+            .get_opt(&record.name_any())
+            .await?
+        {
+            if let Some(fqdn) = live.fqdn() {
+                for zone in zones.values_mut() {
+                    if let Some(index) = zone.proposed.iter().position(|entry| {
+                        entry.fqdn == *fqdn
+                            && entry.type_ == live.spec.type_
+                            && entry.class == live.spec.class
+                            && entry.rdata == live.spec.rdata
+                    }) {
+                        zone.proposed.remove(index);
+                        zone.changed = true;
+                    }
+                }
+            }
+        }
+
+        let zone_ref = record.spec.zone_ref.as_ref().map(|zone_ref| {
+            (
+                zone_ref
+                    .namespace
+                    .clone()
+                    .unwrap_or(record_namespace.clone()),
+                zone_ref.name.clone(),
+            )
+        });
+
+        let Some(fqdn) = qualify(&record.spec.domain_name, zone_ref.clone(), &zones) else {
+            warn!(
+                "unable to determine the fqdn of proposed record {}/{}, skipping",
+                record_namespace,
+                record.name_any()
+            );
+            continue;
+        };
+
+        let Some(zone) = target_zone(&fqdn, zone_ref, &zones).and_then(|key| zones.get_mut(&key))
+        else {
+            warn!("no zone found for proposed record {fqdn}, skipping");
+            continue;
+        };
+
+        let entry = ZoneEntry {
+            fqdn,
+            type_: record.spec.type_,
+            class: record.spec.class,
+            ttl: record.spec.ttl.unwrap_or(zone.zone.spec.ttl),
+            rdata: record.spec.rdata.clone(),
+        };
+
+        if let Some(comment) = record.annotations().get(COMMENT_ANNOTATION) {
+            comments.insert(&entry, comment);
+        }
+
+        zone.proposed.push(entry);
+        zone.changed = true;
+    }
+
+    for zonefile in Api::<ZoneFile>::all(client.clone())
+        .list(&ListParams::default())
+        .await?
+    {
+        let zonefile_namespace = zonefile.namespace().unwrap();
+
+        // Zones are rendered as the controller renders them, though without
+        // the zonefile's template, defaults and synthesized glue.
+        let default_nameservers = default_nameservers(&zonefile, &[], &mut Observations::default());
+        let options = RenderOptions {
+            annotations: zonefile.annotations(),
+            validation: &args.validation,
+            wildcard_policy: wildcard_policy(&zonefile, &mut Observations::default()),
+            format: &args.format,
+            template: &ZoneFileTemplateSpec::default(),
+            negative: &NegativeCachingArgs::default(),
+            default_nameservers: &default_nameservers,
+            glue_sources: &[],
+            flatten_cnames: zonefile
+                .annotations()
+                .get(FLATTEN_CNAMES_ANNOTATION)
+                .is_some_and(|flatten| flatten == "true"),
+            shuffle_rrsets: zonefile
+                .annotations()
+                .get(SHUFFLE_RRSETS_ANNOTATION)
+                .is_some_and(|shuffle| shuffle == "true"),
+            comments: &comments,
+        };
+
+        for zone_ref in &zonefile.spec.zone_refs {
+            let key = (
+                zone_ref
+                    .namespace
+                    .clone()
+                    .unwrap_or(zonefile_namespace.clone()),
+                zone_ref.name.clone(),
+            );
+
+            let Some(zone) = zones.get(&key).filter(|zone| zone.changed) else {
+                continue;
+            };
+
+            let render =
+                |resource: &Zone, origin: &FullyQualifiedDomainName, entries: &[ZoneEntry]| {
+                    render_zone(
+                        resource,
+                        origin,
+                        entries.to_vec(),
+                        Vec::new(),
+                        &options,
+                        &mut Observations::default(),
+                    )
+                    .map(|(_, _, rendered)| rendered)
+                };
+
+            // A zone violating a policy keeps its published contents.
+            let live = zone
+                .live_zone
+                .as_ref()
+                .and_then(|live_zone| render(live_zone, &zone.live_origin, &zone.live).ok())
+                .unwrap_or_default();
+            let proposed = match render(&zone.zone, &zone.origin, &zone.proposed) {
+                Ok(proposed) => proposed,
+                Err(e) => {
+                    println!(
+                        "zonefile {zonefile_namespace}/{}, zone {}: would not be published, {e}",
+                        zonefile.name_any(),
+                        zone.origin
+                    );
+                    continue;
+                }
+            };
+
+            let diff = ZoneDiff::between(&live, &proposed);
+
+            println!(
+                "zonefile {zonefile_namespace}/{}, zone {}:",
+                zonefile.name_any(),
+                zone.origin
+            );
+
+            if diff.added.is_empty() && diff.removed.is_empty() {
+                println!("  no changes");
+            }
+
+            for removed in &diff.removed {
+                println!("- {removed}");
+            }
+
+            for added in &diff.added {
+                println!("+ {added}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Zone described by local manifests, rendered as the controller would render it.
+pub struct LocalZone {
+    pub namespace: String,
+    pub name: String,
+    pub origin: FullyQualifiedDomainName,
+    /// Records of the zone, as rendered.
+    pub entries: Vec<ZoneEntry>,
+    pub rendered: String,
+}
+
+/// Render the zones described by the Records and Zones in the manifest files,
+/// without consulting the cluster.
+pub async fn local_zones(
+    files: &[PathBuf],
+    namespace: &str,
+    validation: &ValidationArgs,
+    format: &FormatArgs,
+) -> Result<Vec<LocalZone>, Error> {
+    let (records, manifest_zones) = read_manifests(files, namespace).await?;
+
+    // Zones may reference parent zones defined later in the manifests,
+    // so origins are resolved until no more of them can be.
+    let mut zones = BTreeMap::new();
+    let mut pending: Vec<&Zone> = manifest_zones.iter().collect();
+    loop {
+        let before = pending.len();
+
+        pending.retain(|zone| {
+            let key = (zone.namespace().unwrap(), zone.name_any());
+            let zone_ref = zone.spec.zone_ref.as_ref().map(|zone_ref| {
+                (
+                    zone_ref.namespace.clone().unwrap_or(key.0.clone()),
+                    zone_ref.name.clone(),
+                )
+            });
+
+            let Some(origin) = qualify(&zone.spec.domain_name, zone_ref, &zones) else {
+                return true;
+            };
+
+            let entries = zone
+                .status
+                .as_ref()
+                .map(|status| status.entries.clone())
+                .unwrap_or_default();
+
+            zones.insert(
+                key,
+                SimulatedZone {
+                    live_zone: None,
+                    zone: (*zone).clone(),
+                    live_origin: origin.clone(),
+                    origin,
+                    live: Vec::new(),
+                    proposed: entries,
+                    changed: true,
+                },
+            );
+            false
+        });
+
+        if pending.is_empty() || pending.len() == before {
+            break;
+        }
+    }
+
+    for zone in pending {
+        warn!("unable to determine the fqdn of zone {zone}, skipping");
+    }
+
+    let mut comments = Comments::default();
+    for record in &records {
+        let record_namespace = record.namespace().unwrap();
+
+        let zone_ref = record.spec.zone_ref.as_ref().map(|zone_ref| {
+            (
+                zone_ref
+                    .namespace
+                    .clone()
+                    .unwrap_or(record_namespace.clone()),
+                zone_ref.name.clone(),
+            )
+        });
+
+        let Some(fqdn) = qualify(&record.spec.domain_name, zone_ref.clone(), &zones) else {
+            warn!(
+                "unable to determine the fqdn of record {}/{}, skipping",
+                record_namespace,
+                record.name_any()
+            );
+            continue;
+        };
+
+        let Some(zone) = target_zone(&fqdn, zone_ref, &zones).and_then(|key| zones.get_mut(&key))
+        else {
+            warn!("no zone found for record {fqdn}, skipping");
+            continue;
+        };
+
+        let entry = ZoneEntry {
+            fqdn,
+            type_: record.spec.type_,
+            class: record.spec.class,
+            ttl: record.spec.ttl.unwrap_or(zone.zone.spec.ttl),
+            rdata: record.spec.rdata.clone(),
+        };
+
+        if let Some(comment) = record.annotations().get(COMMENT_ANNOTATION) {
+            comments.insert(&entry, comment);
+        }
+
+        zone.proposed.push(entry);
+    }
+
+    // Without a zonefile, zones are rendered with the controller's defaults.
+    let annotations = BTreeMap::new();
+    let template = ZoneFileTemplateSpec::default();
+    let negative = NegativeCachingArgs::default();
+    let options = RenderOptions {
+        annotations: &annotations,
+        validation,
+        wildcard_policy: WildcardPolicy::default(),
+        format,
+        template: &template,
+        negative: &negative,
+        default_nameservers: &[],
+        glue_sources: &[],
+        flatten_cnames: false,
+        shuffle_rrsets: false,
+        comments: &comments,
+    };
+
+    zones
+        .into_iter()
+        .map(|((namespace, name), zone)| {
+            let (entries, _, rendered) = render_zone(
+                &zone.zone,
+                &zone.origin,
+                zone.proposed,
+                Vec::new(),
+                &options,
+                &mut Observations::default(),
+            )?;
+
+            Ok(LocalZone {
+                namespace,
+                name,
+                origin: zone.origin,
+                entries,
+                rendered,
+            })
+        })
+        .collect()
+}
+
+/// Render the zones described by the Records and Zones in the manifest files,
+/// without consulting the cluster, and print them or check them against
+/// their golden files.
+pub async fn render(args: RenderArgs) -> Result<(), Error> {
+    let zones = local_zones(&args.files, &args.namespace, &args.validation, &args.format).await?;
+
+    let mut mismatches = 0;
+    for zone in &zones {
+        let rendered = render::finish(&zone.rendered, &args.format)?;
+
+        match &args.golden.golden_dir {
+            Some(directory) => {
+                let origin = zone.origin.to_string();
+                if !golden::check(directory, &origin, &rendered, args.golden.update).await? {
+                    mismatches += 1;
+                }
+            }
+            None => print!("; zone {}/{}\n{rendered}", zone.namespace, zone.name),
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(Error::GoldenMismatch(mismatches));
+    }
+
+    Ok(())
+}
+
+/// Check the records of the zones described by the manifests against the lint
+/// rules, and check that their address records match their PTR records, where
+/// both the forward and reverse zones are among them. Findings are printed, and
+/// those with a severity of error fail the check, as do mismatches.
+pub async fn check(args: CheckArgs) -> Result<(), Error> {
+    let zones = local_zones(
+        &args.files,
+        &args.namespace,
+        &args.validation,
+        &FormatArgs::default(),
+    )
+    .await?;
+
+    let severities = args.lint.severities(&[]);
+    let mut count = 0;
+    for zone in &zones {
+        for finding in args.lint.lint(&severities, &zone.entries) {
+            println!("zone {}: {finding}", zone.origin);
+
+            if finding.severity == Severity::Error {
+                count += 1;
+            }
+        }
+    }
+
+    let mismatches = lint::ptr_mismatches(
+        &zones
+            .into_iter()
+            .map(|zone| (zone.origin, zone.entries))
+            .collect::<Vec<_>>(),
+    );
+
+    for (origin, mismatches) in &mismatches {
+        for mismatch in mismatches {
+            println!("zone {origin}: {mismatch}");
+            count += 1;
+        }
+    }
+
+    if count > 0 {
+        return Err(Error::CheckFailed(count));
+    }
+
+    Ok(())
+}
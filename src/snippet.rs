@@ -0,0 +1,148 @@
+use std::fmt::Display;
+
+use kubizone_crds::kubizone_common::FullyQualifiedDomainName;
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding a comma-separated
+/// list of `<configmap>/<key>` references to snippets of records within its namespace,
+/// such as a shared set of CAA records, which are appended to each of its zones.
+///
+/// Snippets are written relative to the origin of the zone they are appended to.
+/// Their ConfigMaps are not watched, so changes to them are picked up on the next
+/// periodic reconciliation.
+pub const SNIPPETS_ANNOTATION: &str = "zonefile.kubi.zone/snippets";
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) naming the directory in
+/// which the DNS server finds the snippets' ConfigMaps mounted. When set, snippets
+/// are referenced through `$INCLUDE` directives instead of being inlined.
+pub const SNIPPET_INCLUDE_DIRECTORY_ANNOTATION: &str =
+    "zonefile.kubi.zone/snippet-include-directory";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetRef {
+    pub config_map: String,
+    pub key: String,
+}
+
+impl SnippetRef {
+    /// Parse a comma-separated list of `<configmap>/<key>` references.
+    pub fn parse_list(references: &str) -> Result<Vec<Self>, String> {
+        references
+            .split(',')
+            .map(str::trim)
+            .filter(|reference| !reference.is_empty())
+            .map(|reference| match reference.split_once('/') {
+                Some((config_map, key))
+                    if !config_map.is_empty() && !key.is_empty() && !key.contains('/') =>
+                {
+                    Ok(SnippetRef {
+                        config_map: config_map.to_string(),
+                        key: key.to_string(),
+                    })
+                }
+                _ => Err(reference.to_string()),
+            })
+            .collect()
+    }
+}
+
+impl Display for SnippetRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.config_map, self.key)
+    }
+}
+
+/// A snippet, along with its contents.
+pub struct Snippet {
+    pub reference: SnippetRef,
+    pub contents: String,
+}
+
+/// Append the snippets to the rendered zone, either inlining their contents,
+/// or including them from `include_directory`, if given.
+pub fn compose(
+    zone: String,
+    origin: &FullyQualifiedDomainName,
+    snippets: &[Snippet],
+    include_directory: Option<&str>,
+) -> String {
+    snippets.iter().fold(zone, |mut zone, snippet| {
+        match include_directory {
+            Some(directory) => zone.push_str(&format!(
+                "\n\n$INCLUDE {}/{} {origin}",
+                directory.trim_end_matches('/'),
+                snippet.reference.key
+            )),
+            None => zone.push_str(&format!(
+                "\n\n; snippet {}\n{}",
+                snippet.reference,
+                snippet.contents.trim_end()
+            )),
+        }
+
+        zone
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use kubizone_crds::kubizone_common::FullyQualifiedDomainName;
+
+    use super::{compose, Snippet, SnippetRef};
+
+    #[test]
+    fn parses_references() {
+        assert_eq!(
+            SnippetRef::parse_list("shared/caa, shared/mx,").unwrap(),
+            vec![
+                SnippetRef {
+                    config_map: "shared".to_string(),
+                    key: "caa".to_string(),
+                },
+                SnippetRef {
+                    config_map: "shared".to_string(),
+                    key: "mx".to_string(),
+                },
+            ]
+        );
+
+        assert_eq!(SnippetRef::parse_list("shared").unwrap_err(), "shared");
+        assert_eq!(SnippetRef::parse_list("/caa").unwrap_err(), "/caa");
+        assert_eq!(SnippetRef::parse_list("a/b/c").unwrap_err(), "a/b/c");
+    }
+
+    #[test]
+    fn inlines_or_includes_snippets() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+        let snippets = [Snippet {
+            reference: SnippetRef {
+                config_map: "shared".to_string(),
+                key: "caa".to_string(),
+            },
+            contents: "@ 3600 IN CAA 0 issue \"letsencrypt.org\"\n".to_string(),
+        }];
+
+        let zone = "$ORIGIN example.org.\n\nwww 360 IN A 127.0.0.1".to_string();
+
+        assert_eq!(
+            compose(zone.clone(), &origin, &snippets, None),
+            indoc! {r#"
+                $ORIGIN example.org.
+
+                www 360 IN A 127.0.0.1
+
+                ; snippet shared/caa
+                @ 3600 IN CAA 0 issue "letsencrypt.org""#}
+        );
+
+        assert_eq!(
+            compose(zone, &origin, &snippets, Some("/etc/snippets/")),
+            indoc! {"
+                $ORIGIN example.org.
+
+                www 360 IN A 127.0.0.1
+
+                $INCLUDE /etc/snippets/caa example.org."}
+        );
+    }
+}
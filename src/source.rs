@@ -0,0 +1,573 @@
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc, time::Duration};
+
+use clap::Args;
+use futures::StreamExt as _;
+use k8s_openapi::{
+    api::{core::v1::Service, networking::v1::Ingress},
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+    serde_json::Value,
+};
+use kube::{
+    api::{
+        ApiResource, DeleteParams, DynamicObject, GroupVersionKind, ListParams, Patch, PatchParams,
+    },
+    core::ObjectMeta,
+    runtime::{controller::Action, reflector::ObjectRef, watcher, Controller},
+    Api, Client, Resource, ResourceExt as _,
+};
+use kubizone_common::{DomainName, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::{Record, RecordSpec, ZoneRef};
+use tracing::log::*;
+
+use crate::{digest, error::Error};
+
+/// Annotation on an Ingress, HTTPRoute or Service naming the
+/// [`Zone`](kubizone_crds::v1alpha1::Zone), as `[<namespace>/]<name>`, which
+/// Records for its hostnames are created in. Resources without it are ignored.
+pub const SOURCE_ZONE_ANNOTATION: &str = "zonefile.kubi.zone/zone";
+
+/// Annotation on a LoadBalancer Service holding the comma-separated
+/// hostnames which should resolve to the addresses of its load balancer.
+pub const SOURCE_HOSTNAMES_ANNOTATION: &str = "zonefile.kubi.zone/hostnames";
+
+/// Label on Records created for a sourced resource, naming the resource they belong
+/// to, shortened with a hash if the name does not fit within a label value.
+pub const SOURCE_LABEL: &str = "zonefile.kubi.zone/source";
+
+#[derive(Debug, Clone, Args)]
+pub struct SourceArgs {
+    /// Create Records for the hostnames of annotated Ingresses,
+    /// pointing at the addresses of their load balancers.
+    #[arg(long, env = "ZONEFILE_SOURCE_INGRESSES")]
+    pub source_ingresses: bool,
+
+    /// Create Records for the hostnames of annotated Gateway API
+    /// HTTPRoutes, pointing at the addresses of their parent Gateways.
+    #[arg(long, env = "ZONEFILE_SOURCE_HTTPROUTES")]
+    pub source_httproutes: bool,
+
+    /// Create Records for the hostnames annotated on LoadBalancer
+    /// Services, pointing at the addresses of their load balancers.
+    #[arg(long, env = "ZONEFILE_SOURCE_SERVICES")]
+    pub source_services: bool,
+}
+
+/// Address which hostnames resolve to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Address(IpAddr),
+    Hostname(String),
+}
+
+impl Target {
+    fn parse(target: &str) -> Self {
+        match target.parse() {
+            Ok(address) => Target::Address(address),
+            Err(_) => Target::Hostname(target.trim_end_matches('.').to_string()),
+        }
+    }
+}
+
+/// Records pointing each of the hostnames at the targets. Hostnames can only
+/// be aliased through CNAME records when there are no addresses to point them at,
+/// since a CNAME record cannot coexist with other records of the same name.
+fn desired_records(
+    hostnames: &[String],
+    targets: &[Target],
+) -> Vec<(FullyQualifiedDomainName, Type, String)> {
+    let addresses = targets
+        .iter()
+        .filter_map(|target| match target {
+            Target::Address(IpAddr::V4(address)) => Some((Type::A, address.to_string())),
+            Target::Address(IpAddr::V6(address)) => Some((Type::AAAA, address.to_string())),
+            Target::Hostname(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let rdata = if addresses.is_empty() {
+        targets
+            .iter()
+            .filter_map(|target| match target {
+                Target::Hostname(hostname) => Some((Type::CNAME, format!("{hostname}."))),
+                Target::Address(_) => None,
+            })
+            .take(1)
+            .collect()
+    } else {
+        addresses
+    };
+
+    let mut records = Vec::new();
+    for hostname in hostnames {
+        let Ok(fqdn) =
+            FullyQualifiedDomainName::try_from(format!("{}.", hostname.trim_end_matches('.')))
+        else {
+            warn!("ignoring invalid hostname {hostname:?}");
+            continue;
+        };
+
+        for (type_, rdata) in &rdata {
+            records.push((fqdn.clone(), *type_, rdata.clone()));
+        }
+    }
+
+    records.sort();
+    records.dedup();
+    records
+}
+
+fn parse_zone_ref(zone: &str) -> ZoneRef {
+    match zone.split_once('/') {
+        Some((namespace, name)) => ZoneRef {
+            name: name.to_string(),
+            namespace: Some(namespace.to_string()),
+        },
+        None => ZoneRef {
+            name: zone.to_string(),
+            namespace: None,
+        },
+    }
+}
+
+/// Resource whose hostnames are sourced, and the Records which should exist for it.
+struct Source {
+    key: String,
+    namespace: String,
+    owner: OwnerReference,
+    zone_ref: ZoneRef,
+    records: Vec<(FullyQualifiedDomainName, Type, String)>,
+}
+
+/// Value of the source label on the Records of the source.
+fn source_label(key: &str) -> String {
+    digest::truncate(key, 63)
+}
+
+/// Apply the Records of the source, and delete those which are no longer needed.
+async fn sync(client: Client, controller_name: &str, source: Source) -> Result<Action, Error> {
+    let records = Api::<Record>::namespaced(client, &source.namespace);
+
+    let mut names = Vec::with_capacity(source.records.len());
+    for (index, (fqdn, type_, rdata)) in source.records.into_iter().enumerate() {
+        let suffix = format!("-{index}");
+        let name = digest::truncate(&source.key, 253 - suffix.len()) + &suffix;
+
+        let record = Record {
+            metadata: ObjectMeta {
+                name: Some(name.clone()),
+                namespace: Some(source.namespace.clone()),
+                labels: Some(BTreeMap::from([(
+                    SOURCE_LABEL.to_string(),
+                    source_label(&source.key),
+                )])),
+                owner_references: Some(vec![source.owner.clone()]),
+                ..ObjectMeta::default()
+            },
+            spec: RecordSpec {
+                domain_name: DomainName::from(fqdn),
+                zone_ref: Some(source.zone_ref.clone()),
+                type_,
+                rdata,
+                ..RecordSpec::default()
+            },
+            status: None,
+        };
+
+        records
+            .patch(
+                &name,
+                &PatchParams::apply(controller_name),
+                &Patch::Apply(record),
+            )
+            .await?;
+        names.push(name);
+    }
+
+    prune(&records, &source.key, &names).await?;
+
+    Ok(Action::requeue(Duration::from_secs(300)))
+}
+
+/// Delete the Records of the source, other than those named in `keep`.
+async fn prune(records: &Api<Record>, key: &str, keep: &[String]) -> Result<(), Error> {
+    for stale in records
+        .list(&ListParams::default().labels(&format!("{SOURCE_LABEL}={}", source_label(key))))
+        .await?
+        .items
+        .into_iter()
+        .filter(|record| !keep.contains(&record.name_any()))
+    {
+        info!(
+            "deleting stale record {}/{}",
+            stale.namespace().unwrap_or_default(),
+            stale.name_any()
+        );
+        records
+            .delete(&stale.name_any(), &DeleteParams::default())
+            .await?;
+    }
+
+    Ok(())
+}
+
+struct Context {
+    client: Client,
+    controller_name: String,
+}
+
+async fn reconcile_ingress(ingress: Arc<Ingress>, ctx: Arc<Context>) -> Result<Action, Error> {
+    let key = format!("ingress-{}", ingress.name_any());
+    let namespace = ingress.namespace().unwrap();
+
+    // Records are withdrawn when the annotation is removed.
+    let Some(zone) = ingress.annotations().get(SOURCE_ZONE_ANNOTATION) else {
+        prune(&Api::namespaced(ctx.client.clone(), &namespace), &key, &[]).await?;
+        return Ok(Action::await_change());
+    };
+
+    let hostnames = ingress
+        .spec
+        .iter()
+        .flat_map(|spec| spec.rules.iter().flatten())
+        .filter_map(|rule| rule.host.clone())
+        .collect::<Vec<_>>();
+
+    let targets = ingress
+        .status
+        .iter()
+        .filter_map(|status| status.load_balancer.as_ref())
+        .flat_map(|load_balancer| load_balancer.ingress.iter().flatten())
+        .filter_map(|ingress| ingress.ip.as_ref().or(ingress.hostname.as_ref()))
+        .map(String::as_str)
+        .map(Target::parse)
+        .collect::<Vec<_>>();
+
+    sync(
+        ctx.client.clone(),
+        &ctx.controller_name,
+        Source {
+            key,
+            namespace,
+            owner: ingress.controller_owner_ref(&()).unwrap(),
+            zone_ref: parse_zone_ref(zone),
+            records: desired_records(&hostnames, &targets),
+        },
+    )
+    .await
+}
+
+async fn reconcile_service(service: Arc<Service>, ctx: Arc<Context>) -> Result<Action, Error> {
+    let key = format!("service-{}", service.name_any());
+    let namespace = service.namespace().unwrap();
+
+    let is_load_balancer =
+        service.spec.as_ref().and_then(|spec| spec.type_.as_deref()) == Some("LoadBalancer");
+
+    // Records are withdrawn when the annotation is removed, or the Service changes type.
+    let (Some(zone), true) = (
+        service.annotations().get(SOURCE_ZONE_ANNOTATION),
+        is_load_balancer,
+    ) else {
+        prune(&Api::namespaced(ctx.client.clone(), &namespace), &key, &[]).await?;
+        return Ok(Action::await_change());
+    };
+
+    let hostnames = service
+        .annotations()
+        .get(SOURCE_HOSTNAMES_ANNOTATION)
+        .into_iter()
+        .flat_map(|hostnames| hostnames.split(','))
+        .map(str::trim)
+        .filter(|hostname| !hostname.is_empty())
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let targets = service
+        .status
+        .iter()
+        .filter_map(|status| status.load_balancer.as_ref())
+        .flat_map(|load_balancer| load_balancer.ingress.iter().flatten())
+        .filter_map(|ingress| ingress.ip.as_ref().or(ingress.hostname.as_ref()))
+        .map(String::as_str)
+        .map(Target::parse)
+        .collect::<Vec<_>>();
+
+    sync(
+        ctx.client.clone(),
+        &ctx.controller_name,
+        Source {
+            key,
+            namespace,
+            owner: service.controller_owner_ref(&()).unwrap(),
+            zone_ref: parse_zone_ref(zone),
+            records: desired_records(&hostnames, &targets),
+        },
+    )
+    .await
+}
+
+fn gateway_api_resource(kind: &str) -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "gateway.networking.k8s.io",
+        "v1",
+        kind,
+    ))
+}
+
+/// Namespaces and names of the Gateways which the HTTPRoute is attached to.
+fn parent_gateways(route: &DynamicObject) -> Vec<(String, String)> {
+    let namespace = route.namespace().unwrap_or_default();
+
+    route
+        .data
+        .pointer("/spec/parentRefs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|parent| {
+            parent
+                .get("kind")
+                .and_then(Value::as_str)
+                .unwrap_or("Gateway")
+                == "Gateway"
+        })
+        .filter_map(|parent| {
+            let name = parent.get("name").and_then(Value::as_str)?;
+            let parent_namespace = parent
+                .get("namespace")
+                .and_then(Value::as_str)
+                .unwrap_or(&namespace);
+
+            Some((parent_namespace.to_string(), name.to_string()))
+        })
+        .collect()
+}
+
+async fn reconcile_httproute(
+    route: Arc<DynamicObject>,
+    ctx: Arc<Context>,
+) -> Result<Action, Error> {
+    let key = format!("httproute-{}", route.name_any());
+    let namespace = route.namespace().unwrap();
+
+    // Records are withdrawn when the annotation is removed.
+    let Some(zone) = route.annotations().get(SOURCE_ZONE_ANNOTATION) else {
+        prune(&Api::namespaced(ctx.client.clone(), &namespace), &key, &[]).await?;
+        return Ok(Action::await_change());
+    };
+
+    let hostnames = route
+        .data
+        .pointer("/spec/hostnames")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let gateway_resource = gateway_api_resource("Gateway");
+
+    let mut targets = Vec::new();
+    for (parent_namespace, name) in parent_gateways(&route) {
+        let Some(gateway) = Api::<DynamicObject>::namespaced_with(
+            ctx.client.clone(),
+            &parent_namespace,
+            &gateway_resource,
+        )
+        .get_opt(&name)
+        .await?
+        else {
+            debug!(
+                "gateway {parent_namespace}/{name} of httproute {namespace}/{} does not exist",
+                route.name_any()
+            );
+            continue;
+        };
+
+        targets.extend(
+            gateway
+                .data
+                .pointer("/status/addresses")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|address| address.get("value").and_then(Value::as_str))
+                .map(Target::parse),
+        );
+    }
+
+    sync(
+        ctx.client.clone(),
+        &ctx.controller_name,
+        Source {
+            key,
+            owner: route
+                .controller_owner_ref(&gateway_api_resource("HTTPRoute"))
+                .unwrap(),
+            namespace,
+            zone_ref: parse_zone_ref(zone),
+            records: desired_records(&hostnames, &targets),
+        },
+    )
+    .await
+}
+
+fn error_policy<K: Resource>(resource: Arc<K>, error: &Error, _ctx: Arc<Context>) -> Action {
+    let delay = error.retry_after().unwrap_or(Duration::from_secs(300));
+    error!(
+        "sourcing records for {} failed, retrying in {}s: {error}",
+        resource.name_any(),
+        delay.as_secs()
+    );
+    Action::requeue(delay)
+}
+
+/// Run the sourcing controllers enabled by the arguments.
+pub async fn run(
+    client: Client,
+    args: SourceArgs,
+    controller_name: String,
+    watcher_config: watcher::Config,
+) {
+    let ctx = Arc::new(Context {
+        client: client.clone(),
+        controller_name,
+    });
+
+    let ingresses = async {
+        if args.source_ingresses {
+            Controller::new(Api::<Ingress>::all(client.clone()), watcher_config.clone())
+                .shutdown_on_signal()
+                .run(reconcile_ingress, error_policy, ctx.clone())
+                .for_each(|_| futures::future::ready(()))
+                .await;
+        }
+    };
+
+    let httproutes = async {
+        if args.source_httproutes {
+            let resource = gateway_api_resource("HTTPRoute");
+            let gateway_resource = gateway_api_resource("Gateway");
+
+            let controller = Controller::new_with(
+                Api::<DynamicObject>::all_with(client.clone(), &resource),
+                watcher_config.clone(),
+                resource,
+            );
+
+            // Routes point at the addresses of their Gateways, which are
+            // usually only assigned after the routes have been created.
+            let routes = controller.store();
+            controller
+                .watches_with(
+                    Api::<DynamicObject>::all_with(client.clone(), &gateway_resource),
+                    gateway_resource,
+                    watcher_config.clone(),
+                    move |gateway| {
+                        let gateway = (gateway.namespace().unwrap_or_default(), gateway.name_any());
+
+                        routes
+                            .state()
+                            .into_iter()
+                            .filter(|route| parent_gateways(route).contains(&gateway))
+                            .map(|route| {
+                                ObjectRef::from_obj_with(
+                                    route.as_ref(),
+                                    gateway_api_resource("HTTPRoute"),
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    },
+                )
+                .shutdown_on_signal()
+                .run(reconcile_httproute, error_policy, ctx.clone())
+                .for_each(|_| futures::future::ready(()))
+                .await;
+        }
+    };
+
+    let services = async {
+        if args.source_services {
+            Controller::new(Api::<Service>::all(client.clone()), watcher_config.clone())
+                .shutdown_on_signal()
+                .run(reconcile_service, error_policy, ctx.clone())
+                .for_each(|_| futures::future::ready(()))
+                .await;
+        }
+    };
+
+    futures::join!(ingresses, httproutes, services);
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::serde_json::json;
+    use kube::api::DynamicObject;
+    use kubizone_common::Type;
+
+    use super::{desired_records, gateway_api_resource, parent_gateways, source_label, Target};
+
+    #[test]
+    fn records_for_targets() {
+        let hostnames = vec!["www.example.org".to_string(), "example.org".to_string()];
+
+        let records = desired_records(
+            &hostnames,
+            &[
+                Target::parse("192.0.2.1"),
+                Target::parse("2001:db8::1"),
+                Target::parse("lb.example.net"),
+            ],
+        );
+
+        assert_eq!(
+            records
+                .iter()
+                .map(|(fqdn, type_, rdata)| format!("{fqdn} {type_} {rdata}"))
+                .collect::<Vec<_>>(),
+            vec![
+                "example.org. A 192.0.2.1",
+                "example.org. AAAA 2001:db8::1",
+                "www.example.org. A 192.0.2.1",
+                "www.example.org. AAAA 2001:db8::1",
+            ]
+        );
+
+        let records = desired_records(&hostnames[..1], &[Target::parse("lb.example.net.")]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].1, Type::CNAME);
+        assert_eq!(records[0].2, "lb.example.net.");
+    }
+
+    #[test]
+    fn attached_gateways() {
+        let resource = gateway_api_resource("HTTPRoute");
+        let route = DynamicObject::new("www", &resource)
+            .within("apps")
+            .data(json!({
+                "spec": {
+                    "parentRefs": [
+                        { "name": "internal" },
+                        { "name": "public", "namespace": "gateways" },
+                        { "name": "mesh", "kind": "Service" },
+                    ],
+                },
+            }));
+
+        assert_eq!(
+            parent_gateways(&route),
+            vec![
+                ("apps".to_string(), "internal".to_string()),
+                ("gateways".to_string(), "public".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_source_labels() {
+        let key = format!("ingress-{}", "a".repeat(100));
+        assert_eq!(source_label(&key).len(), 63);
+        assert_eq!(source_label("ingress-www"), "ingress-www");
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use kube::ResourceExt as _;
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{
+    conditions::{Observations, DEGRADED},
+    http::HttpClient,
+};
+
+/// Annotation on a [`ZoneFile`] holding the number of seconds a changed
+/// zone is published to its staging key before being promoted.
+pub const STAGING_SOAK_ANNOTATION: &str = "zonefile.kubi.zone/staging-soak";
+
+/// Annotation on a [`ZoneFile`] holding the URL of a probe which verifies
+/// a staged zone. A successful response promotes the zone immediately.
+///
+/// The probe is requested with `origin` and `serial` query parameters. It must
+/// use https, and its host must be among the controller's allowed http hosts.
+pub const STAGING_PROBE_ANNOTATION: &str = "zonefile.kubi.zone/staging-probe";
+
+/// How often a staged zone is probed while waiting for promotion.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Staged zones are published under this suffix, next to the primary key.
+pub fn staging_key(origin: &str) -> String {
+    format!("{origin}.staging")
+}
+
+/// Whether the key holds a staged zone, which only the zonefile's own ConfigMap carries.
+pub fn is_staging_key(key: &str) -> bool {
+    key.ends_with("..staging")
+}
+
+/// Conditions under which a staged zone is promoted. Either suffices.
+pub struct StagingPolicy {
+    pub soak: Option<Duration>,
+    pub probe: Option<String>,
+}
+
+impl StagingPolicy {
+    /// Read the staging policy of the zonefile, if staged publishing is enabled.
+    pub fn for_zonefile(
+        zonefile: &ZoneFile,
+        http: &HttpClient,
+        observations: &mut Observations,
+    ) -> Option<Self> {
+        let annotations = zonefile.annotations();
+
+        let soak = annotations
+            .get(STAGING_SOAK_ANNOTATION)
+            .and_then(|soak| match soak.parse() {
+                Ok(seconds) => Some(Duration::from_secs(seconds)),
+                Err(_) => {
+                    observations.observe(
+                        DEGRADED,
+                        "InvalidStagingSoak",
+                        format!("invalid staging soak period {soak:?}"),
+                    );
+                    None
+                }
+            });
+
+        // A refused probe never succeeds, so that the zone is not promoted unverified.
+        let probe = annotations.get(STAGING_PROBE_ANNOTATION).cloned();
+        if let Some(Err(e)) = probe.as_deref().map(|url| http.allow(url)) {
+            observations.observe(
+                DEGRADED,
+                "DisallowedStagingProbe",
+                format!("staging probe refused: {e}"),
+            );
+        }
+
+        (soak.is_some() || probe.is_some()).then_some(StagingPolicy { soak, probe })
+    }
+
+    /// Time to wait before checking a zone which has been staged for `staged_for` again.
+    pub fn recheck_after(&self, staged_for: Duration) -> Duration {
+        let soak_remaining = self
+            .soak
+            .map(|soak| soak.saturating_sub(staged_for))
+            .unwrap_or(Duration::MAX);
+
+        if self.probe.is_some() {
+            soak_remaining.min(PROBE_INTERVAL)
+        } else {
+            soak_remaining
+        }
+    }
+}
+
+/// Contents currently staged for each zone, per zonefile, along with the time they were staged.
+///
+/// This is kept in memory only, so a restarted controller begins
+/// the soak period of zones which were already staged anew.
+#[derive(Default)]
+pub struct StagedZones(Mutex<HashMap<(String, String), (String, Instant)>>);
+
+impl StagedZones {
+    /// Stage the contents of a zone, returning how long these exact contents have been staged.
+    pub fn stage(&self, zonefile: &str, origin: &str, contents: &str) -> Duration {
+        let mut staged = self.0.lock().unwrap();
+        let entry = staged
+            .entry((zonefile.to_string(), origin.to_string()))
+            .or_insert_with(|| (contents.to_string(), Instant::now()));
+
+        if entry.0 != contents {
+            *entry = (contents.to_string(), Instant::now());
+        }
+
+        entry.1.elapsed()
+    }
+
+    /// Forget the staged contents of a zone, once they have been promoted.
+    pub fn clear(&self, zonefile: &str, origin: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .remove(&(zonefile.to_string(), origin.to_string()));
+    }
+}
+
+/// Ask the verification probe whether the staged zone may be promoted.
+pub async fn probe(http: &HttpClient, url: &str, origin: &str, serial: u32) -> bool {
+    let url = match http.allow(url) {
+        Ok(url) => url,
+        Err(e) => {
+            debug!("staging probe for zone {origin} refused: {e}");
+            return false;
+        }
+    };
+
+    let result = http
+        .client()
+        .get(url)
+        .query(&[
+            ("origin", origin.to_string()),
+            ("serial", serial.to_string()),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            debug!("staging probe for zone {origin} did not succeed: {e}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{is_staging_key, staging_key, StagedZones, StagingPolicy, PROBE_INTERVAL};
+
+    #[test]
+    fn recognizes_staging_keys() {
+        assert!(is_staging_key(&staging_key("example.org.")));
+        assert!(is_staging_key(&staging_key(".")));
+        assert!(!is_staging_key("example.org."));
+        assert!(!is_staging_key("staging."));
+        assert!(!is_staging_key("example.org..stats.json"));
+    }
+
+    #[test]
+    fn restaging_restarts_the_soak() {
+        let staged = StagedZones::default();
+
+        staged.stage("default/example", "example.org.", "old");
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert!(
+            staged.stage("default/example", "example.org.", "old") >= Duration::from_millis(10)
+        );
+        assert!(staged.stage("default/example", "example.org.", "new") < Duration::from_millis(10));
+    }
+
+    #[test]
+    fn recheck_interval() {
+        let policy = StagingPolicy {
+            soak: Some(Duration::from_secs(600)),
+            probe: None,
+        };
+
+        assert_eq!(
+            policy.recheck_after(Duration::from_secs(100)),
+            Duration::from_secs(500)
+        );
+
+        let policy = StagingPolicy {
+            probe: Some(String::from("https://probe")),
+            ..policy
+        };
+
+        assert_eq!(
+            policy.recheck_after(Duration::from_secs(100)),
+            PROBE_INTERVAL
+        );
+    }
+}
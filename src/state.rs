@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use clap::Args;
+use kube::{
+    api::{Patch, PatchParams},
+    Api, Client, CustomResource, ResourceExt as _,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::log::*;
+use zonefile_crds::ZoneFile;
+
+use crate::{error::Error, output::DeliveredOutputs, ownership};
+
+/// State of the publication of a [`ZoneFile`], persisted under the name of the
+/// zonefile once its status is written, so that a restarted controller, or one
+/// taking over from another instance, resumes where the previous one left off.
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[kube(
+    group = "kubi.zone",
+    version = "v1alpha1",
+    kind = "ZoneFileState",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneFileStateSpec {
+    /// Hashes of the keys last delivered to each of the zonefile's outputs, by
+    /// output. Keys whose hashes match are not delivered again, which spares
+    /// the output targets from redundant uploads and NOTIFY messages.
+    #[serde(default)]
+    pub delivered: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct StateArgs {
+    /// Persist the outputs delivered for each zonefile in a ZoneFileState of
+    /// the same name, rather than delivering all outputs again after a restart.
+    #[arg(long, env = "ZONEFILE_PERSIST_STATE")]
+    pub persist_state: bool,
+}
+
+/// Restore the outputs delivered for the zonefile from its persisted state, once
+/// after the controller started, unless outputs were delivered since then.
+pub async fn restore(
+    client: Client,
+    zonefile: &ZoneFile,
+    zonefile_name: &str,
+    outputs: &DeliveredOutputs,
+) -> Result<(), Error> {
+    if outputs.restored(zonefile_name) || outputs.known(zonefile_name) {
+        return Ok(());
+    }
+
+    let state = Api::<ZoneFileState>::namespaced(client, zonefile.namespace().as_ref().unwrap())
+        .get_opt(&zonefile.name_any())
+        .await?;
+
+    // Zonefiles without any persisted state are not looked up again either.
+    outputs.mark_restored(zonefile_name);
+    let Some(state) = state else {
+        return Ok(());
+    };
+
+    debug!(
+        "restoring {} delivered outputs of zonefile {zonefile_name}",
+        state.spec.delivered.len()
+    );
+    for (target, hashes) in &state.spec.delivered {
+        outputs.delivered(zonefile_name, target, hashes);
+    }
+
+    Ok(())
+}
+
+/// Persist the outputs delivered for the zonefile.
+pub async fn persist(
+    client: Client,
+    params: &PatchParams,
+    zonefile: &ZoneFile,
+    zonefile_name: &str,
+    label_ownership: bool,
+    outputs: &DeliveredOutputs,
+) -> Result<(), Error> {
+    let mut state = ZoneFileState::new(
+        &zonefile.name_any(),
+        ZoneFileStateSpec {
+            delivered: outputs.of(zonefile_name),
+        },
+    );
+    state.metadata.namespace = zonefile.namespace();
+    ownership::mark_owned(zonefile, label_ownership, &mut state.metadata);
+
+    Api::<ZoneFileState>::namespaced(client, zonefile.namespace().as_ref().unwrap())
+        .patch(&zonefile.name_any(), params, &Patch::Apply(state))
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+
+use clap::Args;
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+
+#[derive(Debug, Clone, Args)]
+pub struct StatsArgs {
+    /// Publish the statistics of each zone as an `<origin>.stats.json`
+    /// key in the zonefile's ConfigMap, alongside the zone itself.
+    #[arg(long, env = "ZONEFILE_PUBLISH_STATS")]
+    pub publish_stats: bool,
+}
+
+/// The set of records sharing the same name and type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordSet {
+    pub name: FullyQualifiedDomainName,
+    pub type_: Type,
+    pub records: usize,
+}
+
+/// Summary of a single rendered zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneStats {
+    pub records: usize,
+    /// Number of records of each type present in the zone.
+    pub types: BTreeMap<Type, usize>,
+    /// The record set with the most records. Ties are broken
+    /// by picking the first in name and type order.
+    pub largest_rrset: Option<RecordSet>,
+    /// Duplicate records omitted from the zone.
+    pub duplicates: usize,
+    /// Size of the rendered zonefile in bytes.
+    pub size: usize,
+    /// Whether a detached signature is published for the zone.
+    pub signed: bool,
+}
+
+impl ZoneStats {
+    pub fn compute(entries: &[ZoneEntry], contents: &str, signed: bool) -> Self {
+        let mut types = BTreeMap::<Type, usize>::new();
+        let mut rrsets = BTreeMap::<(&FullyQualifiedDomainName, Type), usize>::new();
+
+        for entry in entries {
+            *types.entry(entry.type_).or_default() += 1;
+            *rrsets.entry((&entry.fqdn, entry.type_)).or_default() += 1;
+        }
+
+        let largest_rrset = rrsets
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, records)| *records)
+            .map(|((name, type_), records)| RecordSet {
+                name: name.clone(),
+                type_,
+                records,
+            });
+
+        ZoneStats {
+            records: entries.len(),
+            types,
+            largest_rrset,
+            duplicates: 0,
+            size: contents.len(),
+            signed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::{RecordSet, ZoneStats};
+
+    #[test]
+    fn counts_records_and_rrsets() {
+        let entry = |name: &str, type_: Type, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let entries = vec![
+            entry("example.org.", Type::NS, "ns1.example.org."),
+            entry("example.org.", Type::NS, "ns2.example.org."),
+            entry("www.example.org.", Type::A, "192.0.2.1"),
+            entry("www.example.org.", Type::A, "192.0.2.2"),
+            entry("www.example.org.", Type::AAAA, "2001:db8::1"),
+        ];
+
+        assert_eq!(
+            ZoneStats::compute(&entries, "$ORIGIN example.org.", false),
+            ZoneStats {
+                records: 5,
+                types: BTreeMap::from([(Type::A, 2), (Type::AAAA, 1), (Type::NS, 2)]),
+                largest_rrset: Some(RecordSet {
+                    name: FullyQualifiedDomainName::try_from("example.org.").unwrap(),
+                    type_: Type::NS,
+                    records: 2,
+                }),
+                duplicates: 0,
+                size: 20,
+                signed: false,
+            }
+        );
+    }
+}
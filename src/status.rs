@@ -0,0 +1,56 @@
+use k8s_openapi::serde_json::Value;
+
+/// Whether merging the patch into the live object would leave it unchanged, so
+/// that reconciliations which arrive at the status the zonefile already has can
+/// skip writing it, while status edits made by anyone else are still corrected.
+pub fn is_current(live: &Value, patch: &Value) -> bool {
+    match (live, patch) {
+        (Value::Object(live), Value::Object(patch)) => patch.iter().all(|(key, patch)| {
+            match live.get(key) {
+                Some(live) => is_current(live, patch),
+                // Merge patches remove the fields they set to null.
+                None => patch.is_null(),
+            }
+        }),
+        (live, patch) => live == patch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::serde_json::json;
+
+    use super::is_current;
+
+    #[test]
+    fn compares_against_live_status() {
+        let live = json!({
+            "status": {
+                "serial": { "example.org.": 1 },
+                "outputs": [{ "target": "configmap:dns/zones", "delivered": true }],
+            },
+        });
+
+        assert!(is_current(&live, &json!({ "status": {} })));
+        assert!(is_current(
+            &live,
+            &json!({ "status": { "serial": { "example.org.": 1 }, "frozen": null } })
+        ));
+        assert!(!is_current(
+            &live,
+            &json!({ "status": { "serial": { "example.org.": 2 } } })
+        ));
+        assert!(!is_current(
+            &live,
+            &json!({ "status": { "outputs": [{ "target": "configmap:dns/zones", "delivered": false }] } })
+        ));
+        assert!(!is_current(
+            &live,
+            &json!({ "status": { "outputs": null } })
+        ));
+        assert!(!is_current(
+            &json!({ "status": null }),
+            &json!({ "status": { "serial": {} } })
+        ));
+    }
+}
@@ -0,0 +1,324 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use clap::Args;
+use k8s_openapi::serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncRead, AsyncWrite, AsyncWriteExt as _, BufReader},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{
+        pki_types::{CertificateDer, ServerName},
+        ClientConfig, RootCertStore,
+    },
+    TlsConnector,
+};
+use tracing::log::*;
+
+use crate::{
+    diff::ZoneChange,
+    error::Error,
+    notify::{payload, NotifyFormat},
+};
+
+/// Time allowed for connecting to the server and publishing a single event.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Args)]
+pub struct StreamArgs {
+    /// Address of a NATS server, as `tls://<host>:<port>`, or `nats://<host>:<port>`
+    /// for a server without TLS, which will receive a structured event whenever
+    /// the rendered contents of a zone changes.
+    #[arg(long, env = "ZONEFILE_NATS_URL")]
+    pub nats_url: Option<String>,
+
+    /// PEM bundle of certificate authorities trusted when connecting to
+    /// the NATS server over TLS, instead of the bundled Mozilla roots.
+    #[arg(long, env = "ZONEFILE_NATS_CA_BUNDLE")]
+    pub nats_ca_bundle: Option<PathBuf>,
+
+    /// Subject which events are published to.
+    #[arg(
+        long,
+        env = "ZONEFILE_NATS_SUBJECT",
+        default_value = "kubizone.zonefile.changes"
+    )]
+    pub nats_subject: String,
+
+    /// User to authenticate as. Credentials are best passed through
+    /// environment variables populated from a Secret, and are only
+    /// ever sent to servers connected to over TLS.
+    #[arg(long, env = "ZONEFILE_NATS_USER", requires = "nats_password")]
+    pub nats_user: Option<String>,
+
+    /// Password of `--nats-user`.
+    #[arg(
+        long,
+        env = "ZONEFILE_NATS_PASSWORD",
+        requires = "nats_user",
+        hide_env_values = true
+    )]
+    pub nats_password: Option<String>,
+
+    /// Token to authenticate with, instead of a user and password.
+    #[arg(
+        long,
+        env = "ZONEFILE_NATS_TOKEN",
+        conflicts_with = "nats_user",
+        hide_env_values = true
+    )]
+    pub nats_token: Option<String>,
+}
+
+/// Publishes [`ZoneChange`]s to a NATS subject.
+pub struct EventStream {
+    address: String,
+    subject: String,
+    connect: Value,
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+}
+
+/// `CONNECT` message of the NATS client protocol, with the configured credentials.
+fn connect_options(args: &StreamArgs, tls: bool) -> Value {
+    let mut options = json!({
+        "verbose": false,
+        "pedantic": false,
+        "name": "kubizone-zonefile",
+        "lang": "rust",
+        "version": env!("CARGO_PKG_VERSION"),
+        "protocol": 0,
+        "tls_required": tls,
+    });
+
+    if let (Some(user), Some(password)) = (&args.nats_user, &args.nats_password) {
+        options["user"] = json!(user);
+        options["pass"] = json!(password);
+    }
+
+    if let Some(token) = &args.nats_token {
+        options["auth_token"] = json!(token);
+    }
+
+    options
+}
+
+/// Frame the payload as a `PUB` message of the NATS client protocol,
+/// followed by a `PING`, whose `PONG` confirms it was accepted.
+fn publish_message(subject: &str, payload: &str) -> String {
+    format!("PUB {subject} {}\r\n{payload}\r\nPING\r\n", payload.len())
+}
+
+/// Client configuration trusting the CA bundle, or the Mozilla roots without one.
+fn tls_connector(ca_bundle: Option<&PathBuf>) -> Result<TlsConnector, Error> {
+    let mut roots = RootCertStore::empty();
+
+    match ca_bundle {
+        Some(ca_bundle) => {
+            let certificates = pem::parse_many(std::fs::read(ca_bundle)?)
+                .map_err(|e| Error::Config(format!("invalid NATS CA bundle: {e}")))?;
+
+            for certificate in certificates {
+                roots
+                    .add(CertificateDer::from(certificate.into_contents()))
+                    .map_err(|e| Error::Config(format!("invalid NATS CA certificate: {e}")))?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    Ok(TlsConnector::from(Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )))
+}
+
+impl EventStream {
+    /// Construct an event stream from the command line arguments, if a server was configured.
+    /// Credentials are refused for servers which are not connected to over TLS.
+    pub fn from_args(args: &StreamArgs) -> Result<Option<Self>, Error> {
+        let Some(url) = args.nats_url.as_ref() else {
+            return Ok(None);
+        };
+
+        let (address, tls) = match (url.strip_prefix("tls://"), url.strip_prefix("nats://")) {
+            (Some(address), _) => (address, true),
+            (None, Some(address)) => (address, false),
+            (None, None) => (url.as_str(), false),
+        };
+
+        if !tls && (args.nats_user.is_some() || args.nats_token.is_some()) {
+            return Err(Error::Config(format!(
+                "NATS credentials are only sent over TLS, use tls:// instead of {url}"
+            )));
+        }
+
+        let tls = if tls {
+            // The port is optional, and IPv6 addresses are enclosed in brackets.
+            let host = match address.rsplit_once(':') {
+                Some((host, port)) if !port.contains(']') => host,
+                _ => address,
+            };
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+
+            let server_name = ServerName::try_from(host.to_string())
+                .map_err(|e| Error::Config(format!("invalid NATS server name {host:?}: {e}")))?;
+
+            Some((tls_connector(args.nats_ca_bundle.as_ref())?, server_name))
+        } else {
+            None
+        };
+
+        Ok(Some(EventStream {
+            address: address.to_string(),
+            subject: args.nats_subject.clone(),
+            connect: connect_options(args, tls.is_some()),
+            tls,
+        }))
+    }
+
+    async fn send(&self, payload: &str) -> Result<(), std::io::Error> {
+        let mut stream = BufReader::new(TcpStream::connect(&self.address).await?);
+
+        // The server greets clients with its INFO, before accepting any
+        // messages, and before the connection is upgraded to TLS.
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        if !line.starts_with("INFO") {
+            return Err(std::io::Error::other(format!(
+                "unexpected greeting {:?}",
+                line.trim_end()
+            )));
+        }
+
+        match &self.tls {
+            Some((connector, server_name)) => {
+                let stream = connector
+                    .connect(server_name.clone(), stream.into_inner())
+                    .await?;
+                self.exchange(BufReader::new(stream), payload).await
+            }
+            None => self.exchange(stream, payload).await,
+        }
+    }
+
+    /// Authenticate and publish the payload, once greeted by the server.
+    async fn exchange(
+        &self,
+        mut stream: BufReader<impl AsyncRead + AsyncWrite + Unpin>,
+        payload: &str,
+    ) -> Result<(), std::io::Error> {
+        let mut line = String::new();
+
+        stream
+            .get_mut()
+            .write_all(
+                format!(
+                    "CONNECT {}\r\n{}",
+                    self.connect,
+                    publish_message(&self.subject, payload)
+                )
+                .as_bytes(),
+            )
+            .await?;
+
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+            }
+
+            match line.trim_end() {
+                "PONG" => return Ok(()),
+                "PING" => stream.get_mut().write_all(b"PONG\r\n").await?,
+                error if error.starts_with("-ERR") => {
+                    return Err(std::io::Error::other(error.to_string()))
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Publish the change to the subject.
+    ///
+    /// Failure to publish is logged, but never fails the reconciliation
+    /// which produced the change.
+    pub async fn publish(&self, change: &ZoneChange<'_>) {
+        let payload = payload(change, NotifyFormat::Json).to_string();
+
+        match tokio::time::timeout(PUBLISH_TIMEOUT, self.send(&payload)).await {
+            Ok(Ok(())) => debug!(
+                "published change to zone {} to {}",
+                change.origin, self.subject
+            ),
+            Ok(Err(e)) => warn!(
+                "failed to publish change to zone {} to {}: {e}",
+                change.origin, self.subject
+            ),
+            Err(_) => warn!(
+                "timed out publishing change to zone {} to {}",
+                change.origin, self.subject
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{connect_options, publish_message, EventStream, StreamArgs};
+
+    #[test]
+    fn protocol_messages() {
+        assert_eq!(
+            publish_message("kubizone.zonefile.changes", "{\"origin\":\"example.org.\"}"),
+            "PUB kubizone.zonefile.changes 25\r\n{\"origin\":\"example.org.\"}\r\nPING\r\n"
+        );
+
+        let args = StreamArgs {
+            nats_url: Some("tls://nats:4222".to_string()),
+            nats_ca_bundle: None,
+            nats_subject: "kubizone.zonefile.changes".to_string(),
+            nats_user: Some("zonefile".to_string()),
+            nats_password: Some("hunter2".to_string()),
+            nats_token: None,
+        };
+        let options = connect_options(&args, true);
+
+        assert_eq!(options["user"], "zonefile");
+        assert_eq!(options["pass"], "hunter2");
+        assert!(options.get("auth_token").is_none());
+        assert_eq!(options["verbose"], false);
+        assert_eq!(options["tls_required"], true);
+    }
+
+    #[test]
+    fn credentials_require_tls() {
+        let args = StreamArgs {
+            nats_url: Some("tls://nats:4222".to_string()),
+            nats_ca_bundle: None,
+            nats_subject: "kubizone.zonefile.changes".to_string(),
+            nats_user: None,
+            nats_password: None,
+            nats_token: Some("s3cr3t".to_string()),
+        };
+
+        let stream = EventStream::from_args(&args).unwrap().unwrap();
+        assert_eq!(stream.address, "nats:4222");
+        assert!(stream.tls.is_some());
+
+        assert!(EventStream::from_args(&StreamArgs {
+            nats_url: Some("nats://nats:4222".to_string()),
+            ..args.clone()
+        })
+        .is_err());
+
+        let stream = EventStream::from_args(&StreamArgs {
+            nats_url: Some("nats://nats:4222".to_string()),
+            nats_token: None,
+            ..args
+        })
+        .unwrap()
+        .unwrap();
+        assert!(stream.tls.is_none());
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeMap,
+    hash::{Hash, Hasher},
+};
+
+use kube::{
+    runtime::reflector::{ObjectRef, Store},
+    CustomResource, ResourceExt as _,
+};
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneEntry;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use zonefile_crds::ZoneFile;
+
+use crate::render::{Charset, FormatArgs, LineEnding};
+
+/// Annotation on a [`ZoneFile`] naming the [`ZoneFileTemplate`] within
+/// its namespace, from which it takes its formatting and SOA defaults.
+pub const TEMPLATE_ANNOTATION: &str = "zonefile.kubi.zone/template";
+
+/// Settings shared by many [`ZoneFile`]s. Settings left out of the
+/// template fall back to the controller's own options.
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, Default, Hash, JsonSchema)]
+#[kube(
+    group = "kubi.zone",
+    version = "v1alpha1",
+    kind = "ZoneFileTemplate",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneFileTemplateSpec {
+    /// Formatting of the rendered zones.
+    #[serde(default)]
+    pub format: TemplateFormat,
+    /// Values replacing those of the zones' SOA records.
+    #[serde(default)]
+    pub soa: SoaDefaults,
+    /// Bounds on the TTLs of the zones' records.
+    #[serde(default)]
+    pub ttl: TtlPolicy,
+}
+
+/// Overrides of the controller's [`FormatArgs`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Hash, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateFormat {
+    pub omit_class: Option<bool>,
+    pub default_ttl: Option<bool>,
+    pub group_owners: Option<bool>,
+    pub multiline_soa: Option<bool>,
+    pub expand_generate: Option<bool>,
+    pub name_width: Option<usize>,
+    pub no_align: Option<bool>,
+    pub relative_rdata: Option<bool>,
+    pub sub_origin_threshold: Option<usize>,
+    pub line_ending: Option<LineEnding>,
+    pub charset: Option<Charset>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Hash, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SoaDefaults {
+    /// Email address of the party responsible for the zones, used unless
+    /// the zone or zonefile is annotated with a contact of its own.
+    pub contact: Option<String>,
+    pub refresh: Option<u32>,
+    pub retry: Option<u32>,
+    pub expire: Option<u32>,
+    /// Negative caching TTL, unless the zone or zonefile is annotated with
+    /// one of its own. It is bounded by the controller's maximum.
+    pub minimum: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TtlPolicy {
+    /// Records with a lower TTL are raised to it.
+    pub min: Option<u32>,
+    /// Records with a higher TTL are lowered to it.
+    pub max: Option<u32>,
+
+    /// TTLs given to all records of a type, such as `TXT: 300`, regardless of the bounds.
+    /// The original TTLs of the records are kept in comments on them.
+    #[serde(default)]
+    pub overrides: BTreeMap<Type, u32>,
+}
+
+// Record types are hashed by their names.
+impl Hash for TtlPolicy {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.min.hash(state);
+        self.max.hash(state);
+        for (type_, ttl) in &self.overrides {
+            type_.to_string().hash(state);
+            ttl.hash(state);
+        }
+    }
+}
+
+impl TemplateFormat {
+    /// The controller's formatting options, overridden by those of the template.
+    pub fn apply(&self, format: &FormatArgs) -> FormatArgs {
+        FormatArgs {
+            omit_class: self.omit_class.unwrap_or(format.omit_class),
+            default_ttl: self.default_ttl.unwrap_or(format.default_ttl),
+            group_owners: self.group_owners.unwrap_or(format.group_owners),
+            multiline_soa: self.multiline_soa.unwrap_or(format.multiline_soa),
+            expand_generate: self.expand_generate.unwrap_or(format.expand_generate),
+            name_width: self.name_width.or(format.name_width),
+            no_align: self.no_align.unwrap_or(format.no_align),
+            relative_rdata: self.relative_rdata.unwrap_or(format.relative_rdata),
+            sub_origin_threshold: self.sub_origin_threshold.or(format.sub_origin_threshold),
+            line_ending: self.line_ending.unwrap_or(format.line_ending),
+            charset: self.charset.unwrap_or(format.charset),
+        }
+    }
+}
+
+impl SoaDefaults {
+    /// These values, falling back to the defaults for those left out.
+    pub fn or(&self, defaults: &SoaDefaults) -> SoaDefaults {
+        SoaDefaults {
+            contact: self.contact.clone().or_else(|| defaults.contact.clone()),
+            refresh: self.refresh.or(defaults.refresh),
+            retry: self.retry.or(defaults.retry),
+            expire: self.expire.or(defaults.expire),
+            minimum: self.minimum.or(defaults.minimum),
+        }
+    }
+
+    /// Replace the timers of the zone's SOA record with those of the template.
+    pub fn apply(&self, origin: &FullyQualifiedDomainName, entries: &mut [ZoneEntry]) {
+        let Some(soa) = entries
+            .iter_mut()
+            .find(|entry| entry.type_ == Type::SOA && entry.fqdn == *origin)
+        else {
+            return;
+        };
+
+        let mut fields: Vec<String> = soa.rdata.split_whitespace().map(String::from).collect();
+        if fields.len() != 7 {
+            return;
+        }
+
+        for (index, value) in [
+            (3, self.refresh),
+            (4, self.retry),
+            (5, self.expire),
+            (6, self.minimum),
+        ] {
+            if let Some(value) = value {
+                fields[index] = value.to_string();
+            }
+        }
+
+        soa.rdata = fields.join(" ");
+    }
+}
+
+impl TtlPolicy {
+    /// This policy, falling back to the defaults for the bounds and types it leaves out.
+    pub fn or(&self, defaults: &TtlPolicy) -> TtlPolicy {
+        let mut overrides = defaults.overrides.clone();
+        overrides.extend(&self.overrides);
+
+        TtlPolicy {
+            min: self.min.or(defaults.min),
+            max: self.max.or(defaults.max),
+            overrides,
+        }
+    }
+
+    /// Clamp the TTLs of the records to the bounds of the policy, then override those
+    /// of the types it covers. Returns the overridden records with their original TTLs.
+    pub fn apply(&self, entries: &mut [ZoneEntry]) -> Vec<(ZoneEntry, u32)> {
+        let mut overridden = Vec::new();
+
+        for entry in entries {
+            let original = entry.ttl;
+
+            if let Some(min) = self.min {
+                entry.ttl = entry.ttl.max(min);
+            }
+
+            if let Some(max) = self.max {
+                entry.ttl = entry.ttl.min(max);
+            }
+
+            if let Some(ttl) = self.overrides.get(&entry.type_) {
+                entry.ttl = *ttl;
+
+                if original != *ttl {
+                    overridden.push((entry.clone(), original));
+                }
+            }
+        }
+
+        overridden
+    }
+}
+
+/// Zonefiles using the template, which should therefore be reconciled when it changes.
+pub fn zonefiles_using(
+    template: &ZoneFileTemplate,
+    zonefiles: &Store<ZoneFile>,
+) -> Vec<ObjectRef<ZoneFile>> {
+    zonefiles
+        .state()
+        .iter()
+        .filter(|zonefile| {
+            zonefile.namespace() == template.namespace()
+                && zonefile.annotations().get(TEMPLATE_ANNOTATION) == Some(&template.name_any())
+        })
+        .map(|zonefile| ObjectRef::from_obj(zonefile.as_ref()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::{SoaDefaults, TemplateFormat, TtlPolicy};
+    use crate::render::FormatArgs;
+
+    fn entry(fqdn: &str, type_: Type, ttl: u32, rdata: &str) -> ZoneEntry {
+        ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(fqdn).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl,
+            rdata: rdata.to_string(),
+        }
+    }
+
+    #[test]
+    fn template_overrides_format() {
+        let format = TemplateFormat {
+            omit_class: Some(false),
+            multiline_soa: Some(true),
+            ..TemplateFormat::default()
+        }
+        .apply(&FormatArgs {
+            omit_class: true,
+            group_owners: true,
+            ..FormatArgs::default()
+        });
+
+        assert!(!format.omit_class);
+        assert!(format.group_owners);
+        assert!(format.multiline_soa);
+        assert!(!format.default_ttl);
+    }
+
+    #[test]
+    fn template_overrides_soa_and_ttls() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        let mut entries = vec![
+            entry(
+                "example.org.",
+                Type::SOA,
+                360,
+                "ns1.example.org. hostmaster.example.org. 2024010101 86400 7200 3600000 360",
+            ),
+            entry("www.example.org.", Type::A, 30, "127.0.0.1"),
+            entry("mail.example.org.", Type::A, 172800, "127.0.0.2"),
+        ];
+
+        SoaDefaults {
+            refresh: Some(3600),
+            minimum: Some(60),
+            ..SoaDefaults::default()
+        }
+        .apply(&origin, &mut entries);
+
+        TtlPolicy {
+            min: Some(60),
+            max: Some(86400),
+            ..TtlPolicy::default()
+        }
+        .apply(&mut entries);
+
+        assert_eq!(
+            entries[0].rdata,
+            "ns1.example.org. hostmaster.example.org. 2024010101 3600 7200 3600000 60"
+        );
+        assert_eq!(
+            entries.iter().map(|entry| entry.ttl).collect::<Vec<_>>(),
+            vec![360, 60, 86400]
+        );
+    }
+
+    #[test]
+    fn template_overrides_ttls_by_type() {
+        let mut entries = vec![
+            entry("example.org.", Type::NS, 3600, "ns1.example.org."),
+            entry("example.org.", Type::TXT, 86400, "\"v=spf1 -all\""),
+            entry("www.example.org.", Type::A, 30, "127.0.0.1"),
+            entry("mail.example.org.", Type::TXT, 300, "\"hello\""),
+        ];
+
+        let overridden = TtlPolicy {
+            min: Some(60),
+            max: Some(3600),
+            overrides: BTreeMap::from([(Type::NS, 86400), (Type::TXT, 300)]),
+        }
+        .apply(&mut entries);
+
+        assert_eq!(
+            entries.iter().map(|entry| entry.ttl).collect::<Vec<_>>(),
+            vec![86400, 300, 60, 300]
+        );
+        assert_eq!(
+            overridden
+                .iter()
+                .map(|(entry, original)| (entry.type_, *original))
+                .collect::<Vec<_>>(),
+            vec![(Type::NS, 3600), (Type::TXT, 86400)]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) holding the minimum
+/// number of seconds between two publications of the same zone. Changes made
+/// in the meantime are coalesced and published once the interval has passed.
+pub const MIN_PUBLISH_INTERVAL_ANNOTATION: &str = "zonefile.kubi.zone/min-publish-interval";
+
+/// Time of the most recent publication of each zone, per zonefile.
+///
+/// This is kept in memory only, so a restarted controller
+/// publishes pending changes immediately.
+#[derive(Default)]
+pub struct PublishThrottle(Mutex<HashMap<(String, String), Instant>>);
+
+impl PublishThrottle {
+    /// Time remaining until the zone may be published again, if it must wait.
+    pub fn delay(&self, zonefile: &str, origin: &str, interval: Duration) -> Option<Duration> {
+        let published = self
+            .0
+            .lock()
+            .unwrap()
+            .get(&(zonefile.to_string(), origin.to_string()))
+            .copied()?;
+
+        interval
+            .checked_sub(published.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Record that the zone has just been published.
+    pub fn published(&self, zonefile: &str, origin: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .insert((zonefile.to_string(), origin.to_string()), Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::PublishThrottle;
+
+    #[test]
+    fn delays_republication() {
+        let throttle = PublishThrottle::default();
+        let hour = Duration::from_secs(3600);
+
+        assert_eq!(
+            throttle.delay("default/example", "example.org.", hour),
+            None
+        );
+
+        throttle.published("default/example", "example.org.");
+
+        assert!(throttle
+            .delay("default/example", "example.org.", hour)
+            .is_some_and(|delay| delay <= hour));
+        assert_eq!(
+            throttle.delay("default/example", "example.com.", hour),
+            None
+        );
+        assert_eq!(
+            throttle.delay("default/example", "example.org.", Duration::ZERO),
+            None
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Write as _;
+
+use hickory_proto::{
+    rr::{Name, RData, Record},
+    serialize::binary::{BinEncodable as _, BinEncoder, EncodeMode},
+};
+
+/// Names in tinydns-data are written without their trailing dot.
+fn name(name: &Name) -> String {
+    let name = name.to_ascii();
+    match name.strip_suffix('.') {
+        Some("") | None => name,
+        Some(name) => name.to_string(),
+    }
+}
+
+/// Escape a field, writing any byte which is not printable, or which would
+/// otherwise end the field, as a three-digit octal escape.
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for byte in bytes {
+        if (0x20..=0x7e).contains(byte) && !matches!(byte, b':' | b'\\') {
+            escaped.push(*byte as char);
+        } else {
+            let _ = write!(escaped, "\\{byte:03o}");
+        }
+    }
+    escaped
+}
+
+/// Render the records of a zone as [tinydns-data](https://cr.yp.to/djbdns/tinydns-data.html)
+/// lines, the SOA record first. Types without a line of their own are written as
+/// generic records holding their wire format rdata.
+pub fn serialize(records: &[Record]) -> String {
+    let mut lines = Vec::with_capacity(records.len());
+
+    for record in records {
+        let fqdn = escape(name(record.name()).as_bytes());
+        let ttl = record.ttl();
+
+        let line = match record.data() {
+            Some(RData::SOA(soa)) => format!(
+                "Z{fqdn}:{}:{}:{}:{}:{}:{}:{}:{ttl}",
+                escape(name(soa.mname()).as_bytes()),
+                escape(name(soa.rname()).as_bytes()),
+                soa.serial(),
+                soa.refresh(),
+                soa.retry(),
+                soa.expire(),
+                soa.minimum(),
+            ),
+            Some(RData::NS(ns)) => format!("&{fqdn}::{}:{ttl}", escape(name(&ns.0).as_bytes())),
+            Some(RData::A(a)) => format!("+{fqdn}:{}:{ttl}", a.0),
+            Some(RData::MX(mx)) => format!(
+                "@{fqdn}::{}:{}:{ttl}",
+                escape(name(mx.exchange()).as_bytes()),
+                mx.preference()
+            ),
+            Some(RData::CNAME(cname)) => {
+                format!("C{fqdn}:{}:{ttl}", escape(name(&cname.0).as_bytes()))
+            }
+            Some(RData::PTR(ptr)) => format!("^{fqdn}:{}:{ttl}", escape(name(&ptr.0).as_bytes())),
+            // tinydns splits long strings itself, but joins several strings into one.
+            Some(RData::TXT(txt)) if txt.txt_data().len() == 1 => {
+                format!("'{fqdn}:{}:{ttl}", escape(&txt.txt_data()[0]))
+            }
+            Some(rdata) => {
+                let mut bytes = Vec::new();
+                let mut encoder = BinEncoder::with_mode(&mut bytes, EncodeMode::Signing);
+                if rdata.emit(&mut encoder).is_err() {
+                    continue;
+                }
+
+                format!(
+                    ":{fqdn}:{}:{}:{ttl}",
+                    u16::from(record.record_type()),
+                    escape(&bytes)
+                )
+            }
+            None => continue,
+        };
+
+        lines.push((!matches!(record.data(), Some(RData::SOA(_))), line));
+    }
+
+    // The sort is stable, so records otherwise keep their order.
+    lines.sort_by_key(|(not_soa, _)| *not_soa);
+
+    lines.into_iter().map(|(_, line)| line + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::{rr::Name, serialize::txt::Parser};
+    use indoc::indoc;
+
+    use super::serialize;
+
+    #[test]
+    fn serializes_tinydns_data() {
+        let (_, records) = Parser::new(
+            indoc! {r#"
+                $ORIGIN example.org.
+                www 300 IN A 192.0.2.1
+                @ 3600 IN SOA ns1 hostmaster 2024010101 7200 3600 1209600 300
+                @ 3600 IN NS ns1
+                @ 3600 IN MX 10 mail
+                mail 300 IN AAAA 2001:db8::1
+                alias 300 IN CNAME www
+                @ 300 IN TXT "v=spf1 -all"
+                _sip._tcp 300 IN SRV 10 60 5060 sip
+            "#},
+            None,
+            Some(Name::from_ascii("example.org.").unwrap()),
+        )
+        .parse()
+        .unwrap();
+
+        let records: Vec<_> = records
+            .values()
+            .flat_map(|rrset| rrset.records_without_rrsigs().cloned())
+            .collect();
+
+        let serialized = serialize(&records);
+        let lines: Vec<_> = serialized.lines().collect();
+
+        // The parser gives the SOA record the expire time as its TTL.
+        assert_eq!(
+            lines[0],
+            "Zexample.org:ns1.example.org:hostmaster.example.org:2024010101:7200:3600:1209600:300:1209600"
+        );
+
+        for line in [
+            "+www.example.org:192.0.2.1:300",
+            "&example.org::ns1.example.org:3600",
+            "@example.org::mail.example.org:10:3600",
+            "Calias.example.org:www.example.org:300",
+            "'example.org:v=spf1 -all:300",
+            ":mail.example.org:28: \\001\\015\\270\\000\\000\\000\\000\\000\\000\\000\\000\\000\\000\\000\\001:300",
+            ":_sip._tcp.example.org:33:\\000\\012\\000<\\023\\304\\003sip\\007example\\003org\\000:300",
+        ] {
+            assert!(lines.contains(&line), "{line} missing from {serialized}");
+        }
+    }
+}
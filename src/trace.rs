@@ -0,0 +1,73 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Annotation on the published ConfigMap holding the [`ReconcileId`]
+/// of the reconciliation which last updated it.
+pub const RECONCILE_ID_ANNOTATION: &str = "zonefile.kubi.zone/reconcile-id";
+
+/// Identifier of a single reconciliation of a zonefile, attached to the
+/// span its log lines are emitted in and to the outputs it produces.
+///
+/// Identifiers consist of the time the controller started and a sequence
+/// number, so they are unique across restarts of the controller, and sort
+/// in the order the reconciliations started within one run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconcileId {
+    started: u64,
+    sequence: u64,
+}
+
+impl ReconcileId {
+    pub fn next() -> Self {
+        static STARTED: OnceLock<u64> = OnceLock::new();
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+        let started = *STARTED.get_or_init(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_millis() as u64)
+                .unwrap_or_default()
+        });
+
+        ReconcileId {
+            started,
+            sequence: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Display for ReconcileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:x}-{:08x}", self.started, self.sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReconcileId;
+
+    #[test]
+    fn identifiers_are_unique_and_ordered() {
+        let first = ReconcileId::next();
+        let second = ReconcileId::next();
+
+        assert_ne!(first, second);
+        assert!(first.to_string() < second.to_string());
+        assert_eq!(
+            first
+                .to_string()
+                .split_once('-')
+                .map(|(started, _)| started),
+            second
+                .to_string()
+                .split_once('-')
+                .map(|(started, _)| started),
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hickory_proto::rr::{
+    dnssec::{rdata::tsig::TsigAlgorithm, tsig::TSigner},
+    Name,
+};
+use k8s_openapi::api::core::v1::Secret;
+use kube::{Api, Client, ResourceExt as _};
+use zonefile_crds::ZoneFile;
+
+use crate::error::Error;
+
+/// Annotation on a [`ZoneFile`] naming a Secret in the same namespace, which
+/// holds the TSIG key used to authenticate the zone transfers and NOTIFY
+/// messages sent for the zonefile.
+pub const TSIG_KEY_ANNOTATION: &str = "zonefile.kubi.zone/tsig-key";
+
+/// Key within the TSIG Secret which holds the name of the key, as known to the peer.
+pub const TSIG_NAME_SECRET_KEY: &str = "name";
+
+/// Key within the TSIG Secret which holds the algorithm of the key,
+/// such as `hmac-sha256`, which is used if it is left out.
+pub const TSIG_ALGORITHM_SECRET_KEY: &str = "algorithm";
+
+/// Key within the TSIG Secret which holds the base64-encoded key,
+/// as printed by `tsig-keygen`.
+pub const TSIG_SECRET_SECRET_KEY: &str = "secret";
+
+/// Allowed difference in seconds between the clocks of the controller and its peers.
+const FUDGE: u16 = 300;
+
+/// Build a TSIG signer from the name, algorithm and base64-encoded secret of a key.
+pub fn signer(name: &str, algorithm: Option<&str>, secret: &str) -> Result<TSigner, Error> {
+    let name = Name::from_ascii(name)
+        .map_err(|e| Error::Tsig(format!("invalid key name {name:?}: {e}")))?;
+
+    let algorithm = match algorithm {
+        Some(algorithm) => TsigAlgorithm::from_name(
+            Name::from_ascii(algorithm.to_ascii_lowercase())
+                .map_err(|e| Error::Tsig(format!("invalid algorithm {algorithm:?}: {e}")))?,
+        ),
+        None => TsigAlgorithm::HmacSha256,
+    };
+
+    let secret = STANDARD
+        .decode(secret.trim())
+        .map_err(|e| Error::Tsig(format!("invalid secret: {e}")))?;
+
+    TSigner::new(secret, algorithm, name, FUDGE).map_err(|e| Error::Tsig(e.to_string()))
+}
+
+/// Fetch the TSIG key referenced by the zonefile's
+/// [`TSIG_KEY_ANNOTATION`], if it has one.
+pub async fn for_zonefile(client: Client, zonefile: &ZoneFile) -> Result<Option<TSigner>, Error> {
+    let Some(secret_name) = zonefile.annotations().get(TSIG_KEY_ANNOTATION) else {
+        return Ok(None);
+    };
+
+    let secret = Api::<Secret>::namespaced(client, zonefile.namespace().as_ref().unwrap())
+        .get(secret_name)
+        .await?;
+
+    let field = |key: &str| {
+        secret
+            .data
+            .as_ref()
+            .and_then(|data| data.get(key))
+            .map(|value| String::from_utf8_lossy(&value.0).trim().to_string())
+    };
+
+    let (Some(name), Some(key)) = (field(TSIG_NAME_SECRET_KEY), field(TSIG_SECRET_SECRET_KEY))
+    else {
+        return Err(Error::Tsig(format!(
+            "secret {secret_name} must hold both the {TSIG_NAME_SECRET_KEY} and {TSIG_SECRET_SECRET_KEY} keys"
+        )));
+    };
+
+    signer(&name, field(TSIG_ALGORITHM_SECRET_KEY).as_deref(), &key).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::rr::dnssec::rdata::tsig::TsigAlgorithm;
+
+    use super::signer;
+
+    #[test]
+    fn builds_signers() {
+        let key = "2vgqVbBZJ3fK4LFbfdSaGGbJ8Lgu0hXzCLK/ybqTLtM=";
+
+        let tsig = signer("transfer.", None, key).unwrap();
+        assert_eq!(tsig.algorithm(), &TsigAlgorithm::HmacSha256);
+        assert_eq!(tsig.signer_name().to_ascii(), "transfer.");
+
+        let tsig = signer("transfer", Some("HMAC-SHA512"), key).unwrap();
+        assert_eq!(tsig.algorithm(), &TsigAlgorithm::HmacSha512);
+
+        // Algorithms which cannot be computed are rejected up front.
+        assert!(signer("transfer", Some("hmac-md5.sig-alg.reg.int"), key).is_err());
+        assert!(signer("transfer", None, "not base64").is_err());
+    }
+}
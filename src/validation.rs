@@ -0,0 +1,1170 @@
+use std::{
+    collections::HashSet,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use clap::Args;
+use kubizone_common::Type;
+use kubizone_crds::v1alpha1::ZoneEntry;
+use thiserror::Error;
+use unicode_script::{Script, UnicodeScript as _};
+
+use crate::normalize;
+
+/// Produced when the rdata of a record does not match the
+/// presentation format of its type.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("unterminated quoted string")]
+    UnterminatedQuote,
+    #[error("missing {0}")]
+    MissingField(&'static str),
+    #[error("unexpected trailing data {0:?}")]
+    TrailingData(String),
+    #[error("invalid {field} {value:?}")]
+    InvalidField { field: &'static str, value: String },
+    #[error("certificate authority {0:?} is not permitted")]
+    DisallowedIssuer(String),
+}
+
+impl ValidationError {
+    /// Whether the record is valid, but violates a policy of the controller.
+    /// Zones holding such records are not published, rather than published
+    /// without them.
+    pub fn is_policy_violation(&self) -> bool {
+        matches!(self, ValidationError::DisallowedIssuer(_))
+    }
+}
+
+#[derive(Debug, Clone, Default, Hash, Args)]
+pub struct ValidationArgs {
+    /// Certificate authorities which CAA `issue` and `issuewild` records
+    /// may name. If left empty, any certificate authority is permitted.
+    #[arg(long, env = "ZONEFILE_CAA_ALLOWED_ISSUERS", value_delimiter = ',')]
+    pub caa_allowed_issuers: Vec<String>,
+}
+
+impl ValidationArgs {
+    /// Check the canonical rdata of an entry against the configured policies.
+    fn check_policies(&self, type_: Type, rdata: &str) -> Result<(), ValidationError> {
+        if type_ == Type::CAA && !self.caa_allowed_issuers.is_empty() {
+            check_caa_issuer(rdata, &self.caa_allowed_issuers)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Annotation on a [`ZoneFile`](zonefile_crds::ZoneFile) which determines how
+/// wildcard records are treated. See [`WildcardPolicy`].
+pub const WILDCARD_POLICY_ANNOTATION: &str = "zonefile.kubi.zone/wildcard-policy";
+
+/// Treatment of records with a wildcard (`*`) owner name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WildcardPolicy {
+    /// Wildcard records are published.
+    #[default]
+    Allow,
+    /// Wildcard records are published, but a warning is logged.
+    Warn,
+    /// Wildcard records are excluded, and the zonefile is marked as degraded.
+    Deny,
+}
+
+impl FromStr for WildcardPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(WildcardPolicy::Allow),
+            "warn" => Ok(WildcardPolicy::Warn),
+            "deny" => Ok(WildcardPolicy::Deny),
+            other => Err(format!(
+                "unknown wildcard policy {other:?}, expected allow, warn or deny"
+            )),
+        }
+    }
+}
+
+/// Returns true if the leftmost label of the entry's owner name is a wildcard.
+pub fn is_wildcard(entry: &ZoneEntry) -> bool {
+    entry
+        .fqdn
+        .iter()
+        .next()
+        .is_some_and(|segment| segment.as_ref() == "*")
+}
+
+/// Labels of the domain name which mix characters of several scripts, such
+/// as a Cyrillic `а` within an otherwise Latin label, as is typical of
+/// homograph attacks. Punycode labels are checked in their decoded form.
+///
+/// Han, Hiragana, Katakana and Hangul are treated as a single script,
+/// since Japanese and Korean names legitimately mix them.
+pub fn mixed_script_labels(name: &str) -> Vec<String> {
+    let (name, _) = idna::domain_to_unicode(name);
+
+    name.split('.')
+        .filter(|label| {
+            let scripts: HashSet<_> = label
+                .chars()
+                .map(|c| match c.script() {
+                    Script::Hiragana | Script::Katakana | Script::Hangul | Script::Bopomofo => {
+                        Script::Han
+                    }
+                    script => script,
+                })
+                .filter(|script| !matches!(script, Script::Common | Script::Inherited))
+                .collect();
+
+            scripts.len() > 1
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Validate the entries of a zone, returning the valid entries with
+/// their rdata in canonical form, along with the invalid entries and
+/// the reason they were rejected.
+pub fn validate_entries(
+    entries: &[ZoneEntry],
+    args: &ValidationArgs,
+) -> (Vec<ZoneEntry>, Vec<(ZoneEntry, ValidationError)>) {
+    let mut valid = Vec::with_capacity(entries.len());
+    let mut invalid = Vec::new();
+
+    for entry in entries {
+        match validate_rdata(entry.type_, &entry.rdata, args) {
+            Ok(rdata) => valid.push(ZoneEntry {
+                rdata,
+                ..entry.clone()
+            }),
+            Err(error) => invalid.push((entry.clone(), error)),
+        }
+    }
+
+    (valid, invalid)
+}
+
+/// Validate the rdata of a record and check it against the configured policies,
+/// returning it in canonical presentation format.
+pub fn validate_rdata(
+    type_: Type,
+    rdata: &str,
+    args: &ValidationArgs,
+) -> Result<String, ValidationError> {
+    let rdata = canonical_rdata(type_, rdata)?;
+    args.check_policies(type_, &rdata)?;
+    Ok(rdata)
+}
+
+/// Validate the rdata of a record, returning it in canonical presentation format.
+///
+/// Types which are not understood are passed through as-is.
+pub fn canonical_rdata(type_: Type, rdata: &str) -> Result<String, ValidationError> {
+    match type_ {
+        Type::SVCB | Type::HTTPS => svcb(rdata),
+        Type::MX => mx(rdata),
+        Type::SRV => srv(rdata),
+        Type::NAPTR => naptr(rdata),
+        Type::TLSA => tlsa(rdata),
+        Type::CAA => caa(rdata),
+        Type::A => address::<Ipv4Addr>(rdata),
+        Type::AAAA => address::<Ipv6Addr>(rdata),
+        Type::CNAME | Type::DNAME | Type::NS | Type::PTR => target(rdata),
+        _ => Ok(rdata.to_string()),
+    }
+}
+
+/// Split rdata into whitespace-separated fields, keeping quoted
+/// strings (and any quotes within a field) intact.
+pub fn tokenize(rdata: &str) -> Result<Vec<String>, ValidationError> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for character in rdata.chars() {
+        if escaped {
+            escaped = false;
+        } else if character == '\\' {
+            escaped = true;
+        } else if character == '"' {
+            quoted = !quoted;
+        } else if character.is_whitespace() && !quoted {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+            continue;
+        }
+
+        token.push(character);
+    }
+
+    if quoted || escaped {
+        return Err(ValidationError::UnterminatedQuote);
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Strip surrounding quotes from a value, if present.
+fn unquote(value: &str) -> Result<&str, ValidationError> {
+    match value.strip_prefix('"') {
+        Some(inner) => inner
+            .strip_suffix('"')
+            .ok_or(ValidationError::UnterminatedQuote),
+        None => Ok(value),
+    }
+}
+
+/// Quote a value if it contains characters which would otherwise
+/// be interpreted by the zonefile parser, escaping any quotes within
+/// it which are not escaped already.
+fn quote(value: &str) -> String {
+    if value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | ';' | '(' | ')'))
+    {
+        let mut quoted = String::from('"');
+        let mut escaped = false;
+        for character in value.chars() {
+            if character == '"' && !escaped {
+                quoted.push('\\');
+            }
+            escaped = !escaped && character == '\\';
+            quoted.push(character);
+        }
+        quoted.push('"');
+        quoted
+    } else {
+        value.to_string()
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(
+    field: &'static str,
+    value: &str,
+) -> Result<T, ValidationError> {
+    value.parse().map_err(|_| ValidationError::InvalidField {
+        field,
+        value: value.to_string(),
+    })
+}
+
+/// Validate a (possibly relative) domain name appearing within rdata.
+fn validate_hostname(field: &'static str, value: &str) -> Result<(), ValidationError> {
+    let invalid = || ValidationError::InvalidField {
+        field,
+        value: value.to_string(),
+    };
+
+    if value == "." || value == "@" {
+        return Ok(());
+    }
+
+    if value.len() > 254 {
+        return Err(invalid());
+    }
+
+    // Internationalized names are converted to punycode during normalization.
+    let value = normalize::to_ascii(value);
+
+    for label in value.strip_suffix('.').unwrap_or(&value).split('.') {
+        if label.is_empty()
+            || label.len() > 63
+            || !label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(invalid());
+        }
+    }
+
+    Ok(())
+}
+
+/// Pull the next field from the token iterator.
+fn next_field<'a>(
+    tokens: &mut impl Iterator<Item = &'a String>,
+    field: &'static str,
+) -> Result<&'a str, ValidationError> {
+    tokens
+        .next()
+        .map(String::as_str)
+        .ok_or(ValidationError::MissingField(field))
+}
+
+/// Fail if any tokens remain after all fields have been consumed.
+fn expect_end<'a>(tokens: impl Iterator<Item = &'a String>) -> Result<(), ValidationError> {
+    let remainder = tokens.map(String::as_str).collect::<Vec<_>>().join(" ");
+
+    if remainder.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError::TrailingData(remainder))
+    }
+}
+
+/// Validate a `<character-string>`, returning it in quoted form.
+fn character_string(field: &'static str, value: &str) -> Result<String, ValidationError> {
+    let inner = unquote(value)?;
+
+    let mut escaped = false;
+    let unescaped_quote = inner.chars().any(|c| {
+        let quote = c == '"' && !escaped;
+        escaped = c == '\\' && !escaped;
+        quote
+    });
+
+    if unescaped_quote {
+        return Err(ValidationError::InvalidField {
+            field,
+            value: value.to_string(),
+        });
+    }
+
+    Ok(format!("\"{inner}\""))
+}
+
+/// A and AAAA records, holding a single address of their family.
+/// The address is kept as written, so that published zones do not change.
+fn address<T: FromStr>(rdata: &str) -> Result<String, ValidationError> {
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let address = next_field(&mut tokens, "address")?;
+    parse_field::<T>("address", address)?;
+    expect_end(tokens)?;
+
+    Ok(address.to_string())
+}
+
+/// CNAME, DNAME, NS and PTR records, holding a single domain name.
+fn target(rdata: &str) -> Result<String, ValidationError> {
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let target = next_field(&mut tokens, "target")?;
+    validate_hostname("target", target)?;
+    expect_end(tokens)?;
+
+    Ok(target.to_string())
+}
+
+/// MX records ([RFC 1035](https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.9)):
+///
+/// `preference exchange`
+///
+/// A null MX ([RFC 7505](https://datatracker.ietf.org/doc/html/rfc7505)),
+/// with an exchange of `.`, must have a preference of zero.
+fn mx(rdata: &str) -> Result<String, ValidationError> {
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let preference: u16 = parse_field("preference", next_field(&mut tokens, "preference")?)?;
+
+    let exchange = next_field(&mut tokens, "exchange")?;
+    validate_hostname("exchange", exchange)?;
+
+    if exchange == "." && preference != 0 {
+        return Err(ValidationError::InvalidField {
+            field: "preference",
+            value: preference.to_string(),
+        });
+    }
+
+    expect_end(tokens)?;
+
+    Ok(format!("{preference} {exchange}"))
+}
+
+/// SRV records ([RFC 2782](https://datatracker.ietf.org/doc/html/rfc2782)):
+///
+/// `priority weight port target`
+fn srv(rdata: &str) -> Result<String, ValidationError> {
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let priority: u16 = parse_field("priority", next_field(&mut tokens, "priority")?)?;
+    let weight: u16 = parse_field("weight", next_field(&mut tokens, "weight")?)?;
+    let port: u16 = parse_field("port", next_field(&mut tokens, "port")?)?;
+
+    let target = next_field(&mut tokens, "target")?;
+    validate_hostname("target", target)?;
+
+    expect_end(tokens)?;
+
+    Ok(format!("{priority} {weight} {port} {target}"))
+}
+
+/// NAPTR records ([RFC 3403](https://datatracker.ietf.org/doc/html/rfc3403)):
+///
+/// `order preference "flags" "services" "regexp" replacement`
+fn naptr(rdata: &str) -> Result<String, ValidationError> {
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let order: u16 = parse_field("order", next_field(&mut tokens, "order")?)?;
+    let preference: u16 = parse_field("preference", next_field(&mut tokens, "preference")?)?;
+
+    let flags = character_string("flags", next_field(&mut tokens, "flags")?)?;
+    if !flags
+        .trim_matches('"')
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(ValidationError::InvalidField {
+            field: "flags",
+            value: flags,
+        });
+    }
+
+    let services = character_string("services", next_field(&mut tokens, "services")?)?;
+    let regexp = character_string("regexp", next_field(&mut tokens, "regexp")?)?;
+
+    let replacement = next_field(&mut tokens, "replacement")?;
+    validate_hostname("replacement", replacement)?;
+
+    expect_end(tokens)?;
+
+    Ok(format!(
+        "{order} {preference} {flags} {services} {regexp} {replacement}"
+    ))
+}
+
+/// TLSA records ([RFC 6698](https://datatracker.ietf.org/doc/html/rfc6698)):
+///
+/// `usage selector matching-type certificate-association-data`
+///
+/// The certificate association data may be split across multiple
+/// whitespace-separated fields, and is rendered as a single
+/// upper-case hexadecimal string.
+fn tlsa(rdata: &str) -> Result<String, ValidationError> {
+    // Reserved for private use by each of the fields (RFC 6698, section 7).
+    const PRIVATE_USE: u8 = 255;
+
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let usage = next_field(&mut tokens, "usage")?;
+    let usage: u8 = parse_field("usage", usage)
+        .ok()
+        .filter(|usage| *usage <= 3 || *usage == PRIVATE_USE)
+        .ok_or_else(|| ValidationError::InvalidField {
+            field: "usage",
+            value: usage.to_string(),
+        })?;
+
+    let selector = next_field(&mut tokens, "selector")?;
+    let selector: u8 = parse_field("selector", selector)
+        .ok()
+        .filter(|selector| *selector <= 1 || *selector == PRIVATE_USE)
+        .ok_or_else(|| ValidationError::InvalidField {
+            field: "selector",
+            value: selector.to_string(),
+        })?;
+
+    let matching_type = next_field(&mut tokens, "matching type")?;
+    let matching_type: u8 = parse_field("matching type", matching_type)
+        .ok()
+        .filter(|matching_type| *matching_type <= 2 || *matching_type == PRIVATE_USE)
+        .ok_or_else(|| ValidationError::InvalidField {
+            field: "matching type",
+            value: matching_type.to_string(),
+        })?;
+
+    let data = tokens.map(String::as_str).collect::<String>();
+
+    let expected_length = match matching_type {
+        // SHA-256
+        1 => Some(64),
+        // SHA-512
+        2 => Some(128),
+        // Full certificate or public key
+        _ => None,
+    };
+
+    if data.is_empty()
+        || data.len() % 2 != 0
+        || !data.chars().all(|c| c.is_ascii_hexdigit())
+        || expected_length.is_some_and(|length| data.len() != length)
+    {
+        return Err(ValidationError::InvalidField {
+            field: "certificate association data",
+            value: data,
+        });
+    }
+
+    Ok(format!(
+        "{usage} {selector} {matching_type} {}",
+        data.to_ascii_uppercase()
+    ))
+}
+
+/// CAA records ([RFC 8659](https://datatracker.ietf.org/doc/html/rfc8659)):
+///
+/// `flags tag "value"`
+fn caa(rdata: &str) -> Result<String, ValidationError> {
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let flags: u8 = parse_field("flags", next_field(&mut tokens, "flags")?)?;
+
+    let tag = next_field(&mut tokens, "tag")?;
+    if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ValidationError::InvalidField {
+            field: "tag",
+            value: tag.to_string(),
+        });
+    }
+    let tag = tag.to_ascii_lowercase();
+
+    let value = character_string("value", next_field(&mut tokens, "value")?)?;
+
+    expect_end(tokens)?;
+
+    if tag == "issue" || tag == "issuewild" {
+        let issuer = caa_issuer(&value);
+        if !issuer.is_empty() {
+            validate_hostname("issuer", issuer)?;
+        }
+    }
+
+    Ok(format!("{flags} {tag} {value}"))
+}
+
+/// Extract the issuer domain from the quoted value of an `issue` or `issuewild` CAA record,
+/// stripping any parameters. An empty issuer forbids issuance entirely.
+fn caa_issuer(value: &str) -> &str {
+    let value = value.trim_matches('"');
+    value
+        .split_once(';')
+        .map_or(value, |(issuer, _)| issuer)
+        .trim()
+}
+
+/// Verify that a canonical `issue` or `issuewild` CAA record names one of the allowed issuers.
+fn check_caa_issuer(rdata: &str, allowed_issuers: &[String]) -> Result<(), ValidationError> {
+    let mut fields = rdata.splitn(3, ' ').skip(1);
+
+    let (Some(tag), Some(value)) = (fields.next(), fields.next()) else {
+        return Ok(());
+    };
+
+    if tag != "issue" && tag != "issuewild" {
+        return Ok(());
+    }
+
+    let issuer = caa_issuer(value);
+    if issuer.is_empty()
+        || allowed_issuers.iter().any(|allowed| {
+            allowed
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(issuer.trim_end_matches('.'))
+        })
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::DisallowedIssuer(issuer.to_string()))
+    }
+}
+
+/// SVCB and HTTPS records ([RFC 9460](https://datatracker.ietf.org/doc/html/rfc9460)):
+///
+/// `priority target [key=value ...]`
+fn svcb(rdata: &str) -> Result<String, ValidationError> {
+    let tokens = tokenize(rdata)?;
+    let mut tokens = tokens.iter();
+
+    let priority = tokens
+        .next()
+        .ok_or(ValidationError::MissingField("priority"))?;
+    let priority: u16 = parse_field("priority", priority)?;
+
+    let target = tokens
+        .next()
+        .ok_or(ValidationError::MissingField("target"))?;
+    validate_hostname("target", target)?;
+
+    let mut params = tokens
+        .map(String::as_str)
+        .map(svc_param)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Priority 0 denotes AliasMode, in which parameters are not allowed.
+    if priority == 0 && !params.is_empty() {
+        return Err(ValidationError::TrailingData(
+            params
+                .into_iter()
+                .map(|(_, param)| param)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ));
+    }
+
+    // Keys must appear at most once, whether given by name or by number.
+    // Named keys without a known number can only be checked by name.
+    let mut names = HashSet::new();
+    let mut numbers = HashSet::new();
+    for (key, param) in &params {
+        let duplicate = key.number.is_some_and(|number| !numbers.insert(number));
+
+        if duplicate || !names.insert(key.name.as_str()) {
+            return Err(ValidationError::InvalidField {
+                field: "service parameter",
+                value: param.clone(),
+            });
+        }
+    }
+
+    // Keys listed as mandatory must be present, and must not include `mandatory` itself.
+    if let Some((_, mandatory)) = params.iter().find(|(key, _)| key.number == Some(0)) {
+        let listed = mandatory
+            .split_once('=')
+            .map(|(_, value)| value.trim_matches('"'))
+            .unwrap_or_default();
+
+        for listed in listed.split(',') {
+            let listed = SvcParamKey::from(listed);
+            let present = listed.number != Some(0)
+                && params.iter().any(|(key, _)| {
+                    key.name == listed.name || key.number.is_some() && key.number == listed.number
+                });
+
+            if !present {
+                return Err(ValidationError::InvalidField {
+                    field: "mandatory",
+                    value: listed.name,
+                });
+            }
+        }
+    }
+
+    // Presentation format allows keys in any order, but the canonical form
+    // lists them in ascending order of their numbers, with unknown named keys last.
+    params.sort_by_key(|(key, _)| key.number.unwrap_or(u16::MAX));
+    let params = params.into_iter().map(|(_, param)| param);
+
+    Ok([priority.to_string(), target.to_string()]
+        .into_iter()
+        .chain(params)
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Name of a SvcParamKey, along with its number, if it is known.
+struct SvcParamKey {
+    name: String,
+    number: Option<u16>,
+}
+
+impl From<&str> for SvcParamKey {
+    fn from(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        let number = match name.as_str() {
+            "mandatory" => Some(0),
+            "alpn" => Some(1),
+            "no-default-alpn" => Some(2),
+            "port" => Some(3),
+            "ipv4hint" => Some(4),
+            "ech" => Some(5),
+            "ipv6hint" => Some(6),
+            "dohpath" => Some(7),
+            "ohttp" => Some(8),
+            generic => generic
+                .strip_prefix("key")
+                .and_then(|number| number.parse().ok()),
+        };
+
+        SvcParamKey { name, number }
+    }
+}
+
+/// Validate a single `key=value` SvcParam, returning its key along with
+/// the parameter in canonical form.
+///
+/// Named keys registered after RFC 9460 which are not known here are passed
+/// through verbatim, so that zones can make use of them before this does.
+fn svc_param(param: &str) -> Result<(SvcParamKey, String), ValidationError> {
+    let invalid = || ValidationError::InvalidField {
+        field: "service parameter",
+        value: param.to_string(),
+    };
+
+    let (name, value) = match param.split_once('=') {
+        Some((name, value)) => (name, Some(unquote(value)?)),
+        None => (param, None),
+    };
+    let svc_param_key = SvcParamKey::from(name);
+    let key = svc_param_key.name.clone();
+
+    let list = |value: &str| -> Result<Vec<String>, ValidationError> {
+        let items = value_list(value);
+        if items.iter().any(String::is_empty) {
+            return Err(invalid());
+        }
+        Ok(items)
+    };
+
+    let param = match (key.as_str(), value) {
+        ("no-default-alpn" | "ohttp", None) => Ok(key),
+        ("mandatory" | "alpn", Some(value)) => {
+            Ok(format!("{key}={}", quote(&list(value)?.join(","))))
+        }
+        ("port", Some(value)) => {
+            let port: u16 = parse_field("port", value)?;
+            Ok(format!("{key}={port}"))
+        }
+        ("ipv4hint", Some(value)) => {
+            let hints = list(value)?
+                .iter()
+                .map(|hint| parse_field::<Ipv4Addr>("ipv4hint", hint).map(|ip| ip.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("{key}={}", hints.join(",")))
+        }
+        ("ipv6hint", Some(value)) => {
+            let hints = list(value)?
+                .iter()
+                .map(|hint| parse_field::<Ipv6Addr>("ipv6hint", hint).map(|ip| ip.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("{key}={}", hints.join(",")))
+        }
+        ("ech", Some(value)) if !value.is_empty() => Ok(format!("{key}={}", quote(value))),
+        // A relative URI template, which must contain the `dns` variable (RFC 9461).
+        ("dohpath", Some(value)) if value.starts_with('/') && value.contains("{?dns}") => {
+            Ok(format!("{key}={}", quote(value)))
+        }
+        (
+            "mandatory" | "alpn" | "no-default-alpn" | "port" | "ipv4hint" | "ipv6hint" | "ech"
+            | "dohpath" | "ohttp",
+            _,
+        ) => Err(invalid()),
+        (generic, value)
+            if svc_param_key.number.is_some()
+                || !generic.is_empty()
+                    && generic
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') =>
+        {
+            match value {
+                Some(value) => Ok(format!("{key}={}", quote(value))),
+                None => Ok(key),
+            }
+        }
+        _ => Err(invalid()),
+    }?;
+
+    Ok((svc_param_key, param))
+}
+
+/// Split a SvcParam value list into its items, as written. Character-string
+/// escapes are undone before looking for the commas separating the items, so
+/// that only commas escaped at the value list level are kept within an item
+/// ([RFC 9460, Appendix A.1](https://datatracker.ietf.org/doc/html/rfc9460#appendix-A.1)).
+fn value_list(value: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut escaped = false;
+    let mut chars = value.chars();
+
+    while let Some(character) = chars.next() {
+        let mut raw = character.to_string();
+        let mut decoded = character;
+
+        if character == '\\' {
+            let rest = chars.as_str();
+            if rest.chars().take(3).filter(char::is_ascii_digit).count() == 3 {
+                raw.push_str(&rest[..3]);
+                decoded = rest[..3].parse::<u8>().map_or('\0', char::from);
+                chars = rest[3..].chars();
+            } else if let Some(next) = chars.next() {
+                raw.push(next);
+                decoded = next;
+            }
+        }
+
+        if decoded == ',' && !escaped {
+            items.push(String::new());
+        } else {
+            items.last_mut().unwrap().push_str(&raw);
+        }
+
+        escaped = !escaped && decoded == '\\';
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::Type;
+
+    use kubizone_common::{Class, FullyQualifiedDomainName};
+    use kubizone_crds::v1alpha1::ZoneEntry;
+
+    use super::{
+        canonical_rdata, mixed_script_labels, validate_entries, value_list, ValidationArgs,
+        ValidationError, WildcardPolicy,
+    };
+
+    #[test]
+    fn svcb_canonicalization() {
+        assert_eq!(
+            canonical_rdata(
+                Type::HTTPS,
+                r#"1   .  ALPN="h2,h3"  ipv4hint=192.0.2.1,192.0.2.2 ipv6hint="2001:DB8::1""#
+            ),
+            Ok(String::from(
+                "1 . alpn=h2,h3 ipv4hint=192.0.2.1,192.0.2.2 ipv6hint=2001:db8::1"
+            ))
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::SVCB, "0 svc.example.org."),
+            Ok(String::from("0 svc.example.org."))
+        );
+    }
+
+    #[test]
+    fn addresses_and_targets() {
+        assert_eq!(
+            canonical_rdata(Type::A, "192.0.2.1"),
+            Ok(String::from("192.0.2.1"))
+        );
+        assert_eq!(
+            canonical_rdata(Type::AAAA, "2001:DB8::1"),
+            Ok(String::from("2001:DB8::1"))
+        );
+        assert!(canonical_rdata(Type::A, "192.0.2.256").is_err());
+        assert!(canonical_rdata(Type::A, "2001:db8::1").is_err());
+        assert!(canonical_rdata(Type::AAAA, "192.0.2.1").is_err());
+        assert!(canonical_rdata(Type::A, "192.0.2.1 192.0.2.2").is_err());
+
+        assert_eq!(
+            canonical_rdata(Type::PTR, "host-1.example.org."),
+            Ok(String::from("host-1.example.org."))
+        );
+        assert!(canonical_rdata(Type::CNAME, "host$.example.org.").is_err());
+        assert!(canonical_rdata(Type::NS, "").is_err());
+    }
+
+    #[test]
+    fn svcb_value_lists() {
+        // Both spellings of the example in RFC 9460, Appendix A.1, hold three items.
+        assert_eq!(
+            value_list(r#"part1,part2,part3\\,part4\\\\"#),
+            [r#"part1"#, r#"part2"#, r#"part3\\,part4\\\\"#]
+        );
+        assert_eq!(
+            value_list(r#"part1\,\p\a\r\t\2\044part3\092,part4\092\\"#),
+            [r#"part1"#, r#"\p\a\r\t\2"#, r#"part3\092,part4\092\\"#]
+        );
+
+        // The test vectors of RFC 9460, Appendix D.2.
+        assert_eq!(
+            canonical_rdata(Type::SVCB, r#"16 foo.example.org. alpn="f\\\\oo\\,bar,h2""#),
+            Ok(String::from(r#"16 foo.example.org. alpn=f\\\\oo\\,bar,h2"#))
+        );
+        assert_eq!(
+            canonical_rdata(
+                Type::SVCB,
+                r#"16 foo.example.org. alpn=f\\\092oo\092,bar,h2"#
+            ),
+            Ok(String::from(
+                r#"16 foo.example.org. alpn=f\\\092oo\092,bar,h2"#
+            ))
+        );
+        assert_eq!(
+            canonical_rdata(Type::SVCB, r#"1 foo.example.com. key667="hello\210qoo""#),
+            Ok(String::from(r#"1 foo.example.com. key667=hello\210qoo"#))
+        );
+
+        // Quoted values keep their quotes where they are needed, without escaping
+        // the quotes within them again.
+        assert_eq!(
+            canonical_rdata(Type::SVCB, r#"1 foo.example.com. key667="hello \"world\"""#),
+            Ok(String::from(
+                r#"1 foo.example.com. key667="hello \"world\"""#
+            ))
+        );
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, r#"1 . alpn="h2,h3 draft""#),
+            Ok(String::from(r#"1 . alpn="h2,h3 draft""#))
+        );
+
+        // Items may not be empty, unless their separating comma is escaped.
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, r#"1 . alpn="h2,""#),
+            Err(ValidationError::InvalidField {
+                field: "service parameter",
+                value: String::from(r#"alpn="h2,""#)
+            })
+        );
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, r#"1 . alpn=h2\,,h3"#),
+            Err(ValidationError::InvalidField {
+                field: "service parameter",
+                value: String::from(r#"alpn=h2\,,h3"#)
+            })
+        );
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, r#"1 . alpn=h2\\,,h3"#),
+            Ok(String::from(r#"1 . alpn=h2\\,,h3"#))
+        );
+    }
+
+    #[test]
+    fn srv_naptr_tlsa_canonicalization() {
+        assert_eq!(
+            canonical_rdata(Type::SRV, "10  60 5060   sip.example.org."),
+            Ok(String::from("10 60 5060 sip.example.org."))
+        );
+
+        assert_eq!(
+            canonical_rdata(
+                Type::NAPTR,
+                r#"100 10 S SIP+D2U "!^.*$!sip:info@example.org!" _sip._udp.example.org."#
+            ),
+            Ok(String::from(
+                r#"100 10 "S" "SIP+D2U" "!^.*$!sip:info@example.org!" _sip._udp.example.org."#
+            ))
+        );
+
+        assert_eq!(
+            canonical_rdata(
+                Type::TLSA,
+                "3 1 1 d2abde240d7cd3ee6b4b28c54df034b9 7983a1d16e8a410e4561cb106618e971"
+            ),
+            Ok(String::from(
+                "3 1 1 D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971"
+            ))
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::TLSA, "255 255 255 0a0b"),
+            Ok(String::from("255 255 255 0A0B"))
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::TLSA, "4 1 1 0a0b"),
+            Err(ValidationError::InvalidField {
+                field: "usage",
+                value: String::from("4")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::TLSA, "3 1 1 d2abde"),
+            Err(ValidationError::InvalidField {
+                field: "certificate association data",
+                value: String::from("d2abde")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::SRV, "10 60 5060 sip.example.org. extra"),
+            Err(ValidationError::TrailingData(String::from("extra")))
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::SRV, "-1 60 5060 sip.example.org."),
+            Err(ValidationError::InvalidField {
+                field: "priority",
+                value: String::from("-1")
+            })
+        );
+    }
+
+    #[test]
+    fn mx_validation() {
+        assert_eq!(
+            canonical_rdata(Type::MX, "10   mail.example.org."),
+            Ok(String::from("10 mail.example.org."))
+        );
+
+        assert_eq!(canonical_rdata(Type::MX, "0 ."), Ok(String::from("0 .")));
+
+        assert_eq!(
+            canonical_rdata(Type::MX, "10 ."),
+            Err(ValidationError::InvalidField {
+                field: "preference",
+                value: String::from("10")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::MX, "65536 mail.example.org."),
+            Err(ValidationError::InvalidField {
+                field: "preference",
+                value: String::from("65536")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::MX, "mail.example.org."),
+            Err(ValidationError::InvalidField {
+                field: "preference",
+                value: String::from("mail.example.org.")
+            })
+        );
+    }
+
+    #[test]
+    fn caa_issuer_policy() {
+        let entry = |rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from("example.org.").unwrap(),
+            type_: Type::CAA,
+            class: Class::IN,
+            ttl: 360,
+            rdata: rdata.to_string(),
+        };
+
+        let args = ValidationArgs {
+            caa_allowed_issuers: vec![String::from("letsencrypt.org")],
+        };
+
+        let (valid, invalid) = validate_entries(
+            &[
+                entry(r#"0 ISSUE "letsencrypt.org; validationmethods=dns-01""#),
+                entry(r#"0 issuewild ";""#),
+                entry(r#"0 iodef "mailto:security@example.org""#),
+                entry(r#"0 issue "letsencrypt.orgg""#),
+                entry("0 issue letsencrypt.org"),
+            ],
+            &args,
+        );
+
+        assert_eq!(
+            valid
+                .into_iter()
+                .map(|entry| entry.rdata)
+                .collect::<Vec<_>>(),
+            vec![
+                String::from(r#"0 issue "letsencrypt.org; validationmethods=dns-01""#),
+                String::from(r#"0 issuewild ";""#),
+                String::from(r#"0 iodef "mailto:security@example.org""#),
+                String::from(r#"0 issue "letsencrypt.org""#),
+            ]
+        );
+
+        assert_eq!(
+            invalid
+                .into_iter()
+                .map(|(_, error)| error)
+                .collect::<Vec<_>>(),
+            vec![ValidationError::DisallowedIssuer(String::from(
+                "letsencrypt.orgg"
+            ))]
+        );
+
+        // Disallowed issuers fail the whole zone, malformed records only themselves.
+        assert!(ValidationError::DisallowedIssuer(String::new()).is_policy_violation());
+        assert!(!ValidationError::UnterminatedQuote.is_policy_violation());
+    }
+
+    #[test]
+    fn svcb_rejects_invalid_parameters() {
+        assert_eq!(
+            canonical_rdata(Type::SVCB, "0 svc.example.org. alpn=h2"),
+            Err(ValidationError::TrailingData(String::from("alpn=h2")))
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, "1 . port=99999"),
+            Err(ValidationError::InvalidField {
+                field: "port",
+                value: String::from("99999")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, r#"1 . alpn="h2"#),
+            Err(ValidationError::UnterminatedQuote)
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, "1 . port=443 alpn=h2"),
+            Ok(String::from("1 . alpn=h2 port=443"))
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, "1 . port=443 alpn=h2 port=8443"),
+            Err(ValidationError::InvalidField {
+                field: "service parameter",
+                value: String::from("port=8443")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, "1 . alpn=h2 key1=h3"),
+            Err(ValidationError::InvalidField {
+                field: "service parameter",
+                value: String::from("key1=h3")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, "1 . mandatory=alpn,port alpn=h2"),
+            Err(ValidationError::InvalidField {
+                field: "mandatory",
+                value: String::from("port")
+            })
+        );
+    }
+
+    #[test]
+    fn svcb_dohpath_and_unknown_keys() {
+        assert_eq!(
+            canonical_rdata(
+                Type::SVCB,
+                "1 dns.example.org. mandatory=alpn alpn=h2 dohpath=/dns-query{?dns} ohttp"
+            ),
+            Ok(String::from(
+                "1 dns.example.org. mandatory=alpn alpn=h2 dohpath=/dns-query{?dns} ohttp"
+            ))
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::SVCB, "1 dns.example.org. dohpath=/dns-query"),
+            Err(ValidationError::InvalidField {
+                field: "service parameter",
+                value: String::from("dohpath=/dns-query")
+            })
+        );
+
+        assert_eq!(
+            canonical_rdata(Type::HTTPS, "1 . port=443 tls-supported-groups=29,23"),
+            Ok(String::from("1 . port=443 tls-supported-groups=29,23"))
+        );
+    }
+
+    #[test]
+    fn wildcard_policy_parsing() {
+        assert_eq!("deny".parse(), Ok(WildcardPolicy::Deny));
+        assert_eq!("warn".parse(), Ok(WildcardPolicy::Warn));
+        assert!("block".parse::<WildcardPolicy>().is_err());
+    }
+
+    #[test]
+    fn flags_mixed_script_labels() {
+        // Latin "p" and "ypal" around a Cyrillic "а".
+        assert_eq!(
+            mixed_script_labels("www.p\u{0430}ypal.com."),
+            vec![String::from("p\u{0430}ypal")]
+        );
+        assert_eq!(
+            mixed_script_labels("xn--pypal-4ve.com."),
+            vec![String::from("p\u{0430}ypal")]
+        );
+
+        assert!(mixed_script_labels("xn--bcher-kva.example.").is_empty());
+        assert!(mixed_script_labels("東京タワー.jp.").is_empty());
+        assert!(mixed_script_labels("_sip._udp.example.org.").is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use kube::Resource;
+use kubizone_crds::v1alpha1::Zone;
+use zonefile_crds::ZoneFile;
+
+use crate::{
+    backup::ZoneBackup, defaults::ZoneFileDefaults, metrics, state::ZoneFileState,
+    template::ZoneFileTemplate,
+};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_SHA: &str = env!("ZONEFILE_GIT_SHA");
+pub const BUILD_TIMESTAMP: &str = env!("ZONEFILE_BUILD_TIMESTAMP");
+pub const TARGET: &str = env!("ZONEFILE_TARGET");
+
+/// Version shown by `--version`, along with the build it came from.
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ncommit: ",
+    env!("ZONEFILE_GIT_SHA"),
+    "\nbuilt: ",
+    env!("ZONEFILE_BUILD_TIMESTAMP"),
+    "\ntarget: ",
+    env!("ZONEFILE_TARGET"),
+);
+
+/// API versions and kinds of the custom resources the controller reads or writes.
+fn api_versions() -> Vec<String> {
+    [
+        (ZoneFile::api_version(&()), ZoneFile::kind(&())),
+        (Zone::api_version(&()), Zone::kind(&())),
+        (
+            ZoneFileTemplate::api_version(&()),
+            ZoneFileTemplate::kind(&()),
+        ),
+        (ZoneBackup::api_version(&()), ZoneBackup::kind(&())),
+        (
+            ZoneFileDefaults::api_version(&()),
+            ZoneFileDefaults::kind(&()),
+        ),
+        (ZoneFileState::api_version(&()), ZoneFileState::kind(&())),
+    ]
+    .into_iter()
+    .map(|(api_version, kind)| format!("{api_version}/{kind}"))
+    .collect()
+}
+
+/// Print the version of the controller, the build it came from and the
+/// custom resources it supports.
+pub fn print() {
+    println!("version: {VERSION}");
+    println!("commit: {GIT_SHA}");
+    println!("built: {BUILD_TIMESTAMP}");
+    println!("target: {TARGET}");
+    println!("resources:");
+    for api_version in api_versions() {
+        println!("  - {api_version}");
+    }
+}
+
+/// Expose the build through the build info metric.
+pub fn record() {
+    metrics::BUILD_INFO.set(
+        &[
+            ("version", VERSION),
+            ("commit", GIT_SHA),
+            ("built", BUILD_TIMESTAMP),
+            ("target", TARGET),
+        ],
+        1.0,
+    );
+}
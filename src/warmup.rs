@@ -0,0 +1,90 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash as _, Hasher as _},
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+
+#[derive(Debug, Clone, Args)]
+pub struct WarmupArgs {
+    /// Seconds over which the reconciliations of all zonefiles on startup are
+    /// spread, rather than running at once. Each zonefile is given a fixed
+    /// point in the window, before which its reconciliations are postponed.
+    #[arg(long, env = "ZONEFILE_STARTUP_SPREAD", default_value_t = 0)]
+    pub startup_spread: u64,
+}
+
+impl WarmupArgs {
+    pub fn warmup(&self) -> Warmup {
+        Warmup {
+            started: Instant::now(),
+            spread: Duration::from_secs(self.startup_spread),
+        }
+    }
+}
+
+/// Window after startup over which the initial reconciliations are spread.
+pub struct Warmup {
+    started: Instant,
+    spread: Duration,
+}
+
+impl Warmup {
+    /// Time remaining until the zonefile may be reconciled, if it must wait.
+    pub fn delay(&self, zonefile: &str) -> Option<Duration> {
+        self.delay_at(zonefile, Instant::now())
+    }
+
+    fn delay_at(&self, zonefile: &str, now: Instant) -> Option<Duration> {
+        if self.spread.is_zero() {
+            return None;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        zonefile.hash(&mut hasher);
+        let offset = Duration::from_millis(hasher.finish() % self.spread.as_millis() as u64);
+
+        (self.started + offset)
+            .checked_duration_since(now)
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Warmup;
+
+    #[test]
+    fn spreads_startup() {
+        let started = Instant::now();
+        let warmup = Warmup {
+            started,
+            spread: Duration::from_secs(60),
+        };
+
+        let delays = (0..100)
+            .map(|i| warmup.delay_at(&format!("default/zonefile-{i}"), started))
+            .collect::<Vec<_>>();
+
+        assert!(delays
+            .iter()
+            .flatten()
+            .all(|delay| *delay < Duration::from_secs(60)));
+        assert!(delays.iter().flatten().count() > 90);
+        assert_ne!(delays[0], delays[1]);
+
+        assert_eq!(
+            warmup.delay_at("default/zonefile-0", started + Duration::from_secs(60)),
+            None
+        );
+
+        let disabled = Warmup {
+            started,
+            spread: Duration::ZERO,
+        };
+        assert_eq!(disabled.delay_at("default/zonefile-0", started), None);
+    }
+}
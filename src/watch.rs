@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use clap::Args;
+use kube::runtime::watcher;
+
+/// Tuning of the watches on zonefiles and zones. Watch bookmarks are
+/// always requested, so that dropped watches resume where they left off
+/// rather than relisting every resource.
+#[derive(Debug, Clone, Args)]
+pub struct WatchArgs {
+    /// Seconds after which each watch call is ended and restarted by the
+    /// API server. Capped at 295 seconds.
+    #[arg(long, env = "ZONEFILE_WATCH_TIMEOUT", value_parser = clap::value_parser!(u32).range(1..=295))]
+    pub watch_timeout: Option<u32>,
+
+    /// Maximum number of resources fetched per request when relisting.
+    #[arg(long, env = "ZONEFILE_WATCH_PAGE_SIZE", default_value_t = 500)]
+    pub watch_page_size: u32,
+
+    /// Allow relists to be served from the API server's watch cache,
+    /// instead of reading them from etcd. Much cheaper on large clusters,
+    /// at the cost of possibly starting from slightly stale state.
+    #[arg(long, env = "ZONEFILE_WATCH_FROM_CACHE")]
+    pub watch_from_cache: bool,
+
+    /// Fetch the initial state through streaming lists, rather than
+    /// paginated list calls. Requires the WatchList feature gate,
+    /// introduced in Kubernetes 1.27.
+    #[arg(long, env = "ZONEFILE_WATCH_STREAMING_LISTS")]
+    pub watch_streaming_lists: bool,
+
+    /// Seconds between periodic reconciliations of each zonefile, in the
+    /// absence of changes to it or its zones.
+    #[arg(long, env = "ZONEFILE_RESYNC_INTERVAL", default_value_t = 300)]
+    pub resync_interval: u64,
+}
+
+impl WatchArgs {
+    /// Configuration of the watcher for a single kind of resource.
+    pub fn watcher_config(&self) -> watcher::Config {
+        let mut config = watcher::Config::default().page_size(self.watch_page_size);
+
+        if let Some(timeout) = self.watch_timeout {
+            config = config.timeout(timeout);
+        }
+
+        if self.watch_from_cache {
+            config = config.any_semantic();
+        }
+
+        if self.watch_streaming_lists {
+            config = config.streaming_lists();
+        }
+
+        config
+    }
+
+    pub fn resync_interval(&self) -> Duration {
+        Duration::from_secs(self.resync_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kube::runtime::watcher::{InitialListStrategy, ListSemantic};
+
+    use super::WatchArgs;
+
+    #[test]
+    fn watcher_configuration() {
+        let args = WatchArgs {
+            watch_timeout: Some(120),
+            watch_page_size: 100,
+            watch_from_cache: true,
+            watch_streaming_lists: false,
+            resync_interval: 300,
+        };
+
+        let config = args.watcher_config();
+
+        assert!(config.bookmarks);
+        assert_eq!(config.timeout, Some(120));
+        assert_eq!(config.page_size, Some(100));
+        assert_eq!(config.list_semantic, ListSemantic::Any);
+        assert_eq!(config.initial_list_strategy, InitialListStrategy::ListWatch);
+    }
+}